@@ -0,0 +1,179 @@
+use crate::config::{Dialect, IdentifierQuoting};
+use crate::connection::TableStats;
+use crate::identifier::quote_identifier;
+use crate::stages::StageFile;
+use crate::switcher::SessionContext;
+
+/// Runs `sql` and returns the named columns (matched case-insensitively) of
+/// every row; a column the result lacks reads as None. Each backend
+/// provides one, so the lookups below are written once.
+pub type ColumnQuery<'q> = &'q dyn Fn(&str, &[&str]) -> Result<Vec<Vec<Option<String>>>, String>;
+
+/// ROW_COUNT and LAST_ALTERED from INFORMATION_SCHEMA.TABLES; empty on error
+/// since the stats are only decoration
+pub fn table_stats(query: ColumnQuery<'_>, database: &str, schema: &str) -> Vec<TableStats> {
+    let sql = format!(
+        "SELECT TABLE_NAME, ROW_COUNT, TO_VARCHAR(LAST_ALTERED, 'YYYY-MM-DD HH24:MI') AS LAST_ALTERED \
+         FROM {}.INFORMATION_SCHEMA.TABLES WHERE TABLE_SCHEMA = '{}'",
+        quote_identifier(database, IdentifierQuoting::Always),
+        schema.replace('\\', "\\\\").replace('\'', "''"),
+    );
+    let rows = query(&sql, &["TABLE_NAME", "ROW_COUNT", "LAST_ALTERED"]).unwrap_or_default();
+    rows.into_iter()
+        .filter_map(|mut row| {
+            let last_altered = row.pop()?;
+            let row_count = row.pop()?.and_then(|n| n.parse().ok());
+            Some(TableStats { name: row.pop()??, row_count, last_altered })
+        })
+        .collect()
+}
+
+/// The `name` column of a SHOW statement
+pub fn show_names(query: ColumnQuery<'_>, sql: &str) -> Result<Vec<String>, String> {
+    let rows = query(sql, &["name"])?;
+    Ok(rows.into_iter().filter_map(|mut row| row.pop().flatten()).collect())
+}
+
+/// Stages of the current schema as `db.schema.name`
+pub fn stages(query: ColumnQuery<'_>) -> Result<Vec<String>, String> {
+    let rows = query("SHOW STAGES", &["database_name", "schema_name", "name"])?;
+    Ok(rows.into_iter()
+        .filter_map(|row| {
+            let parts: Option<Vec<String>> = row.into_iter().collect();
+            parts.map(|parts| parts.iter()
+                .map(|p| quote_identifier(p, IdentifierQuoting::Smart))
+                .collect::<Vec<_>>()
+                .join("."))
+        })
+        .collect())
+}
+
+pub fn stage_files(query: ColumnQuery<'_>, stage: &str) -> Result<Vec<StageFile>, String> {
+    let rows = query(&format!("LIST @{}", stage), &["name", "size"])?;
+    Ok(rows.into_iter()
+        .filter_map(|row| Some(StageFile {
+            name: row[0].clone()?,
+            size: row[1].as_deref().and_then(|s| s.parse().ok()).unwrap_or(0),
+        }))
+        .collect())
+}
+
+/// `GET_DDL` output for a table or, failing that, a view; the table error
+/// is the one reported
+pub fn ddl(query: ColumnQuery<'_>, name: &str) -> Result<String, String> {
+    let literal = name.replace('\\', "\\\\").replace('\'', "''");
+    let get_ddl = |kind: &str| {
+        let sql = format!("SELECT GET_DDL('{}', '{}', TRUE) AS ddl", kind, literal);
+        query(&sql, &["ddl"])
+            .map(|rows| rows.into_iter().next().and_then(|mut row| row.remove(0)).unwrap_or_default())
+    };
+    get_ddl("TABLE").or_else(|e| get_ddl("VIEW").map_err(|_| e))
+}
+
+/// The first `rows` rows of table `name`, which the caller has checked is
+/// a plain (possibly qualified and quoted) name
+pub fn preview_sql(name: &str, rows: usize, dialect: Dialect) -> String {
+    match dialect {
+        Dialect::SqlServer => format!("SELECT TOP {} * FROM {}", rows, name),
+        Dialect::Generic => format!("SELECT * FROM {} FETCH FIRST {} ROWS ONLY", name, rows),
+        _ => format!("SELECT * FROM {} LIMIT {}", name, rows),
+    }
+}
+
+/// Column names, types, nullability and defaults of table `name`.
+/// Snowflake's DESCRIBE output is reshaped by a script; the other dialects
+/// read INFORMATION_SCHEMA.COLUMNS, matching unquoted parts of the name
+/// case-insensitively.
+pub fn describe_sql(name: &str, dialect: Dialect) -> String {
+    if dialect == Dialect::Snowflake {
+        return format!(
+            "EXECUTE IMMEDIATE $$\nBEGIN\n  DESCRIBE TABLE {};\n  \
+             LET columns RESULTSET := (SELECT \"name\", \"type\", \"null?\", \"default\", \"comment\" \
+             FROM TABLE(RESULT_SCAN(LAST_QUERY_ID())));\n  RETURN TABLE(columns);\nEND;\n$$",
+            name,
+        );
+    }
+    let parts = name_parts(name);
+    let filters: Vec<String> = ["table_name", "table_schema", "table_catalog"].iter()
+        .zip(parts.iter().rev())
+        .map(|(column, (part, quoted))| {
+            let literal = part.replace('\'', "''");
+            if *quoted {
+                format!("{} = '{}'", column, literal)
+            } else {
+                format!("UPPER({}) = UPPER('{}')", column, literal)
+            }
+        })
+        .collect();
+    format!(
+        "SELECT column_name, data_type, is_nullable, column_default FROM information_schema.columns \
+         WHERE {} ORDER BY ordinal_position",
+        filters.join(" AND "),
+    )
+}
+
+/// The parts of a dotted name, unquoted, each with whether it was quoted
+fn name_parts(name: &str) -> Vec<(String, bool)> {
+    let mut parts = vec![(String::new(), false)];
+    let mut chars = name.chars().peekable();
+    let mut in_quotes = false;
+    while let Some(c) = chars.next() {
+        let (part, quoted) = parts.last_mut().expect("one part");
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                part.push('"');
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                *quoted = true;
+            }
+            '.' if !in_quotes => parts.push((String::new(), false)),
+            c => part.push(c),
+        }
+    }
+    parts
+}
+
+/// Every column of a database's tables and views from its INFORMATION_SCHEMA
+pub fn columns(query: ColumnQuery<'_>, database: &str) -> Result<Vec<(String, String, String)>, String> {
+    let sql = format!(
+        "SELECT TABLE_SCHEMA, TABLE_NAME, COLUMN_NAME FROM {}.INFORMATION_SCHEMA.COLUMNS \
+         WHERE TABLE_SCHEMA <> 'INFORMATION_SCHEMA' ORDER BY TABLE_SCHEMA, TABLE_NAME, ORDINAL_POSITION",
+        quote_identifier(database, IdentifierQuoting::Always),
+    );
+    let rows = query(&sql, &["TABLE_SCHEMA", "TABLE_NAME", "COLUMN_NAME"])?;
+    Ok(rows.into_iter()
+        .filter_map(|row| match <[Option<String>; 3]>::try_from(row) {
+            Ok([Some(schema), Some(table), Some(column)]) => Some((schema, table, column)),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Current role, warehouse, database and schema of the session; empty on
+/// error. Other dialects report their user as the role and have no
+/// warehouse.
+pub fn session_context(query: ColumnQuery<'_>, dialect: Dialect) -> SessionContext {
+    let sql = match dialect {
+        Dialect::Snowflake => {
+            "SELECT CURRENT_ROLE() AS role_name, CURRENT_WAREHOUSE() AS warehouse_name, \
+             CURRENT_DATABASE() AS database_name, CURRENT_SCHEMA() AS schema_name, \
+             TO_CHAR(CURRENT_TIMESTAMP(), 'TZH:TZM') AS utc_offset, CURRENT_CLIENT() AS client"
+        }
+        Dialect::Postgres => {
+            "SELECT current_user AS role_name, current_database() AS database_name, current_schema() AS schema_name"
+        }
+        Dialect::SqlServer => "SELECT USER_NAME() AS role_name, DB_NAME() AS database_name, SCHEMA_NAME() AS schema_name",
+        Dialect::DuckDb => "SELECT current_database() AS database_name, current_schema() AS schema_name",
+        Dialect::Generic => return SessionContext::default(),
+    };
+    let columns = ["role_name", "warehouse_name", "database_name", "schema_name", "utc_offset", "client"];
+    let row = query(sql, &columns).ok().and_then(|rows| rows.into_iter().next());
+    match row.map(<[Option<String>; 6]>::try_from) {
+        Some(Ok([role, warehouse, database, schema, utc_offset, client])) => {
+            SessionContext { role, warehouse, database, schema, utc_offset, client }
+        }
+        _ => SessionContext::default(),
+    }
+}
@@ -0,0 +1,215 @@
+use crate::overlay::OverlayOutcome;
+use crate::results::printable;
+use crate::tile_rowstore::NULL_SENTINEL;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    symbols::Marker,
+    text::{Line, Span},
+    widgets::{Axis, Chart, Dataset, GraphType, Paragraph},
+};
+
+/// Rows plotted; the rest of a large result is left out
+pub const MAX_CHART_ROWS: usize = 1000;
+
+/// Colors of the y series, in the order they were picked
+const SERIES_COLORS: [Color; 6] = [Color::Cyan, Color::Yellow, Color::Magenta, Color::Green, Color::LightRed, Color::LightBlue];
+
+/// Columns of a result plotted against one another: an x column and one or
+/// more numeric y columns, as lines or bars. Left / Right pick a column,
+/// x makes it the x axis, Space adds or removes it as a series.
+#[derive(Debug)]
+pub struct ChartView {
+    pub headers: Vec<String>,
+    /// Plotted rows, values in `headers` order
+    rows: Vec<Vec<String>>,
+    /// 0-based result row of `rows[0]`
+    pub first_row: usize,
+    /// Per header: every non-NULL value is a number
+    numeric: Vec<bool>,
+    pub x: usize,
+    pub ys: Vec<usize>,
+    pub bars: bool,
+    /// Column picked with Left / Right
+    pub column: usize,
+}
+
+impl ChartView {
+    /// Chart of `rows`; the first column is x, unless `ys` leaves only
+    /// non-numeric columns to pick from. Without numeric y columns to plot
+    /// there is no chart.
+    pub fn new(headers: Vec<String>, rows: Vec<Vec<String>>, first_row: usize, ys: Vec<usize>) -> Result<Self, String> {
+        let numeric: Vec<bool> = (0..headers.len())
+            .map(|c| {
+                let mut values = rows.iter().filter_map(|r| r.get(c)).filter(|v| *v != NULL_SENTINEL).peekable();
+                values.peek().is_some() && values.all(|v| number(v).is_some())
+            })
+            .collect();
+        let x = (0..headers.len()).find(|c| !ys.contains(c)).unwrap_or(0);
+        let mut ys: Vec<usize> = ys.into_iter().filter(|&c| c != x && numeric.get(c) == Some(&true)).collect();
+        if ys.is_empty() {
+            ys = (0..headers.len()).filter(|&c| c != x && numeric[c]).collect();
+        }
+        if ys.is_empty() {
+            return Err("No numeric columns to chart".to_string());
+        }
+        Ok(Self { headers, rows, first_row, numeric, x, ys, bars: false, column: 0 })
+    }
+
+    /// Rows plotted
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        let last = self.headers.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') => return OverlayOutcome::Close,
+            KeyCode::Left => self.column = self.column.saturating_sub(1),
+            KeyCode::Right => self.column = (self.column + 1).min(last),
+            KeyCode::Home => self.column = 0,
+            KeyCode::End => self.column = last,
+            KeyCode::Char('x') => {
+                self.x = self.column;
+                self.ys.retain(|&c| c != self.column);
+            }
+            KeyCode::Char(' ') if self.column != self.x && self.numeric[self.column] => {
+                if let Some(i) = self.ys.iter().position(|&c| c == self.column) {
+                    self.ys.remove(i);
+                } else {
+                    self.ys.push(self.column);
+                }
+            }
+            KeyCode::Char('t') | KeyCode::Char('b') => self.bars = !self.bars,
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+
+    /// Column strip on the first line, the chart below it
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        if area.height < 2 {
+            return;
+        }
+        let mut spans = Vec::new();
+        for (c, header) in self.headers.iter().enumerate() {
+            let role = if c == self.x {
+                Some(("x ", Style::default().add_modifier(Modifier::BOLD)))
+            } else {
+                self.ys.iter().position(|&y| y == c).map(|i| ("y ", Style::default().fg(series_color(i))))
+            };
+            let (prefix, mut style) = role.unwrap_or(("", Style::default().fg(Color::DarkGray)));
+            if c == self.column {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            spans.push(Span::styled(format!("{}{}", prefix, printable(header)), style));
+            spans.push(Span::raw("  "));
+        }
+        // Keep the picked column on screen
+        let before: usize = spans.iter().take(self.column * 2).map(|s| s.width()).sum();
+        let skip = (before + spans.get(self.column * 2).map_or(0, |s| s.width())).saturating_sub(area.width as usize);
+        frame.render_widget(Paragraph::new(Line::from(spans)).scroll((0, skip as u16)), Rect { height: 1, ..area });
+        let chart_area = Rect { y: area.y + 1, height: area.height - 1, ..area };
+
+        if self.ys.is_empty() {
+            frame.render_widget(Paragraph::new("Pick a y column with Space"), chart_area);
+            return;
+        }
+
+        // A numeric x is plotted to scale, anything else by row
+        let numeric_x = self.numeric[self.x];
+        let xs: Vec<f64> = self.rows.iter().enumerate()
+            .map(|(i, r)| if numeric_x { r.get(self.x).and_then(|v| number(v)).unwrap_or(f64::NAN) } else { i as f64 })
+            .collect();
+        let width = self.ys.len() as f64 + 1.0;
+        let series: Vec<Vec<(f64, f64)>> = self.ys.iter().enumerate()
+            .map(|(n, &c)| {
+                // Bars of a row sit side by side rather than on top of each other
+                let offset = if self.bars && !numeric_x { n as f64 / width } else { 0.0 };
+                self.rows.iter().zip(&xs)
+                    .filter_map(|(r, &x)| Some((x + offset, number(r.get(c)?)?)))
+                    .filter(|(x, _)| x.is_finite())
+                    .collect()
+            })
+            .collect();
+
+        let (mut x_min, mut x_max) = bounds(series.iter().flatten().map(|p| p.0));
+        if !numeric_x {
+            x_min = -0.5;
+            x_max = x_max.max(0.0) + 0.5;
+        }
+        let (mut y_min, mut y_max) = bounds(series.iter().flatten().map(|p| p.1));
+        if self.bars {
+            y_min = y_min.min(0.0);
+            y_max = y_max.max(0.0);
+        }
+        let pad = ((y_max - y_min) * 0.05).max(f64::EPSILON);
+        let (y_min, y_max) = (y_min - pad, y_max + pad);
+
+        let x_labels: Vec<Span> = if numeric_x {
+            vec![axis_label(x_min), axis_label((x_min + x_max) / 2.0), axis_label(x_max)].into_iter().map(Span::raw).collect()
+        } else {
+            let label = |i: usize| self.rows.get(i).and_then(|r| r.get(self.x)).map(|v| printable(v).into_owned()).unwrap_or_default();
+            let last = self.rows.len().saturating_sub(1);
+            vec![Span::raw(label(0)), Span::raw(label(last / 2)), Span::raw(label(last))]
+        };
+        let datasets: Vec<Dataset> = self.ys.iter().zip(&series).enumerate()
+            .map(|(n, (&c, points))| {
+                Dataset::default()
+                    .name(printable(&self.headers[c]).into_owned())
+                    .marker(if self.bars { Marker::HalfBlock } else { Marker::Braille })
+                    .graph_type(if self.bars { GraphType::Bar } else { GraphType::Line })
+                    .style(Style::default().fg(series_color(n)))
+                    .data(points)
+            })
+            .collect();
+        let axis_style = Style::default().fg(Color::DarkGray);
+        let chart = Chart::new(datasets)
+            .x_axis(Axis::default()
+                .title(printable(&self.headers[self.x]).into_owned())
+                .style(axis_style)
+                .bounds([x_min, x_max])
+                .labels(x_labels))
+            .y_axis(Axis::default()
+                .style(axis_style)
+                .bounds([y_min, y_max])
+                .labels(vec![axis_label(y_min), axis_label((y_min + y_max) / 2.0), axis_label(y_max)]));
+        frame.render_widget(chart, chart_area);
+    }
+}
+
+/// A plain number, as written in a result cell
+fn number(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+fn series_color(n: usize) -> Color {
+    SERIES_COLORS[n % SERIES_COLORS.len()]
+}
+
+/// Smallest and largest of `values`; (0, 1) when there are none and a
+/// unit range around a single value
+fn bounds(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    if min > max {
+        (0.0, 1.0)
+    } else if min == max {
+        (min - 0.5, max + 0.5)
+    } else {
+        (min, max)
+    }
+}
+
+/// `value` to a few significant digits, for axis labels
+fn axis_label(value: f64) -> String {
+    let magnitude = value.abs();
+    if magnitude >= 1e6 || (magnitude < 1e-3 && magnitude > 0.0) {
+        format!("{:.2e}", value)
+    } else if magnitude >= 100.0 || value.fract() == 0.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.2}", value)
+    }
+}
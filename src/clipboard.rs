@@ -0,0 +1,111 @@
+use crate::overlay::OverlayOutcome;
+use arboard::Clipboard;
+use base64::Engine;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::{
+    io::{self, Write},
+    sync::{Mutex, OnceLock},
+};
+
+/// Copies kept for pasting from history
+const RING_LEN: usize = 20;
+
+/// Text last copied in this session, pasted when there is no system
+/// clipboard to read
+static INTERNAL: Mutex<String> = Mutex::new(String::new());
+
+/// The last RING_LEN texts copied in this session, newest first
+static RING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The system clipboard, opened on first use and kept open so X11 keeps
+/// serving what was copied; None on headless and SSH sessions
+fn system() -> Option<&'static Mutex<Clipboard>> {
+    static SYSTEM: OnceLock<Option<Mutex<Clipboard>>> = OnceLock::new();
+    SYSTEM.get_or_init(|| Clipboard::new().ok().map(Mutex::new)).as_ref()
+}
+
+/// Copy `text` to the system clipboard or, without one, to the terminal's
+/// through an OSC 52 escape sequence. Either way it is kept for pasting.
+pub fn set_text(text: impl Into<String>) -> io::Result<()> {
+    let text = text.into();
+    let copied = system().is_some_and(|clipboard| clipboard.lock().unwrap().set_text(text.as_str()).is_ok());
+    let result = if copied { Ok(()) } else { write_osc52(&text) };
+    remember(&text);
+    *INTERNAL.lock().unwrap() = text;
+    result
+}
+
+/// Put `text` at the front of the ring; copying it again moves it there
+fn remember(text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    let mut ring = RING.lock().unwrap();
+    ring.retain(|kept| kept != text);
+    ring.insert(0, text.to_string());
+    ring.truncate(RING_LEN);
+}
+
+/// Texts copied in this session, newest first
+pub fn history() -> Vec<String> {
+    RING.lock().unwrap().clone()
+}
+
+/// Text to paste: the system clipboard's, or the last copy made here
+pub fn get_text() -> Option<String> {
+    match system() {
+        Some(clipboard) => clipboard.lock().unwrap().get_text().ok(),
+        None => Some(INTERNAL.lock().unwrap().clone()).filter(|text| !text.is_empty()),
+    }
+}
+
+/// Ask the terminal to set its clipboard. Terminals without OSC 52 ignore
+/// the sequence, so success here doesn't mean anything was copied.
+fn write_osc52(text: &str) -> io::Result<()> {
+    let sequence = format!("\x1b]52;c;{}\x07", base64::engine::general_purpose::STANDARD.encode(text));
+    // tmux only forwards the sequence to the outer terminal when wrapped
+    let sequence = if std::env::var_os("TMUX").is_some() {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    };
+    let mut stdout = io::stdout();
+    stdout.write_all(sequence.as_bytes())?;
+    stdout.flush()
+}
+
+/// Pick among earlier copies to paste; Enter inserts the selected one
+#[derive(Debug)]
+pub struct HistoryList {
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+
+impl HistoryList {
+    pub fn new() -> Self {
+        Self { items: history(), selected: 0 }
+    }
+
+    pub fn selected_item(&self) -> Option<&String> {
+        self.items.get(self.selected)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        let last = self.items.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Enter if !self.items.is_empty() => return OverlayOutcome::Submit,
+            KeyCode::Char('q') => return OverlayOutcome::Close,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            // 1-9 pick an entry directly
+            KeyCode::Char(c @ '1'..='9') if (c as usize - '1' as usize) < self.items.len() => {
+                self.selected = c as usize - '1' as usize;
+                return OverlayOutcome::Submit;
+            }
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+}
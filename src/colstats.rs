@@ -0,0 +1,198 @@
+use crate::tile_rowstore::NULL_SENTINEL;
+use crate::util::fnv1a;
+use std::collections::HashSet;
+
+/// Distinct values counted exactly before switching to the estimate
+const EXACT_DISTINCT_LIMIT: usize = 10_000;
+
+/// HyperLogLog precision: 2^12 registers, about 1.6% standard error
+const HLL_BITS: u32 = 12;
+
+/// Profile of one result column, built by feeding every cell through `add`
+#[derive(Debug)]
+pub struct ColumnStats {
+    pub count: usize,
+    pub nulls: usize,
+    exact: Option<HashSet<String>>,
+    registers: Vec<u8>,
+    min: Option<String>,
+    max: Option<String>,
+    /// Parsed values while every non-NULL cell is numeric; None after the
+    /// first non-numeric one
+    numbers: Option<Vec<f64>>,
+}
+
+impl Default for ColumnStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            nulls: 0,
+            exact: Some(HashSet::new()),
+            registers: vec![0; 1 << HLL_BITS],
+            min: None,
+            max: None,
+            numbers: Some(Vec::new()),
+        }
+    }
+}
+
+impl ColumnStats {
+    pub fn add(&mut self, value: &str) {
+        self.count += 1;
+        if value == NULL_SENTINEL {
+            self.nulls += 1;
+            return;
+        }
+
+        let hash = mix(fnv1a(value.as_bytes()));
+        let idx = (hash >> (64 - HLL_BITS)) as usize;
+        let rank = ((hash << HLL_BITS) | (1 << (HLL_BITS - 1))).leading_zeros() as u8 + 1;
+        self.registers[idx] = self.registers[idx].max(rank);
+        if let Some(exact) = &mut self.exact {
+            if exact.len() < EXACT_DISTINCT_LIMIT {
+                exact.insert(value.to_string());
+            } else if !exact.contains(value) {
+                self.exact = None;
+            }
+        }
+
+        if let Some(numbers) = &mut self.numbers {
+            match value.trim().parse::<f64>() {
+                Ok(n) if n.is_finite() => numbers.push(n),
+                _ => self.numbers = None,
+            }
+        }
+        if self.min.as_deref().is_none_or(|m| value < m) {
+            self.min = Some(value.to_string());
+        }
+        if self.max.as_deref().is_none_or(|m| value > m) {
+            self.max = Some(value.to_string());
+        }
+    }
+
+    /// Exact distinct count, or a HyperLogLog estimate once there are too
+    /// many values to keep; the bool says whether it is exact
+    pub fn distinct(&self) -> (usize, bool) {
+        if let Some(exact) = &self.exact {
+            return (exact.len(), true);
+        }
+        let m = self.registers.len() as f64;
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        let estimate = if estimate <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        };
+        (estimate.round() as usize, false)
+    }
+
+    /// Label/value pairs for the popup. Min and max compare numerically for
+    /// numeric columns and as text otherwise.
+    pub fn finish(mut self) -> Vec<(&'static str, String)> {
+        let (distinct, exact) = self.distinct();
+        let mut lines = vec![
+            ("Rows", self.count.to_string()),
+            ("NULLs", format!("{} ({:.1}%)", self.nulls, percent(self.nulls, self.count))),
+            ("Distinct", if exact { distinct.to_string() } else { format!("~{}", distinct) }),
+        ];
+        match &mut self.numbers {
+            Some(numbers) if !numbers.is_empty() => {
+                numbers.sort_by(|a, b| a.total_cmp(b));
+                let n = numbers.len();
+                let mean = numbers.iter().sum::<f64>() / n as f64;
+                let median = if n % 2 == 1 {
+                    numbers[n / 2]
+                } else {
+                    (numbers[n / 2 - 1] + numbers[n / 2]) / 2.0
+                };
+                lines.push(("Min", numbers[0].to_string()));
+                lines.push(("Max", numbers[n - 1].to_string()));
+                lines.push(("Mean", format!("{:.4}", mean)));
+                lines.push(("Median", median.to_string()));
+            }
+            _ => {
+                lines.push(("Min", self.min.take().unwrap_or_else(|| "-".to_string())));
+                lines.push(("Max", self.max.take().unwrap_or_else(|| "-".to_string())));
+            }
+        }
+        lines
+    }
+}
+
+/// Count/sum/avg/min/max over a block selection. Sum and friends cover the
+/// cells that parse as numbers; count is every non-NULL cell.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SelectionStats {
+    pub count: usize,
+    pub nulls: usize,
+    /// Cells that parsed as numbers
+    pub numeric: usize,
+    pub sum: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl SelectionStats {
+    pub fn add(&mut self, value: &str) {
+        if value == NULL_SENTINEL {
+            self.nulls += 1;
+            return;
+        }
+        self.count += 1;
+        if let Ok(n) = value.trim().parse::<f64>() {
+            if n.is_finite() {
+                self.numeric += 1;
+                self.sum += n;
+                self.min = Some(self.min.map_or(n, |m| m.min(n)));
+                self.max = Some(self.max.map_or(n, |m| m.max(n)));
+            }
+        }
+    }
+
+    /// Label/value pairs; the numeric ones only when a cell was numeric
+    pub fn pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![("Count", self.count.to_string())];
+        if self.nulls > 0 {
+            pairs.push(("NULLs", self.nulls.to_string()));
+        }
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            pairs.push(("Sum", format_number(self.sum)));
+            pairs.push(("Avg", format_number(self.sum / self.numeric as f64)));
+            pairs.push(("Min", format_number(min)));
+            pairs.push(("Max", format_number(max)));
+        }
+        pairs
+    }
+
+    /// One-line form for the grid footer
+    pub fn summary(&self) -> String {
+        self.pairs()
+            .iter()
+            .map(|(label, value)| format!("{} {}", label.to_lowercase(), value))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Whole numbers without a fraction, others to at most 4 decimals
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        return format!("{}", n as i64);
+    }
+    let text = format!("{:.4}", n);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn percent(part: usize, whole: usize) -> f64 {
+    if whole == 0 { 0.0 } else { part as f64 * 100.0 / whole as f64 }
+}
+
+/// Spread FNV's output over all 64 bits (splitmix64 finalizer); HyperLogLog
+/// reads the top bits, which FNV leaves poorly mixed for short strings
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
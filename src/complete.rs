@@ -0,0 +1,72 @@
+use crate::overlay::OverlayOutcome;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Split the name being typed (`db.schema.ta`) into its unquoted qualifier
+/// parts and the partial last part; returns the partial's length in bytes
+/// as typed, which is what a completion replaces
+pub fn split_prefix(line_before_caret: &str) -> (Vec<String>, String, usize) {
+    let is_name = |c: char| c.is_alphanumeric() || matches!(c, '_' | '$' | '.' | '"');
+    let start = line_before_caret.char_indices()
+        .rev()
+        .find(|&(_, c)| !is_name(c))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let typed = &line_before_caret[start..];
+    let mut parts: Vec<&str> = typed.split('.').collect();
+    let partial = parts.pop().unwrap_or_default();
+    let unquote = |s: &str| s.trim_matches('"').to_string();
+    (parts.into_iter().map(unquote).collect(), unquote(partial), partial.len())
+}
+
+/// Completion popup for the name at the caret; typing narrows the list,
+/// Enter or Tab inserts the selected name
+#[derive(Debug)]
+pub struct CompletionList {
+    items: Vec<String>,
+    /// Typed while the popup is open; inserted as part of the completion
+    pub filter: String,
+    pub selected: usize,
+    /// Bytes before the caret the completion replaces
+    pub replace_len: usize,
+}
+
+impl CompletionList {
+    pub fn new(items: Vec<String>, replace_len: usize) -> Self {
+        Self { items, filter: String::new(), selected: 0, replace_len }
+    }
+
+    /// Items starting with the filter
+    pub fn visible(&self) -> Vec<&str> {
+        let filter = self.filter.to_lowercase();
+        self.items.iter()
+            .filter(|item| item.to_lowercase().starts_with(&filter))
+            .map(|item| item.as_str())
+            .collect()
+    }
+
+    pub fn selected_item(&self) -> Option<String> {
+        self.visible().get(self.selected).map(|item| item.to_string())
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        let last = self.visible().len().saturating_sub(1);
+        match key.code {
+            KeyCode::Enter | KeyCode::Tab if self.selected_item().is_some() => return OverlayOutcome::Submit,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+            KeyCode::PageDown => self.selected = (self.selected + 10).min(last),
+            KeyCode::Backspace => {
+                if self.filter.pop().is_none() {
+                    return OverlayOutcome::Close;
+                }
+                self.selected = 0;
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.filter.push(c);
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+}
@@ -1,18 +1,286 @@
+use ratatui::style::Color;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::fs;
+use crate::masking::MaskRule;
+use crate::tile_rowstore::{TileCompression, TileStoreOptions};
+
+/// Config file name, both in the config dir and the legacy exe-dir location
+const CONFIG_FILE_NAME: &str = "Frost.toml";
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
-    /// Snowflake connection string
+    /// File this config was loaded from
+    #[serde(skip)]
+    pub path: PathBuf,
+    
+    /// How queries reach Snowflake: the ODBC driver or the SQL REST API
+    pub backend: Backend,
+    
+    /// SQL dialect of the database behind the ODBC driver; anything but
+    /// snowflake turns off the Snowflake-only features
+    pub dialect: Dialect,
+    
+    /// Snowflake connection string; `${ENV_VAR}` references are expanded at connect time
     pub connection_string: String,
     
+    /// Account and authentication for `backend = "rest"`
+    pub rest: RestConfig,
+    
+    /// Where the connection password comes from
+    pub password_source: PasswordSource,
+    
+    /// Keyring account name; defaults to the UID in the connection string
+    pub keyring_account: Option<String>,
+    
+    /// Environment this profile connects to; "prod" / "production" shows a
+    /// banner and guards statements that write
+    pub environment: Option<String>,
+    
+    /// Refuse writes on a production profile unless Frost was started with
+    /// `--allow-prod-writes`, instead of only asking for confirmation
+    pub prod_writes_need_flag: bool,
+    
+    /// `--allow-prod-writes` was given on the command line
+    #[serde(skip)]
+    pub allow_prod_writes: bool,
+    
+    /// `--read-only` was given: the editor starts refusing edits
+    #[serde(skip)]
+    pub read_only: bool,
+    
+    /// Shrink caches, buffers and undo history for small containers / jump hosts
+    pub low_memory: bool,
+    
+    /// Undo steps the editor keeps; low-memory mode caps it at 100
+    pub undo_depth: usize,
+    
+    /// Keep the undo history of saved files between sessions
+    pub persistent_undo: bool,
+    
+    /// Size limit of the undo history kept per file, in kilobytes
+    pub persistent_undo_max_kb: usize,
+    
+    /// Show the environment health report on every launch, not only when a
+    /// check fails
+    pub health_report_on_start: bool,
+    
+    /// Show cached EXPLAIN bytes and last run time next to each statement
+    pub show_cost_annotations: bool,
+    
+    /// Project folder whose `.sql` files are also scanned for TODO comments
+    pub todo_folder: Option<PathBuf>,
+    
+    /// Rows fetched per result before stopping (0 fetches everything); the
+    /// rest can be fetched in further batches of this size
+    pub max_rows: usize,
+    
+    /// Results up to this many rows and kilobytes of values are kept in
+    /// memory; larger ones go to a temp file
+    pub result_memory_rows: usize,
+    pub result_memory_kb: usize,
+    
+    /// Compression of result tiles in the temp file: lz4, zstd or none
+    pub result_compression: TileCompression,
+    
+    /// Seconds a statement may run before it is cancelled (0 disables)
+    pub query_timeout_secs: u64,
+    
+    /// Connections a `parallel=true` batch runs its statements on at once
+    pub max_parallel_queries: usize,
+    
+    /// Minutes between `SELECT 1` pings that keep an idle session from
+    /// expiring (0 disables)
+    pub keepalive_mins: u64,
+    
+    /// Full redraws every tick, reduced redraws for slow remote terminals,
+    /// or auto (switch to reduced when draws are slow)
+    pub redraw_mode: RedrawMode,
+    
+    /// Seconds between autosaves of unsaved editor content (0 disables)
+    pub autosave_interval_secs: u64,
+    
+    /// Seconds a closed results tab (and its temp file) can still be reopened
+    pub closed_tab_grace_secs: u64,
+    
+    /// How identifiers inserted from the object tree or autocomplete are quoted
+    pub identifier_quoting: IdentifierQuoting,
+    
+    /// Seconds before cached object names of a database are fetched again
+    /// in the background (0 only refreshes on request)
+    pub metadata_max_age_secs: u64,
+    
+    /// Values per `IN (...)` clause when copying a column as an IN list;
+    /// larger lists are split into OR-joined clauses
+    pub in_list_batch_size: usize,
+    
+    /// JSON exports write NUMBER and BOOLEAN columns as JSON numbers and
+    /// booleans rather than strings (t in the export prompt toggles)
+    pub export_typed_json: bool,
+    
+    /// Lines kept visible above and below the editor caret
+    pub scrolloff: usize,
+    
+    /// Rows kept visible above and below the results grid cursor
+    pub grid_scrolloff: usize,
+    
+    /// Center the view when the caret or cursor jumps off screen
+    pub center_on_jump: bool,
+    
+    /// Rows (or editor columns, with Shift) one mouse wheel notch scrolls
+    pub scroll_step: usize,
+    
+    /// Type closing brackets, quotes and `$$` along with the opening ones
+    pub auto_close: bool,
+    
+    /// Case SQL keywords are changed to when a word typed in the editor ends
+    pub keyword_case: KeywordCase,
+    
+    /// Show tabs, trailing spaces and non-breaking spaces as faint glyphs
+    pub show_whitespace: bool,
+    
+    /// Wrap long lines in the editor instead of scrolling sideways
+    pub word_wrap: bool,
+    
+    /// Columns between tab stops when drawing hard tabs
+    pub tab_width: usize,
+    
+    /// Indent with `tab_width` spaces rather than a tab
+    pub insert_spaces: bool,
+    
+    /// strftime format of timestamps inserted by the insert_now_* actions
+    pub now_format: String,
+    
+    /// Wrap inserted timestamps in single quotes (epoch values never are)
+    pub now_quoted: bool,
+    
+    /// Columns masked as rows are fetched, so raw values never reach the
+    /// grid or exports (e.g. for production connections)
+    pub masking: Vec<MaskRule>,
+    
+    /// Key overrides for workspace actions, e.g. `run_query = "F5"`
+    pub keys: HashMap<String, String>,
+    
+    /// Snippet bodies by trigger, added to and replacing the built-in ones
+    pub snippets: HashMap<String, String>,
+    
     /// Theme colors (all RGB values)
     pub colors: ColorConfig,
 }
 
+/// Convert a config RGB triple to a terminal color
+pub fn rgb(c: [u8; 3]) -> Color {
+    Color::Rgb(c[0], c[1], c[2])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Snowflake ODBC driver, configured by `connection_string`
+    #[default]
+    Odbc,
+    /// Snowflake SQL API over HTTPS; no driver to install, but no PUT/GET
+    Rest,
+}
+
+/// Case keywords are changed to as they are typed
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeywordCase {
+    /// Left as typed
+    #[default]
+    Off,
+    Upper,
+    Lower,
+}
+
+/// Database the ODBC backend talks to. Only Snowflake gets secondary
+/// roles, scripting blocks, SHOW-based pickers, stages, GET_DDL, EXPLAIN
+/// and query IDs; the others run plain statements.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dialect {
+    #[default]
+    Snowflake,
+    Postgres,
+    SqlServer,
+    DuckDb,
+    /// Any other ODBC driver; standard SQL only
+    Generic,
+}
+
+impl Dialect {
+    pub fn name(self) -> &'static str {
+        match self {
+            Dialect::Snowflake => "snowflake",
+            Dialect::Postgres => "postgres",
+            Dialect::SqlServer => "sqlserver",
+            Dialect::DuckDb => "duckdb",
+            Dialect::Generic => "generic",
+        }
+    }
+
+    /// Error for a feature only Snowflake has
+    pub fn unsupported(self, feature: &str) -> String {
+        format!("{} needs dialect = \"snowflake\" (this connection is {})", feature, self.name())
+    }
+}
+
+/// Connection settings of the SQL API backend. Authenticates with a key
+/// pair (the public key registered with `ALTER USER ... SET RSA_PUBLIC_KEY`)
+/// or, without a key, an OAuth access token.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestConfig {
+    /// Account identifier, e.g. `myorg-myaccount` or `xy12345.eu-west-1`
+    pub account: String,
+    pub user: String,
+    /// Unencrypted PKCS#8 (or PKCS#1) PEM private key
+    pub private_key_path: Option<PathBuf>,
+    /// OAuth access token; `${ENV_VAR}` references are expanded
+    pub oauth_token: Option<String>,
+    /// Session context sent with each statement; unset ones use the user's defaults
+    pub role: Option<String>,
+    pub warehouse: Option<String>,
+    pub database: Option<String>,
+    pub schema: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PasswordSource {
+    /// Password (if any) is part of `connection_string`
+    #[default]
+    Inline,
+    /// Password is read from the OS keyring (service "frost") and appended as PWD
+    Keyring,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedrawMode {
+    /// Start in full mode, switch to reduced if draws are consistently slow
+    #[default]
+    Auto,
+    Full,
+    /// Redraw only on input or batched status changes; no running timer
+    Reduced,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdentifierQuoting {
+    /// Quote only when the name would not resolve unquoted
+    #[default]
+    Smart,
+    Always,
+    Never,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ColorConfig {
     // Editor colors
     pub editor_border: [u8; 3],
@@ -24,6 +292,7 @@ pub struct ColorConfig {
     pub selection_fg: [u8; 3],
     pub selection_bg: [u8; 3],
     pub bracket_match_bg: [u8; 3],
+    pub word_occurrence_bg: [u8; 3],
     
     // Results colors
     pub results_border: [u8; 3],
@@ -34,6 +303,7 @@ pub struct ColorConfig {
     pub table_sel_bg: [u8; 3],
     pub table_caret_fg: [u8; 3],
     pub table_caret_bg: [u8; 3],
+    pub null_fg: [u8; 3],
 
     // Find/Search colors
     pub find_match_fg: [u8; 3],
@@ -41,6 +311,10 @@ pub struct ColorConfig {
     pub find_current_fg: [u8; 3],
     pub find_current_bg: [u8; 3],
 
+    // Diff colors
+    pub diff_added: [u8; 3],
+    pub diff_removed: [u8; 3],
+
     // Autocomplete colors
     pub autocomplete_bg: [u8; 3],
     pub autocomplete_border: [u8; 3],
@@ -69,6 +343,9 @@ pub struct ColorConfig {
 impl Default for Config {
     fn default() -> Self {
         Self {
+            path: PathBuf::new(),
+            backend: Backend::default(),
+            dialect: Dialect::default(),
             connection_string: String::from(
                 "Driver=SnowflakeDSIIDriver;\
                 Server=your-account.snowflakecomputing.com;\
@@ -79,6 +356,49 @@ impl Default for Config {
                 Database=your_database;\
                 Schema=your_schema;"
             ),
+            rest: RestConfig::default(),
+            password_source: PasswordSource::default(),
+            keyring_account: None,
+            environment: None,
+            prod_writes_need_flag: false,
+            allow_prod_writes: false,
+            read_only: false,
+            low_memory: false,
+            undo_depth: 1000,
+            persistent_undo: true,
+            persistent_undo_max_kb: 1024,
+            health_report_on_start: false,
+            show_cost_annotations: true,
+            todo_folder: None,
+            max_rows: 100_000,
+            result_memory_rows: 10_000,
+            result_memory_kb: 4096,
+            result_compression: TileCompression::default(),
+            query_timeout_secs: 0,
+            max_parallel_queries: 4,
+            keepalive_mins: 60,
+            redraw_mode: RedrawMode::default(),
+            autosave_interval_secs: 5,
+            closed_tab_grace_secs: 300,
+            identifier_quoting: IdentifierQuoting::default(),
+            metadata_max_age_secs: 86400,
+            in_list_batch_size: 1000,
+            export_typed_json: true,
+            scrolloff: 3,
+            grid_scrolloff: 2,
+            center_on_jump: true,
+            scroll_step: 3,
+            auto_close: true,
+            keyword_case: KeywordCase::default(),
+            show_whitespace: false,
+            word_wrap: true,
+            tab_width: 4,
+            insert_spaces: true,
+            now_format: "%Y-%m-%dT%H:%M:%S%.3f%:z".to_string(),
+            now_quoted: true,
+            masking: Vec::new(),
+            keys: HashMap::new(),
+            snippets: HashMap::new(),
             colors: ColorConfig::default(),
         }
     }
@@ -97,6 +417,7 @@ impl Default for ColorConfig {
             selection_fg: [200, 200, 200],         // RICE_PAPER
             selection_bg: [54, 54, 70],            // DUSKY_SLATE
             bracket_match_bg: [45, 79, 103],       // DEEP_SEA (bluish for bracket matching)
+            word_occurrence_bg: [42, 42, 55],      // INDIGO_SHADOW
             
             // Results colors
             results_border: [42, 42, 55],          // INDIGO_SHADOW
@@ -107,6 +428,7 @@ impl Default for ColorConfig {
             table_sel_bg: [84, 84, 109],           // STEEL_VIOLET
             table_caret_fg: [22, 22, 22],          // INKSTONE
             table_caret_bg: [238, 185, 225],       // SAKURA_PETAL
+            null_fg: [84, 84, 109],                // STEEL_VIOLET
             
             // Find/Search colors
             find_match_fg: [22, 22, 22],           // INKSTONE
@@ -114,6 +436,10 @@ impl Default for ColorConfig {
             find_current_fg: [22, 22, 22],         // INKSTONE  
             find_current_bg: [238, 185, 225],      // SAKURA_PETAL (same as caret)
 
+            // Diff colors
+            diff_added: [106, 149, 137],           // PINE_NEEDLE
+            diff_removed: [232, 36, 36],           // TORII_VERMILION
+
             // Autocomplete colors
             autocomplete_bg: [30, 31, 40],      // Dark background (OBSIDIAN_FOG)
             autocomplete_border: [84, 84, 109], // STEEL_VIOLET
@@ -142,10 +468,18 @@ impl Default for ColorConfig {
 }
 
 impl Config {
-    pub fn load() -> anyhow::Result<Self> {
-        let config_path = Self::config_path()?;
+    /// Load the config from `override_path` (the `--config` flag) or from the
+    /// per-user config directory, migrating a legacy exe-dir file if present.
+    pub fn load(override_path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let config_path = match override_path {
+            Some(path) => path,
+            None => Self::config_path()?,
+        };
         
         if !config_path.exists() {
+            if let Some(parent) = config_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
             Self::create_default_config(&config_path)?;
             return Err(anyhow::anyhow!(
                 "Created config file at: {}. Please edit it with your Snowflake connection details.", 
@@ -154,20 +488,143 @@ impl Config {
         }
         
         let contents = fs::read_to_string(&config_path)?;
-        let config: Config = toml::from_str(&contents)?;
+        let mut config: Config = toml::from_str(&contents)?;
+        config.path = config_path;
         Ok(config)
     }
     
+    /// Connection string with `${ENV_VAR}` references expanded and, for
+    /// `password_source = "keyring"`, the keyring password appended as PWD.
+    pub fn resolve_connection_string(&self) -> anyhow::Result<String> {
+        let mut conn_str = crate::secrets::interpolate_env(&self.connection_string)?;
+        if self.password_source == PasswordSource::Keyring {
+            let account = self.keyring_account.clone()
+                .or_else(|| crate::secrets::connection_string_value(&conn_str, "UID"))
+                .ok_or_else(|| anyhow::anyhow!("password_source = \"keyring\" needs keyring_account or a UID in connection_string"))?;
+            let password = crate::secrets::keyring_password("frost", &account)?;
+            let trimmed = conn_str.trim_end();
+            if !trimmed.is_empty() && !trimmed.ends_with(';') {
+                conn_str = format!("{};", trimmed);
+            } else {
+                conn_str = trimmed.to_string();
+            }
+            conn_str.push_str(&format!("PWD={{{}}};", password.replace('}', "}}")));
+        }
+        Ok(conn_str)
+    }
+    
+    /// Profile tagged `environment = "prod"` (or "production")
+    pub fn is_production(&self) -> bool {
+        self.environment.as_deref().is_some_and(|env| {
+            env.eq_ignore_ascii_case("prod") || env.eq_ignore_ascii_case("production")
+        })
+    }
+    
+    /// Tile store sizing for query results
+    pub fn tile_store_options(&self) -> TileStoreOptions {
+        let (memory_rows, memory_bytes) = (self.result_memory_rows, self.result_memory_kb * 1024);
+        let compression = self.result_compression;
+        if self.low_memory {
+            let low = TileStoreOptions::low_memory();
+            TileStoreOptions {
+                memory_rows: memory_rows.min(low.memory_rows),
+                memory_bytes: memory_bytes.min(low.memory_bytes),
+                compression,
+                ..low
+            }
+        } else {
+            TileStoreOptions { memory_rows, memory_bytes, compression, ..TileStoreOptions::default() }
+        }
+    }
+    
+    /// Timeout for a statement: its `-- frost: timeout=` directive, else
+    /// `query_timeout_secs`; zero means none
+    pub fn query_timeout(&self, directive_secs: Option<u64>) -> Option<std::time::Duration> {
+        match directive_secs.unwrap_or(self.query_timeout_secs) {
+            0 => None,
+            secs => Some(std::time::Duration::from_secs(secs)),
+        }
+    }
+    
+    /// Idle time after which the worker pings the session; None if disabled
+    pub fn keepalive(&self) -> Option<std::time::Duration> {
+        match self.keepalive_mins {
+            0 => None,
+            mins => Some(std::time::Duration::from_secs(mins * 60)),
+        }
+    }
+    
+    /// Size limit of the undo history kept per file; None if it isn't kept
+    pub fn persistent_undo_limit(&self) -> Option<usize> {
+        self.persistent_undo.then_some(self.persistent_undo_max_kb * 1024)
+    }
+    
+    /// Dialect statements are written in; the REST backend is always Snowflake
+    pub fn sql_dialect(&self) -> Dialect {
+        match self.backend {
+            Backend::Odbc => self.dialect,
+            Backend::Rest => Dialect::Snowflake,
+        }
+    }
+    
+    /// Maximum number of undo groups kept by the editor
+    pub fn undo_limit(&self) -> usize {
+        if self.low_memory { self.undo_depth.min(100) } else { self.undo_depth }
+    }
+    
+    /// Resolve the config file location.
+    ///
+    /// Prefers `~/.config/frost/Frost.toml` (or `$XDG_CONFIG_HOME/frost`) on
+    /// Linux/macOS and `%APPDATA%\frost\Frost.toml` on Windows. A `Frost.toml`
+    /// next to the executable is copied there on first run; if the copy fails
+    /// (e.g. no home directory) the exe-dir file is used in place.
     fn config_path() -> anyhow::Result<PathBuf> {
+        let legacy = Self::legacy_config_path()?;
+        let Some(dir) = Self::config_dir() else {
+            return Ok(legacy);
+        };
+        let preferred = dir.join(CONFIG_FILE_NAME);
+        
+        if !preferred.exists() && legacy.exists() {
+            if Self::migrate_legacy_config(&legacy, &dir).is_err() {
+                return Ok(legacy);
+            }
+        }
+        Ok(preferred)
+    }
+    
+    fn config_dir() -> Option<PathBuf> {
+        #[cfg(target_os = "windows")]
+        {
+            directories::BaseDirs::new().map(|d| d.config_dir().join("frost"))
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            std::env::var_os("XDG_CONFIG_HOME")
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| directories::BaseDirs::new().map(|d| d.home_dir().join(".config")))
+                .map(|base| base.join("frost"))
+        }
+    }
+    
+    fn legacy_config_path() -> anyhow::Result<PathBuf> {
         let exe_path = std::env::current_exe()?;
         let exe_dir = exe_path.parent()
             .ok_or_else(|| anyhow::anyhow!("Could not find executable directory"))?;
-        Ok(exe_dir.join("Frost.toml"))
+        Ok(exe_dir.join(CONFIG_FILE_NAME))
+    }
+    
+    fn migrate_legacy_config(legacy: &Path, dir: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(dir)?;
+        write_atomic(&dir.join(CONFIG_FILE_NAME), &fs::read(legacy)?)?;
+        Ok(())
     }
     
-    fn create_default_config(path: &PathBuf) -> anyhow::Result<()> {
+    fn create_default_config(path: &Path) -> anyhow::Result<()> {
         let default_toml = r#"# Frost Configuration
-# Place this file in the same directory as the Frost executable
+# Location: ~/.config/frost/Frost.toml (Linux/macOS) or %APPDATA%\frost\Frost.toml (Windows)
+# A different file can be used with: frost --config <path>
 
 # Snowflake connection string
 connection_string = """
@@ -181,6 +638,210 @@ Database=your_database;
 Schema=your_schema;
 """
 
+# Connect through the Snowflake ODBC driver ("odbc", using connection_string)
+# or the SQL REST API ("rest", using the [rest] section below; no driver to
+# install, but no PUT/GET for stages)
+backend = "odbc"
+
+# Database behind the ODBC driver: "snowflake", "postgres", "sqlserver",
+# "duckdb" or "generic". Other than snowflake, secondary roles, script=true
+# wrapping, the role/warehouse/database pickers, stages, DDL, EXPLAIN and
+# query IDs are turned off and statements are sent as written
+dialect = "snowflake"
+
+# Keep secrets out of this file: ${ENV_VAR} in connection_string is replaced
+# with the environment variable's value, e.g. PWD=${SNOWFLAKE_PASSWORD};
+# Or read the password from the OS keyring (service "frost", account = UID):
+# password_source = "keyring"
+# keyring_account = "your-email@example.com"
+
+# Tag profiles that connect to production. A production profile shows a red
+# banner and asks for confirmation before running anything but SELECT / WITH,
+# SHOW, DESCRIBE, EXPLAIN, LIST and USE. With `prod_writes_need_flag = true`
+# such statements are refused unless Frost was started with
+# --allow-prod-writes.
+# environment = "prod"
+# prod_writes_need_flag = false
+
+# Low-memory mode: smaller result tile cache, no pinned tiles for huge results,
+# shorter undo history and smaller I/O buffers
+low_memory = false
+
+# Undo steps kept by the editor. A run of typing or of deleting is one step,
+# as is a paste, an indent or a replace
+undo_depth = 1000
+
+# Keep the undo history of a file when it is saved, so it can be undone past
+# the save after reopening it. Histories are stored in the data directory
+# (e.g. ~/.local/share/frost/undo), dropping the oldest steps beyond the size
+# limit, and are ignored if the file was changed elsewhere in between
+persistent_undo = true
+persistent_undo_max_kb = 1024
+
+# Show the environment health report (config path, ODBC driver, clipboard,
+# terminal colors, temp space, last session) on every launch. It is always
+# shown when a check fails, and F12 opens it at any time.
+health_report_on_start = false
+
+# Show "~2.1 GB scanned, 14s last run" after the first line of each statement
+# that has been explained (F6, or F8 to explain every statement) or run on
+# its own
+show_cost_annotations = true
+
+# F9 lists the -- TODO: and -- FIXME: comments in the editor buffer. Set a
+# project folder to include the .sql files under it as well.
+# todo_folder = "/path/to/project"
+
+# Stop fetching a result after this many rows (0 fetches everything). A cut
+# off result says so in its footer; M in the results pane fetches the next
+# max_rows rows into the same tab.
+max_rows = 100000
+
+# Results up to this many rows and kilobytes are kept in memory; a larger one
+# is written to a temp file as it arrives. Low-memory mode caps these at 1000
+# rows and 512 KB.
+result_memory_rows = 10000
+result_memory_kb = 4096
+
+# Compression of the result tiles written to the temp file: "lz4", "zstd"
+# (smaller, slower to write) or "none" (read straight from the mapped file,
+# no decoding, but more temp space)
+result_compression = "lz4"
+
+# Cancel statements running longer than this many seconds (0 disables).
+# Override for one statement with a comment: -- frost: timeout=600
+query_timeout_secs = 0
+
+# Statements of a batch that starts with -- frost: parallel=true run at the
+# same time, each on its own connection (opened with the current role,
+# warehouse, database and schema), up to this many at once. Only use it for
+# statements that don't depend on each other.
+max_parallel_queries = 4
+
+# Run SELECT 1 after this many idle minutes so Snowflake doesn't expire the
+# session between queries (0 disables). No pings are sent while a query runs.
+keepalive_mins = 60
+
+# Redrawing over slow SSH links: "full" redraws continuously, "reduced" only
+# redraws on input and batches status updates (no running timer), "auto"
+# switches to reduced when drawing is consistently slow
+redraw_mode = "auto"
+
+# Seconds between crash-recovery autosaves of unsaved editor content (0 disables)
+autosave_interval_secs = 5
+
+# Seconds a closed results tab can be reopened with Ctrl+Shift+T
+closed_tab_grace_secs = 300
+
+# Quoting of identifiers inserted from the object tree or autocomplete:
+# "smart" (only when required), "always", or "never"
+identifier_quoting = "smart"
+
+# Database, schema, table and column names for autocomplete (Ctrl+Space) are
+# cached on disk per account and user. Databases whose names are older than
+# this many seconds are fetched again in the background after connecting
+# (0 only refreshes on Shift+F8).
+metadata_max_age_secs = 86400
+
+# Values per IN (...) clause when copying a results column as an IN list
+# (I in the results pane); longer lists become several clauses joined with OR
+in_list_batch_size = 1000
+
+# JSON and JSON lines exports write NUMBER and BOOLEAN columns as JSON numbers
+# and booleans; false writes every value as a string. t in the export prompt
+# switches for one export.
+export_typed_json = true
+
+# Lines kept visible around the caret in the editor (`scrolloff`) and around
+# the cursor row in the results grid (`grid_scrolloff`). Ctrl+D / Ctrl+U in the
+# grid and Alt+D / Ctrl+U in the editor, where Ctrl+D adds the next occurrence
+# as a caret, move half a page, PageUp / PageDown a full page. Ctrl+Up / Ctrl+Down scroll the editor a line, moving the caret
+# only to keep it `scrolloff` lines from the edge; Ctrl+L centers it.
+# `center_on_jump` re-centers the view when a jump (search match, Ctrl+Home,
+# undo far away) lands off screen instead of scrolling it to the edge.
+scrolloff = 3
+grid_scrolloff = 2
+center_on_jump = true
+
+# Rows one mouse wheel notch scrolls the editor or results grid. Shift+wheel
+# (or a sideways wheel) scrolls the editor that many columns when word wrap
+# is off, and the grid one column.
+scroll_step = 3
+
+# Typing ( [ ' or " also types its closer after the caret, typing the closer
+# steps over it, and typing an opener over a selection wraps it. A second $
+# opens a $$ ... $$ block for Snowflake scripting.
+auto_close = true
+
+# Change SQL keywords to "upper" or "lower" case as each word is finished
+# ("off" leaves them as typed). Alt+Shift+U / L / T convert the selection or
+# the word at the caret to upper, lower or title case.
+keyword_case = "off"
+
+# Draw tabs as →, trailing spaces as · and non-breaking spaces as ␣, which
+# Snowflake rejects as unexpected characters when they come in pasted SQL.
+# Alt+W toggles it in the editor.
+show_whitespace = false
+
+# Wrap lines wider than the editor. Off, long lines run past the edge and the
+# view scrolls sideways to keep the caret in sight (Shift+wheel scrolls it by
+# hand). Alt+Z toggles it in the editor.
+word_wrap = true
+
+# Hard tabs are drawn up to the next multiple of `tab_width` columns.
+# Alt+. (indent_lines) inserts `tab_width` spaces, or a tab with
+# `insert_spaces = false`, before the caret's line or the selected lines, and
+# Alt+, (outdent_lines) takes one level off; Alt+I (convert_indentation)
+# rewrites the whole buffer's indentation that way.
+tab_width = 4
+insert_spaces = true
+
+# Timestamps inserted at the caret (F7 UTC, Shift+F7 session time zone,
+# Ctrl+F7 epoch seconds). `now_format` uses strftime syntax; the default
+# is ISO-8601 with offset. `now_quoted` wraps them in single quotes.
+now_format = "%Y-%m-%dT%H:%M:%S%.3f%:z"
+now_quoted = true
+
+# Mask sensitive columns as rows are fetched. `column` is a regex matched
+# against result column names; `mask` is "hash", "last4" or "redact".
+# Masked values never reach the grid, the clipboard or exports. A rule with
+# `environments` only applies to profiles tagged with one of them. "hash"
+# digests are keyed with a random per-install secret kept in the data
+# directory (mask.key), so they match within this install only.
+# [[masking]]
+# column = "(?i)^(email|phone)$"
+# mask = "hash"
+# environments = ["prod"]
+
+# SQL REST API connection for backend = "rest". Authenticate with a key pair
+# (an unencrypted PEM private key whose public key is registered with
+# ALTER USER ... SET RSA_PUBLIC_KEY = '...') or an OAuth access token.
+# [rest]
+# account = "myorg-myaccount"
+# user = "your_user"
+# private_key_path = "/home/you/.ssh/snowflake_rsa_key.p8"
+# oauth_token = "${SNOWFLAKE_OAUTH_TOKEN}"
+# role = "your_role"
+# warehouse = "your_warehouse"
+# database = "your_database"
+# schema = "your_schema"
+
+# Key overrides for workspace actions (press F1 in Frost for the full list)
+# Actions: quit, save, switch_focus, run_query, cancel_query, cancel_queue,
+# explain_plan, explain_json, refresh_costs, switch_context, health_report,
+# todo_list, goto_line, toggle_read_only, diff_buffer, paste_history,
+# convert_indentation, indent_lines, outdent_lines, stage_browser, object_tree, show_ddl, preview_table, describe_table, insert_now_utc, insert_now_session, insert_now_epoch,
+# perf_overlay, autocomplete, refresh_metadata, grow_editor, shrink_editor,
+# editor_only, results_only, show_both, reopen_tab, help
+[keys]
+# run_query = "F5"
+
+# Snippets: type a trigger and press Tab to expand it. Tab then visits $1,
+# $2, ... in turn (${1:text} fills one in, selected) and ends at $0. Built
+# in: sel, merge, copy, task; an entry here with the same name replaces one.
+[snippets]
+# top = "SELECT TOP ${1:10} * FROM $2"
+
 # Theme colors - all values are RGB arrays [red, green, blue]
 # You can customize any of these colors to your preference
 
@@ -195,6 +856,7 @@ caret_cell_bg = [238, 185, 225]         # Cursor background color
 selection_fg = [200, 200, 200]          # Selected text foreground
 selection_bg = [54, 54, 70]             # Selected text background
 bracket_match_bg = [45, 79, 103]        # Background for matching brackets
+word_occurrence_bg = [42, 42, 55]       # Other uses of the name under the caret
 
 # Results pane colors
 results_border = [42, 42, 55]           # Border when results pane is not focused
@@ -205,6 +867,7 @@ table_sel_fg = [200, 200, 200]          # Selected cell foreground
 table_sel_bg = [84, 84, 109]            # Selected cell background
 table_caret_fg = [22, 22, 22]           # Cursor cell foreground
 table_caret_bg = [238, 185, 225]        # Cursor cell background
+null_fg = [84, 84, 109]                 # NULL cells (dim italic)
 
 # Find/Search colors
 find_match_fg = [22, 22, 22]            # Search match foreground
@@ -212,6 +875,10 @@ find_match_bg = [84, 54, 77]            # Search match background (dark sakura)
 find_current_fg = [22, 22, 22]          # Current search match foreground  
 find_current_bg = [238, 185, 225]       # Current search match background (sakura petal)
 
+# Diff colors
+diff_added = [106, 149, 137]            # Lines only in the buffer
+diff_removed = [232, 36, 36]            # Lines only in the saved file
+
 # Autocomplete colors
 autocomplete_bg = [30, 31, 40]           # Dark background
 autocomplete_border = [84, 84, 109]      # Border color
@@ -236,7 +903,30 @@ syntax_function = [210, 126, 153]       # Function names
 syntax_variable = [230, 195, 132]       # Variables and parameters
 syntax_plain = [200, 200, 200]          # Plain text
 "#;
-        fs::write(path, default_toml)?;
+        write_atomic(path, default_toml.as_bytes())?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Replace `path` without ever leaving a half-written file: write a temp file
+/// next to it, sync, then rename over the original. The previous version is
+/// kept as `<name>.bak`.
+fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    
+    let file_name = path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| CONFIG_FILE_NAME.to_string());
+    let tmp_path = path.with_file_name(format!("{}.tmp", file_name));
+    let bak_path = path.with_file_name(format!("{}.bak", file_name));
+    
+    let mut tmp = fs::File::create(&tmp_path)?;
+    tmp.write_all(contents)?;
+    tmp.sync_all()?;
+    drop(tmp);
+    
+    if path.exists() {
+        fs::copy(path, &bak_path)?;
+    }
+    fs::rename(&tmp_path, path)
+}
@@ -1,9 +1,24 @@
+use crate::catalog;
+use crate::config::{Backend, Config, Dialect, IdentifierQuoting};
+use crate::cost::split_statements;
+use crate::directive::Directives;
+use crate::export::ColumnKind;
+use crate::identifier::quote_identifier;
+use crate::masking::{ColumnMask, Masker};
+use crate::plan::{PlanFormat, PlanView};
+use crate::rest::RestExecutor;
 use crate::results::ResultsContent;
-use crate::tile_rowstore::TileRowStore;
-use odbc::{create_environment_v3, Statement, ResultSetState, Data, Handle};
-use odbc::ffi::{SQLCancel, SQLHSTMT};
+use crate::secrets::connection_string_value;
+use crate::stages::StageFile;
+use crate::switcher::{ContextKind, SessionContext};
+use crate::tile_rowstore::{TileRowStore, TileStoreOptions, NULL_SENTINEL};
+use odbc::{create_environment_v3, Connection, Environment, Statement, ResultSetState, Data, Executed, Handle, HasResult, Version3};
+use odbc::odbc_safe::AutocommitOn;
+use odbc::ffi::{SqlDataType, SQLCancel, SQLHSTMT};
 use std::{
+    collections::{HashMap, VecDeque},
     sync::{Arc, Mutex},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
     sync::mpsc::{self, Receiver, Sender},
     thread,
     time::{Duration, Instant},
@@ -14,42 +29,441 @@ pub struct SafeStmt(SQLHSTMT);
 unsafe impl Send for SafeStmt {}
 unsafe impl Sync for SafeStmt {}
 
+/// Lets the UI thread cancel the running statement directly; the worker is
+/// blocked inside ODBC calls while a query runs and can't read requests.
+#[derive(Clone)]
+pub struct CancelHandle {
+    stmt: Arc<Mutex<Option<SafeStmt>>>,
+    requested: Arc<AtomicBool>,
+    /// The cancel came from the query timeout watchdog, not the user
+    timed_out: Arc<AtomicBool>,
+    /// Skip the rest of the batch but let the running statement finish
+    queue_dropped: Arc<AtomicBool>,
+    /// Statements of a parallel batch running on pool connections, by
+    /// batch index
+    pool: Arc<Mutex<HashMap<usize, CancelHandle>>>,
+}
+
+impl CancelHandle {
+    fn new() -> Self {
+        Self {
+            stmt: Arc::new(Mutex::new(None)),
+            requested: Arc::new(AtomicBool::new(false)),
+            timed_out: Arc::new(AtomicBool::new(false)),
+            queue_dropped: Arc::new(AtomicBool::new(false)),
+            pool: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+    
+    /// Don't start any more statements of the running batch
+    pub fn drop_queue(&self) {
+        self.queue_dropped.store(true, Ordering::SeqCst);
+    }
+    
+    /// Abort the running statement (every one of a parallel batch) and
+    /// stop the worker fetching its rows
+    pub fn cancel(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        {
+            // Holding the lock keeps the worker from freeing the handle meanwhile
+            let current = self.stmt.lock().unwrap();
+            if let Some(SafeStmt(handle)) = *current {
+                unsafe {
+                    let _ = SQLCancel(handle);
+                }
+            }
+        }
+        for handle in self.pool.lock().unwrap().values() {
+            handle.cancel();
+        }
+    }
+    
+    /// Abort statement `idx` of a parallel batch, leaving the others
+    /// running; false if it is not running on a pool connection
+    pub fn cancel_statement(&self, idx: usize) -> bool {
+        match self.pool.lock().unwrap().get(&idx) {
+            Some(handle) => {
+                handle.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+    
+    fn cancel_for_timeout(&self) {
+        self.timed_out.store(true, Ordering::SeqCst);
+        self.cancel();
+    }
+    
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+    
+    pub fn is_queue_dropped(&self) -> bool {
+        self.queue_dropped.load(Ordering::SeqCst)
+    }
+    
+    pub fn reset(&self) {
+        self.requested.store(false, Ordering::SeqCst);
+        self.timed_out.store(false, Ordering::SeqCst);
+        self.queue_dropped.store(false, Ordering::SeqCst);
+    }
+    
+    /// Cancel the running statement if it is still going after `timeout`.
+    /// The watchdog stands down when the returned sender is dropped.
+    pub fn watchdog(&self, timeout: Duration) -> Sender<()> {
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let handle = self.clone();
+        thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = done_rx.recv_timeout(timeout) {
+                handle.cancel_for_timeout();
+            }
+        });
+        done_tx
+    }
+    
+    /// Response for a statement stopped by `cancel` or the watchdog
+    pub fn stopped_response(&self, query_idx: usize, elapsed: Duration, timeout: Option<Duration>) -> DbWorkerResponse {
+        match timeout {
+            Some(timeout) if self.timed_out.load(Ordering::SeqCst) => DbWorkerResponse::QueryError {
+                query_idx,
+                elapsed,
+                query_id: None,
+                message: format!("Query timed out after {}s and was cancelled", timeout.as_secs()),
+            },
+            _ => DbWorkerResponse::QueryCancelled { query_idx, elapsed },
+        }
+    }
+    
+    /// Publish `handle` as the running statement until the guard is dropped
+    fn register(&self, handle: SQLHSTMT) -> RunningStmt<'_> {
+        *self.stmt.lock().unwrap() = Some(SafeStmt(handle));
+        RunningStmt(self)
+    }
+}
+
+/// Clears the registered statement handle on every exit path
+struct RunningStmt<'a>(&'a CancelHandle);
+
+impl Drop for RunningStmt<'_> {
+    fn drop(&mut self) {
+        *self.0.stmt.lock().unwrap() = None;
+    }
+}
+
+/// One statement to execute, with the settings it runs under
+#[derive(Debug, Clone)]
+pub struct QueryRequest {
+    pub sql: String,
+    pub context: String,
+    /// Temporary warehouse for this statement only (from a `-- frost:` directive)
+    pub warehouse: Option<String>,
+    /// Set for `EXPLAIN` statements; the output is returned as a plan tab
+    pub explain: Option<PlanFormat>,
+    /// Cancel the statement and report an error if it runs longer than this
+    pub timeout: Option<Duration>,
+}
+
+/// Lines `EXECUTE IMMEDIATE $$` puts in front of a scripting block, which
+/// error positions count
+pub const SCRIPT_WRAPPER_LINES: usize = 1;
+
+impl QueryRequest {
+    /// The statements of `sql` as requests, each with the byte offset its
+    /// text starts at. With the `script` directive the whole text is one
+    /// request, on Snowflake wrapped in `EXECUTE IMMEDIATE` and elsewhere
+    /// sent as written for the driver to run as a batch.
+    pub fn batch(sql: &str, directives: &Directives, timeout: Option<Duration>, dialect: Dialect) -> Result<Vec<(usize, QueryRequest)>, String> {
+        if directives.warehouse.is_some() && dialect != Dialect::Snowflake {
+            return Err(dialect.unsupported("The warehouse directive"));
+        }
+        let request = |sql: String| QueryRequest {
+            context: statement_label(&sql),
+            sql,
+            warehouse: directives.warehouse.clone(),
+            explain: None,
+            timeout,
+        };
+        if directives.script && dialect != Dialect::Snowflake {
+            let mut script = request(sql.to_string());
+            script.context = "Script".to_string();
+            return Ok(vec![(0, script)]);
+        }
+        if directives.script {
+            if sql.contains("$$") {
+                return Err("A script=true batch can't contain $$; quote strings in it with ' instead".to_string());
+            }
+            let mut script = request(format!("EXECUTE IMMEDIATE $$\n{}\n$$", sql));
+            script.context = "Script".to_string();
+            return Ok(vec![(0, script)]);
+        }
+        Ok(split_statements(sql, dialect)
+            .into_iter()
+            .map(|(offset, statement)| (offset, request(statement.to_string())))
+            .collect())
+    }
+}
+
+/// First line of a statement that is not a comment, shortened, to tell
+/// the tabs of a batch apart
+fn statement_label(sql: &str) -> String {
+    let line = sql.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("--"))
+        .unwrap_or_default();
+    match line.char_indices().nth(60) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// Approximate size of one table, shown dimmed next to its name in the object tree
+#[derive(Debug, Clone)]
+pub struct TableStats {
+    pub name: String,
+    pub row_count: Option<u64>,
+    pub last_altered: Option<String>,
+}
+
+impl TableStats {
+    /// Short label such as `~1.2M rows, 2024-05-01 09:30`
+    pub fn annotation(&self) -> String {
+        let rows = self.row_count.map(|n| {
+            let (value, unit) = match n {
+                n if n >= 1_000_000_000 => (n as f64 / 1e9, "B"),
+                n if n >= 1_000_000 => (n as f64 / 1e6, "M"),
+                n if n >= 1_000 => (n as f64 / 1e3, "K"),
+                n => return format!("{} row{}", n, if n == 1 { "" } else { "s" }),
+            };
+            format!("~{:.1}{} rows", value, unit)
+        });
+        rows.into_iter()
+            .chain(self.last_altered.clone())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
 #[derive(Debug)]
 pub enum DbWorkerRequest {
-    RunQueries(Vec<(String, String)>), // (query, context)
-    Cancel,
+    RunQueries(Vec<QueryRequest>),
+    /// Row counts and last-altered times for the tables of a schema
+    FetchTableStats { database: String, schema: String },
+    /// Names of the roles, warehouses or databases the session can use
+    ListContext(ContextKind),
+    /// `USE ROLE/WAREHOUSE/DATABASE <name>`
+    UseContext { kind: ContextKind, name: String },
+    /// `EXPLAIN USING JSON` each statement for its cost annotation
+    EstimateCosts(Vec<String>),
+    /// Statements that don't depend on each other, run on up to
+    /// `max_parallel` connections of their own at once
+    RunParallel { queries: Vec<QueryRequest>, max_parallel: usize },
+    /// Up to this many more rows of the last result cut off at `max_rows`
+    FetchMore(usize),
+    /// `SHOW STAGES` in the current schema
+    ListStages,
+    /// `LIST @stage`
+    ListStageFiles(String),
+    /// A `PUT` or `GET` statement; `label` names the file for progress
+    StageTransfer { label: String, sql: String },
+    /// `GET_DDL` of a table, falling back to a view of that name
+    FetchDdl(String),
+    /// Databases to cache object names for
+    ListMetadataDatabases,
+    /// Schemas, tables and columns of one database for the metadata cache
+    FetchMetadata(String),
     Quit,
 }
 
 #[derive(Debug)]
 pub enum DbWorkerResponse {
     Connected,
-    QueryStarted { query_idx: usize, started: Instant, query_context: String },
-    QueryFinished { query_idx: usize, elapsed: Duration, result: ResultsContent },
-    QueryError { query_idx: usize, elapsed: Duration, message: String },
+    /// `request` is the statement as sent, kept with its tab for running it again
+    QueryStarted { query_idx: usize, started: Instant, request: QueryRequest },
+    /// `truncated`: the fetch stopped at `max_rows` and `FetchMore` can resume it
+    QueryFinished { query_idx: usize, elapsed: Duration, query_id: Option<String>, result: ResultsContent, truncated: bool },
+    QueryError { query_idx: usize, elapsed: Duration, query_id: Option<String>, message: String },
+    QueryCancelled { query_idx: usize, elapsed: Duration },
+    TableStats { database: String, schema: String, stats: Vec<TableStats> },
+    ContextOptions { kind: ContextKind, names: Result<Vec<String>, String> },
+    /// Sent after connecting, after each batch and after a context switch
+    SessionContext(SessionContext),
+    ContextSwitchFailed { message: String },
+    /// `bytesAssigned` of one statement from `EstimateCosts`; None if the
+    /// EXPLAIN failed
+    CostEstimate { statement: String, bytes: Option<u64> },
+    /// Rows from `FetchMore`; `more` if the result continues past them.
+    /// `error` says why fetching stopped short; a retry picks up from there.
+    MoreRows { rows: Vec<Vec<String>>, more: bool, error: Option<String> },
+    /// The driver could not be loaded or the connection was refused
+    ConnectionFailed { message: String },
+    /// The idle keepalive ping failed; the session has likely expired
+    KeepaliveFailed { message: String },
+    /// Fully qualified stage names
+    Stages(Result<Vec<String>, String>),
+    StageFiles { stage: String, files: Result<Vec<StageFile>, String> },
+    /// Sent when a transfer starts and for each file it reports
+    StageProgress { message: String },
+    StageTransferDone { result: Result<String, String> },
+    Ddl { name: String, ddl: Result<String, String> },
+    MetadataDatabases(Result<Vec<String>, String>),
+    /// `(schema, table, column)` rows
+    Metadata { database: String, columns: Result<Vec<(String, String, String)>, String> },
 }
 
-pub fn start_db_worker(
-    conn_str: String,
-) -> (
-    Sender<DbWorkerRequest>,
+/// Request channel to the worker that counts the requests it has not
+/// picked up yet, for the perf overlay
+#[derive(Clone)]
+pub struct RequestSender {
+    tx: Sender<DbWorkerRequest>,
+    queued: Arc<AtomicUsize>,
+}
+
+impl RequestSender {
+    pub fn send(&self, request: DbWorkerRequest) -> Result<(), mpsc::SendError<DbWorkerRequest>> {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        let result = self.tx.send(request);
+        if result.is_err() {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Requests waiting behind the one the worker is running
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+}
+
+/// Result set left open after its fetch stopped at `max_rows`
+struct OpenCursor<'env, 'con> {
+    statement: Statement<'env, 'con, Executed, HasResult, AutocommitOn>,
+    masks: Vec<Option<ColumnMask>>,
+    /// Row read past the limit to find out the result continues
+    next_row: Vec<String>,
+}
+
+/// Requests for a worker, counting down the sender's queue as they are
+/// picked up
+pub struct WorkerRequests {
+    rx: Receiver<DbWorkerRequest>,
+    queued: Arc<AtomicUsize>,
+}
+
+impl WorkerRequests {
+    /// Waits for the next request; with a timeout, gives up after it so the
+    /// caller can use the idle time (e.g. to ping the session)
+    pub fn recv(&self, timeout: Option<Duration>) -> Result<DbWorkerRequest, mpsc::RecvTimeoutError> {
+        let request = match timeout {
+            Some(timeout) => self.rx.recv_timeout(timeout),
+            None => self.rx.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+        };
+        if request.is_ok() {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        request
+    }
+}
+
+/// What answers the worker's requests: `OdbcExecutor` runs them on
+/// Snowflake, `fake::FakeExecutor` answers from canned result sets so the
+/// UI can be tested and demoed without an account
+pub trait QueryExecutor: Send {
+    /// Serve `requests` on the worker thread until `Quit` or until the
+    /// sender is dropped, answering on `resp_tx`
+    fn serve(self: Box<Self>, requests: WorkerRequests, resp_tx: Sender<DbWorkerResponse>, cancel: CancelHandle);
+
+    /// Server and user the session belongs to, for caches kept per
+    /// account; None keeps them in memory only
+    fn account(&self) -> Option<(String, String)> {
+        None
+    }
+}
+
+/// The backend the config selects, fetching up to `max_rows` rows per
+/// result (0 for all)
+pub fn configured_executor(config: &Config, masker: Masker, max_rows: usize) -> anyhow::Result<Box<dyn QueryExecutor>> {
+    Ok(match config.backend {
+        Backend::Odbc => Box::new(OdbcExecutor::new(
+            config.resolve_connection_string()?,
+            config.tile_store_options(),
+            max_rows,
+            masker,
+            config.keepalive(),
+            config.dialect,
+        )),
+        Backend::Rest => Box::new(RestExecutor::new(&config.rest, config.tile_store_options(), max_rows, masker)?),
+    })
+}
+
+/// Start the worker thread for `executor`; requests go in through the
+/// sender, responses come out of the receiver, and the handle cancels the
+/// running statement
+pub fn start_db_worker(executor: Box<dyn QueryExecutor>) -> (
+    RequestSender,
     Receiver<DbWorkerResponse>,
-    Arc<Mutex<Option<SafeStmt>>>,
+    CancelHandle,
 ) {
     let (req_tx, req_rx) = mpsc::channel();
     let (resp_tx, resp_rx) = mpsc::channel();
+    let req_tx = RequestSender { tx: req_tx, queued: Arc::new(AtomicUsize::new(0)) };
+    let requests = WorkerRequests { rx: req_rx, queued: req_tx.queued.clone() };
     
-    let current_stmt: Arc<Mutex<Option<SafeStmt>>> = Arc::new(Mutex::new(None));
-    let thread_stmt = Arc::clone(&current_stmt);
+    let cancel = CancelHandle::new();
+    let thread_cancel = cancel.clone();
+    thread::spawn(move || executor.serve(requests, resp_tx, thread_cancel));
     
-    thread::spawn(move || {
+    (req_tx, resp_rx, cancel)
+}
+
+/// Runs requests on one ODBC connection opened from `conn_str`
+pub struct OdbcExecutor {
+    conn_str: String,
+    store_options: TileStoreOptions,
+    /// Rows fetched per result before stopping (0 fetches everything)
+    max_rows: usize,
+    masker: Masker,
+    /// Idle time after which the session is pinged
+    keepalive: Option<Duration>,
+    dialect: Dialect,
+}
+
+impl OdbcExecutor {
+    pub fn new(
+        conn_str: String,
+        store_options: TileStoreOptions,
+        max_rows: usize,
+        masker: Masker,
+        keepalive: Option<Duration>,
+        dialect: Dialect,
+    ) -> Self {
+        Self { conn_str, store_options, max_rows, masker, keepalive, dialect }
+    }
+}
+
+impl QueryExecutor for OdbcExecutor {
+    fn account(&self) -> Option<(String, String)> {
+        Some((
+            connection_string_value(&self.conn_str, "Server").unwrap_or_default(),
+            connection_string_value(&self.conn_str, "UID").unwrap_or_default(),
+        ))
+    }
+
+    fn serve(self: Box<Self>, requests: WorkerRequests, resp_tx: Sender<DbWorkerResponse>, cancel: CancelHandle) {
+        let OdbcExecutor { conn_str, store_options, max_rows, masker, keepalive, dialect } = *self;
+        let snowflake = dialect == Dialect::Snowflake;
+        
         // Try to create environment
         let env = match create_environment_v3() {
             Ok(env) => env,
-            Err(_) => {
+            Err(e) => {
+                let detail = e.map(|e| e.to_string()).unwrap_or_else(|| "no diagnostics".to_string());
+                let _ = resp_tx.send(DbWorkerResponse::ConnectionFailed {
+                    message: format!("ODBC environment unavailable: {}", detail),
+                });
                 // Keep thread alive but not connected
                 loop {
-                    match req_rx.recv() {
+                    match requests.recv(None) {
                         Ok(DbWorkerRequest::Quit) | Err(_) => break,
                         _ => continue,
                     }
@@ -65,16 +479,18 @@ pub fn start_db_worker(
                 let _ = resp_tx.send(DbWorkerResponse::Connected);
                 
                 // Enable all secondary roles by default
-                if let Ok(stmt) = Statement::with_parent(&conn) {
-                    let _ = stmt.exec_direct("USE SECONDARY ROLES ALL");
+                if snowflake {
+                    let _ = exec_silent(&conn, "USE SECONDARY ROLES ALL");
                 }
+                let _ = resp_tx.send(DbWorkerResponse::SessionContext(catalog::session_context(&columns_of(&conn), dialect)));
                 
                 conn
             }
             Err(e) => {
+                let _ = resp_tx.send(DbWorkerResponse::ConnectionFailed { message: e.to_string() });
                 // Keep thread alive but not connected
                 loop {
-                    match req_rx.recv() {
+                    match requests.recv(None) {
                         Ok(DbWorkerRequest::Quit) | Err(_) => break,
                         _ => continue,
                     }
@@ -83,153 +499,628 @@ pub fn start_db_worker(
             }
         };
         
+        let row_limit = if max_rows == 0 { usize::MAX } else { max_rows };
+        let executor = Executor {
+            cancel: &cancel,
+            resp_tx: &resp_tx,
+            masker: &masker,
+            store_options,
+            row_limit,
+            dialect,
+        };
+        let mut open_cursor: Option<OpenCursor<'_, '_>> = None;
+        
         // Main worker loop
         loop {
-            match req_rx.recv() {
+            match requests.recv(keepalive) {
                 Ok(DbWorkerRequest::RunQueries(queries)) => {
-                    for (idx, (query, context)) in queries.into_iter().enumerate() {
-                        let started = Instant::now();
-                        
-                        // Send query started notification
-                        let _ = resp_tx.send(DbWorkerResponse::QueryStarted {
+                    cancel.reset();
+                    open_cursor = None;
+                    let mut failed = false;
+                    for (idx, request) in queries.into_iter().enumerate() {
+                        // A cancel or a failed statement also drops the rest of the batch
+                        if cancel.is_requested() || cancel.is_queue_dropped() || failed {
+                            break;
+                        }
+                        let (cursor, statement_failed) = executor.run(&conn, idx, request);
+                        open_cursor = cursor;
+                        failed = statement_failed;
+                    }
+                    // The batch may have run USE statements of its own
+                    let _ = resp_tx.send(DbWorkerResponse::SessionContext(catalog::session_context(&columns_of(&conn), dialect)));
+                }
+                Ok(DbWorkerRequest::RunParallel { queries, max_parallel }) => {
+                    cancel.reset();
+                    open_cursor = None;
+                    run_parallel(&conn, &conn_str, queries, max_parallel, executor);
+                }
+                Ok(DbWorkerRequest::FetchTableStats { database, schema }) => {
+                    // Row counts in INFORMATION_SCHEMA.TABLES are a Snowflake extension
+                    let stats = if snowflake {
+                        catalog::table_stats(&columns_of(&conn), &database, &schema)
+                    } else {
+                        Vec::new()
+                    };
+                    let _ = resp_tx.send(DbWorkerResponse::TableStats { database, schema, stats });
+                }
+                Ok(DbWorkerRequest::ListContext(kind)) => {
+                    let names = if snowflake {
+                        catalog::show_names(&columns_of(&conn), kind.show_sql())
+                    } else {
+                        Err(dialect.unsupported("Switching roles, warehouses and databases"))
+                    };
+                    let _ = resp_tx.send(DbWorkerResponse::ContextOptions { kind, names });
+                }
+                Ok(DbWorkerRequest::UseContext { kind, name }) => {
+                    let used = if snowflake {
+                        exec_silent(&conn, &kind.use_sql(&name))
+                    } else {
+                        Err(dialect.unsupported("USE ROLE/WAREHOUSE/DATABASE"))
+                    };
+                    if let Err(e) = used {
+                        let _ = resp_tx.send(DbWorkerResponse::ContextSwitchFailed {
+                            message: format!("Failed to use {}: {}", name, e),
+                        });
+                    }
+                    let _ = resp_tx.send(DbWorkerResponse::SessionContext(catalog::session_context(&columns_of(&conn), dialect)));
+                }
+                Ok(DbWorkerRequest::EstimateCosts(statements)) => {
+                    for statement in statements {
+                        let bytes = snowflake
+                            .then(|| explain_json(&conn, &statement))
+                            .flatten()
+                            .and_then(|plan| crate::cost::bytes_assigned(&plan));
+                        let _ = resp_tx.send(DbWorkerResponse::CostEstimate { statement, bytes });
+                    }
+                }
+                Ok(DbWorkerRequest::FetchMore(count)) => {
+                    cancel.reset();
+                    let Some(mut cursor) = open_cursor.take() else {
+                        let _ = resp_tx.send(DbWorkerResponse::MoreRows { rows: Vec::new(), more: false, error: None });
+                        continue;
+                    };
+                    let mut rows = vec![std::mem::take(&mut cursor.next_row)];
+                    let mut exhausted = false;
+                    while rows.len() < count && !cancel.is_requested() {
+                        match fetch_row(&mut cursor.statement, &cursor.masks) {
+                            Some(row) => rows.push(row),
+                            None => {
+                                exhausted = true;
+                                break;
+                            }
+                        }
+                    }
+                    // A cancel keeps the rows fetched so far but closes the cursor
+                    let next_row = if exhausted || cancel.is_requested() {
+                        None
+                    } else {
+                        fetch_row(&mut cursor.statement, &cursor.masks)
+                    };
+                    let more = next_row.is_some();
+                    if let Some(next_row) = next_row {
+                        cursor.next_row = next_row;
+                        open_cursor = Some(cursor);
+                    }
+                    let _ = resp_tx.send(DbWorkerResponse::MoreRows { rows, more, error: None });
+                }
+                Ok(DbWorkerRequest::ListStages) if !snowflake => {
+                    let _ = resp_tx.send(DbWorkerResponse::Stages(Err(dialect.unsupported("Stages"))));
+                }
+                Ok(DbWorkerRequest::ListStages) => {
+                    let _ = resp_tx.send(DbWorkerResponse::Stages(catalog::stages(&columns_of(&conn))));
+                }
+                Ok(DbWorkerRequest::ListStageFiles(stage)) if !snowflake => {
+                    let _ = resp_tx.send(DbWorkerResponse::StageFiles { stage, files: Err(dialect.unsupported("Stages")) });
+                }
+                Ok(DbWorkerRequest::ListStageFiles(stage)) => {
+                    let files = catalog::stage_files(&columns_of(&conn), &stage);
+                    let _ = resp_tx.send(DbWorkerResponse::StageFiles { stage, files });
+                }
+                Ok(DbWorkerRequest::StageTransfer { .. }) if !snowflake => {
+                    let _ = resp_tx.send(DbWorkerResponse::StageTransferDone { result: Err(dialect.unsupported("PUT and GET")) });
+                }
+                Ok(DbWorkerRequest::StageTransfer { label, sql }) => {
+                    // The driver does the whole transfer inside one call, so
+                    // progress is per file rather than per byte
+                    let _ = resp_tx.send(DbWorkerResponse::StageProgress { message: format!("{}...", label) });
+                    let result = query_columns(&conn, &sql, &["source", "file", "status", "message"]).map(|rows| {
+                        for row in &rows {
+                            let file = row[0].clone().or_else(|| row[1].clone()).unwrap_or_default();
+                            let status = row[2].clone().unwrap_or_default();
+                            let _ = resp_tx.send(DbWorkerResponse::StageProgress { message: format!("{}: {}", file, status) });
+                        }
+                        let failed: Vec<String> = rows.iter()
+                            .filter(|row| row[2].as_deref().is_some_and(|s| s.eq_ignore_ascii_case("error")))
+                            .map(|row| row[3].clone().unwrap_or_default())
+                            .collect();
+                        match failed.first() {
+                            Some(message) => format!("{}: {} of {} file(s) failed: {}", label, failed.len(), rows.len(), message),
+                            None => format!("{}: {} file(s) done", label, rows.len()),
+                        }
+                    });
+                    let _ = resp_tx.send(DbWorkerResponse::StageTransferDone { result });
+                }
+                Ok(DbWorkerRequest::FetchDdl(name)) => {
+                    let ddl = if snowflake {
+                        catalog::ddl(&columns_of(&conn), &name)
+                    } else {
+                        Err(dialect.unsupported("GET_DDL"))
+                    };
+                    let _ = resp_tx.send(DbWorkerResponse::Ddl { name, ddl });
+                }
+                Ok(DbWorkerRequest::ListMetadataDatabases) => {
+                    // Without SHOW DATABASES only the current database is described
+                    let names = if snowflake {
+                        catalog::show_names(&columns_of(&conn), "SHOW DATABASES")
+                    } else {
+                        Ok(catalog::session_context(&columns_of(&conn), dialect).database.into_iter().collect())
+                    };
+                    let _ = resp_tx.send(DbWorkerResponse::MetadataDatabases(names));
+                }
+                Ok(DbWorkerRequest::FetchMetadata(database)) => {
+                    let columns = catalog::columns(&columns_of(&conn), &database);
+                    let _ = resp_tx.send(DbWorkerResponse::Metadata { database, columns });
+                }
+                // Nothing was asked of the worker for a while; queries
+                // block this loop, so this only pings an idle session
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if let Err(message) = exec_silent(&conn, "SELECT 1") {
+                        let _ = resp_tx.send(DbWorkerResponse::KeepaliveFailed { message });
+                    }
+                }
+                Ok(DbWorkerRequest::Quit) | Err(_) => break,
+            }
+        }
+    }
+}
+
+/// What the statements of a batch run with
+#[derive(Clone, Copy)]
+struct Executor<'r> {
+    cancel: &'r CancelHandle,
+    resp_tx: &'r Sender<DbWorkerResponse>,
+    masker: &'r Masker,
+    store_options: TileStoreOptions,
+    row_limit: usize,
+    dialect: Dialect,
+}
+
+impl Executor<'_> {
+    /// Run statement `idx` of a batch on its warehouse and report its
+    /// start and outcome. Returns the cursor of a result cut off at
+    /// `row_limit`, and whether the statement failed.
+    fn run<'a, 'b>(&self, conn: &'a Connection<'b, AutocommitOn>, idx: usize, request: QueryRequest) -> (Option<OpenCursor<'a, 'b>>, bool) {
+        let started = Instant::now();
+        let _ = self.resp_tx.send(DbWorkerResponse::QueryStarted {
+            query_idx: idx,
+            started,
+            request: request.clone(),
+        });
+        let QueryRequest { sql: query, warehouse, explain, timeout, .. } = request;
+        
+        // Switch warehouse for this statement, remembering the session's;
+        // without one to switch back to, the override would stick
+        let previous_warehouse = match &warehouse {
+            Some(wh) => {
+                let switched = match query_single_value(conn, "SELECT CURRENT_WAREHOUSE()") {
+                    Some(previous) => exec_silent(conn, &format!("USE WAREHOUSE {}", wh))
+                        .map(|_| previous)
+                        .map_err(|e| format!("Failed to switch to warehouse {}: {}", wh, e)),
+                    None => Err(format!(
+                        "Not switching to warehouse {}: the session warehouse can't be read to switch back to",
+                        wh
+                    )),
+                };
+                match switched {
+                    Ok(previous) => Some(previous),
+                    Err(message) => {
+                        let _ = self.resp_tx.send(DbWorkerResponse::QueryError {
                             query_idx: idx,
-                            started,
-                            query_context: context.clone(),
+                            elapsed: started.elapsed(),
+                            query_id: None,
+                            message,
                         });
-                        
-                        // Execute query
-                        match Statement::with_parent(&conn) {
-                            Ok(mut stmt) => {
-                                // Store statement handle for cancellation
-                                unsafe {
-                                    let mut current = thread_stmt.lock().unwrap();
-                                    *current = Some(SafeStmt(stmt.handle()));
-                                }
-                                
-                                match stmt.exec_direct(&query) {
-                                    Ok(ResultSetState::Data(mut statement)) => {
-                                        // Collect column headers
-                                        let num_cols = match statement.num_result_cols() {
-                                            Ok(n) => n,
-                                            Err(e) => {
-                                                let _ = resp_tx.send(DbWorkerResponse::QueryError {
-                                                    query_idx: idx,
-                                                    elapsed: started.elapsed(),
-                                                    message: format!("Failed to get column count: {:?}", e),
-                                                });
-                                                continue;
-                                            }
-                                        };
-                                        
-                                        let mut col_names = Vec::with_capacity(num_cols as usize);
-                                        for i in 1..=num_cols {
-                                            match statement.describe_col(i as u16) {
-                                                Ok(desc) => col_names.push(desc.name),
-                                                Err(e) => {
-                                                    let _ = resp_tx.send(DbWorkerResponse::QueryError {
-                                                        query_idx: idx,
-                                                        elapsed: started.elapsed(),
-                                                        message: format!("Failed to get column name: {:?}", e),
-                                                    });
-                                                    continue;
-                                                }
-                                            }
-                                        }
-                                        
-                                        // Create tile store from results
-                                        let tile_store = match TileRowStore::from_rows(
-                                            &col_names,
-                                            std::iter::from_fn(|| {
-                                                match statement.fetch() {
-                                                    Ok(Some(mut cursor)) => {
-                                                        let mut row = Vec::with_capacity(col_names.len());
-                                                        for idx in 0..col_names.len() {
-                                                            let val: Option<String> = cursor.get_data(idx as u16 + 1).unwrap_or(None);
-                                                            row.push(val.unwrap_or_else(|| crate::tile_rowstore::NULL_SENTINEL.to_string()));
-                                                        }
-                                                        Some(row)
-                                                    }
-                                                    _ => None
-                                                }
-                                            })
-                                        ) {
-                                            Ok(store) => store,
-                                            Err(e) => {
-                                                let _ = resp_tx.send(DbWorkerResponse::QueryError {
-                                                    query_idx: idx,
-                                                    elapsed: started.elapsed(),
-                                                    message: format!("Failed to create tile store: {:?}", e),
-                                                });
-                                                continue;
-                                            }
-                                        };
-                                        
-                                        let _ = resp_tx.send(DbWorkerResponse::QueryFinished {
-                                            query_idx: idx,
-                                            elapsed: started.elapsed(),
-                                            result: ResultsContent::Table {
-                                                headers: col_names,
-                                                tile_store,
-                                            },
-                                        });
-                                    }
-                                    Ok(ResultSetState::NoData(statement)) => {
-                                        let msg = if let Ok(cnt) = statement.affected_row_count() {
-                                            if cnt > 0 {
-                                                format!("Statement affected {} row{}", cnt, if cnt == 1 { "" } else { "s" })
-                                            } else if cnt == 0 {
-                                                "Statement executed successfully (no rows affected).".to_string()
-                                            } else {
-                                                "Statement executed successfully.".to_string()
-                                            }
-                                        } else {
-                                            "Statement executed successfully.".to_string()
-                                        };
-                                        
-                                        let _ = resp_tx.send(DbWorkerResponse::QueryFinished {
-                                            query_idx: idx,
-                                            elapsed: started.elapsed(),
-                                            result: ResultsContent::Info { message: msg },
-                                        });
-                                    }
-                                    Err(e) => {
-                                        let _ = resp_tx.send(DbWorkerResponse::QueryError {
-                                            query_idx: idx,
-                                            elapsed: started.elapsed(),
-                                            message: format!("Query execution failed: {:?}", e),
-                                        });
-                                    }
+                        return (None, true);
+                    }
+                }
+            }
+            None => None,
+        };
+        
+        let outcome = self.execute(conn, idx, &query, explain, timeout, started);
+        if let Some(previous) = previous_warehouse {
+            if let Err(e) = restore_warehouse(conn, &previous) {
+                let _ = self.resp_tx.send(DbWorkerResponse::ContextSwitchFailed {
+                    message: format!("Failed to switch back to warehouse {}: {}", previous, e),
+                });
+            }
+        }
+        outcome
+    }
+    
+    /// Query ID of the statement just run on `conn`, which only Snowflake has
+    fn query_id(&self, conn: &Connection<AutocommitOn>) -> Option<String> {
+        match self.dialect {
+            Dialect::Snowflake => last_query_id(conn),
+            _ => None,
+        }
+    }
+    
+    fn execute<'a, 'b>(
+        &self,
+        conn: &'a Connection<'b, AutocommitOn>,
+        idx: usize,
+        query: &str,
+        explain: Option<PlanFormat>,
+        timeout: Option<Duration>,
+        started: Instant,
+    ) -> (Option<OpenCursor<'a, 'b>>, bool) {
+        let Executor { cancel, resp_tx, masker, store_options, row_limit, .. } = *self;
+        match Statement::with_parent(conn) {
+            Ok(mut stmt) => {
+                // Store statement handle for cancellation. Each arm takes the
+                // guard after binding the statement, so the handle is
+                // unregistered before the statement frees it.
+                let running = cancel.register(unsafe { stmt.handle() });
+                let _watchdog = timeout.map(|t| cancel.watchdog(t));
+            
+                match stmt.exec_direct(query) {
+                    Ok(ResultSetState::Data(mut statement)) if explain.is_some() => {
+                        let _running = running;
+                        // The plan comes back as lines of a single text column
+                        let mut output = String::new();
+                        while let Ok(Some(mut cursor)) = statement.fetch() {
+                            if let Ok(Some(line)) = cursor.get_data::<String>(1) {
+                                output.push_str(&line);
+                                output.push('\n');
+                            }
+                        }
+                        let format = explain.unwrap_or(PlanFormat::Text);
+                        let _ = match PlanView::parse(format, &output) {
+                            Ok(plan) => resp_tx.send(DbWorkerResponse::QueryFinished {
+                                query_idx: idx,
+                                elapsed: started.elapsed(),
+                                query_id: self.query_id(conn),
+                                result: ResultsContent::Plan(plan),
+                                truncated: false,
+                            }),
+                            Err(message) => resp_tx.send(DbWorkerResponse::QueryError {
+                                query_idx: idx,
+                                elapsed: started.elapsed(),
+                                query_id: self.query_id(conn),
+                                message,
+                            }),
+                        };
+                    }
+                    Ok(ResultSetState::Data(mut statement)) => {
+                        let _running = running;
+                        // Collect column headers
+                        let num_cols = match statement.num_result_cols() {
+                            Ok(n) => n,
+                            Err(e) => {
+                                let _ = resp_tx.send(DbWorkerResponse::QueryError {
+                                    query_idx: idx,
+                                    elapsed: started.elapsed(),
+                                    query_id: self.query_id(conn),
+                                    message: format!("Failed to get column count: {:?}", e),
+                                });
+                                return (None, true);
+                            }
+                        };
+                    
+                        let mut col_names = Vec::with_capacity(num_cols as usize);
+                        let mut kinds = Vec::with_capacity(num_cols as usize);
+                        for i in 1..=num_cols {
+                            match statement.describe_col(i as u16) {
+                                Ok(desc) => {
+                                    kinds.push(column_kind(desc.data_type));
+                                    col_names.push(desc.name);
                                 }
-                                
-                                // Clear statement handle
-                                {
-                                    let mut current = thread_stmt.lock().unwrap();
-                                    *current = None;
+                                Err(e) => {
+                                    let _ = resp_tx.send(DbWorkerResponse::QueryError {
+                                        query_idx: idx,
+                                        elapsed: started.elapsed(),
+                                        query_id: self.query_id(conn),
+                                        message: format!("Failed to get column name: {:?}", e),
+                                    });
+                                    continue;
                                 }
                             }
+                        }
+                    
+                        let masks = masker.for_columns(&col_names);
+                    
+                        // Create tile store from results, up to max_rows
+                        let tile_store = match TileRowStore::from_rows(
+                            &col_names,
+                            &kinds,
+                            std::iter::from_fn(|| {
+                                if cancel.is_requested() {
+                                    return None;
+                                }
+                                fetch_row(&mut statement, &masks)
+                            })
+                            .take(row_limit),
+                            store_options,
+                        ) {
+                            Ok(store) => store,
                             Err(e) => {
                                 let _ = resp_tx.send(DbWorkerResponse::QueryError {
                                     query_idx: idx,
                                     elapsed: started.elapsed(),
-                                    message: format!("Failed to create statement: {}", e),
+                                    query_id: self.query_id(conn),
+                                    message: format!("Failed to create tile store: {:?}", e),
                                 });
+                                return (None, true);
                             }
+                        };
+                    
+                        // One row past the limit tells whether the result goes on
+                        let next_row = if tile_store.nrows == row_limit && !cancel.is_requested() {
+                            fetch_row(&mut statement, &masks)
+                        } else {
+                            None
+                        };
+                        let truncated = next_row.is_some();
+                    
+                        // Rows fetched before the cancel are discarded
+                        let _ = if cancel.is_requested() {
+                            resp_tx.send(cancel.stopped_response(idx, started.elapsed(), timeout))
+                        } else {
+                            resp_tx.send(DbWorkerResponse::QueryFinished {
+                                query_idx: idx,
+                                elapsed: started.elapsed(),
+                                query_id: self.query_id(conn),
+                                result: ResultsContent::table(col_names, tile_store, kinds),
+                                truncated,
+                            })
+                        };
+                        if let Some(next_row) = next_row {
+                            return (Some(OpenCursor { statement, masks, next_row }), false);
                         }
                     }
+                    Ok(ResultSetState::NoData(statement)) => {
+                        let _running = running;
+                        let affected = statement.affected_row_count().ok();
+                        let msg = if let Some(cnt) = affected {
+                            if cnt > 0 {
+                                format!("Statement affected {} row{}", cnt, if cnt == 1 { "" } else { "s" })
+                            } else if cnt == 0 {
+                                "Statement executed successfully (no rows affected).".to_string()
+                            } else {
+                                "Statement executed successfully.".to_string()
+                            }
+                        } else {
+                            "Statement executed successfully.".to_string()
+                        };
+                    
+                        let _ = resp_tx.send(DbWorkerResponse::QueryFinished {
+                            query_idx: idx,
+                            elapsed: started.elapsed(),
+                            query_id: self.query_id(conn),
+                            result: ResultsContent::info(msg, affected.and_then(|n| u64::try_from(n).ok())),
+                            truncated: false,
+                        });
+                    }
+                    Err(_) if cancel.is_requested() => {
+                        let _running = running;
+                        let _ = resp_tx.send(cancel.stopped_response(idx, started.elapsed(), timeout));
+                    }
+                    Err(e) => {
+                        let _running = running;
+                        let _ = resp_tx.send(DbWorkerResponse::QueryError {
+                            query_idx: idx,
+                            elapsed: started.elapsed(),
+                            query_id: self.query_id(conn),
+                            message: format!("Query execution failed: {:?}", e),
+                        });
+                        return (None, true);
+                    }
                 }
-                Ok(DbWorkerRequest::Cancel) => {
-                    // Cancel current statement if any
-                    let current = thread_stmt.lock().unwrap();
-                    if let Some(SafeStmt(handle)) = *current {
-                        unsafe {
-                            let _ = SQLCancel(handle);
+            }
+            Err(e) => {
+                let _ = resp_tx.send(DbWorkerResponse::QueryError {
+                    query_idx: idx,
+                    elapsed: started.elapsed(),
+                    query_id: None,
+                    message: format!("Failed to create statement: {}", e),
+                });
+                return (None, true);
+            }
+        }
+        (None, false)
+    }
+}
+
+/// Run a batch of independent statements on up to `max_parallel`
+/// connections opened for it, each taking the next statement when its
+/// last one finishes. The connections close with the batch, so results cut
+/// off at `max_rows` can't be resumed.
+fn run_parallel(conn: &Connection<AutocommitOn>, conn_str: &str, queries: Vec<QueryRequest>, max_parallel: usize, executor: Executor<'_>) {
+    let context = catalog::session_context(&columns_of(conn), executor.dialect);
+    let workers = max_parallel.clamp(1, queries.len().max(1));
+    let queue = Mutex::new(queries.into_iter().enumerate().collect::<VecDeque<_>>());
+    let connected = AtomicUsize::new(workers);
+    let Executor { cancel: batch_cancel, masker, store_options, row_limit, dialect, .. } = executor;
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let resp_tx = executor.resp_tx.clone();
+            let (queue, context, connected) = (&queue, &context, &connected);
+            scope.spawn(move || {
+                let next = || {
+                    if batch_cancel.is_requested() || batch_cancel.is_queue_dropped() {
+                        return None;
+                    }
+                    queue.lock().unwrap().pop_front()
+                };
+                let env = create_environment_v3().map_err(|e| {
+                    e.map(|e| e.to_string()).unwrap_or_else(|| "no diagnostics".to_string())
+                });
+                let pool_conn = env.as_ref()
+                    .map_err(|e| e.clone())
+                    .and_then(|env| open_pool_connection(env, conn_str, context, dialect));
+                let pool_conn = match pool_conn {
+                    Ok(pool_conn) => pool_conn,
+                    // The other connections take this one's share; if none
+                    // could be opened the statements fail rather than stay queued
+                    Err(_) if connected.fetch_sub(1, Ordering::SeqCst) > 1 => return,
+                    Err(e) => {
+                        while let Some((idx, request)) = next() {
+                            let _ = resp_tx.send(DbWorkerResponse::QueryStarted {
+                                query_idx: idx,
+                                started: Instant::now(),
+                                request,
+                            });
+                            let _ = resp_tx.send(DbWorkerResponse::QueryError {
+                                query_idx: idx,
+                                elapsed: Duration::ZERO,
+                                query_id: None,
+                                message: format!("Opening a parallel connection failed: {}", e),
+                            });
                         }
+                        return;
+                    }
+                };
+                while let Some((idx, request)) = next() {
+                    let cancel = CancelHandle::new();
+                    batch_cancel.pool.lock().unwrap().insert(idx, cancel.clone());
+                    // A cancel of the whole batch may have come before the handle was listed
+                    if batch_cancel.is_requested() {
+                        cancel.cancel();
                     }
+                    let executor = Executor { cancel: &cancel, resp_tx: &resp_tx, masker, store_options, row_limit, dialect };
+                    let _ = executor.run(&pool_conn, idx, request);
+                    batch_cancel.pool.lock().unwrap().remove(&idx);
                 }
-                Ok(DbWorkerRequest::Quit) | Err(_) => break,
-            }
+            });
         }
     });
-    
-    (req_tx, resp_rx, current_stmt)
-}
\ No newline at end of file
+    let _ = executor.resp_tx.send(DbWorkerResponse::SessionContext(context));
+}
+
+/// Connection for a parallel batch, on the main session's role,
+/// warehouse, database and schema. Other dialects get the connection
+/// string's defaults.
+fn open_pool_connection<'env>(
+    env: &'env Environment<Version3>,
+    conn_str: &str,
+    context: &SessionContext,
+    dialect: Dialect,
+) -> Result<Connection<'env, AutocommitOn>, String> {
+    let conn = env.connect_with_connection_string(conn_str).map_err(|e| e.to_string())?;
+    if dialect != Dialect::Snowflake {
+        return Ok(conn);
+    }
+    let _ = exec_silent(&conn, "USE SECONDARY ROLES ALL");
+    let uses = [
+        ("ROLE", &context.role),
+        ("WAREHOUSE", &context.warehouse),
+        ("DATABASE", &context.database),
+        ("SCHEMA", &context.schema),
+    ];
+    for (kind, name) in uses {
+        if let Some(name) = name {
+            exec_silent(&conn, &format!("USE {} {}", kind, quote_identifier(name, IdentifierQuoting::Always)))?;
+        }
+    }
+    Ok(conn)
+}
+
+/// Next row of a result as stored in the tile store: NULLs as the
+/// sentinel, masked columns masked. None at the end or on a fetch error.
+fn fetch_row(
+    statement: &mut Statement<'_, '_, Executed, HasResult, AutocommitOn>,
+    masks: &[Option<ColumnMask>],
+) -> Option<Vec<String>> {
+    let mut cursor = statement.fetch().ok()??;
+    let mut row = Vec::with_capacity(masks.len());
+    for (idx, mask) in masks.iter().enumerate() {
+        let val: Option<String> = cursor.get_data(idx as u16 + 1).unwrap_or(None);
+        let val = val.unwrap_or_else(|| NULL_SENTINEL.to_string());
+        row.push(match mask {
+            Some(mask) => mask.apply(&val),
+            None => val,
+        });
+    }
+    Some(row)
+}
+
+/// How JSON export writes a column of this SQL type
+fn column_kind(data_type: SqlDataType) -> ColumnKind {
+    use SqlDataType::*;
+    match data_type {
+        SQL_NUMERIC | SQL_DECIMAL | SQL_INTEGER | SQL_SMALLINT | SQL_FLOAT | SQL_REAL | SQL_DOUBLE
+        | SQL_EXT_BIGINT | SQL_EXT_TINYINT => ColumnKind::Number,
+        SQL_EXT_BIT => ColumnKind::Boolean,
+        _ => ColumnKind::Text,
+    }
+}
+
+/// Execute a statement, discarding any result
+fn exec_silent(conn: &Connection<AutocommitOn>, sql: &str) -> Result<(), String> {
+    let stmt = Statement::with_parent(conn).map_err(|e| e.to_string())?;
+    stmt.exec_direct(sql).map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// First column of the first row of `sql`, or None if there is no (non-NULL) value
+fn query_single_value(conn: &Connection<AutocommitOn>, sql: &str) -> Option<String> {
+    let stmt = Statement::with_parent(conn).ok()?;
+    match stmt.exec_direct(sql).ok()? {
+        ResultSetState::Data(mut stmt) => {
+            let mut cursor = stmt.fetch().ok()??;
+            cursor.get_data::<String>(1).ok()?
+        }
+        ResultSetState::NoData(_) => None,
+    }
+}
+
+/// `EXPLAIN USING JSON` output of `sql`, or None if it can't be explained
+fn explain_json(conn: &Connection<AutocommitOn>, sql: &str) -> Option<String> {
+    let stmt = Statement::with_parent(conn).ok()?;
+    let explain = format!("EXPLAIN USING JSON {}", sql.trim().trim_end_matches(';'));
+    let ResultSetState::Data(mut stmt) = stmt.exec_direct(&explain).ok()? else { return None };
+    let mut output = String::new();
+    while let Ok(Some(mut cursor)) = stmt.fetch() {
+        if let Ok(Some(line)) = cursor.get_data::<String>(1) {
+            output.push_str(&line);
+        }
+    }
+    Some(output)
+}
+
+/// The named columns of every row of `sql`; a column the result lacks
+/// reads as None
+fn query_columns(conn: &Connection<AutocommitOn>, sql: &str, columns: &[&str]) -> Result<Vec<Vec<Option<String>>>, String> {
+    let stmt = Statement::with_parent(conn).map_err(|e| e.to_string())?;
+    let mut stmt = match stmt.exec_direct(sql).map_err(|e| e.to_string())? {
+        ResultSetState::Data(stmt) => stmt,
+        ResultSetState::NoData(_) => return Ok(Vec::new()),
+    };
+    let ncols = stmt.num_result_cols().map_err(|e| e.to_string())?;
+    let positions: Vec<Option<u16>> = columns.iter()
+        .map(|col| (1..=ncols as u16).find(|&i| stmt.describe_col(i).map_or(false, |d| d.name.eq_ignore_ascii_case(col))))
+        .collect();
+    let mut rows = Vec::new();
+    while let Ok(Some(mut cursor)) = stmt.fetch() {
+        rows.push(positions.iter()
+            .map(|pos| pos.and_then(|i| cursor.get_data::<String>(i).ok().flatten()))
+            .collect());
+    }
+    Ok(rows)
+}
+
+/// `query_columns` on `conn`, for the lookups in `catalog`
+fn columns_of<'c>(conn: &'c Connection<AutocommitOn>) -> impl Fn(&str, &[&str]) -> Result<Vec<Vec<Option<String>>>, String> + 'c {
+    move |sql, columns| query_columns(conn, sql, columns)
+}
+
+/// Snowflake query ID of the statement just run on `conn`
+fn last_query_id(conn: &Connection<AutocommitOn>) -> Option<String> {
+    query_single_value(conn, "SELECT LAST_QUERY_ID()")
+}
+
+/// Switch back to the session warehouse after a per-statement override
+fn restore_warehouse(conn: &Connection<AutocommitOn>, previous: &str) -> Result<(), String> {
+    let quoted = format!("\"{}\"", previous.replace('"', "\"\""));
+    exec_silent(conn, &format!("USE WAREHOUSE {}", quoted))
+}
@@ -0,0 +1,198 @@
+use crate::config::Dialect;
+use crate::health::human_bytes;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// What is known about the cost of one statement, from EXPLAIN and from
+/// its last execution
+#[derive(Debug, Clone, Default)]
+pub struct StatementCost {
+    /// `bytesAssigned` from the plan's GlobalStats
+    pub bytes_assigned: Option<u64>,
+    pub last_run: Option<Duration>,
+}
+
+impl StatementCost {
+    /// `~2.1 GB scanned, 14s last run`
+    pub fn annotation(&self) -> String {
+        let scanned = self.bytes_assigned.map(|b| format!("~{} scanned", human_bytes(b)));
+        let run = self.last_run.map(|d| match d.as_secs() {
+            0 => format!("{}ms last run", d.as_millis()),
+            s if s < 60 => format!("{}s last run", s),
+            s => format!("{}m{}s last run", s / 60, s % 60),
+        });
+        scanned.into_iter().chain(run).collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// Statement costs keyed by `statement_key`, so edits to whitespace or the
+/// trailing `;` don't lose them
+#[derive(Debug, Default)]
+pub struct CostCache {
+    entries: HashMap<String, StatementCost>,
+}
+
+impl CostCache {
+    pub fn record_bytes(&mut self, statement: &str, bytes: u64) {
+        self.entries.entry(statement_key(statement)).or_default().bytes_assigned = Some(bytes);
+    }
+
+    pub fn record_run(&mut self, statement: &str, elapsed: Duration) {
+        self.entries.entry(statement_key(statement)).or_default().last_run = Some(elapsed);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// (byte offset of the statement, annotation) for each statement in
+    /// `text` with known costs
+    pub fn annotations(&self, text: &str, dialect: Dialect) -> Vec<(usize, String)> {
+        if self.entries.is_empty() {
+            return Vec::new();
+        }
+        split_statements(text, dialect)
+            .into_iter()
+            .filter_map(|(offset, sql)| {
+                let cost = self.entries.get(&statement_key(sql))?;
+                Some((offset, cost.annotation()))
+            })
+            .filter(|(_, annotation)| !annotation.is_empty())
+            .collect()
+    }
+}
+
+/// Whitespace-collapsed statement without its trailing `;`
+pub fn statement_key(sql: &str) -> String {
+    sql.trim().trim_end_matches(';').split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Statements in `text` as (byte offset of first character, statement text),
+/// split on `;` outside string literals, quoted identifiers, `$$` blocks and
+/// comments. Statements that are only comments are skipped. A doubled quote
+/// stays inside its literal or identifier; only Snowflake string literals
+/// also take backslash escapes.
+pub fn split_statements(text: &str, dialect: Dialect) -> Vec<(usize, &str)> {
+    let bytes = text.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\'' | b'"' => {
+                let quote = bytes[i];
+                let backslash_escapes = quote == b'\'' && dialect == Dialect::Snowflake;
+                i += 1;
+                while i < bytes.len() {
+                    if bytes[i] == quote && bytes.get(i + 1) == Some(&quote) {
+                        i += 2;
+                    } else if bytes[i] == quote {
+                        break;
+                    } else {
+                        i += if backslash_escapes && bytes[i] == b'\\' { 2 } else { 1 };
+                    }
+                }
+            }
+            b'$' if bytes.get(i + 1) == Some(&b'$') => {
+                i = text[i + 2..].find("$$").map_or(bytes.len(), |end| i + 2 + end + 1);
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = text[i..].find('\n').map_or(bytes.len(), |end| i + end);
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = text[i + 2..].find("*/").map_or(bytes.len(), |end| i + 2 + end + 1);
+            }
+            b';' => {
+                push_statement(text, start, i, &mut statements);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    if start < bytes.len() {
+        push_statement(text, start, bytes.len(), &mut statements);
+    }
+    statements
+}
+
+fn push_statement<'a>(text: &'a str, from: usize, to: usize, statements: &mut Vec<(usize, &'a str)>) {
+    let piece = &text[from..to];
+    let lead = piece.len() - piece.trim_start().len();
+    if !strip_comments(piece).trim().is_empty() {
+        statements.push((from + lead, piece.trim()));
+    }
+}
+
+/// `text` with `--` and `/* */` comments removed (only used to detect
+/// comment-only pieces, so quotes are not considered)
+fn strip_comments(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("--") {
+            rest = after.find('\n').map_or("", |end| &after[end..]);
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            rest = after.find("*/").map_or("", |end| &after[end + 2..]);
+        } else {
+            let ch = rest.chars().next().unwrap_or(' ');
+            out.push(ch);
+            rest = &rest[ch.len_utf8()..];
+        }
+    }
+    out
+}
+
+/// `bytesAssigned` from `EXPLAIN USING JSON` output
+pub fn bytes_assigned(plan_json: &str) -> Option<u64> {
+    let plan: serde_json::Value = serde_json::from_str(plan_json).ok()?;
+    plan.get("GlobalStats")?.get("bytesAssigned")?.as_u64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(text: &str, dialect: Dialect) -> Vec<&str> {
+        split_statements(text, dialect).into_iter().map(|(_, statement)| statement).collect()
+    }
+
+    #[test]
+    fn semicolons_in_quotes_comments_and_dollar_blocks_dont_split() {
+        let text = "SELECT ';' AS a, \"x;y\" FROM t; -- a; comment\n\
+                    SELECT 1 /* b; c */; \
+                    CREATE FUNCTION f() RETURNS INT AS $$ SELECT 1; $$";
+        assert_eq!(split(text, Dialect::Snowflake), vec![
+            "SELECT ';' AS a, \"x;y\" FROM t",
+            "-- a; comment\nSELECT 1 /* b; c */",
+            "CREATE FUNCTION f() RETURNS INT AS $$ SELECT 1; $$",
+        ]);
+    }
+
+    #[test]
+    fn doubled_quotes_stay_inside_the_literal() {
+        assert_eq!(
+            split("SELECT 'it''s; here'; SELECT \"a\"\";b\" FROM t", Dialect::Postgres),
+            vec!["SELECT 'it''s; here'", "SELECT \"a\"\";b\" FROM t"],
+        );
+    }
+
+    #[test]
+    fn backslash_escapes_only_in_snowflake_literals() {
+        let text = r"SELECT 'a\'; b'; SELECT 2";
+        assert_eq!(split(text, Dialect::Snowflake), vec![r"SELECT 'a\'; b'", "SELECT 2"]);
+        // Elsewhere the backslash is a plain character and the quote ends the literal
+        assert_eq!(split(text, Dialect::Postgres), vec![r"SELECT 'a\'", "b'; SELECT 2"]);
+    }
+
+    #[test]
+    fn trailing_statement_without_semicolon_is_kept() {
+        let text = "SELECT 1;\n  SELECT 2\n";
+        assert_eq!(split_statements(text, Dialect::Snowflake), vec![(0, "SELECT 1"), (12, "SELECT 2")]);
+    }
+
+    #[test]
+    fn comment_only_pieces_are_skipped() {
+        assert_eq!(split("SELECT 1; -- done\n", Dialect::Snowflake), vec!["SELECT 1"]);
+    }
+}
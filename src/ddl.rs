@@ -0,0 +1,47 @@
+use crate::overlay::OverlayOutcome;
+use crossterm::event::{KeyCode, KeyEvent};
+
+/// Read-only view of an object's `GET_DDL` output; c copies it, e opens
+/// it in the editor as a read-only buffer
+#[derive(Debug)]
+pub struct DdlView {
+    pub name: String,
+    /// None while the worker fetches it
+    pub ddl: Option<Result<String, String>>,
+    pub scroll: usize,
+    /// Submitted with e rather than c
+    pub open_in_editor: bool,
+}
+
+impl DdlView {
+    pub fn new(name: String) -> Self {
+        Self { name, ddl: None, scroll: 0, open_in_editor: false }
+    }
+
+    /// The DDL text, once fetched successfully
+    pub fn text(&self) -> Option<&str> {
+        match &self.ddl {
+            Some(Ok(ddl)) => Some(ddl),
+            _ => None,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        match key.code {
+            KeyCode::Char('c') | KeyCode::Char('C') if self.text().is_some() => return OverlayOutcome::Submit,
+            KeyCode::Char('e') | KeyCode::Char('E') if self.text().is_some() => {
+                self.open_in_editor = true;
+                return OverlayOutcome::Submit;
+            }
+            KeyCode::Char('q') => return OverlayOutcome::Close,
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Down => self.scroll += 1,
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::PageDown => self.scroll += 10,
+            KeyCode::Home => self.scroll = 0,
+            KeyCode::End => self.scroll = usize::MAX,
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+}
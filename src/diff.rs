@@ -0,0 +1,194 @@
+use crate::overlay::OverlayOutcome;
+use crossterm::event::{KeyCode, KeyEvent};
+
+/// Edit distance past which the middle of two texts is shown as removed
+/// and added wholesale rather than searched further
+const MAX_EDITS: usize = 1_000;
+
+/// `DiffView::against` when comparing with the clipboard
+pub const CLIPBOARD: &str = "clipboard";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Same,
+    /// Only in the buffer
+    Added,
+    /// Only in the text compared against
+    Removed,
+}
+
+/// One line of an inline diff
+#[derive(Debug, Clone)]
+pub struct DiffLine {
+    pub change: Change,
+    /// 0-based line in the text compared against, unless added
+    pub old_line: Option<usize>,
+    /// 0-based line in the buffer, unless removed
+    pub new_line: Option<usize>,
+    pub text: String,
+}
+
+/// Inline diff turning `old` into `new`, line by line
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (mid_a, mid_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut lines = Vec::with_capacity(a.len().max(b.len()));
+    let same = |i: usize, j: usize| DiffLine { change: Change::Same, old_line: Some(i), new_line: Some(j), text: a[i].to_string() };
+    lines.extend((0..prefix).map(|i| same(i, i)));
+    for edit in edits(mid_a, mid_b) {
+        lines.push(match edit {
+            Edit::Same(i, j) => same(prefix + i, prefix + j),
+            Edit::Removed(i) => DiffLine {
+                change: Change::Removed,
+                old_line: Some(prefix + i),
+                new_line: None,
+                text: mid_a[i].to_string(),
+            },
+            Edit::Added(j) => DiffLine {
+                change: Change::Added,
+                old_line: None,
+                new_line: Some(prefix + j),
+                text: mid_b[j].to_string(),
+            },
+        });
+    }
+    lines.extend((0..suffix).map(|n| same(a.len() - suffix + n, b.len() - suffix + n)));
+    lines
+}
+
+enum Edit {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// Shortest edit script from `a` to `b` (Myers). Each round keeps the
+/// furthest point reached on the diagonals it touched, to walk back from
+/// the end once it is reached.
+fn edits(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (a.len() + b.len()).min(MAX_EDITS) as isize;
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * max as usize + 3];
+    // Diagonals -d - 1 ..= d + 1 of `v` as it was before round d
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut reached = None;
+    'rounds: for d in 0..=max {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let at = |k: isize| v[(offset + k) as usize];
+            let mut x = if k == -d || (k != d && at(k - 1) < at(k + 1)) { at(k + 1) } else { at(k - 1) + 1 };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(offset + k) as usize] = x;
+            if x >= n && y >= m {
+                reached = Some(d);
+                break 'rounds;
+            }
+        }
+    }
+    let Some(d_end) = reached else {
+        return (0..a.len()).map(Edit::Removed).chain((0..b.len()).map(Edit::Added)).collect();
+    };
+
+    let mut script = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (0..=d_end).rev() {
+        let before = &trace[d as usize];
+        let at = |k: isize| before[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            script.push(Edit::Same(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                script.push(Edit::Added(prev_y as usize));
+            } else {
+                script.push(Edit::Removed(prev_x as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    script.reverse();
+    script
+}
+
+/// The editor buffer diffed against its saved file or the clipboard;
+/// n / p step between changes, Enter jumps to the top line and c switches
+/// to the clipboard
+#[derive(Debug)]
+pub struct DiffView {
+    /// What the buffer is compared against, e.g. the file's name
+    pub against: String,
+    pub lines: Vec<DiffLine>,
+    /// First line shown
+    pub scroll: usize,
+    /// Submitted with c, to compare against the clipboard instead
+    pub compare_clipboard: bool,
+}
+
+impl DiffView {
+    pub fn new(against: String, old: &str, new: &str) -> Self {
+        Self { against, lines: diff_lines(old, new), scroll: 0, compare_clipboard: false }
+    }
+
+    /// Lines added and removed
+    pub fn counts(&self) -> (usize, usize) {
+        let count = |change| self.lines.iter().filter(|l| l.change == change).count();
+        (count(Change::Added), count(Change::Removed))
+    }
+
+    /// Buffer line to put the caret on for the top line shown: its own,
+    /// or the next one kept for a removed line
+    pub fn target_line(&self) -> Option<usize> {
+        self.lines.get(self.scroll..)?.iter().find_map(|l| l.new_line)
+    }
+
+    /// Start of each run of changed lines
+    fn hunks(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.lines.len()).filter(|&i| {
+            self.lines[i].change != Change::Same && (i == 0 || self.lines[i - 1].change == Change::Same)
+        })
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        let last = self.lines.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Enter if self.target_line().is_some() => return OverlayOutcome::Submit,
+            KeyCode::Char('q') => return OverlayOutcome::Close,
+            KeyCode::Char('c') if self.against != CLIPBOARD => {
+                self.compare_clipboard = true;
+                return OverlayOutcome::Submit;
+            }
+            KeyCode::Char('n') => {
+                let next = self.hunks().find(|&i| i > self.scroll);
+                self.scroll = next.unwrap_or(self.scroll);
+            }
+            KeyCode::Char('p') => {
+                let prev = self.hunks().filter(|&i| i < self.scroll).last();
+                self.scroll = prev.unwrap_or(self.scroll);
+            }
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Down => self.scroll = (self.scroll + 1).min(last),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::PageDown => self.scroll = (self.scroll + 10).min(last),
+            KeyCode::Home => self.scroll = 0,
+            KeyCode::End => self.scroll = last,
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+}
@@ -0,0 +1,79 @@
+/// Per-statement settings given in a `-- frost:` comment, e.g.
+///
+/// ```sql
+/// -- frost: warehouse=LOADING_XL timeout=600
+/// INSERT INTO big_table SELECT ...
+/// ```
+///
+/// A batch containing a Snowflake Scripting block (`DECLARE` / `BEGIN ...
+/// END`) needs `-- frost: script=true`, since it can't be split into
+/// statements. `-- frost: parallel=true` runs the statements of a batch
+/// that don't depend on each other at the same time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Directives {
+    /// Warehouse to run this statement on; the session warehouse is restored afterward
+    pub warehouse: Option<String>,
+    /// Query timeout in seconds for this statement, overriding `query_timeout_secs` (0 disables)
+    pub timeout_secs: Option<u64>,
+    /// Run the whole batch as one scripting block in `EXECUTE IMMEDIATE $$ ... $$`
+    /// instead of statement by statement
+    pub script: bool,
+    /// Run the statements of the batch concurrently, each in its own session
+    pub parallel: bool,
+}
+
+const PREFIX: &str = "-- frost:";
+
+impl Directives {
+    /// Collect directives from `-- frost:` comment lines anywhere in `sql`.
+    /// Pairs are `key=value`, separated by whitespace or commas.
+    pub fn parse(sql: &str) -> Result<Self, String> {
+        let mut directives = Directives::default();
+        for line in sql.lines() {
+            let line = line.trim_start();
+            let Some(rest) = line.get(..PREFIX.len())
+                .filter(|p| p.eq_ignore_ascii_case(PREFIX))
+                .map(|_| &line[PREFIX.len()..])
+            else {
+                continue;
+            };
+            for pair in rest.split(|c: char| c.is_whitespace() || c == ',').filter(|p| !p.is_empty()) {
+                let (key, value) = pair.split_once('=')
+                    .ok_or_else(|| format!("Invalid frost directive '{}': expected key=value", pair))?;
+                match key.to_ascii_lowercase().as_str() {
+                    "warehouse" => {
+                        if !is_object_name(value) {
+                            return Err(format!("Invalid warehouse name '{}' in frost directive", value));
+                        }
+                        directives.warehouse = Some(value.to_string());
+                    }
+                    "timeout" => {
+                        let secs = value.parse()
+                            .map_err(|_| format!("Invalid timeout '{}' in frost directive: expected seconds", value))?;
+                        directives.timeout_secs = Some(secs);
+                    }
+                    "script" => directives.script = parse_flag("script", value)?,
+                    "parallel" => directives.parallel = parse_flag("parallel", value)?,
+                    other => return Err(format!("Unknown frost directive '{}'", other)),
+                }
+            }
+        }
+        Ok(directives)
+    }
+}
+
+fn parse_flag(key: &str, value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "on" | "yes" => Ok(true),
+        "false" | "off" | "no" => Ok(false),
+        _ => Err(format!("Invalid {} value '{}' in frost directive: expected true or false", key, value)),
+    }
+}
+
+/// Plain or double-quoted object name, safe to splice into `USE WAREHOUSE`
+fn is_object_name(name: &str) -> bool {
+    if let Some(inner) = name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        return !inner.is_empty() && !inner.contains('"');
+    }
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
@@ -0,0 +1,163 @@
+use crate::overlay::OverlayOutcome;
+use crate::results::printable;
+use crate::row_view::ViewReader;
+use crate::tile_rowstore::NULL_SENTINEL;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, TryRecvError},
+    Arc,
+};
+use unicode_width::UnicodeWidthStr;
+
+/// Most frequent values kept for display
+const TOP_VALUES: usize = 50;
+
+/// Distinct values counted; values first seen after this many are left out,
+/// which makes the ranking approximate
+const MAX_TRACKED: usize = 100_000;
+
+/// Longer values are cut off
+const MAX_LABEL_WIDTH: usize = 30;
+
+/// Eighths of a block, for the fractional end of a bar
+const PARTIAL_BLOCKS: [&str; 8] = ["", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+
+/// Counts of one column's values
+#[derive(Debug)]
+pub struct Tally {
+    /// Most frequent values first, ties in value order; None is NULL
+    pub top: Vec<(Option<String>, usize)>,
+    pub distinct: usize,
+    /// More than MAX_TRACKED distinct values were seen
+    pub capped: bool,
+}
+
+impl Tally {
+    /// Columns the values are shown in, up to MAX_LABEL_WIDTH
+    pub fn label_width(&self) -> usize {
+        self.top.iter()
+            .map(|(v, _)| v.as_deref().map_or(4, |v| printable(v).width()))
+            .max()
+            .unwrap_or(1)
+            .clamp(4, MAX_LABEL_WIDTH)
+    }
+}
+
+#[derive(Debug)]
+enum ScanEvent {
+    Progress(usize),
+    Done(Result<Tally, String>),
+}
+
+/// Most frequent values of a column and their counts, counted over every
+/// tile on a background thread and drawn as horizontal bars
+#[derive(Debug)]
+pub struct Distribution {
+    pub column: String,
+    /// Rows in the result
+    pub total: usize,
+    /// Rows counted so far
+    pub scanned: usize,
+    /// Set once the scan ends
+    pub tally: Option<Result<Tally, String>>,
+    pub scroll: usize,
+    rx: Receiver<ScanEvent>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Distribution {
+    /// Count the values of `column` (an index into the headers)
+    pub fn start(mut reader: ViewReader, name: String, column: usize, total: usize) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        std::thread::spawn(move || {
+            let mut counts: HashMap<String, usize> = HashMap::new();
+            let mut capped = false;
+            let mut scanned = 0;
+            for tile in 0..reader.tile_count() {
+                if thread_stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                let rows = match reader.read_tile(tile) {
+                    Ok(rows) => rows,
+                    Err(e) => {
+                        let _ = tx.send(ScanEvent::Done(Err(e.to_string())));
+                        return;
+                    }
+                };
+                for value in rows.iter().filter_map(|r| r.get(column)) {
+                    if let Some(count) = counts.get_mut(value) {
+                        *count += 1;
+                    } else if counts.len() < MAX_TRACKED {
+                        counts.insert(value.clone(), 1);
+                    } else {
+                        capped = true;
+                    }
+                }
+                scanned += rows.len();
+                if tx.send(ScanEvent::Progress(scanned)).is_err() {
+                    return;
+                }
+            }
+            let distinct = counts.len();
+            let mut top: Vec<(String, usize)> = counts.into_iter().collect();
+            top.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            let top = top.into_iter()
+                .take(TOP_VALUES)
+                .map(|(v, n)| ((v != NULL_SENTINEL).then_some(v), n))
+                .collect();
+            let _ = tx.send(ScanEvent::Done(Ok(Tally { top, distinct, capped })));
+        });
+        Self { column: name, total, scanned: 0, tally: None, scroll: 0, rx, stop }
+    }
+
+    /// Take progress sent since the last call; true if anything changed
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            match self.rx.try_recv() {
+                Ok(ScanEvent::Progress(scanned)) => self.scanned = scanned,
+                Ok(ScanEvent::Done(tally)) => self.tally = Some(tally),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if self.tally.is_none() {
+                        self.tally = Some(Err("scan stopped".to_string()));
+                        changed = true;
+                    }
+                    break;
+                }
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        match key.code {
+            KeyCode::Char('q') => return OverlayOutcome::Close,
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Down => self.scroll += 1,
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::PageDown => self.scroll += 10,
+            KeyCode::Home => self.scroll = 0,
+            KeyCode::End => self.scroll = TOP_VALUES,
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+}
+
+impl Drop for Distribution {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A bar `fraction` of `width` columns long, to an eighth of a column
+pub fn bar(fraction: f64, width: usize) -> String {
+    let eighths = (fraction.clamp(0.0, 1.0) * width as f64 * 8.0).round() as usize;
+    format!("{}{}", "█".repeat(eighths / 8), PARTIAL_BLOCKS[eighths % 8])
+}
@@ -0,0 +1,39 @@
+/// Where a Snowflake error points in the submitted text: 0-based line and
+/// 0-based character column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorPosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Every `line <n> at position <m>` in an error message, as reported (lines
+/// 1-based, positions 0-based)
+fn reported_positions(message: &str) -> Vec<(usize, usize)> {
+    let words: Vec<&str> = message.split_whitespace().collect();
+    let number = |word: &str| word.trim_end_matches([',', '.', ':']).parse::<usize>().ok();
+    words.windows(5)
+        .filter(|w| w[0].eq_ignore_ascii_case("line") && w[2] == "at" && w[3] == "position")
+        .filter_map(|w| Some((number(w[1])?, number(w[4])?)))
+        .collect()
+}
+
+/// Error position in the text the user ran, given how many lines a wrapper
+/// (e.g. `EXECUTE IMMEDIATE $$`) put in front of it. Errors raised inside a
+/// scripting block report the statement's position in the block ("on line 3
+/// at position 2") and then the position within that statement ("error line
+/// 1 at position 7"); the two are combined. None if the message has no
+/// position or it points into the wrapper.
+pub fn parse(message: &str, wrapper_lines: usize) -> Option<ErrorPosition> {
+    let positions = reported_positions(message);
+    let (line, column) = match positions.as_slice() {
+        [] => return None,
+        [only] => *only,
+        [(outer_line, outer_col), (inner_line, inner_col), ..] => {
+            let line = outer_line + inner_line.saturating_sub(1);
+            let column = if *inner_line <= 1 { outer_col + inner_col } else { *inner_col };
+            (line, column)
+        }
+    };
+    let line = line.checked_sub(1 + wrapper_lines)?;
+    Some(ErrorPosition { line, column })
+}
@@ -0,0 +1,356 @@
+use crate::overlay::OverlayOutcome;
+use crate::tile_rowstore::NULL_SENTINEL;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Rows per `INSERT` statement when exporting as INSERTs
+const ROWS_PER_INSERT: usize = 1000;
+
+/// Most significant digits a number can have and still be written as a
+/// double without losing any
+const MAX_DOUBLE_DIGITS: usize = 15;
+
+/// Output formats for results copy/export
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    /// GitHub-flavored Markdown table
+    Markdown,
+    /// `INSERT INTO <table> (...) VALUES ...` statements
+    Insert { table: String },
+    /// One JSON object per row, optionally gzip-compressed when written to
+    /// a file
+    JsonLines { gzip: bool },
+    /// A JSON array of one object per row
+    Json,
+    /// Typed Parquet file, for pandas / DuckDB; written by `ParquetWriter`
+    Parquet,
+    /// Excel workbook with a sheet for the current tab, or for every table
+    /// tab; written by `XlsxWriter`
+    Xlsx { all_tabs: bool },
+}
+
+/// How a column's values are written where the output has types (JSON);
+/// taken from the column metadata of the result set
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ColumnKind {
+    Number,
+    Boolean,
+    Text,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Insert { .. } => "sql",
+            ExportFormat::JsonLines { gzip: false } => "jsonl",
+            ExportFormat::JsonLines { gzip: true } => "jsonl.gz",
+            ExportFormat::Json => "json",
+            ExportFormat::Parquet => "parquet",
+            ExportFormat::Xlsx { .. } => "xlsx",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::Insert { .. } => "INSERT statements",
+            ExportFormat::JsonLines { gzip: false } => "JSON lines",
+            ExportFormat::JsonLines { gzip: true } => "gzipped JSON lines",
+            ExportFormat::Json => "JSON",
+            ExportFormat::Parquet => "Parquet",
+            ExportFormat::Xlsx { .. } => "Excel workbook",
+        }
+    }
+}
+
+/// Format picker for copy/export. With a block selected the selection goes
+/// to the clipboard, otherwise the whole result is written to a file.
+#[derive(Debug)]
+pub struct ExportPrompt {
+    pub tab_idx: usize,
+    pub to_clipboard: bool,
+    /// Prefill for the INSERT target table
+    pub table_guess: String,
+    /// Set once a format is picked; INSERT waits here for its table name
+    pub format: Option<ExportFormat>,
+    /// JSON formats write numbers and booleans unquoted; t toggles
+    pub typed: bool,
+}
+
+impl ExportPrompt {
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        if let Some(ExportFormat::Insert { table }) = &mut self.format {
+            match key.code {
+                KeyCode::Enter if !table.trim().is_empty() => return OverlayOutcome::Submit,
+                KeyCode::Backspace => { table.pop(); }
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => table.push(c),
+                _ => {}
+            }
+            return OverlayOutcome::Consumed;
+        }
+        let format = match key.code {
+            KeyCode::Char('c') => ExportFormat::Csv,
+            KeyCode::Char('m') => ExportFormat::Markdown,
+            KeyCode::Char('j') => ExportFormat::JsonLines { gzip: false },
+            KeyCode::Char('g') if !self.to_clipboard => ExportFormat::JsonLines { gzip: true },
+            KeyCode::Char('a') => ExportFormat::Json,
+            KeyCode::Char('p') if !self.to_clipboard => ExportFormat::Parquet,
+            KeyCode::Char('x') if !self.to_clipboard => ExportFormat::Xlsx { all_tabs: false },
+            KeyCode::Char('X') if !self.to_clipboard => ExportFormat::Xlsx { all_tabs: true },
+            KeyCode::Char('t') => {
+                self.typed = !self.typed;
+                return OverlayOutcome::Consumed;
+            }
+            KeyCode::Char('i') => {
+                self.format = Some(ExportFormat::Insert { table: self.table_guess.clone() });
+                return OverlayOutcome::Consumed;
+            }
+            _ => return OverlayOutcome::Consumed,
+        };
+        self.format = Some(format);
+        OverlayOutcome::Submit
+    }
+}
+
+/// Writes rows in an `ExportFormat` a chunk at a time, so callers can stream
+/// a result set tile by tile instead of loading it whole.
+pub struct RowWriter<'a, W: Write> {
+    out: W,
+    format: &'a ExportFormat,
+    headers: &'a [String],
+    /// Per header; columns without a kind are written as text
+    kinds: &'a [ColumnKind],
+    rows_written: usize,
+}
+
+impl<'a, W: Write> RowWriter<'a, W> {
+    pub fn new(mut out: W, format: &'a ExportFormat, headers: &'a [String]) -> io::Result<Self> {
+        match format {
+            ExportFormat::Csv => {
+                let line: Vec<String> = headers.iter().map(|h| csv_field(h)).collect();
+                writeln!(out, "{}", line.join(","))?;
+            }
+            ExportFormat::Markdown => {
+                let line: Vec<String> = headers.iter().map(|h| markdown_cell(h)).collect();
+                writeln!(out, "| {} |", line.join(" | "))?;
+                writeln!(out, "|{}", "---|".repeat(headers.len()))?;
+            }
+            ExportFormat::Json => write!(out, "[")?,
+            ExportFormat::Parquet | ExportFormat::Xlsx { .. } => {
+                return Err(io::Error::new(io::ErrorKind::Unsupported, "binary formats have their own writers"));
+            }
+            ExportFormat::Insert { .. } | ExportFormat::JsonLines { .. } => {}
+        }
+        Ok(Self { out, format, headers, kinds: &[], rows_written: 0 })
+    }
+
+    /// Write numbers and booleans as JSON numbers and booleans
+    pub fn with_kinds(mut self, kinds: &'a [ColumnKind]) -> Self {
+        self.kinds = kinds;
+        self
+    }
+
+    pub fn write_rows(&mut self, rows: &[Vec<String>]) -> io::Result<()> {
+        for row in rows {
+            self.write_row(row)?;
+        }
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> io::Result<()> {
+        let out = &mut self.out;
+        match self.format {
+            ExportFormat::Csv => {
+                let fields: Vec<String> = row.iter()
+                    .map(|v| if v == NULL_SENTINEL { String::new() } else { csv_field(v) })
+                    .collect();
+                writeln!(out, "{}", fields.join(","))?;
+            }
+            ExportFormat::Markdown => {
+                let cells: Vec<String> = row.iter()
+                    .map(|v| if v == NULL_SENTINEL { "NULL".to_string() } else { markdown_cell(v) })
+                    .collect();
+                writeln!(out, "| {} |", cells.join(" | "))?;
+            }
+            ExportFormat::Insert { table } => {
+                if self.rows_written.is_multiple_of(ROWS_PER_INSERT) {
+                    if self.rows_written > 0 {
+                        writeln!(out, ";")?;
+                    }
+                    let columns: Vec<String> = self.headers.iter()
+                        .map(|h| format!("\"{}\"", h.replace('"', "\"\"")))
+                        .collect();
+                    write!(out, "INSERT INTO {} ({}) VALUES\n  ", table, columns.join(", "))?;
+                } else {
+                    write!(out, ",\n  ")?;
+                }
+                let values: Vec<String> = row.iter().map(|v| sql_value(v)).collect();
+                write!(out, "({})", values.join(", "))?;
+            }
+            ExportFormat::JsonLines { .. } | ExportFormat::Json => {
+                let fields: Vec<String> = self.headers.iter()
+                    .zip(row)
+                    .enumerate()
+                    .map(|(i, (h, v))| {
+                        let kind = self.kinds.get(i).copied().unwrap_or(ColumnKind::Text);
+                        format!("{}:{}", serde_json::Value::from(h.as_str()), json_value(v, kind))
+                    })
+                    .collect();
+                if matches!(self.format, ExportFormat::Json) {
+                    let separator = if self.rows_written == 0 { "" } else { "," };
+                    write!(out, "{}\n  {{{}}}", separator, fields.join(","))?;
+                } else {
+                    writeln!(out, "{{{}}}", fields.join(","))?;
+                }
+            }
+            ExportFormat::Parquet | ExportFormat::Xlsx { .. } => unreachable!("RowWriter::new rejects binary formats"),
+        }
+        self.rows_written += 1;
+        Ok(())
+    }
+
+    /// Terminate the output and flush; returns the number of rows written
+    pub fn finish(mut self) -> io::Result<usize> {
+        match self.format {
+            ExportFormat::Insert { .. } if self.rows_written > 0 => writeln!(self.out, ";")?,
+            ExportFormat::Json if self.rows_written > 0 => writeln!(self.out, "\n]")?,
+            ExportFormat::Json => writeln!(self.out, "]")?,
+            _ => {}
+        }
+        self.out.flush()?;
+        Ok(self.rows_written)
+    }
+}
+
+/// A stored value as JSON: NULL as null, numbers and booleans bare when
+/// their column says so and the text is valid as one, anything else a string
+fn json_value(value: &str, kind: ColumnKind) -> String {
+    if value == NULL_SENTINEL {
+        return "null".to_string();
+    }
+    match kind {
+        // Written as-is so wide NUMBER(38, x) values keep every digit
+        ColumnKind::Number if is_json_number(value) => value.to_string(),
+        ColumnKind::Boolean => match parse_bool(value) {
+            Some(b) => b.to_string(),
+            None => serde_json::Value::from(value).to_string(),
+        },
+        _ => serde_json::Value::from(value).to_string(),
+    }
+}
+
+/// A number with few enough significant digits to survive as a double,
+/// for binary formats
+pub fn parse_double(value: &str) -> Option<f64> {
+    let mantissa = value.split(['e', 'E']).next().unwrap_or(value);
+    let digits = mantissa.trim_start_matches(['-', '+', '0', '.']).bytes().filter(u8::is_ascii_digit).count();
+    value.parse::<f64>().ok().filter(|n| n.is_finite() && digits <= MAX_DOUBLE_DIGITS)
+}
+
+/// `true` / `false` in any case, or `1` / `0`
+pub fn parse_bool(value: &str) -> Option<bool> {
+    if value.eq_ignore_ascii_case("true") || value == "1" {
+        Some(true)
+    } else if value.eq_ignore_ascii_case("false") || value == "0" {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// `-12`, `0.5`, `1.2e-7`: JSON number syntax (no leading `+`, `.5` or `NaN`)
+fn is_json_number(value: &str) -> bool {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let unsigned = value.strip_prefix('-').unwrap_or(value);
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(i) => (&unsigned[..i], Some(&unsigned[i + 1..])),
+        None => (unsigned, None),
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((int, frac)) => (int, Some(frac)),
+        None => (mantissa, None),
+    };
+    digits(int)
+        && (int == "0" || !int.starts_with('0'))
+        && frac.is_none_or(digits)
+        && exponent.is_none_or(|e| digits(e.strip_prefix(['+', '-']).unwrap_or(e)))
+}
+
+/// Run `write` against `out`, gzip-compressed if asked, and finish the
+/// stream; returns what `write` returned
+pub fn write_maybe_gzip<W: Write>(
+    mut out: W,
+    gzip: bool,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<usize>,
+) -> io::Result<usize> {
+    if gzip {
+        let mut encoder = GzEncoder::new(out, Compression::default());
+        let n = write(&mut encoder)?;
+        encoder.finish()?.flush()?;
+        Ok(n)
+    } else {
+        let n = write(&mut out)?;
+        out.flush()?;
+        Ok(n)
+    }
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Escape pipes and line breaks, which would break the table layout
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace("\r\n", "<br>").replace('\n', "<br>")
+}
+
+/// NULL or a single-quoted Snowflake string literal
+fn sql_value(value: &str) -> String {
+    if value == NULL_SENTINEL {
+        "NULL".to_string()
+    } else {
+        format!("'{}'", value.replace('\\', "\\\\").replace('\'', "''"))
+    }
+}
+
+/// `column IN (...)` predicates of at most `batch` values each, OR-joined.
+/// Values of a number column are left bare, any other kind's are string
+/// literals.
+pub fn in_list(column: &str, kind: ColumnKind, values: &[String], batch: usize) -> String {
+    let literal = |v: &String| if kind == ColumnKind::Number { v.trim().to_string() } else { sql_value(v) };
+    values.chunks(batch.max(1))
+        .map(|chunk| {
+            let list: Vec<String> = chunk.iter().map(literal).collect();
+            format!("{} IN ({})", column, list.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n   OR ")
+}
+
+/// Write headers and rows as CSV; NULLs become empty fields
+pub fn write_csv(path: &Path, headers: &[String], rows: &[Vec<String>]) -> io::Result<()> {
+    let mut writer = RowWriter::new(BufWriter::new(File::create(path)?), &ExportFormat::Csv, headers)?;
+    writer.write_rows(rows)?;
+    writer.finish().map(|_| ())
+}
+
+/// `frost_<label>_<timestamp>.<ext>` in the current directory
+pub fn timestamped_path(label: &str, ext: &str) -> PathBuf {
+    let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+    PathBuf::from(format!("frost_{}_{}.{}", label, stamp, ext))
+}
@@ -0,0 +1,252 @@
+use crate::health::human_bytes;
+use crate::row_view::ViewReader;
+use crate::tile_rowstore::TILE_SIZE;
+use arrow_array::RecordBatch;
+use std::{
+    io::{self, Write},
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Progress is sent at most this often
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+enum JobEvent {
+    Progress { done: usize, rows: usize },
+    /// Message to show for how the export ended
+    Done(String),
+}
+
+/// The rows of a result, read a tile at a time through a separate handle
+/// on the store's file and cut down to the exported columns
+pub struct TileSource {
+    reader: ViewReader,
+    cols: Vec<usize>,
+    /// View rows exported, all of them unless narrowed by `with_rows`
+    rows: Range<usize>,
+    pub nrows: usize,
+}
+
+impl TileSource {
+    pub fn new(reader: ViewReader, cols: Vec<usize>, nrows: usize) -> Self {
+        Self { reader, cols, rows: 0..nrows, nrows }
+    }
+
+    /// Only view rows `rows`, e.g. a selection
+    pub fn with_rows(mut self, rows: Range<usize>) -> Self {
+        self.rows = rows.start.min(self.nrows)..rows.end.min(self.nrows);
+        self.nrows = self.rows.len();
+        self
+    }
+
+    /// Hand every row to `each` a tile at a time, counting them as written
+    /// rows or only as work done
+    pub fn scan(
+        &mut self,
+        progress: &mut JobProgress,
+        writes_rows: bool,
+        each: &mut dyn FnMut(&[Vec<String>]) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let tiles = self.rows.start / TILE_SIZE..self.rows.end.div_ceil(TILE_SIZE).min(self.reader.tile_count());
+        for tile in tiles {
+            let rows = self.reader.read_tile(tile)?;
+            let first = tile * TILE_SIZE;
+            let from = self.rows.start.saturating_sub(first).min(rows.len());
+            let to = (self.rows.end - first).min(rows.len());
+            let projected: Vec<Vec<String>> = rows[from..to].iter()
+                .map(|row| self.cols.iter().map(|&c| row.get(c).cloned().unwrap_or_default()).collect())
+                .collect();
+            each(&projected)?;
+            progress.advance(projected.len(), if writes_rows { projected.len() } else { 0 })?;
+        }
+        Ok(())
+    }
+
+    /// Like `scan`, but hand over each tile as a record batch of the
+    /// exported columns, sharing the tile's arrays rather than turning its
+    /// values into text
+    pub fn scan_batches(
+        &mut self,
+        progress: &mut JobProgress,
+        writes_rows: bool,
+        each: &mut dyn FnMut(&RecordBatch) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let tiles = self.rows.start / TILE_SIZE..self.rows.end.div_ceil(TILE_SIZE).min(self.reader.tile_count());
+        for tile in tiles {
+            let batch = self.reader.read_batch(tile)?;
+            let first = tile * TILE_SIZE;
+            let from = self.rows.start.saturating_sub(first).min(batch.num_rows());
+            let to = (self.rows.end - first).min(batch.num_rows());
+            let batch = batch.slice(from, to - from).project(&self.cols).map_err(io::Error::other)?;
+            each(&batch)?;
+            progress.advance(batch.num_rows(), if writes_rows { batch.num_rows() } else { 0 })?;
+        }
+        Ok(())
+    }
+}
+
+/// The export's side of a job: reports progress and says when to stop
+pub struct JobProgress {
+    tx: Sender<JobEvent>,
+    stop: Arc<AtomicBool>,
+    bytes: Arc<AtomicU64>,
+    last_sent: Instant,
+    done: usize,
+    rows: usize,
+}
+
+impl JobProgress {
+    /// `units` more of the work finished, `rows` of them written rows;
+    /// fails once the export is cancelled
+    pub fn advance(&mut self, units: usize, rows: usize) -> io::Result<()> {
+        if self.stop.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+        self.done += units;
+        self.rows += rows;
+        if self.last_sent.elapsed() >= PROGRESS_INTERVAL {
+            self.last_sent = Instant::now();
+            let _ = self.tx.send(JobEvent::Progress { done: self.done, rows: self.rows });
+        }
+        Ok(())
+    }
+
+    /// `inner` with the bytes written through it counted in the progress
+    pub fn count<W: Write>(&self, inner: W) -> CountingWriter<W> {
+        CountingWriter { inner, bytes: self.bytes.clone() }
+    }
+}
+
+/// Counts the bytes of an export as they reach its file
+pub struct CountingWriter<W> {
+    inner: W,
+    bytes: Arc<AtomicU64>,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A file export running on a worker thread, so writing a large result
+/// doesn't hold up the UI. A cancelled or failed export removes its file.
+pub struct ExportJob {
+    /// Format name, for the progress line
+    pub label: String,
+    /// Units of work in all: rows, once per pass over the result
+    total: usize,
+    done: usize,
+    rows: usize,
+    bytes: Arc<AtomicU64>,
+    started: Instant,
+    /// Set once the export ends
+    pub outcome: Option<String>,
+    rx: Receiver<JobEvent>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ExportJob {
+    /// Run `export` to `path` on a new thread; it returns the message shown
+    /// when it succeeds
+    pub fn start<F>(label: String, path: PathBuf, total: usize, export: F) -> Self
+    where
+        F: FnOnce(&mut JobProgress) -> io::Result<String> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let bytes = Arc::new(AtomicU64::new(0));
+        let mut progress = JobProgress {
+            tx: tx.clone(),
+            stop: stop.clone(),
+            bytes: bytes.clone(),
+            last_sent: Instant::now(),
+            done: 0,
+            rows: 0,
+        };
+        std::thread::spawn(move || {
+            let outcome = match export(&mut progress) {
+                Ok(message) => message,
+                Err(e) => {
+                    let _ = std::fs::remove_file(&path);
+                    if progress.stop.load(Ordering::Relaxed) {
+                        "Export cancelled".to_string()
+                    } else {
+                        format!("Export failed: {}", e)
+                    }
+                }
+            };
+            let _ = tx.send(JobEvent::Done(outcome));
+        });
+        Self { label, total, done: 0, rows: 0, bytes, started: Instant::now(), outcome: None, rx, stop }
+    }
+
+    /// Take progress sent since the last call; true if anything changed
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            match self.rx.try_recv() {
+                Ok(JobEvent::Progress { done, rows }) => {
+                    self.done = done;
+                    self.rows = rows;
+                }
+                Ok(JobEvent::Done(outcome)) => self.outcome = Some(outcome),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if self.outcome.is_none() {
+                        self.outcome = Some("Export stopped".to_string());
+                        changed = true;
+                    }
+                    break;
+                }
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// Ask the export to stop after the tile it is on
+    pub fn cancel(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// `Exporting CSV: 42% · 120000 rows · 14.2 MB · ETA 12s (c cancels)`
+    pub fn status(&self) -> String {
+        let mut parts = vec![format!("{} rows", self.rows)];
+        if let Some(percent) = (self.done * 100).checked_div(self.total) {
+            parts.insert(0, format!("{}%", percent));
+        }
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        if bytes > 0 {
+            parts.push(human_bytes(bytes));
+        }
+        if self.done > 0 && self.done < self.total {
+            let left = self.started.elapsed().mul_f64((self.total - self.done) as f64 / self.done as f64);
+            parts.push(format!("ETA {}s", left.as_secs() + 1));
+        }
+        if self.stop.load(Ordering::Relaxed) {
+            format!("Cancelling {} export: {}", self.label, parts.join(" · "))
+        } else {
+            format!("Exporting {}: {} (c cancels)", self.label, parts.join(" · "))
+        }
+    }
+}
+
+impl Drop for ExportJob {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
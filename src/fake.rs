@@ -0,0 +1,242 @@
+use crate::connection::{CancelHandle, DbWorkerRequest, DbWorkerResponse, QueryExecutor, QueryRequest, WorkerRequests};
+use crate::export::ColumnKind;
+use crate::results::ResultsContent;
+use crate::switcher::{ContextKind, SessionContext};
+use crate::tile_rowstore::{TileRowStore, TileStoreOptions};
+use std::{
+    sync::mpsc::Sender,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// What a fake statement returns
+#[derive(Debug, Clone)]
+pub enum CannedResult {
+    Rows { headers: Vec<String>, rows: Vec<Vec<String>> },
+    /// A statement without a result set, e.g. DDL
+    Message(String),
+    Error(String),
+}
+
+/// In-memory stand-in for Snowflake: statements are answered with the
+/// canned result registered for their text (compared ignoring case,
+/// whitespace and a trailing semicolon), anything else fails
+#[derive(Debug, Clone, Default)]
+pub struct FakeExecutor {
+    results: Vec<(String, CannedResult)>,
+    context: SessionContext,
+    store_options: TileStoreOptions,
+    /// Time each statement pretends to run, so running and cancelling can
+    /// be seen
+    latency: Duration,
+}
+
+impl FakeExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_rows(mut self, sql: &str, headers: &[&str], rows: &[&[&str]]) -> Self {
+        let result = CannedResult::Rows {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: rows.iter().map(|row| row.iter().map(|v| v.to_string()).collect()).collect(),
+        };
+        self.results.push((normalize(sql), result));
+        self
+    }
+
+    pub fn with_message(mut self, sql: &str, message: &str) -> Self {
+        self.results.push((normalize(sql), CannedResult::Message(message.to_string())));
+        self
+    }
+
+    pub fn with_error(mut self, sql: &str, message: &str) -> Self {
+        self.results.push((normalize(sql), CannedResult::Error(message.to_string())));
+        self
+    }
+
+    /// Role, warehouse, database and schema reported as the session's
+    pub fn with_context(mut self, context: SessionContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn with_store_options(mut self, store_options: TileStoreOptions) -> Self {
+        self.store_options = store_options;
+        self
+    }
+
+    /// A small sample schema for `--demo`
+    pub fn demo() -> Self {
+        let context = SessionContext {
+            role: Some("DEMO_ROLE".to_string()),
+            warehouse: Some("DEMO_WH".to_string()),
+            database: Some("DEMO".to_string()),
+            schema: Some("PUBLIC".to_string()),
+            utc_offset: Some("+00:00".to_string()),
+            client: Some("frost demo".to_string()),
+        };
+        Self::new()
+            .with_context(context)
+            .with_latency(Duration::from_millis(300))
+            .with_rows("SELECT 1", &["1"], &[&["1"]])
+            .with_rows(
+                "SELECT * FROM orders",
+                &["ORDER_ID", "CUSTOMER", "STATUS", "AMOUNT", "ORDERED_AT"],
+                &[
+                    &["1001", "Acme Corp", "SHIPPED", "1250.00", "2024-03-01 09:15:00"],
+                    &["1002", "Globex", "PENDING", "89.99", "2024-03-01 11:42:10"],
+                    &["1003", "Initech", "SHIPPED", "430.50", "2024-03-02 08:03:55"],
+                    &["1004", "Acme Corp", "CANCELLED", "15.00", "2024-03-02 16:27:31"],
+                    &["1005", "Umbrella", "SHIPPED", "2999.00", "2024-03-03 13:00:00"],
+                ],
+            )
+            .with_rows(
+                "SELECT customer, COUNT(*) AS orders, SUM(amount) AS total FROM orders GROUP BY customer",
+                &["CUSTOMER", "ORDERS", "TOTAL"],
+                &[
+                    &["Acme Corp", "2", "1265.00"],
+                    &["Globex", "1", "89.99"],
+                    &["Initech", "1", "430.50"],
+                    &["Umbrella", "1", "2999.00"],
+                ],
+            )
+            .with_message("CREATE TABLE scratch (id NUMBER)", "Table SCRATCH successfully created.")
+            .with_error(
+                "SELECT * FROM missing",
+                "SQL compilation error:\nObject 'MISSING' does not exist or not authorized.",
+            )
+    }
+
+    fn lookup(&self, sql: &str) -> CannedResult {
+        let key = normalize(sql);
+        self.results.iter()
+            .find(|(canned, _)| *canned == key)
+            .map(|(_, result)| result.clone())
+            .unwrap_or_else(|| CannedResult::Error(format!("No canned result for: {}", sql.trim())))
+    }
+
+    /// Run a batch in order; like the ODBC worker, a cancel or an error
+    /// drops the rest of it
+    fn run_batch(&self, queries: Vec<QueryRequest>, resp_tx: &Sender<DbWorkerResponse>, cancel: &CancelHandle) {
+        cancel.reset();
+        for (idx, request) in queries.into_iter().enumerate() {
+            if cancel.is_requested() || cancel.is_queue_dropped() {
+                break;
+            }
+            let started = Instant::now();
+            let _ = resp_tx.send(DbWorkerResponse::QueryStarted {
+                query_idx: idx,
+                started,
+                request: request.clone(),
+            });
+            let _watchdog = request.timeout.map(|timeout| cancel.watchdog(timeout));
+            while started.elapsed() < self.latency && !cancel.is_requested() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            if cancel.is_requested() {
+                let _ = resp_tx.send(cancel.stopped_response(idx, started.elapsed(), request.timeout));
+                break;
+            }
+            let result = match self.lookup(&request.sql) {
+                CannedResult::Rows { headers, rows } => {
+                    let kinds = column_kinds(headers.len(), &rows);
+                    TileRowStore::from_rows(&headers, &kinds, rows.into_iter(), self.store_options)
+                        .map(|tile_store| ResultsContent::table(headers, tile_store, kinds))
+                        .map_err(|e| format!("Failed to create tile store: {:?}", e))
+                }
+                CannedResult::Message(message) => Ok(ResultsContent::info(message, None)),
+                CannedResult::Error(message) => Err(message),
+            };
+            let elapsed = started.elapsed();
+            match result {
+                Ok(result) => {
+                    let _ = resp_tx.send(DbWorkerResponse::QueryFinished {
+                        query_idx: idx,
+                        elapsed,
+                        query_id: None,
+                        result,
+                        truncated: false,
+                    });
+                }
+                Err(message) => {
+                    let _ = resp_tx.send(DbWorkerResponse::QueryError { query_idx: idx, elapsed, query_id: None, message });
+                    break;
+                }
+            }
+        }
+        let _ = resp_tx.send(DbWorkerResponse::SessionContext(self.context.clone()));
+    }
+}
+
+impl QueryExecutor for FakeExecutor {
+    fn serve(mut self: Box<Self>, requests: WorkerRequests, resp_tx: Sender<DbWorkerResponse>, cancel: CancelHandle) {
+        let _ = resp_tx.send(DbWorkerResponse::Connected);
+        let _ = resp_tx.send(DbWorkerResponse::SessionContext(self.context.clone()));
+        let unsupported = || "Not available without a Snowflake connection".to_string();
+        loop {
+            let response = match requests.recv(None) {
+                Ok(DbWorkerRequest::RunQueries(queries)) | Ok(DbWorkerRequest::RunParallel { queries, .. }) => {
+                    self.run_batch(queries, &resp_tx, &cancel);
+                    continue;
+                }
+                Ok(DbWorkerRequest::FetchTableStats { database, schema }) => {
+                    DbWorkerResponse::TableStats { database, schema, stats: Vec::new() }
+                }
+                Ok(DbWorkerRequest::ListContext(kind)) => {
+                    let current = match kind {
+                        ContextKind::Role => &self.context.role,
+                        ContextKind::Warehouse => &self.context.warehouse,
+                        ContextKind::Database => &self.context.database,
+                    };
+                    DbWorkerResponse::ContextOptions { kind, names: Ok(current.iter().cloned().collect()) }
+                }
+                Ok(DbWorkerRequest::UseContext { kind, name }) => {
+                    let current = match kind {
+                        ContextKind::Role => &mut self.context.role,
+                        ContextKind::Warehouse => &mut self.context.warehouse,
+                        ContextKind::Database => &mut self.context.database,
+                    };
+                    *current = Some(name);
+                    DbWorkerResponse::SessionContext(self.context.clone())
+                }
+                Ok(DbWorkerRequest::EstimateCosts(statements)) => {
+                    for statement in statements {
+                        let _ = resp_tx.send(DbWorkerResponse::CostEstimate { statement, bytes: None });
+                    }
+                    continue;
+                }
+                Ok(DbWorkerRequest::FetchMore(_)) => DbWorkerResponse::MoreRows { rows: Vec::new(), more: false, error: None },
+                Ok(DbWorkerRequest::ListStages) => DbWorkerResponse::Stages(Ok(Vec::new())),
+                Ok(DbWorkerRequest::ListStageFiles(stage)) => DbWorkerResponse::StageFiles { stage, files: Ok(Vec::new()) },
+                Ok(DbWorkerRequest::StageTransfer { .. }) => DbWorkerResponse::StageTransferDone { result: Err(unsupported()) },
+                Ok(DbWorkerRequest::FetchDdl(name)) => DbWorkerResponse::Ddl { name, ddl: Err(unsupported()) },
+                Ok(DbWorkerRequest::ListMetadataDatabases) => DbWorkerResponse::MetadataDatabases(Ok(Vec::new())),
+                Ok(DbWorkerRequest::FetchMetadata(database)) => DbWorkerResponse::Metadata { database, columns: Ok(Vec::new()) },
+                Ok(DbWorkerRequest::Quit) | Err(_) => break,
+            };
+            let _ = resp_tx.send(response);
+        }
+    }
+}
+
+/// Lowercased with whitespace collapsed and no trailing semicolon
+fn normalize(sql: &str) -> String {
+    let sql = sql.trim().trim_end_matches(';');
+    sql.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Number for columns whose every value parses as one, else text
+fn column_kinds(columns: usize, rows: &[Vec<String>]) -> Vec<ColumnKind> {
+    (0..columns)
+        .map(|col| {
+            let numeric = rows.iter().all(|row| row.get(col).is_some_and(|v| v.parse::<f64>().is_ok()));
+            if numeric && !rows.is_empty() { ColumnKind::Number } else { ColumnKind::Text }
+        })
+        .collect()
+}
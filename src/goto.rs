@@ -0,0 +1,71 @@
+use crate::overlay::OverlayOutcome;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Where the go-to prompt's input points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GotoTarget {
+    /// 1-based line and optional 1-based column, as shown in the status bar
+    Line { line: usize, col: Option<usize> },
+    /// Lines down (positive) or up (negative) from the caret's
+    Relative(isize),
+}
+
+impl GotoTarget {
+    /// `line`, `line:col`, `+N` or `-N`; None for anything else
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        if let Some(n) = input.strip_prefix('+') {
+            return n.parse().ok().map(GotoTarget::Relative);
+        }
+        if input.starts_with('-') {
+            return input.parse().ok().map(GotoTarget::Relative);
+        }
+        match input.split_once(':') {
+            Some((line, col)) => Some(GotoTarget::Line { line: line.parse().ok()?, col: Some(col.parse().ok()?) }),
+            None => Some(GotoTarget::Line { line: input.parse().ok()?, col: None }),
+        }
+    }
+
+    /// 0-based line and column to put the caret at, from the caret's line
+    /// `current` in a buffer of `line_count` lines
+    pub fn resolve(self, current: usize, line_count: usize) -> (usize, usize) {
+        let last = line_count.saturating_sub(1);
+        match self {
+            GotoTarget::Line { line, col } => (line.saturating_sub(1).min(last), col.unwrap_or(1).saturating_sub(1)),
+            GotoTarget::Relative(n) => (current.saturating_add_signed(n).min(last), 0),
+        }
+    }
+}
+
+/// Ctrl+G prompt for a line to jump to
+#[derive(Debug, Default)]
+pub struct GotoPrompt {
+    pub input: String,
+    /// Set when Enter was pressed on input that isn't a target
+    pub invalid: bool,
+}
+
+impl GotoPrompt {
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        match key.code {
+            KeyCode::Enter if GotoTarget::parse(&self.input).is_some() => return OverlayOutcome::Submit,
+            KeyCode::Enter => self.invalid = true,
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.invalid = false;
+            }
+            KeyCode::Char(c)
+                if (c.is_ascii_digit() || matches!(c, ':' | '+' | '-')) && !key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                self.input.push(c);
+                self.invalid = false;
+            }
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+
+    pub fn target(&self) -> Option<GotoTarget> {
+        GotoTarget::parse(&self.input)
+    }
+}
@@ -0,0 +1,151 @@
+use crate::config::Dialect;
+use crate::cost::split_statements;
+
+/// Leading keywords of statements that only read. SHOW, DESCRIBE, EXPLAIN,
+/// LIST and USE change no data, so they pass the production guard along
+/// with queries.
+const READ_KEYWORDS: [&str; 9] = ["SELECT", "WITH", "SHOW", "DESCRIBE", "DESC", "EXPLAIN", "LIST", "LS", "USE"];
+
+/// First keyword of `statement`, uppercased, after leading comments and
+/// parentheses
+pub fn first_keyword(statement: &str) -> Option<String> {
+    let mut rest = statement;
+    loop {
+        rest = rest.trim_start().trim_start_matches('(');
+        if let Some(after) = rest.strip_prefix("--") {
+            rest = after.find('\n').map_or("", |end| &after[end..]);
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            rest = after.find("*/").map_or("", |end| &after[end + 2..]);
+        } else {
+            break;
+        }
+    }
+    let word: String = rest.chars().take_while(|c| c.is_ascii_alphabetic() || *c == '_').collect();
+    Some(word.to_ascii_uppercase()).filter(|w| !w.is_empty())
+}
+
+/// Keyword of the statement a `WITH` clause leads into: the first word
+/// outside the CTE bodies, quotes and comments that starts a statement.
+/// `WITH t AS (...) DELETE FROM ...` writes even though it opens like a query.
+fn cte_keyword(statement: &str, dialect: Dialect) -> Option<String> {
+    const BODIES: [&str; 6] = ["SELECT", "INSERT", "UPDATE", "DELETE", "MERGE", "VALUES"];
+    let backslash_escapes = dialect == Dialect::Snowflake;
+    let mut chars = statement.char_indices().peekable();
+    let mut depth = 0usize;
+    let mut word_start = None;
+    while let Some((i, c)) = chars.next() {
+        if c.is_ascii_alphabetic() || c == '_' {
+            word_start.get_or_insert(i);
+            if chars.peek().is_some_and(|(_, next)| next.is_ascii_alphanumeric() || *next == '_') {
+                continue;
+            }
+            let word = statement[word_start.take().unwrap_or(i)..=i].to_ascii_uppercase();
+            if depth == 0 && BODIES.contains(&word.as_str()) {
+                return Some(word);
+            }
+            continue;
+        }
+        if c.is_ascii_digit() && word_start.is_some() {
+            continue;
+        }
+        word_start = None;
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            '\'' | '"' => {
+                while let Some((_, next)) = chars.next() {
+                    if next == '\\' && c == '\'' && backslash_escapes {
+                        chars.next();
+                    } else if next == c {
+                        if chars.peek().is_some_and(|(_, after)| *after == c) {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+            '-' if chars.peek().is_some_and(|(_, next)| *next == '-') => {
+                for (_, next) in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek().is_some_and(|(_, next)| *next == '*') => {
+                chars.next();
+                let mut star = false;
+                for (_, next) in chars.by_ref() {
+                    if star && next == '/' {
+                        break;
+                    }
+                    star = next == '*';
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Leading keywords of the statements in `text` that may write, in order
+/// and without repeats; empty if the text only reads
+pub fn write_keywords(text: &str, dialect: Dialect) -> Vec<String> {
+    let mut keywords = Vec::new();
+    for (_, statement) in split_statements(text, dialect) {
+        let mut keyword = first_keyword(statement).unwrap_or_else(|| "?".to_string());
+        if keyword == "WITH" {
+            keyword = cte_keyword(statement, dialect).unwrap_or(keyword);
+        }
+        if !READ_KEYWORDS.contains(&keyword.as_str()) && !keywords.contains(&keyword) {
+            keywords.push(keyword);
+        }
+    }
+    keywords
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn writes(text: &str) -> Vec<String> {
+        write_keywords(text, Dialect::Snowflake)
+    }
+
+    #[test]
+    fn queries_pass() {
+        assert!(writes("SELECT 1; show tables; DESC t; explain select 2; use db").is_empty());
+        assert!(writes("WITH a AS (SELECT 1) SELECT * FROM a").is_empty());
+    }
+
+    #[test]
+    fn leading_comments_are_skipped() {
+        assert_eq!(writes("-- tidy up\n/* old rows */ DELETE FROM t"), vec!["DELETE"]);
+        assert!(writes("/* DELETE */ -- DROP\nSELECT 1").is_empty());
+    }
+
+    #[test]
+    fn keywords_are_matched_in_any_case() {
+        assert_eq!(writes("drop table t; Update t set a = 1; insert into t values (1)"), vec!["DROP", "UPDATE", "INSERT"]);
+    }
+
+    #[test]
+    fn ctes_that_end_in_a_write_are_caught() {
+        assert_eq!(writes("WITH old AS (SELECT id FROM t WHERE stale) DELETE FROM t USING old"), vec!["DELETE"]);
+        assert_eq!(writes("with a as (select 1), b (x) as (select 2) insert into t select * from a"), vec!["INSERT"]);
+        assert_eq!(writes("WITH a AS (SELECT 1) UPDATE t SET x = 1"), vec!["UPDATE"]);
+    }
+
+    #[test]
+    fn merge_is_a_write() {
+        assert_eq!(writes("MERGE INTO t USING s ON t.id = s.id WHEN MATCHED THEN DELETE"), vec!["MERGE"]);
+    }
+
+    #[test]
+    fn keywords_in_literals_and_comments_dont_trip_the_guard() {
+        assert!(writes("SELECT 'DELETE FROM t; DROP TABLE t' AS note").is_empty());
+        assert!(writes("WITH a AS (SELECT 'x') SELECT 'DELETE' -- UPDATE\nFROM a").is_empty());
+        assert!(writes("WITH \"delete\" AS (SELECT 1) SELECT * FROM \"delete\"").is_empty());
+        assert!(write_keywords(r"WITH a AS (SELECT 'it\'s) DELETE') SELECT 1", Dialect::Snowflake).is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+use crate::config::Config;
+use crate::connection::{start_db_worker, DbWorkerRequest, DbWorkerResponse, QueryExecutor, QueryRequest};
+use crate::directive::Directives;
+use crate::export::{self, ExportFormat, RowWriter};
+use crate::guard;
+use crate::results::ResultsContent;
+use crate::tile_rowstore::TILE_SIZE;
+use anyhow::{anyhow, bail, Result};
+use std::io::{self, BufWriter, Read};
+
+/// `--jsonl <file|->` run: execute the SQL without the TUI and stream the
+/// result to stdout as JSON lines, gzipped with `--gzip`
+#[derive(Debug)]
+pub struct Headless {
+    /// SQL file, or `-` for stdin
+    source: String,
+    gzip: bool,
+}
+
+impl Headless {
+    /// None unless `--jsonl` is on the command line
+    pub fn from_args() -> Result<Option<Self>> {
+        let mut source = None;
+        let mut gzip = false;
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--jsonl" {
+                source = Some(args.next().ok_or_else(|| anyhow!("--jsonl requires a SQL file or -"))?);
+            } else if let Some(path) = arg.strip_prefix("--jsonl=") {
+                source = Some(path.to_string());
+            } else if arg == "--gzip" {
+                gzip = true;
+            }
+        }
+        match source {
+            Some(source) => Ok(Some(Self { source, gzip })),
+            None if gzip => bail!("--gzip only applies to --jsonl output"),
+            None => Ok(None),
+        }
+    }
+
+    /// Run with `executor`, which should fetch every row (`max_rows` 0)
+    pub fn run(self, config: &Config, executor: Box<dyn QueryExecutor>) -> Result<()> {
+        let sql = if self.source == "-" {
+            let mut sql = String::new();
+            io::stdin().read_to_string(&mut sql)?;
+            sql
+        } else {
+            std::fs::read_to_string(&self.source)
+                .map_err(|e| anyhow!("Reading {}: {}", self.source, e))?
+        };
+        if sql.trim().is_empty() {
+            bail!("No SQL to run");
+        }
+        // Nobody is there to confirm, so production writes always need the flag
+        if config.is_production() && !config.allow_prod_writes {
+            let writes = guard::write_keywords(&sql, config.sql_dialect());
+            if !writes.is_empty() {
+                bail!("{} refused: writes to production need --allow-prod-writes", writes.join(", "));
+            }
+        }
+        let directives = Directives::parse(&sql).map_err(|e| anyhow!(e))?;
+
+        let requests: Vec<QueryRequest> = QueryRequest::batch(&sql, &directives, config.query_timeout(directives.timeout_secs), config.sql_dialect())
+            .map_err(|e| anyhow!(e))?
+            .into_iter()
+            .map(|(_, request)| request)
+            .collect();
+        if requests.is_empty() {
+            bail!("No SQL to run");
+        }
+        let last = requests.len() - 1;
+
+        let (req_tx, resp_rx, _cancel) = start_db_worker(executor);
+        let _ = req_tx.send(DbWorkerRequest::RunQueries(requests));
+
+        // The last statement's result is the output, as for a script
+        let result = loop {
+            match resp_rx.recv() {
+                Ok(DbWorkerResponse::QueryFinished { query_idx, result, .. }) if query_idx == last => break result,
+                Ok(DbWorkerResponse::QueryError { message, .. }) => bail!(message),
+                Ok(DbWorkerResponse::QueryCancelled { .. }) => bail!("Query cancelled"),
+                Ok(DbWorkerResponse::ConnectionFailed { message }) => bail!("Connection failed: {}", message),
+                Ok(_) => continue,
+                Err(_) => bail!("Database worker stopped"),
+            }
+        };
+        let _ = req_tx.send(DbWorkerRequest::Quit);
+
+        match result {
+            ResultsContent::Table { headers, mut tile_store, kinds, .. } => {
+                let format = ExportFormat::JsonLines { gzip: self.gzip };
+                let stdout = BufWriter::new(io::stdout().lock());
+                export::write_maybe_gzip(stdout, self.gzip, |out| {
+                    let mut writer = RowWriter::new(out, &format, &headers)?.with_kinds(&kinds);
+                    let mut start = 0;
+                    while start < tile_store.nrows {
+                        let chunk = tile_store.get_rows(start, TILE_SIZE)?;
+                        if chunk.is_empty() {
+                            break;
+                        }
+                        writer.write_rows(&chunk)?;
+                        start += chunk.len();
+                    }
+                    writer.finish()
+                })?;
+            }
+            // Statements without a result set report to stderr, keeping stdout clean
+            ResultsContent::Info { message, .. } => eprintln!("{}", message),
+            _ => {}
+        }
+        Ok(())
+    }
+}
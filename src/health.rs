@@ -0,0 +1,194 @@
+use crate::config::{Backend, Config, Dialect};
+use std::path::Path;
+
+/// Free space in the temp dir below which result spooling is flagged
+const LOW_TEMP_SPACE: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Warn => "warn",
+            CheckStatus::Fail => "FAIL",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// Environment diagnostics shown at startup (always when a check fails) or
+/// with the health_report action, and copied as text for support requests.
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthReport {
+    /// Checks for a session using `config`; the ODBC driver only matters
+    /// for `backend = "odbc"`
+    pub fn collect(config: &Config) -> Self {
+        let driver = match config.backend {
+            Backend::Odbc => check_odbc_driver(config.dialect),
+            Backend::Rest => check("ODBC driver", CheckStatus::Ok, "not needed (backend = \"rest\")".to_string()),
+        };
+        Self {
+            checks: vec![
+                check_config(&config.path),
+                driver,
+                check_clipboard(),
+                check_truecolor(),
+                check_temp_dir(),
+                check_recovery(),
+            ],
+        }
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.checks.iter().any(|c| c.status == CheckStatus::Fail)
+    }
+
+    /// Add the driver version the server reported for this session
+    pub fn set_driver_version(&mut self, client: &str) {
+        if let Some(check) = self.checks.iter_mut().find(|c| c.name == "ODBC driver") {
+            if check.status == CheckStatus::Ok && !check.detail.contains(client) {
+                check.detail.push_str(&format!(" ({})", client));
+            }
+        }
+    }
+
+    /// Plain-text report for pasting into a support request
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Frost {} health report\n", env!("CARGO_PKG_VERSION"));
+        for check in &self.checks {
+            text.push_str(&format!("[{:<4}] {:<14} {}\n", check.status.label(), check.name, check.detail));
+        }
+        text
+    }
+}
+
+fn check(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> HealthCheck {
+    HealthCheck { name, status, detail: detail.into() }
+}
+
+fn check_config(path: &Path) -> HealthCheck {
+    check("Config", CheckStatus::Ok, path.display().to_string())
+}
+
+/// An installed driver for `dialect`, recognized by its description; any
+/// driver will do for the generic dialect
+fn check_odbc_driver(dialect: Dialect) -> HealthCheck {
+    const NAME: &str = "ODBC driver";
+    let (needle, label) = match dialect {
+        Dialect::Snowflake => ("snowflake", "Snowflake"),
+        Dialect::Postgres => ("postgres", "PostgreSQL"),
+        Dialect::SqlServer => ("sql server", "SQL Server"),
+        Dialect::DuckDb => ("duckdb", "DuckDB"),
+        Dialect::Generic => ("", "ODBC"),
+    };
+    let mut env = match odbc::create_environment_v3() {
+        Ok(env) => env,
+        Err(e) => {
+            let detail = e.map(|e| e.to_string()).unwrap_or_else(|| "no diagnostics".to_string());
+            return check(NAME, CheckStatus::Fail, format!("ODBC environment unavailable: {}", detail));
+        }
+    };
+    let drivers = match env.drivers() {
+        Ok(drivers) => drivers,
+        Err(e) => return check(NAME, CheckStatus::Fail, format!("Listing drivers failed: {}", e)),
+    };
+    match drivers.iter().find(|d| d.description.to_lowercase().contains(needle)) {
+        Some(driver) => {
+            let location = driver.attributes.get("Driver").map(|p| format!(" at {}", p)).unwrap_or_default();
+            check(NAME, CheckStatus::Ok, format!("{}{}", driver.description, location))
+        }
+        None => check(
+            NAME,
+            CheckStatus::Fail,
+            format!("No {} driver among {} installed driver(s)", label, drivers.len()),
+        ),
+    }
+}
+
+fn check_clipboard() -> HealthCheck {
+    match arboard::Clipboard::new() {
+        Ok(_) => check("Clipboard", CheckStatus::Ok, "available"),
+        Err(e) => check("Clipboard", CheckStatus::Warn, format!("unavailable ({}); copies go through the terminal (OSC 52) and paste only within frost", e)),
+    }
+}
+
+fn check_truecolor() -> HealthCheck {
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default().to_lowercase();
+    if colorterm == "truecolor" || colorterm == "24bit" || std::env::var_os("WT_SESSION").is_some() {
+        check("Truecolor", CheckStatus::Ok, "supported")
+    } else {
+        check("Truecolor", CheckStatus::Warn, "COLORTERM not set to truecolor; theme colors may be approximated")
+    }
+}
+
+fn check_temp_dir() -> HealthCheck {
+    const NAME: &str = "Temp dir";
+    let dir = std::env::temp_dir();
+    if let Err(e) = tempfile::NamedTempFile::new_in(&dir) {
+        return check(NAME, CheckStatus::Fail, format!("{} not writable: {}", dir.display(), e));
+    }
+    match free_space(&dir) {
+        Some(free) if free < LOW_TEMP_SPACE => check(
+            NAME,
+            CheckStatus::Warn,
+            format!("{} writable, only {} free for result spooling", dir.display(), human_bytes(free)),
+        ),
+        Some(free) => check(NAME, CheckStatus::Ok, format!("{} writable, {} free", dir.display(), human_bytes(free))),
+        None => check(NAME, CheckStatus::Ok, format!("{} writable", dir.display())),
+    }
+}
+
+#[cfg(unix)]
+fn free_space(dir: &Path) -> Option<u64> {
+    fs2::available_space(dir).ok()
+}
+
+#[cfg(not(unix))]
+fn free_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+fn check_recovery() -> HealthCheck {
+    let orphaned = crate::recovery::find_orphaned();
+    if orphaned.is_empty() {
+        check("Last session", CheckStatus::Ok, "exited cleanly, nothing to recover")
+    } else {
+        check(
+            "Last session",
+            CheckStatus::Warn,
+            format!("{} unsaved autosave file(s) from a crashed session", orphaned.len()),
+        )
+    }
+}
+
+/// `512 B`, `2.1 GB`, ...
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
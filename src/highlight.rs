@@ -0,0 +1,183 @@
+use crate::config::{rgb, ColorConfig, Dialect};
+use ratatui::{
+    style::Style,
+    text::{Line, Span},
+};
+
+/// Keywords every dialect shares
+const KEYWORDS: &[&str] = &[
+    "ADD", "ALL", "ALTER", "AND", "AS", "ASC", "BETWEEN", "BY", "CASE", "COLLATE", "COLUMN", "CONSTRAINT",
+    "CREATE", "CROSS", "DATABASE", "DEFAULT", "DELETE", "DESC", "DISTINCT", "DROP", "ELSE", "END", "EXISTS",
+    "FOREIGN", "FROM", "FULL", "FUNCTION", "GRANT", "GROUP", "HAVING", "IF", "IN", "INNER", "INSERT", "INTO",
+    "IS", "JOIN", "KEY", "LEFT", "LIKE", "MERGE", "NOT", "NULL", "ON", "OR", "ORDER", "OUTER", "OVER",
+    "PARTITION", "PRIMARY", "PROCEDURE", "RECURSIVE", "REFERENCES", "REPLACE", "RETURNS", "RIGHT", "ROW",
+    "SCHEMA", "SELECT", "SEQUENCE", "SET", "TABLE", "THEN", "UNION", "UNIQUE", "UPDATE", "USING", "VALUES",
+    "VIEW", "WHEN", "WHERE", "WITH",
+];
+
+const SNOWFLAKE_KEYWORDS: &[&str] = &[
+    "AUTOINCREMENT", "CHANGE_TRACKING", "CLUSTER", "COMMENT", "COPY", "IDENTITY", "LIMIT", "MATERIALIZED",
+    "POLICY", "QUALIFY", "SECURE", "STAGE", "TAG", "TEMPORARY", "TRANSIENT",
+];
+
+const POSTGRES_KEYWORDS: &[&str] = &[
+    "COMMENT", "CONFLICT", "COPY", "DO", "EXTENSION", "ILIKE", "LATERAL", "LIMIT", "MATERIALIZED", "NOTHING",
+    "OFFSET", "RETURNING", "TEMPORARY", "UNLOGGED",
+];
+
+const SQLSERVER_KEYWORDS: &[&str] = &[
+    "APPLY", "BEGIN", "CLUSTERED", "DECLARE", "EXEC", "GO", "IDENTITY", "NOLOCK", "NONCLUSTERED", "OUTPUT",
+    "PIVOT", "TOP", "TRAN", "TRANSACTION", "UNPIVOT",
+];
+
+const DUCKDB_KEYWORDS: &[&str] = &[
+    "ASOF", "ATTACH", "COPY", "ILIKE", "INSTALL", "LIMIT", "LOAD", "MACRO", "OFFSET", "PIVOT", "POSITIONAL",
+    "QUALIFY", "RETURNING", "TEMPORARY", "UNPIVOT",
+];
+
+/// Whether `word`, in uppercase, is a keyword of `dialect`
+pub fn is_keyword(word: &str, dialect: Dialect) -> bool {
+    let extra = match dialect {
+        Dialect::Snowflake => SNOWFLAKE_KEYWORDS,
+        Dialect::Postgres => POSTGRES_KEYWORDS,
+        Dialect::SqlServer => SQLSERVER_KEYWORDS,
+        Dialect::DuckDb => DUCKDB_KEYWORDS,
+        Dialect::Generic => &[],
+    };
+    KEYWORDS.contains(&word) || extra.contains(&word)
+}
+
+/// Where a token continues onto the next line
+#[derive(Clone, Copy, PartialEq)]
+enum Carry {
+    None,
+    BlockComment,
+    String,
+    DollarString,
+}
+
+/// SQL text as highlighted lines, styled with the theme's syntax colors.
+/// A small lexer, not a parser: keywords, literals, comments, `::` casts,
+/// function calls and `$var` / `:var` references, with the keywords of
+/// `dialect`.
+pub fn highlight_sql(text: &str, colors: &ColorConfig, dialect: Dialect) -> Vec<Line<'static>> {
+    let style = |color: [u8; 3]| Style::default().fg(rgb(color));
+    let mut carry = Carry::None;
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut spans = Vec::new();
+        let mut i = 0;
+        let mut cast_pending = false;
+        while i < chars.len() {
+            let start = i;
+            let c = chars[i];
+            let next = chars.get(i + 1).copied();
+            // Opening delimiters only set `carry`; the body is styled by the
+            // first branch on the next pass, which also picks up comments
+            // and strings left open on an earlier line
+            let color = if carry != Carry::None {
+                let end = match carry {
+                    Carry::BlockComment => find_seq(&chars, i, &['*', '/']).map(|p| p + 2),
+                    Carry::String => find_quote_end(&chars, i),
+                    Carry::DollarString => find_seq(&chars, i, &['$', '$']).map(|p| p + 2),
+                    Carry::None => None,
+                };
+                let color = if carry == Carry::BlockComment { colors.syntax_comment } else { colors.syntax_string };
+                match end {
+                    Some(end) => {
+                        i = end;
+                        carry = Carry::None;
+                    }
+                    None => i = chars.len(),
+                }
+                color
+            } else if c == '-' && next == Some('-') {
+                i = chars.len();
+                colors.syntax_comment
+            } else if c == '/' && next == Some('*') {
+                carry = Carry::BlockComment;
+                i += 2;
+                spans.push(Span::styled("/*", style(colors.syntax_comment)));
+                continue;
+            } else if c == '\'' {
+                carry = Carry::String;
+                i += 1;
+                spans.push(Span::styled("'", style(colors.syntax_string)));
+                continue;
+            } else if c == '$' && next == Some('$') {
+                carry = Carry::DollarString;
+                i += 2;
+                spans.push(Span::styled("$$", style(colors.syntax_string)));
+                continue;
+            } else if c == ':' && next == Some(':') {
+                i += 2;
+                cast_pending = true;
+                spans.push(Span::styled("::", style(colors.syntax_cast)));
+                continue;
+            } else if c == '"' {
+                i = chars[i + 1..].iter().position(|&ch| ch == '"').map_or(chars.len(), |p| i + p + 2);
+                colors.syntax_plain
+            } else if c.is_ascii_digit() {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                    i += 1;
+                }
+                colors.syntax_number
+            } else if (c == '$' || c == ':') && next.is_some_and(|n| n.is_alphanumeric() || n == '_') {
+                i += 1;
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                colors.syntax_variable
+            } else if c.is_alphabetic() || c == '_' {
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect::<String>().to_uppercase();
+                let call = chars[i..].iter().find(|ch| !ch.is_whitespace()) == Some(&'(');
+                if cast_pending {
+                    colors.syntax_cast
+                } else if is_keyword(&word, dialect) {
+                    colors.syntax_keyword
+                } else if call {
+                    colors.syntax_function
+                } else {
+                    colors.syntax_plain
+                }
+            } else {
+                i += 1;
+                colors.syntax_plain
+            };
+            if !chars[start].is_whitespace() {
+                cast_pending = false;
+            }
+            spans.push(Span::styled(chars[start..i].iter().collect::<String>(), style(color)));
+        }
+        lines.push(Line::from(spans));
+    }
+    lines
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Index of `seq` at or after `from`
+fn find_seq(chars: &[char], from: usize, seq: &[char]) -> Option<usize> {
+    (from..chars.len().saturating_sub(seq.len() - 1)).find(|&i| chars[i..].starts_with(seq))
+}
+
+/// Index just past the quote closing a string literal, skipping `''` and
+/// backslash escapes
+fn find_quote_end(chars: &[char], from: usize) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '\'' if chars.get(i + 1) == Some(&'\'') => i += 2,
+            '\'' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
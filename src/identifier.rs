@@ -0,0 +1,54 @@
+use crate::config::IdentifierQuoting;
+
+/// Snowflake reserved words that must be quoted when used as identifiers
+const RESERVED_WORDS: &[&str] = &[
+    "ACCOUNT", "ALL", "ALTER", "AND", "ANY", "AS", "BETWEEN", "BY", "CASE", "CAST",
+    "CHECK", "COLUMN", "CONNECT", "CONNECTION", "CONSTRAINT", "CREATE", "CROSS",
+    "CURRENT", "CURRENT_DATE", "CURRENT_TIME", "CURRENT_TIMESTAMP", "CURRENT_USER",
+    "DATABASE", "DELETE", "DISTINCT", "DROP", "ELSE", "EXISTS", "FALSE", "FOLLOWING",
+    "FOR", "FROM", "FULL", "GRANT", "GROUP", "GSCLUSTER", "HAVING", "ILIKE", "IN",
+    "INCREMENT", "INNER", "INSERT", "INTERSECT", "INTO", "IS", "ISSUE", "JOIN",
+    "LATERAL", "LEFT", "LIKE", "LOCALTIME", "LOCALTIMESTAMP", "MINUS", "NATURAL",
+    "NOT", "NULL", "OF", "ON", "OR", "ORDER", "ORGANIZATION", "QUALIFY", "REGEXP",
+    "REVOKE", "RIGHT", "RLIKE", "ROW", "ROWS", "SAMPLE", "SCHEMA", "SELECT", "SET",
+    "SOME", "START", "TABLE", "TABLESAMPLE", "THEN", "TO", "TRIGGER", "TRUE",
+    "TRY_CAST", "UNION", "UNIQUE", "UPDATE", "USING", "VALUES", "VIEW", "WHEN",
+    "WHENEVER", "WHERE", "WITH",
+];
+
+/// True if `name` (as stored in the catalog) only resolves correctly when quoted:
+/// it has lowercase letters, characters outside `[A-Za-z0-9_$]`, a leading
+/// digit or `$`, or is a reserved word.
+pub fn needs_quotes(name: &str) -> bool {
+    let mut chars = name.chars();
+    let Some(first) = chars.next() else { return true };
+    if !(first.is_ascii_alphabetic() || first == '_') {
+        return true;
+    }
+    if name.chars().any(|c| c.is_ascii_lowercase() || !(c.is_ascii_alphanumeric() || c == '_' || c == '$')) {
+        return true;
+    }
+    RESERVED_WORDS.contains(&name)
+}
+
+/// Quote a single identifier according to `mode`
+pub fn quote_identifier(name: &str, mode: IdentifierQuoting) -> String {
+    let quote = match mode {
+        IdentifierQuoting::Always => true,
+        IdentifierQuoting::Never => false,
+        IdentifierQuoting::Smart => needs_quotes(name),
+    };
+    if quote {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    } else {
+        name.to_string()
+    }
+}
+
+/// Quote each part of a qualified name (e.g. database, schema, table) and join with '.'
+pub fn quote_qualified(parts: &[&str], mode: IdentifierQuoting) -> String {
+    parts.iter()
+        .map(|p| quote_identifier(p, mode))
+        .collect::<Vec<_>>()
+        .join(".")
+}
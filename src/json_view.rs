@@ -0,0 +1,249 @@
+use crate::overlay::OverlayOutcome;
+use crossterm::event::{KeyCode, KeyEvent};
+use serde_json::Value;
+
+/// Containers this deep or deeper start folded
+const FOLD_DEPTH: usize = 2;
+
+/// A step from a value into one of its members
+#[derive(Debug, Clone)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// One line of the tree: the root or a member of an object or array
+#[derive(Debug)]
+struct JsonNode {
+    depth: usize,
+    path: Vec<Step>,
+    /// Scalar as JSON, or a count of members for a container
+    summary: String,
+    container: bool,
+    /// Index past the last node under this one
+    end: usize,
+}
+
+/// A VARIANT / JSON cell as a tree that folds objects and arrays; c copies
+/// the selected member's path as `column:path`, C as a JSONPath and v its
+/// value
+#[derive(Debug)]
+pub struct JsonView {
+    /// Column the value came from, as written in a query
+    pub column: String,
+    root: Value,
+    nodes: Vec<JsonNode>,
+    collapsed: Vec<bool>,
+    /// Index into `nodes` of the selected line
+    cursor: usize,
+    /// First visible line shown
+    pub scroll: usize,
+    /// What Submit copies
+    pub copy: JsonCopy,
+}
+
+/// Which text of the selected member is copied
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JsonCopy {
+    /// `column:items[3].sku`, to paste into a query
+    QueryPath,
+    /// `$.items[3].sku`
+    JsonPath,
+    /// The member's value, pretty-printed
+    Value,
+}
+
+impl JsonView {
+    /// The tree of `cell`, if it holds a JSON object or array
+    pub fn parse(column: String, cell: &str) -> Option<Self> {
+        let trimmed = cell.trim_start();
+        if !trimmed.starts_with('{') && !trimmed.starts_with('[') {
+            return None;
+        }
+        let root: Value = serde_json::from_str(cell).ok()?;
+        let mut nodes = Vec::new();
+        flatten(&root, Vec::new(), &mut nodes);
+        let collapsed = nodes.iter().map(|n| n.container && n.depth >= FOLD_DEPTH).collect();
+        Some(Self { column, root, nodes, collapsed, cursor: 0, scroll: 0, copy: JsonCopy::QueryPath })
+    }
+
+    /// Indices of the nodes not hidden in a folded container
+    pub fn visible(&self) -> Vec<usize> {
+        let mut visible = Vec::new();
+        let mut i = 0;
+        while i < self.nodes.len() {
+            visible.push(i);
+            i = if self.collapsed[i] { self.nodes[i].end } else { i + 1 };
+        }
+        visible
+    }
+
+    /// Line of node `i`: indent, fold marker, its key or index, then its
+    /// value or member count
+    pub fn line(&self, i: usize) -> (String, &str) {
+        let node = &self.nodes[i];
+        let marker = match (node.container, self.collapsed[i]) {
+            (false, _) => "  ",
+            (true, true) => "▸ ",
+            (true, false) => "▾ ",
+        };
+        let label = match node.path.last() {
+            None => self.column.clone(),
+            Some(Step::Key(key)) => key.clone(),
+            Some(Step::Index(idx)) => format!("[{}]", idx),
+        };
+        (format!("{}{}{}: ", "  ".repeat(node.depth), marker, label), &node.summary)
+    }
+
+    /// Index into `visible()` of the selected line
+    pub fn cursor_line(&self, visible: &[usize]) -> usize {
+        visible.iter().position(|&i| i == self.cursor).unwrap_or(0)
+    }
+
+    /// Path of the selected member as a JSONPath, e.g. `$.items[3].sku`
+    pub fn json_path(&self) -> String {
+        let mut path = String::from("$");
+        for step in &self.nodes[self.cursor].path {
+            match step {
+                Step::Key(key) if is_plain_key(key) => path.push_str(&format!(".{}", key)),
+                Step::Key(key) => path.push_str(&format!("['{}']", key.replace('\'', "\\'"))),
+                Step::Index(idx) => path.push_str(&format!("[{}]", idx)),
+            }
+        }
+        path
+    }
+
+    /// Path of the selected member in Snowflake's syntax, e.g.
+    /// `col:items[3].sku`; keys that aren't plain names are double-quoted
+    pub fn query_path(&self) -> String {
+        let mut path = self.column.clone();
+        for (n, step) in self.nodes[self.cursor].path.iter().enumerate() {
+            let separator = if n == 0 { ":" } else { "." };
+            match step {
+                Step::Key(key) if is_plain_key(key) => path.push_str(&format!("{}{}", separator, key)),
+                Step::Key(key) => path.push_str(&format!("{}\"{}\"", separator, key.replace('"', "\"\""))),
+                Step::Index(idx) => path.push_str(&format!("[{}]", idx)),
+            }
+        }
+        path
+    }
+
+    /// Text copied for the selected member
+    pub fn copy_text(&self) -> String {
+        match self.copy {
+            JsonCopy::QueryPath => self.query_path(),
+            JsonCopy::JsonPath => self.json_path(),
+            JsonCopy::Value => {
+                let pointer: String = self.nodes[self.cursor].path.iter()
+                    .map(|step| match step {
+                        Step::Key(key) => format!("/{}", key.replace('~', "~0").replace('/', "~1")),
+                        Step::Index(idx) => format!("/{}", idx),
+                    })
+                    .collect();
+                self.root.pointer(&pointer)
+                    .and_then(|value| serde_json::to_string_pretty(value).ok())
+                    .unwrap_or_default()
+            }
+        }
+    }
+
+    /// Move the selection by `delta` visible lines
+    fn move_cursor(&mut self, delta: isize) {
+        let visible = self.visible();
+        let line = self.cursor_line(&visible).saturating_add_signed(delta).min(visible.len() - 1);
+        self.cursor = visible[line];
+    }
+
+    /// Fold the selected container, or go to the one it is in
+    fn collapse_or_parent(&mut self) {
+        if self.nodes[self.cursor].container && !self.collapsed[self.cursor] {
+            self.collapsed[self.cursor] = true;
+            return;
+        }
+        let depth = self.nodes[self.cursor].depth;
+        if let Some(parent) = (0..self.cursor).rev().find(|&i| self.nodes[i].depth < depth) {
+            self.cursor = parent;
+        }
+    }
+
+    fn set_all_collapsed(&mut self, collapsed: bool) {
+        for (i, node) in self.nodes.iter().enumerate() {
+            // The root stays open, or nothing would show
+            self.collapsed[i] = collapsed && node.container && i > 0;
+        }
+        if collapsed {
+            let depth = self.nodes[self.cursor].depth;
+            if depth > 1 {
+                self.cursor = (0..self.cursor).rev().find(|&i| self.nodes[i].depth == 1).unwrap_or(0);
+            }
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        let copy = match key.code {
+            KeyCode::Char('c') => Some(JsonCopy::QueryPath),
+            KeyCode::Char('C') => Some(JsonCopy::JsonPath),
+            KeyCode::Char('v') | KeyCode::Char('V') => Some(JsonCopy::Value),
+            _ => None,
+        };
+        if let Some(copy) = copy {
+            self.copy = copy;
+            return OverlayOutcome::Submit;
+        }
+        match key.code {
+            KeyCode::Char('q') => return OverlayOutcome::Close,
+            KeyCode::Up => self.move_cursor(-1),
+            KeyCode::Down => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-10),
+            KeyCode::PageDown => self.move_cursor(10),
+            KeyCode::Home => self.move_cursor(isize::MIN / 2),
+            KeyCode::End => self.move_cursor(isize::MAX / 2),
+            KeyCode::Enter | KeyCode::Char(' ') if self.nodes[self.cursor].container && self.cursor > 0 => {
+                self.collapsed[self.cursor] = !self.collapsed[self.cursor];
+            }
+            KeyCode::Left => self.collapse_or_parent(),
+            KeyCode::Right => self.collapsed[self.cursor] = false,
+            KeyCode::Char('-') => self.set_all_collapsed(true),
+            KeyCode::Char('+') | KeyCode::Char('=') => self.set_all_collapsed(false),
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+}
+
+/// Add `value` and everything under it to `nodes`, depth first
+fn flatten(value: &Value, path: Vec<Step>, nodes: &mut Vec<JsonNode>) {
+    let idx = nodes.len();
+    let (summary, container) = match value {
+        Value::Object(map) => (format!("{{{} key{}}}", map.len(), if map.len() == 1 { "" } else { "s" }), true),
+        Value::Array(items) => (format!("[{} item{}]", items.len(), if items.len() == 1 { "" } else { "s" }), true),
+        scalar => (scalar.to_string(), false),
+    };
+    nodes.push(JsonNode { depth: path.len(), path: path.clone(), summary, container, end: idx + 1 });
+    let step_into = |step: Step| {
+        let mut child = path.clone();
+        child.push(step);
+        child
+    };
+    match value {
+        Value::Object(map) => {
+            for (key, member) in map {
+                flatten(member, step_into(Step::Key(key.clone())), nodes);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                flatten(item, step_into(Step::Index(i)), nodes);
+            }
+        }
+        _ => {}
+    }
+    nodes[idx].end = nodes.len();
+}
+
+/// A key written bare in a path: letters, digits and `_`, not starting
+/// with a digit
+fn is_plain_key(key: &str) -> bool {
+    key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
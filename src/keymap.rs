@@ -0,0 +1,418 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Workspace-level actions that can be rebound from the `[keys]` config table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Save,
+    SwitchFocus,
+    RunQuery,
+    CancelQuery,
+    CancelQueue,
+    ExplainPlan,
+    ExplainJson,
+    RefreshCosts,
+    SwitchContext,
+    HealthReport,
+    TodoList,
+    GotoLine,
+    ToggleReadOnly,
+    DiffBuffer,
+    PasteHistory,
+    ConvertIndentation,
+    IndentLines,
+    OutdentLines,
+    StageBrowser,
+    ObjectTree,
+    ShowDdl,
+    PreviewTable,
+    DescribeTable,
+    PerfOverlay,
+    Autocomplete,
+    RefreshMetadata,
+    InsertNowUtc,
+    InsertNowSession,
+    InsertNowEpoch,
+    GrowEditor,
+    ShrinkEditor,
+    EditorOnly,
+    ResultsOnly,
+    ShowBoth,
+    ReopenTab,
+    Help,
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::Quit,
+        Action::Save,
+        Action::SwitchFocus,
+        Action::RunQuery,
+        Action::CancelQuery,
+        Action::CancelQueue,
+        Action::ExplainPlan,
+        Action::ExplainJson,
+        Action::RefreshCosts,
+        Action::SwitchContext,
+        Action::HealthReport,
+        Action::TodoList,
+        Action::GotoLine,
+        Action::ToggleReadOnly,
+        Action::DiffBuffer,
+        Action::PasteHistory,
+        Action::ConvertIndentation,
+        Action::IndentLines,
+        Action::OutdentLines,
+        Action::StageBrowser,
+        Action::ObjectTree,
+        Action::ShowDdl,
+        Action::PreviewTable,
+        Action::DescribeTable,
+        Action::PerfOverlay,
+        Action::Autocomplete,
+        Action::RefreshMetadata,
+        Action::InsertNowUtc,
+        Action::InsertNowSession,
+        Action::InsertNowEpoch,
+        Action::GrowEditor,
+        Action::ShrinkEditor,
+        Action::EditorOnly,
+        Action::ResultsOnly,
+        Action::ShowBoth,
+        Action::ReopenTab,
+        Action::Help,
+    ];
+
+    /// Name used in the `[keys]` config table
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Save => "save",
+            Action::SwitchFocus => "switch_focus",
+            Action::RunQuery => "run_query",
+            Action::CancelQuery => "cancel_query",
+            Action::CancelQueue => "cancel_queue",
+            Action::ExplainPlan => "explain_plan",
+            Action::ExplainJson => "explain_json",
+            Action::RefreshCosts => "refresh_costs",
+            Action::SwitchContext => "switch_context",
+            Action::HealthReport => "health_report",
+            Action::TodoList => "todo_list",
+            Action::GotoLine => "goto_line",
+            Action::ToggleReadOnly => "toggle_read_only",
+            Action::DiffBuffer => "diff_buffer",
+            Action::PasteHistory => "paste_history",
+            Action::ConvertIndentation => "convert_indentation",
+            Action::IndentLines => "indent_lines",
+            Action::OutdentLines => "outdent_lines",
+            Action::StageBrowser => "stage_browser",
+            Action::ObjectTree => "object_tree",
+            Action::ShowDdl => "show_ddl",
+            Action::PreviewTable => "preview_table",
+            Action::DescribeTable => "describe_table",
+            Action::PerfOverlay => "perf_overlay",
+            Action::Autocomplete => "autocomplete",
+            Action::RefreshMetadata => "refresh_metadata",
+            Action::InsertNowUtc => "insert_now_utc",
+            Action::InsertNowSession => "insert_now_session",
+            Action::InsertNowEpoch => "insert_now_epoch",
+            Action::GrowEditor => "grow_editor",
+            Action::ShrinkEditor => "shrink_editor",
+            Action::EditorOnly => "editor_only",
+            Action::ResultsOnly => "results_only",
+            Action::ShowBoth => "show_both",
+            Action::ReopenTab => "reopen_tab",
+            Action::Help => "help",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Save => "Save the buffer to its file, asking for one the first time",
+            Action::SwitchFocus => "Switch focus between editor and results",
+            Action::RunQuery => "Run selection or whole buffer",
+            Action::CancelQuery => "Cancel running query",
+            Action::CancelQueue => "Cancel queued statements; the running one finishes",
+            Action::ExplainPlan => "Show query plan (EXPLAIN USING TEXT)",
+            Action::ExplainJson => "Show query plan (EXPLAIN USING JSON)",
+            Action::RefreshCosts => "Refresh cost annotations (EXPLAIN each statement)",
+            Action::SwitchContext => "Switch role / warehouse / database",
+            Action::HealthReport => "Show environment health report",
+            Action::TodoList => "List TODO / FIXME comments",
+            Action::GotoLine => "Go to line, line:col, or +N / -N lines from the caret",
+            Action::ToggleReadOnly => "Make the editor buffer read-only, or editable again",
+            Action::DiffBuffer => "Diff the buffer against its saved file (or the clipboard)",
+            Action::PasteHistory => "Paste one of the last copies made in this session",
+            Action::ConvertIndentation => "Convert the buffer's indentation to spaces (or tabs, per insert_spaces)",
+            Action::IndentLines => "Indent the caret's line or the selected lines by tab_width spaces (or a tab)",
+            Action::OutdentLines => "Take one level of indentation off the caret's line or the selected lines",
+            Action::StageBrowser => "Browse stages, PUT / GET files",
+            Action::ObjectTree => "Browse databases, schemas and tables with their row counts",
+            Action::ShowDdl => "Show DDL of the table under the caret",
+            Action::PreviewTable => "Preview the first 100 rows of the table under the caret",
+            Action::DescribeTable => "Describe the columns of the table under the caret",
+            Action::PerfOverlay => "Toggle frame time / cache / worker queue overlay",
+            Action::Autocomplete => "Complete the database / schema / table / column name at the caret",
+            Action::RefreshMetadata => "Refresh the object names used for completion",
+            Action::InsertNowUtc => "Insert current timestamp (UTC)",
+            Action::InsertNowSession => "Insert current timestamp (session time zone)",
+            Action::InsertNowEpoch => "Insert current epoch seconds",
+            Action::GrowEditor => "Grow editor pane",
+            Action::ShrinkEditor => "Shrink editor pane",
+            Action::EditorOnly => "Show editor only",
+            Action::ResultsOnly => "Show results only",
+            Action::ShowBoth => "Show both panes",
+            Action::ReopenTab => "Reopen last closed results tab",
+            Action::Help => "Toggle this help",
+        }
+    }
+
+    fn default_chord(self) -> KeyChord {
+        let (code, modifiers) = match self {
+            Action::Quit => (KeyCode::Char('q'), KeyModifiers::CONTROL),
+            Action::Save => (KeyCode::Char('s'), KeyModifiers::CONTROL),
+            Action::SwitchFocus => (KeyCode::Tab, KeyModifiers::NONE),
+            Action::RunQuery => (KeyCode::Enter, KeyModifiers::CONTROL),
+            Action::CancelQuery => (KeyCode::Char('c'), KeyModifiers::CONTROL),
+            Action::CancelQueue => (KeyCode::F(5), KeyModifiers::SHIFT),
+            Action::ExplainPlan => (KeyCode::F(6), KeyModifiers::NONE),
+            Action::ExplainJson => (KeyCode::F(6), KeyModifiers::SHIFT),
+            Action::RefreshCosts => (KeyCode::F(8), KeyModifiers::NONE),
+            Action::SwitchContext => (KeyCode::F(4), KeyModifiers::NONE),
+            Action::HealthReport => (KeyCode::F(12), KeyModifiers::NONE),
+            Action::TodoList => (KeyCode::F(9), KeyModifiers::NONE),
+            Action::GotoLine => (KeyCode::Char('g'), KeyModifiers::CONTROL),
+            Action::ToggleReadOnly => (KeyCode::Char('r'), KeyModifiers::ALT),
+            Action::DiffBuffer => (KeyCode::Char('f'), KeyModifiers::ALT),
+            Action::PasteHistory => (KeyCode::Char('v'), KeyModifiers::CONTROL.union(KeyModifiers::SHIFT)),
+            Action::ConvertIndentation => (KeyCode::Char('i'), KeyModifiers::ALT),
+            Action::IndentLines => (KeyCode::Char('.'), KeyModifiers::ALT),
+            Action::OutdentLines => (KeyCode::Char(','), KeyModifiers::ALT),
+            Action::StageBrowser => (KeyCode::F(10), KeyModifiers::NONE),
+            Action::ObjectTree => (KeyCode::Char('b'), KeyModifiers::CONTROL),
+            Action::ShowDdl => (KeyCode::F(3), KeyModifiers::NONE),
+            Action::PreviewTable => (KeyCode::F(2), KeyModifiers::NONE),
+            Action::DescribeTable => (KeyCode::F(2), KeyModifiers::SHIFT),
+            Action::PerfOverlay => (KeyCode::F(12), KeyModifiers::SHIFT),
+            Action::Autocomplete => (KeyCode::Char(' '), KeyModifiers::CONTROL),
+            Action::RefreshMetadata => (KeyCode::F(8), KeyModifiers::SHIFT),
+            Action::InsertNowUtc => (KeyCode::F(7), KeyModifiers::NONE),
+            Action::InsertNowSession => (KeyCode::F(7), KeyModifiers::SHIFT),
+            Action::InsertNowEpoch => (KeyCode::F(7), KeyModifiers::CONTROL),
+            Action::GrowEditor => (KeyCode::Up, KeyModifiers::ALT),
+            Action::ShrinkEditor => (KeyCode::Down, KeyModifiers::ALT),
+            Action::EditorOnly => (KeyCode::Left, KeyModifiers::ALT),
+            Action::ResultsOnly => (KeyCode::Right, KeyModifiers::ALT),
+            Action::ShowBoth => (KeyCode::Char(' '), KeyModifiers::ALT),
+            Action::ReopenTab => (KeyCode::Char('t'), KeyModifiers::CONTROL.union(KeyModifiers::SHIFT)),
+            Action::Help => (KeyCode::F(1), KeyModifiers::NONE),
+        };
+        KeyChord { code, modifiers }
+    }
+}
+
+/// A key plus modifiers, parsed from strings like "Ctrl+Enter" or "Alt+Up"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let parts: Vec<&str> = s.split('+').map(|p| p.trim()).collect();
+        let (key, mods) = parts.split_last()?;
+        for m in mods {
+            match m.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers |= KeyModifiers::CONTROL,
+                "alt" => modifiers |= KeyModifiers::ALT,
+                "shift" => modifiers |= KeyModifiers::SHIFT,
+                _ => return None,
+            }
+        }
+        let code = match key.to_ascii_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "esc" | "escape" => KeyCode::Esc,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            k if k.len() > 1 && k.starts_with('f') => KeyCode::F(k[1..].parse().ok()?),
+            _ => {
+                let mut chars = key.chars();
+                let ch = chars.next()?;
+                if chars.next().is_some() {
+                    return None;
+                }
+                KeyCode::Char(ch.to_ascii_lowercase())
+            }
+        };
+        Some(Self { code, modifiers })
+    }
+
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let code = match key.code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            other => other,
+        };
+        code == self.code && key.modifiers == self.modifiers
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c.to_ascii_uppercase()),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// Pane-local bindings shown in the help overlay
+const EDITOR_KEYS: &[(&str, &str)] = &[
+    ("Ctrl+A", "Select all"),
+    ("Ctrl+C / Ctrl+X / Ctrl+V", "Copy / cut / paste"),
+    ("Ctrl+Z / Ctrl+Y", "Undo / redo"),
+    ("Shift+Arrows", "Extend selection"),
+    ("PageUp / PageDown", "Scroll by page"),
+    ("Home / End", "First non-blank character, then column 0 / end of line"),
+    ("Ctrl+Up / Ctrl+Down", "Scroll the view a line, keeping the caret unless it would leave"),
+    ("Ctrl+L", "Center the view on the caret"),
+    ("Alt+D / Ctrl+U", "Scroll down / up half a page (Alt+U also goes up)"),
+    ("Ctrl+D", "Select word, then add the next occurrence as a caret"),
+    ("Alt+Click", "Add a caret; Esc goes back to one"),
+    ("Ctrl+Shift+D", "Duplicate line or selected lines"),
+    ("Ctrl+Shift+K", "Delete line or selected lines"),
+    ("Alt+Shift+Up / Alt+Shift+Down", "Move line or selected lines up / down"),
+    ("Ctrl+J", "Join the next line onto this one"),
+    ("Alt+Shift+U / L / T", "Selection or word to upper / lower / title case"),
+    ("Alt+- / Alt+= / Alt+0", "Fold the parentheses at the caret / unfold / unfold all"),
+    ("Alt+W", "Show or hide tabs, trailing spaces and non-breaking spaces"),
+    ("Alt+Z", "Wrap long lines or scroll them sideways"),
+    ("Tab", "Expand the snippet named before the caret, then go to its next stop"),
+];
+
+const RESULTS_KEYS: &[(&str, &str)] = &[
+    ("Ctrl+W", "Close results tab"),
+    ("L", "Pin / unpin tab: pinned tabs survive Shift+O and keep their results"),
+    ("Shift+O", "Close all tabs but the pinned ones"),
+    ("Arrows", "Move cell cursor"),
+    ("PageUp / PageDown", "Scroll by page"),
+    ("Ctrl+D / Ctrl+U", "Scroll down / up half a page"),
+    ("Home / End", "First / last column"),
+    ("Ctrl+Home / Ctrl+End", "First / last cell"),
+    ("Ctrl+Arrows", "Jump to the edge of the data, up to a NULL or empty cell"),
+    ("0-9", "Type a row number to jump to it"),
+    ("Shift+Arrows", "Extend block selection"),
+    ("Ctrl+C", "Copy selection as shown (visible columns, display order)"),
+    ("Alt+C", "Copy selected rows with all columns"),
+    ("H / Shift+H", "Hide column / show all columns"),
+    ("< / >", "Move column left / right"),
+    ("F", "Pin columns up to the cursor while scrolling sideways / unpin"),
+    ("- / +", "Narrow / widen column"),
+    ("T", "Transpose: the cursor row as column / value lines (arrows swap)"),
+    ("Enter", "Show the whole row in a popup; c copies a value, Shift+C the row"),
+    ("Shift+J", "Show a JSON / VARIANT cell as a foldable tree; c copies a path as col:a.b[0]"),
+    ("Shift+B", "Compare tabs: mark the base tab, then on another tab match rows on key columns"),
+    ("Shift+V", "Value distribution: the column's most frequent values as bars"),
+    ("Shift+G", "Chart the cursor column, or a selected block (first column as x), as lines or bars"),
+    ("w / W", "Fit column to its widest value / clear set widths"),
+    ("D", "Pick diff column (baseline, then compared)"),
+    ("Shift+D", "Clear diff columns"),
+    ("O", "Sort rows on the column, ascending, again for descending; sorting on another column keeps ties in order"),
+    ("V", "Keep only rows with the cursor cell's value in its column"),
+    ("G", "Drop rows repeating an earlier row"),
+    ("Backspace", "Undo sorts, filters and dropped repeats: all rows in fetch order"),
+    ("/", "Search the table (Enter to run, Esc clears)"),
+    ("N / Shift+N", "Next / previous search match, or diff mismatch"),
+    ("E", "Export the rows in view, or the selection, to CSV"),
+    ("X", "Copy selection / export all rows in the background as CSV, Markdown, INSERTs, JSON, Parquet or Excel"),
+    ("I", "Copy column values as batched IN list"),
+    ("S", "Column stats (count, NULLs, distinct, min/max, mean/median)"),
+    ("A", "Copy selection stats (count, sum, avg, min, max) as a report"),
+    ("K", "Mark key column for replace"),
+    ("U", "Replace values in column (generates UPDATE)"),
+    ("R / Shift+R", "Re-run this tab's query in place / into a new tab"),
+    ("Shift+Q", "Show the SQL this tab came from; c copies it, e copies it to the editor"),
+    ("Z / Shift+Z", "Save this tab's rows and query to a .frostres file / open a saved one in a new tab"),
+    ("Y", "Copy query ID"),
+    ("P", "Fetch query profile stats"),
+    ("M", "Fetch the next max_rows rows of a truncated result"),
+    ("C", "Cancel this tab's statement (a parallel batch keeps the others running), or a running export"),
+    ("Shift+Arrows", "Error / info: select text; Ctrl+C copies it, or the whole message"),
+    ("Enter / Space", "Plan: expand / collapse operator"),
+    ("Left / Right", "Plan: collapse (or go to parent) / expand"),
+    ("- / +", "Plan: collapse / expand all"),
+    ("?", "Toggle this help"),
+];
+
+pub struct Keymap {
+    bindings: Vec<(KeyChord, Action)>,
+}
+
+impl Keymap {
+    /// Default bindings with `[keys]` overrides applied (action name -> chord)
+    pub fn new(overrides: &HashMap<String, String>) -> anyhow::Result<Self> {
+        for name in overrides.keys() {
+            if !Action::ALL.iter().any(|a| a.name() == name) {
+                return Err(anyhow::anyhow!("Unknown action '{}' in [keys]", name));
+            }
+        }
+        let mut bindings = Vec::with_capacity(Action::ALL.len());
+        for &action in Action::ALL {
+            let chord = match overrides.get(action.name()) {
+                Some(s) => KeyChord::parse(s)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid key '{}' for {} in [keys]", s, action.name()))?,
+                None => action.default_chord(),
+            };
+            bindings.push((chord, action));
+        }
+        Ok(Self { bindings })
+    }
+
+    pub fn action_for(&self, key: &KeyEvent) -> Option<Action> {
+        self.bindings.iter()
+            .find(|(chord, _)| chord.matches(key))
+            .map(|(_, action)| *action)
+    }
+
+    /// Help content grouped by focus area: (section title, [(keys, description)])
+    pub fn help_sections(&self) -> Vec<(&'static str, Vec<(String, &'static str)>)> {
+        let global = self.bindings.iter()
+            .map(|(chord, action)| (chord.to_string(), action.description()))
+            .collect();
+        let pane = |keys: &[(&str, &'static str)]| {
+            keys.iter().map(|(k, d)| (k.to_string(), *d)).collect()
+        };
+        vec![
+            ("Global", global),
+            ("Editor", pane(EDITOR_KEYS)),
+            ("Results", pane(RESULTS_KEYS)),
+        ]
+    }
+}
@@ -0,0 +1,107 @@
+pub mod config;
+pub mod tile_rowstore;
+pub mod workspace;
+mod texteditor;
+mod results;
+pub mod connection;
+mod focus;
+mod catalog;
+mod chart;
+mod clipboard;
+mod colstats;
+mod complete;
+mod cost;
+mod ddl;
+mod diff;
+mod distribution;
+mod directive;
+mod error_pos;
+mod export;
+mod export_job;
+pub mod fake;
+mod goto;
+mod guard;
+pub mod headless;
+mod health;
+mod highlight;
+mod identifier;
+mod json_view;
+pub mod keymap;
+pub mod masking;
+mod message_text;
+mod metadata;
+mod object_tree;
+mod overlay;
+mod parquet_export;
+mod perf;
+mod plan;
+mod recovery;
+mod replace;
+mod row_detail;
+mod row_view;
+pub mod rest;
+mod save_as;
+mod saved_result;
+mod secrets;
+mod snippet;
+mod source_sql;
+mod stages;
+mod switcher;
+mod tab_compare;
+mod table_search;
+mod todo;
+mod undo_file;
+mod util;
+mod xlsx_export;
+
+pub use config::Config;
+pub use export::ColumnKind;
+pub use connection::{configured_executor, start_db_worker, CancelHandle, DbWorkerRequest, DbWorkerResponse, OdbcExecutor, QueryExecutor, QueryRequest, RequestSender};
+pub use fake::FakeExecutor;
+pub use rest::RestExecutor;
+pub use tile_rowstore::{TileCompression, TileRowStore, TileStoreOptions};
+pub use workspace::Workspace;
+
+use std::io;
+use anyhow::Result;
+use crossterm::{
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{backend::CrosstermBackend, Terminal};
+
+/// Run the workspace in the terminal until it quits, restoring the terminal
+/// afterwards; errors of the session itself are printed rather than returned
+pub fn run_terminal(mut workspace: Workspace) -> Result<()> {
+    // Setup terminal
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
+    
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+    
+    let res = workspace.run(&mut terminal);
+    
+    // Restore terminal
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste
+    )?;
+    terminal.show_cursor()?;
+    
+    if let Err(err) = res {
+        eprintln!("Error: {:?}", err);
+    }
+    
+    Ok(())
+}
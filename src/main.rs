@@ -1,55 +1,45 @@
-mod config;
-mod tile_rowstore;
-mod workspace;
-mod texteditor;
-mod results;
-mod connection;
-mod focus;
-
-use std::io;
+use std::path::PathBuf;
 use anyhow::Result;
-use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
-use ratatui::{
-    backend::{Backend, CrosstermBackend},
-    Terminal,
-};
+use minimal_frost::{config, configured_executor, headless, keymap, masking, FakeExecutor, Workspace};
 
 fn main() -> Result<()> {
     // Load configuration
-    let config = config::Config::load()?;
-    
-    // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(
-        stdout,
-        EnterAlternateScreen,
-        EnableMouseCapture
-    )?;
-    
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let mut config = config::Config::load(parse_config_arg()?)?;
+    config.allow_prod_writes = std::env::args().any(|arg| arg == "--allow-prod-writes");
+    config.read_only = std::env::args().any(|arg| arg == "--read-only");
+    let keymap = keymap::Keymap::new(&config.keys)?;
     
-    // Create workspace that wraps texteditor
-    let mut workspace = workspace::Workspace::new(config);
-    let res = workspace.run(&mut terminal);
+    // `--demo` answers a few sample queries from memory, no account needed
+    if std::env::args().any(|arg| arg == "--demo") {
+        let executor = FakeExecutor::demo().with_store_options(config.tile_store_options());
+        return minimal_frost::run_terminal(Workspace::new(config, keymap, Box::new(executor)));
+    }
     
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    let masker = masking::Masker::new(&config.masking, config.environment.as_deref())?;
     
-    if let Err(err) = res {
-        eprintln!("Error: {:?}", err);
+    // `--jsonl <file|->` runs without the TUI, writing the result to stdout;
+    // everything is fetched, max_rows is for the interactive grid
+    if let Some(headless) = headless::Headless::from_args()? {
+        let executor = configured_executor(&config, masker, 0)?;
+        return headless.run(&config, executor);
     }
     
-    Ok(())
-}
\ No newline at end of file
+    let executor = configured_executor(&config, masker, config.max_rows)?;
+    minimal_frost::run_terminal(Workspace::new(config, keymap, executor))
+}
+
+/// Parse `--config <path>` / `--config=<path>` from the command line
+fn parse_config_arg() -> Result<Option<PathBuf>> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            let path = args.next()
+                .ok_or_else(|| anyhow::anyhow!("--config requires a path"))?;
+            return Ok(Some(PathBuf::from(path)));
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Ok(Some(PathBuf::from(path)));
+        }
+    }
+    Ok(None)
+}
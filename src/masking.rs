@@ -0,0 +1,151 @@
+use crate::tile_rowstore::NULL_SENTINEL;
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use rsa::sha2::Sha256;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+
+/// Bytes in the per-install key of `hash` masks
+const SECRET_LEN: usize = 32;
+
+/// `[[masking]]` config entry: columns whose name matches `column` are masked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskRule {
+    /// Regex matched against the result column name
+    pub column: String,
+    pub mask: MaskKind,
+    /// Profiles the rule applies to, by their `environment`; empty applies
+    /// it to every profile
+    #[serde(default)]
+    pub environments: Vec<String>,
+}
+
+impl MaskRule {
+    fn applies_to(&self, environment: Option<&str>) -> bool {
+        self.environments.is_empty()
+            || environment.is_some_and(|env| self.environments.iter().any(|e| e.eq_ignore_ascii_case(env)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaskKind {
+    /// Hex digest keyed with this install's secret, so equal values still
+    /// compare equal but can't be looked up in a table of hashed values
+    Hash,
+    /// Keep the last four characters, star out the rest
+    Last4,
+    /// Replace the whole value
+    Redact,
+}
+
+/// How one result column is masked
+#[derive(Debug, Clone)]
+pub struct ColumnMask {
+    kind: MaskKind,
+    /// Key of `hash` masks
+    secret: Arc<[u8]>,
+}
+
+impl ColumnMask {
+    pub fn apply(&self, value: &str) -> String {
+        if value == NULL_SENTINEL {
+            return value.to_string();
+        }
+        match self.kind {
+            MaskKind::Hash => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC takes keys of any length");
+                mac.update(value.as_bytes());
+                // The first 8 bytes keep the column narrow
+                mac.finalize().into_bytes()[..8].iter().map(|b| format!("{:02x}", b)).collect()
+            }
+            MaskKind::Last4 => {
+                let count = value.chars().count();
+                value.chars()
+                    .enumerate()
+                    .map(|(i, c)| if i + 4 < count { '*' } else { c })
+                    .collect()
+            }
+            MaskKind::Redact => "[REDACTED]".to_string(),
+        }
+    }
+}
+
+/// Compiled masking rules of one profile, applied by the DB worker to every
+/// fetched row
+#[derive(Debug, Clone, Default)]
+pub struct Masker {
+    rules: Vec<(Regex, MaskKind)>,
+    secret: Arc<[u8]>,
+}
+
+impl Masker {
+    /// The rules that apply to a profile with `environment`
+    pub fn new(rules: &[MaskRule], environment: Option<&str>) -> anyhow::Result<Self> {
+        let rules: Vec<(Regex, MaskKind)> = rules.iter()
+            .filter(|r| r.applies_to(environment))
+            .map(|r| {
+                Regex::new(&r.column)
+                    .map(|re| (re, r.mask))
+                    .map_err(|e| anyhow::anyhow!("Invalid masking column pattern '{}': {}", r.column, e))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let secret = if rules.iter().any(|(_, mask)| *mask == MaskKind::Hash) {
+            install_secret().map_err(|e| anyhow::anyhow!("Can't read or create the masking key: {}", e))?
+        } else {
+            Vec::new()
+        };
+        Ok(Self { rules, secret: secret.into() })
+    }
+
+    /// Mask for each column of a result, from the first rule matching its name
+    pub fn for_columns(&self, headers: &[String]) -> Vec<Option<ColumnMask>> {
+        headers.iter()
+            .map(|h| {
+                self.rules.iter()
+                    .find(|(re, _)| re.is_match(h))
+                    .map(|(_, mask)| ColumnMask { kind: *mask, secret: self.secret.clone() })
+            })
+            .collect()
+    }
+}
+
+/// `<data dir>/frost/mask.key`, e.g. `~/.local/share/frost/mask.key`
+fn secret_path() -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", "frost").map(|d| d.data_local_dir().join("mask.key"))
+}
+
+/// This install's key for `hash` masks, made on first use and readable only
+/// by its owner
+fn install_secret() -> io::Result<Vec<u8>> {
+    let path = secret_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    match fs::read(&path) {
+        Ok(secret) if secret.len() == SECRET_LEN => return Ok(secret),
+        Ok(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a masking key", path.display()))),
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        Err(_) => {}
+    }
+    let mut secret = vec![0u8; SECRET_LEN];
+    getrandom::getrandom(&mut secret).map_err(io::Error::other)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    match options.open(&path) {
+        Ok(mut file) => {
+            file.write_all(&secret)?;
+            Ok(secret)
+        }
+        // Another Frost made it first
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => fs::read(&path),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,174 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+};
+use std::ops::Range;
+use unicode_width::UnicodeWidthChar;
+
+/// The message of an error or info tab with a caret and selection in it,
+/// both byte offsets into the message. Arrows move the caret over the
+/// wrapped lines, Shift extends the selection and Ctrl+C copies it.
+pub struct MessageText<'a> {
+    pub text: &'a str,
+    pub cursor: &'a mut usize,
+    /// Selected bytes (start, end), end exclusive
+    pub selection: &'a mut Option<(usize, usize)>,
+}
+
+impl MessageText<'_> {
+    /// Move the caret for a motion key; false for keys that aren't one
+    pub fn handle_key(&mut self, key: KeyEvent, width: usize, page: usize) -> bool {
+        let text = self.text;
+        let cursor = (*self.cursor).min(text.len());
+        let lines = wrap(text, width);
+        let line = line_of(&lines, cursor);
+        let last = lines.len() - 1;
+        let col: usize = text[lines[line].start..cursor].chars().map(|c| c.width().unwrap_or(1)).sum();
+        let to_line = |l: usize| at_column(text, &lines[l], col);
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let target = match key.code {
+            KeyCode::Left => text[..cursor].chars().next_back().map_or(0, |c| cursor - c.len_utf8()),
+            KeyCode::Right => text[cursor..].chars().next().map_or(cursor, |c| cursor + c.len_utf8()),
+            KeyCode::Up if line == 0 => lines[0].start,
+            KeyCode::Up => to_line(line - 1),
+            KeyCode::Down if line == last => lines[last].end,
+            KeyCode::Down => to_line(line + 1),
+            KeyCode::PageUp => to_line(line.saturating_sub(page)),
+            KeyCode::PageDown => to_line((line + page).min(last)),
+            KeyCode::Home if ctrl => 0,
+            KeyCode::End if ctrl => text.len(),
+            KeyCode::Home => lines[line].start,
+            KeyCode::End => lines[line].end,
+            KeyCode::Char('a') if ctrl => {
+                *self.selection = (!text.is_empty()).then_some((0, text.len()));
+                *self.cursor = text.len();
+                return true;
+            }
+            KeyCode::Esc => {
+                *self.selection = None;
+                return true;
+            }
+            _ => return false,
+        };
+        if key.modifiers.contains(KeyModifiers::SHIFT) {
+            let anchor = match *self.selection {
+                Some((start, end)) if start == cursor => end,
+                Some((start, _)) => start,
+                None => cursor,
+            };
+            *self.selection = (anchor != target).then(|| (anchor.min(target), anchor.max(target)));
+        } else {
+            *self.selection = None;
+        }
+        *self.cursor = target;
+        true
+    }
+
+    /// The selected text, or the whole message without a selection
+    pub fn selected(&self) -> &str {
+        match *self.selection {
+            Some((start, end)) => self.text.get(start..end).unwrap_or(self.text),
+            None => self.text,
+        }
+    }
+
+    /// Draw the wrapped message from line `scroll`, first moving `scroll`
+    /// so the caret line is on screen
+    pub fn render(&self, frame: &mut Frame, area: Rect, focused: bool, style: Style, scroll: &mut usize) {
+        let height = area.height as usize;
+        if height == 0 {
+            return;
+        }
+        let lines = wrap(self.text, area.width as usize);
+        let cursor = (*self.cursor).min(self.text.len());
+        let line = line_of(&lines, cursor);
+        if line < *scroll {
+            *scroll = line;
+        } else if line >= *scroll + height {
+            *scroll = line + 1 - height;
+        }
+        let caret_style = Style::default().fg(Color::Black).bg(Color::Cyan);
+        let selected_style = style.bg(Color::DarkGray);
+        let shown: Vec<Line> = lines.iter()
+            .skip(*scroll)
+            .take(height)
+            .map(|range| {
+                let mut spans = Vec::new();
+                for (i, c) in self.text[range.clone()].char_indices() {
+                    let at = range.start + i;
+                    let span_style = if focused && at == cursor {
+                        caret_style
+                    } else if self.selection.is_some_and(|(start, end)| (start..end).contains(&at)) {
+                        selected_style
+                    } else {
+                        style
+                    };
+                    // Tabs and other control characters would throw the columns off
+                    let c = if c.is_control() { ' ' } else { c };
+                    spans.push(Span::styled(c.to_string(), span_style));
+                }
+                // The caret past the last character of a line
+                let at_end = range.end == cursor && (range.end == self.text.len() || self.text[range.end..].starts_with('\n'));
+                if focused && at_end {
+                    spans.push(Span::styled(" ", caret_style));
+                }
+                Line::from(spans)
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(shown), area);
+    }
+}
+
+/// `text` wrapped to `width` columns as byte ranges, one per screen line,
+/// broken after whitespace where the line has some. Line breaks are left
+/// out of the ranges.
+fn wrap(text: &str, width: usize) -> Vec<Range<usize>> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in text.split('\n') {
+        let mut start = offset;
+        let mut cols = 0;
+        let mut last_break = None;
+        for (i, c) in line.char_indices() {
+            let at = offset + i;
+            let w = c.width().unwrap_or(1);
+            if cols + w > width && at > start {
+                let end = last_break.filter(|&b| b > start).unwrap_or(at);
+                lines.push(start..end);
+                cols = text[end..at].chars().map(|c| c.width().unwrap_or(1)).sum();
+                start = end;
+                last_break = None;
+            }
+            cols += w;
+            if c.is_whitespace() {
+                last_break = Some(at + c.len_utf8());
+            }
+        }
+        lines.push(start..offset + line.len());
+        offset += line.len() + 1;
+    }
+    lines
+}
+
+/// Index of the line the caret at `cursor` is on; a caret where a wrapped
+/// line ends belongs to the next one
+fn line_of(lines: &[Range<usize>], cursor: usize) -> usize {
+    lines.partition_point(|range| range.start <= cursor).saturating_sub(1)
+}
+
+/// Offset in `line` closest to display column `col`
+fn at_column(text: &str, line: &Range<usize>, col: usize) -> usize {
+    let mut cols = 0;
+    for (i, c) in text[line.clone()].char_indices() {
+        cols += c.width().unwrap_or(1);
+        if cols > col {
+            return line.start + i;
+        }
+    }
+    line.end
+}
@@ -0,0 +1,144 @@
+use crate::util::fnv1a;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Completions offered at once; the list is for picking, not browsing
+const MAX_COMPLETIONS: usize = 200;
+
+/// Schemas -> tables -> columns of one database, as of `refreshed_at`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatabaseMeta {
+    /// Unix seconds
+    pub refreshed_at: i64,
+    pub schemas: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+}
+
+/// Object names of the account, fetched in the background one database at
+/// a time and kept on disk per account and user, so completions work from
+/// the first keystroke of the next session.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MetadataCache {
+    pub databases: BTreeMap<String, DatabaseMeta>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl MetadataCache {
+    /// The cache file for a server and user; empty if there is none yet or
+    /// it can't be read
+    pub fn load(server: &str, user: &str) -> Self {
+        let key = format!("{}|{}", server.to_lowercase(), user.to_lowercase());
+        let path = directories::ProjectDirs::from("", "", "frost")
+            .map(|d| d.cache_dir().join("metadata").join(format!("{:016x}.json", fnv1a(key.as_bytes()))));
+        let mut cache: Self = path.as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        cache.path = path;
+        cache
+    }
+
+    /// Write the cache back; failures only cost the next session a refresh
+    pub fn save(&self) {
+        let Some(path) = &self.path else { return };
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Ok(text) = serde_json::to_string(self) {
+            let _ = std::fs::write(path, text);
+        }
+    }
+
+    /// Replace a database's objects with `(schema, table, column)` rows
+    pub fn set_database(&mut self, database: &str, columns: Vec<(String, String, String)>) {
+        let mut meta = DatabaseMeta { refreshed_at: chrono::Utc::now().timestamp(), ..Default::default() };
+        for (schema, table, column) in columns {
+            meta.schemas.entry(schema).or_default().entry(table).or_default().push(column);
+        }
+        self.databases.insert(database.to_string(), meta);
+    }
+
+    /// Forget databases the role can no longer see
+    pub fn retain_databases(&mut self, names: &[String]) {
+        self.databases.retain(|name, _| names.contains(name));
+    }
+
+    /// Databases among `names` never fetched or older than `max_age_secs`
+    pub fn stale(&self, names: &[String], max_age_secs: u64) -> Vec<String> {
+        let now = chrono::Utc::now().timestamp();
+        names.iter()
+            .filter(|name| {
+                self.databases.get(*name)
+                    .is_none_or(|meta| now - meta.refreshed_at >= max_age_secs as i64)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Names that can follow the qualifier `parts` (what precedes the last
+    /// dot, unquoted) and start with `partial`, case-insensitively.
+    /// Unqualified names resolve against the session database and schema.
+    pub fn completions(&self, parts: &[String], partial: &str, database: Option<&str>, schema: Option<&str>) -> Vec<String> {
+        let mut names: Vec<&String> = Vec::new();
+        let db = |name: &str| find(&self.databases, name);
+        let current_db = database.and_then(db);
+        match parts {
+            [] => {
+                names.extend(self.databases.keys());
+                if let Some(meta) = current_db {
+                    names.extend(meta.schemas.keys());
+                    if let Some(tables) = schema.and_then(|s| tables_in(meta, s)) {
+                        names.extend(tables.keys());
+                    }
+                }
+            }
+            [first] => {
+                if let Some(meta) = db(first.as_str()) {
+                    names.extend(meta.schemas.keys());
+                }
+                if let Some(tables) = current_db.and_then(|meta| tables_in(meta, first)) {
+                    names.extend(tables.keys());
+                }
+                let current_tables = current_db.zip(schema).and_then(|(meta, s)| tables_in(meta, s));
+                if let Some(columns) = current_tables.and_then(|tables| find(tables, first)) {
+                    names.extend(columns);
+                }
+            }
+            [first, second] => {
+                if let Some(tables) = db(first.as_str()).and_then(|meta| tables_in(meta, second)) {
+                    names.extend(tables.keys());
+                }
+                let tables = current_db.and_then(|meta| tables_in(meta, first));
+                if let Some(columns) = tables.and_then(|tables| find(tables, second)) {
+                    names.extend(columns);
+                }
+            }
+            [.., d, s, t] => {
+                let tables = db(d.as_str()).and_then(|meta| tables_in(meta, s));
+                if let Some(columns) = tables.and_then(|tables| find(tables, t)) {
+                    names.extend(columns);
+                }
+            }
+        }
+        let partial = partial.to_lowercase();
+        let mut matches: Vec<String> = names.into_iter()
+            .filter(|name| name.to_lowercase().starts_with(&partial))
+            .cloned()
+            .collect();
+        matches.sort_by_key(|name| name.to_lowercase());
+        matches.dedup();
+        matches.truncate(MAX_COMPLETIONS);
+        matches
+    }
+}
+
+fn tables_in<'a>(meta: &'a DatabaseMeta, schema: &str) -> Option<&'a BTreeMap<String, Vec<String>>> {
+    find(&meta.schemas, schema)
+}
+
+/// Entry matching `name` exactly, else case-insensitively (unquoted
+/// identifiers are stored uppercase but typed in any case)
+fn find<'a, V>(map: &'a BTreeMap<String, V>, name: &str) -> Option<&'a V> {
+    map.get(name).or_else(|| map.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v))
+}
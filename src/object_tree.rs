@@ -0,0 +1,170 @@
+use crate::metadata::MetadataCache;
+use crate::overlay::OverlayOutcome;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::collections::{BTreeMap, HashSet};
+
+/// One line of the tree as drawn
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeNode {
+    Database(String),
+    Schema { database: String, schema: String },
+    Table { database: String, schema: String, table: String },
+}
+
+impl TreeNode {
+    /// Indent level: databases at 0, tables at 2
+    pub fn depth(&self) -> usize {
+        match self {
+            TreeNode::Database(_) => 0,
+            TreeNode::Schema { .. } => 1,
+            TreeNode::Table { .. } => 2,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            TreeNode::Database(name) => name,
+            TreeNode::Schema { schema, .. } => schema,
+            TreeNode::Table { table, .. } => table,
+        }
+    }
+}
+
+/// Databases, schemas and tables of the account as the metadata cache
+/// knows them. Expanding a schema asks the workspace for its table stats;
+/// Enter on a table inserts its qualified name into the editor.
+#[derive(Debug)]
+pub struct ObjectTree {
+    /// Database -> schema -> table names, copied when the tree opens
+    objects: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+    /// Expanded databases and (database, schema) pairs
+    expanded_databases: HashSet<String>,
+    expanded_schemas: HashSet<(String, String)>,
+    pub selected: usize,
+    /// A schema expanded since the workspace last looked
+    expanded: Option<(String, String)>,
+}
+
+impl ObjectTree {
+    pub fn new(metadata: &MetadataCache) -> Self {
+        let objects = metadata.databases.iter()
+            .map(|(database, meta)| {
+                let schemas = meta.schemas.iter()
+                    .map(|(schema, tables)| (schema.clone(), tables.keys().cloned().collect()))
+                    .collect();
+                (database.clone(), schemas)
+            })
+            .collect();
+        Self {
+            objects,
+            expanded_databases: HashSet::new(),
+            expanded_schemas: HashSet::new(),
+            selected: 0,
+            expanded: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// The nodes shown, parents before their children
+    pub fn visible(&self) -> Vec<TreeNode> {
+        let mut nodes = Vec::new();
+        for (database, schemas) in &self.objects {
+            nodes.push(TreeNode::Database(database.clone()));
+            if !self.expanded_databases.contains(database) {
+                continue;
+            }
+            for (schema, tables) in schemas {
+                nodes.push(TreeNode::Schema { database: database.clone(), schema: schema.clone() });
+                if !self.expanded_schemas.contains(&(database.clone(), schema.clone())) {
+                    continue;
+                }
+                nodes.extend(tables.iter().map(|table| TreeNode::Table {
+                    database: database.clone(),
+                    schema: schema.clone(),
+                    table: table.clone(),
+                }));
+            }
+        }
+        nodes
+    }
+
+    pub fn is_expanded(&self, node: &TreeNode) -> bool {
+        match node {
+            TreeNode::Database(database) => self.expanded_databases.contains(database),
+            TreeNode::Schema { database, schema } => self.expanded_schemas.contains(&(database.clone(), schema.clone())),
+            TreeNode::Table { .. } => false,
+        }
+    }
+
+    pub fn selected_node(&self) -> Option<TreeNode> {
+        self.visible().into_iter().nth(self.selected)
+    }
+
+    /// The schema expanded since the last call, whose stats should be fetched
+    pub fn take_expanded(&mut self) -> Option<(String, String)> {
+        self.expanded.take()
+    }
+
+    fn set_expanded(&mut self, node: &TreeNode, expand: bool) {
+        match node {
+            TreeNode::Database(database) if expand => {
+                self.expanded_databases.insert(database.clone());
+            }
+            TreeNode::Database(database) => {
+                self.expanded_databases.remove(database);
+            }
+            TreeNode::Schema { database, schema } if expand => {
+                let key = (database.clone(), schema.clone());
+                if self.expanded_schemas.insert(key.clone()) {
+                    self.expanded = Some(key);
+                }
+            }
+            TreeNode::Schema { database, schema } => {
+                self.expanded_schemas.remove(&(database.clone(), schema.clone()));
+            }
+            TreeNode::Table { .. } => {}
+        }
+    }
+
+    /// Left on an open node closes it, elsewhere it moves to the parent
+    fn collapse_or_parent(&mut self, node: &TreeNode) {
+        if self.is_expanded(node) {
+            self.set_expanded(node, false);
+            return;
+        }
+        let depth = node.depth();
+        if depth == 0 {
+            return;
+        }
+        let visible = self.visible();
+        if let Some(parent) = visible[..self.selected].iter().rposition(|n| n.depth() < depth) {
+            self.selected = parent;
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        let Some(node) = self.selected_node() else {
+            return OverlayOutcome::Consumed;
+        };
+        match key.code {
+            KeyCode::Enter if matches!(node, TreeNode::Table { .. }) => return OverlayOutcome::Submit,
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                let expand = !self.is_expanded(&node);
+                self.set_expanded(&node, expand);
+            }
+            KeyCode::Right => self.set_expanded(&node, true),
+            KeyCode::Left => self.collapse_or_parent(&node),
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.visible().len().saturating_sub(1)),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+            KeyCode::PageDown => self.selected = (self.selected + 10).min(self.visible().len().saturating_sub(1)),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = self.visible().len().saturating_sub(1),
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+}
@@ -0,0 +1,151 @@
+use crate::chart::ChartView;
+use crate::clipboard::HistoryList;
+use crate::complete::CompletionList;
+use crate::ddl::DdlView;
+use crate::diff::DiffView;
+use crate::distribution::Distribution;
+use crate::export::ExportPrompt;
+use crate::goto::GotoPrompt;
+use crate::health::HealthReport;
+use crate::json_view::JsonView;
+use crate::object_tree::ObjectTree;
+use crate::replace::ReplaceForm;
+use crate::row_detail::RowDetail;
+use crate::save_as::SaveAsPrompt;
+use crate::saved_result::ResultFilePrompt;
+use crate::source_sql::SourceView;
+use crate::stages::StageBrowser;
+use crate::switcher::Switcher;
+use crate::tab_compare::CompareForm;
+use crate::todo::TodoList;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::path::PathBuf;
+
+/// What a confirmation overlay does when accepted or declined
+#[derive(Debug, Clone)]
+pub enum ConfirmAction {
+    /// Restore editor content from an orphaned autosave file
+    RestoreRecovery(PathBuf),
+    /// Run a batch that writes, on a production profile
+    RunProdWrites(String),
+    /// Run the current results tab's statement again, on a production profile
+    RerunProdWrites { new_tab: bool },
+}
+
+/// A modal popup drawn above the panes. Overlays live on a stack in the
+/// workspace: the top one gets every key, Esc always closes it, and they
+/// are drawn bottom to top after the panes.
+#[derive(Debug)]
+pub enum Overlay {
+    Help { scroll: usize },
+    /// Yes/no question; y/Enter accepts, n declines
+    Confirm { title: String, message: String, action: ConfirmAction },
+    /// Grid find-and-replace that generates an UPDATE into the editor
+    Replace(ReplaceForm),
+    /// Quick switch of role, warehouse or database
+    Switcher(Switcher),
+    /// Copy/export format picker for the results grid
+    Export(ExportPrompt),
+    /// Environment diagnostics; c copies them as text
+    Health(HealthReport),
+    /// Profile of one result column; any key closes it
+    ColumnStats { column: String, stats: Vec<(&'static str, String)> },
+    /// TODO / FIXME comments; Enter jumps to one
+    Todo(TodoList),
+    /// Line to move the editor caret to
+    Goto(GotoPrompt),
+    /// Databases, schemas and tables with their row counts; Enter inserts a table
+    ObjectTree(ObjectTree),
+    /// Stages and their files; PUT uploads, GET downloads
+    Stages(StageBrowser),
+    /// `GET_DDL` of a table or view; c copies it
+    Ddl(DdlView),
+    /// The buffer against its saved file or the clipboard, line by line
+    Diff(DiffView),
+    /// Names completing the one at the editor caret
+    Completion(CompletionList),
+    /// Earlier copies; Enter pastes one into the editor
+    PasteHistory(HistoryList),
+    /// Every column of one result row; c copies a value
+    RowDetail(RowDetail),
+    /// A JSON cell as a foldable tree; c copies the selected member's path
+    Json(JsonView),
+    /// Numeric columns of a result as a line or bar chart
+    Chart(ChartView),
+    /// Most frequent values of a column, counted in the background
+    Distribution(Distribution),
+    /// The SQL a results tab came from; c copies it, e puts it in the editor
+    Source(SourceView),
+    /// Key columns for comparing two results tabs
+    Compare(CompareForm),
+    /// File name to save a results tab to or open a saved result from
+    ResultFile(ResultFilePrompt),
+    /// File name for an editor buffer saved for the first time
+    SaveAs(SaveAsPrompt),
+}
+
+/// What the workspace should do after an overlay handled a key
+#[derive(Debug, PartialEq)]
+pub enum OverlayOutcome {
+    /// Key consumed, overlay stays open
+    Consumed,
+    /// Pop this overlay
+    Close,
+    /// Pop this overlay and perform its confirm action
+    Accept,
+    /// Pop this overlay and perform its decline action
+    Decline,
+    /// Hand the overlay's input to the workspace; it stays open unless the
+    /// workspace closes it
+    Submit,
+}
+
+impl Overlay {
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        match self {
+            Overlay::Help { scroll } => {
+                match key.code {
+                    KeyCode::F(1) | KeyCode::Char('?') | KeyCode::Char('q') => return OverlayOutcome::Close,
+                    KeyCode::Up => *scroll = scroll.saturating_sub(1),
+                    KeyCode::Down => *scroll += 1,
+                    KeyCode::PageUp => *scroll = scroll.saturating_sub(10),
+                    KeyCode::PageDown => *scroll += 10,
+                    KeyCode::Home => *scroll = 0,
+                    KeyCode::End => *scroll = usize::MAX,
+                    _ => {}
+                }
+                OverlayOutcome::Consumed
+            }
+            Overlay::Confirm { .. } => match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => OverlayOutcome::Accept,
+                KeyCode::Char('n') | KeyCode::Char('N') => OverlayOutcome::Decline,
+                _ => OverlayOutcome::Consumed,
+            },
+            Overlay::Replace(form) => form.handle_key(key),
+            Overlay::Switcher(switcher) => switcher.handle_key(key),
+            Overlay::Export(prompt) => prompt.handle_key(key),
+            Overlay::ColumnStats { .. } => OverlayOutcome::Close,
+            Overlay::Todo(list) => list.handle_key(key),
+            Overlay::Goto(prompt) => prompt.handle_key(key),
+            Overlay::ObjectTree(tree) => tree.handle_key(key),
+            Overlay::Stages(browser) => browser.handle_key(key),
+            Overlay::Ddl(view) => view.handle_key(key),
+            Overlay::Diff(view) => view.handle_key(key),
+            Overlay::Completion(list) => list.handle_key(key),
+            Overlay::PasteHistory(list) => list.handle_key(key),
+            Overlay::RowDetail(detail) => detail.handle_key(key),
+            Overlay::Json(view) => view.handle_key(key),
+            Overlay::Chart(chart) => chart.handle_key(key),
+            Overlay::Distribution(dist) => dist.handle_key(key),
+            Overlay::Source(view) => view.handle_key(key),
+            Overlay::Compare(form) => form.handle_key(key),
+            Overlay::ResultFile(prompt) => prompt.handle_key(key),
+            Overlay::SaveAs(prompt) => prompt.handle_key(key),
+            Overlay::Health(_) => match key.code {
+                KeyCode::Char('c') | KeyCode::Char('C') => OverlayOutcome::Submit,
+                KeyCode::Enter | KeyCode::Char('q') => OverlayOutcome::Close,
+                _ => OverlayOutcome::Consumed,
+            },
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::export::{parse_bool, parse_double, ColumnKind};
+use crate::tile_rowstore::{cell_text, NULL_SENTINEL};
+use arrow_array::{
+    types::Int32Type, Array, ArrayRef, BooleanArray, DictionaryArray, Float64Array, Int64Array, RecordBatch, StringArray,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use std::{
+    io::{self, Write},
+    sync::Arc,
+};
+
+/// Parquet type of an exported column. Starts from the column's kind and
+/// falls back towards text as values that don't fit are seen, so nothing
+/// is lost in conversion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueType {
+    Int,
+    Double,
+    Boolean,
+    Text,
+}
+
+impl ValueType {
+    pub fn from_kind(kind: ColumnKind) -> Self {
+        match kind {
+            ColumnKind::Number => ValueType::Int,
+            ColumnKind::Boolean => ValueType::Boolean,
+            ColumnKind::Text => ValueType::Text,
+        }
+    }
+
+    /// Widen to a type that also holds `value`
+    pub fn widen(&mut self, value: &str) {
+        if value == NULL_SENTINEL {
+            return;
+        }
+        if *self == ValueType::Int && value.parse::<i64>().is_err() {
+            *self = ValueType::Double;
+        }
+        if *self == ValueType::Double && parse_double(value).is_none() {
+            *self = ValueType::Text;
+        }
+        if *self == ValueType::Boolean && parse_bool(value).is_none() {
+            *self = ValueType::Text;
+        }
+    }
+
+    /// Widen to a type that also holds every value of a tile column. An
+    /// integer or boolean column holds its own type already; other columns
+    /// are checked value by value, a dictionary's once per entry.
+    pub fn widen_column(&mut self, column: &dyn Array) {
+        match (column.data_type(), *self) {
+            (_, ValueType::Text) | (DataType::Int64, ValueType::Int) | (DataType::Boolean, ValueType::Boolean) => {}
+            (DataType::Dictionary(..), _) => {
+                if let Some(dict) = column.as_any().downcast_ref::<DictionaryArray<Int32Type>>() {
+                    self.widen_column(dict.values().as_ref());
+                }
+            }
+            _ => (0..column.len())
+                .filter(|&r| column.is_valid(r))
+                .for_each(|r| self.widen(&cell_text(column, r))),
+        }
+    }
+
+    /// A column of this type from text values, None for NULL
+    fn array<'a>(self, values: impl Iterator<Item = Option<&'a str>>) -> ArrayRef {
+        match self {
+            ValueType::Int => Arc::new(values.map(|v| v.and_then(|v| v.parse::<i64>().ok())).collect::<Int64Array>()),
+            ValueType::Double => Arc::new(values.map(|v| v.and_then(parse_double)).collect::<Float64Array>()),
+            ValueType::Boolean => Arc::new(values.map(|v| v.and_then(parse_bool)).collect::<BooleanArray>()),
+            ValueType::Text => Arc::new(values.collect::<StringArray>()),
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            ValueType::Int => DataType::Int64,
+            ValueType::Double => DataType::Float64,
+            ValueType::Boolean => DataType::Boolean,
+            ValueType::Text => DataType::Utf8,
+        }
+    }
+}
+
+/// Writes the tiles of a result to a Parquet file, each its own record
+/// batch, snappy-compressed
+pub struct ParquetWriter<W: Write + Send> {
+    writer: ArrowWriter<W>,
+    schema: SchemaRef,
+    types: Vec<ValueType>,
+    rows_written: usize,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    /// Columns are nullable and typed by `types`, one per header
+    pub fn new(out: W, headers: &[String], types: Vec<ValueType>) -> io::Result<Self> {
+        let fields: Vec<Field> = headers.iter()
+            .zip(&types)
+            .map(|(name, ty)| Field::new(name, ty.data_type(), true))
+            .collect();
+        let schema = Arc::new(Schema::new(fields));
+        let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        let writer = ArrowWriter::try_new(out, schema.clone(), Some(props)).map_err(io::Error::other)?;
+        Ok(Self { writer, schema, types, rows_written: 0 })
+    }
+
+    /// Write a tile's record batch, one column per header. Columns already
+    /// of their Parquet type go in as they are; the others are converted
+    /// from their values' text.
+    pub fn write_batch(&mut self, batch: &RecordBatch) -> io::Result<()> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        let columns: Vec<ArrayRef> = self.types.iter()
+            .zip(batch.columns())
+            .map(|(ty, column)| {
+                if *column.data_type() == ty.data_type() {
+                    return column.clone();
+                }
+                let values: Vec<Option<String>> = (0..column.len())
+                    .map(|r| column.is_valid(r).then(|| cell_text(column.as_ref(), r)))
+                    .collect();
+                ty.array(values.iter().map(Option::as_deref))
+            })
+            .collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), columns).map_err(io::Error::other)?;
+        self.writer.write(&batch).map_err(io::Error::other)?;
+        self.rows_written += batch.num_rows();
+        Ok(())
+    }
+
+    /// Write the footer and flush; returns the number of rows written
+    pub fn finish(self) -> io::Result<usize> {
+        let mut out = self.writer.into_inner().map_err(io::Error::other)?;
+        out.flush()?;
+        Ok(self.rows_written)
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Samples kept per series; about five seconds of frames in full redraw mode
+const SAMPLES: usize = 100;
+
+/// Rolling timings behind the frame time overlay
+#[derive(Debug, Default)]
+pub struct PerfStats {
+    frames: VecDeque<Duration>,
+    /// Time from an input event arriving to the workspace having handled it
+    events: VecDeque<Duration>,
+}
+
+impl PerfStats {
+    pub fn record_frame(&mut self, took: Duration) {
+        push(&mut self.frames, took);
+    }
+
+    pub fn record_event(&mut self, took: Duration) {
+        push(&mut self.events, took);
+    }
+
+    /// Overlay lines; `cache` is (hits, misses) of the current result's
+    /// tile cache, `queued` the requests waiting for the database worker
+    pub fn lines(&self, cache: Option<(u64, u64)>, queued: usize) -> Vec<String> {
+        let cache = match cache {
+            Some((hits, misses)) if hits + misses > 0 => format!(
+                "{:.1}% ({} hit / {} miss)",
+                hits as f64 * 100.0 / (hits + misses) as f64,
+                hits,
+                misses,
+            ),
+            _ => "-".to_string(),
+        };
+        vec![
+            format!("frame    {}", summary(&self.frames)),
+            format!("event    {}", summary(&self.events)),
+            format!("tiles    {}", cache),
+            format!("worker   {} queued", queued),
+        ]
+    }
+}
+
+fn push(samples: &mut VecDeque<Duration>, took: Duration) {
+    if samples.len() == SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(took);
+}
+
+/// `avg 1.2ms  max 8.0ms` over the kept samples
+fn summary(samples: &VecDeque<Duration>) -> String {
+    if samples.is_empty() {
+        return "-".to_string();
+    }
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    let total: Duration = samples.iter().sum();
+    let max = samples.iter().max().copied().unwrap_or_default();
+    format!("avg {:.1}ms  max {:.1}ms", ms(total) / samples.len() as f64, ms(max))
+}
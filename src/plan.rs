@@ -0,0 +1,263 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+};
+
+/// Output format requested from `EXPLAIN USING ...`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanFormat {
+    Text,
+    Json,
+}
+
+impl PlanFormat {
+    pub fn keyword(self) -> &'static str {
+        match self {
+            PlanFormat::Text => "TEXT",
+            PlanFormat::Json => "JSON",
+        }
+    }
+}
+
+/// One line of a plan; its indent decides which lines nest under it
+#[derive(Debug)]
+struct PlanLine {
+    indent: usize,
+    text: String,
+}
+
+/// Query plan shown as a tree of indented lines. A line owns every
+/// following line that is indented deeper, so operators collapse together
+/// with their inputs.
+#[derive(Debug)]
+pub struct PlanView {
+    lines: Vec<PlanLine>,
+    collapsed: Vec<bool>,
+    /// Index into `lines` of the selected line
+    cursor: usize,
+    scroll: usize,
+    /// Visible lines in the last render, used for paging
+    page_rows: usize,
+    /// `bytesAssigned` from the GlobalStats section, if present
+    pub bytes_assigned: Option<u64>,
+}
+
+impl PlanView {
+    pub fn parse(format: PlanFormat, output: &str) -> Result<Self, String> {
+        match format {
+            PlanFormat::Text => Ok(Self::from_text(output)),
+            PlanFormat::Json => Self::from_json(output),
+        }
+    }
+
+    /// Build from `EXPLAIN USING TEXT` output. Operator lines are nested by
+    /// the position of their `->` arrow, other lines by leading whitespace.
+    pub fn from_text(output: &str) -> Self {
+        let lines: Vec<PlanLine> = output.lines()
+            .map(|l| l.trim_end())
+            .filter(|l| !l.is_empty())
+            .map(|l| PlanLine {
+                indent: l.find("->").unwrap_or_else(|| l.len() - l.trim_start().len()),
+                text: l.to_string(),
+            })
+            .collect();
+        let bytes_assigned = lines.iter()
+            .find_map(|l| l.text.trim().strip_prefix("bytesAssigned="))
+            .and_then(|v| v.trim().parse().ok());
+        Self {
+            bytes_assigned,
+            collapsed: vec![false; lines.len()],
+            lines,
+            cursor: 0,
+            scroll: 0,
+            page_rows: 1,
+        }
+    }
+
+    /// Build from `EXPLAIN USING JSON` output by laying the operators out
+    /// the way the text format does, children under their parent operator.
+    pub fn from_json(output: &str) -> Result<Self, String> {
+        let plan: serde_json::Value = serde_json::from_str(output)
+            .map_err(|e| format!("Failed to parse JSON plan: {}", e))?;
+        let mut text = String::new();
+
+        if let Some(stats) = plan.get("GlobalStats").and_then(|s| s.as_object()) {
+            text.push_str("GlobalStats:\n");
+            for (key, value) in stats {
+                text.push_str(&format!("    {}={}\n", key, value));
+            }
+        }
+
+        text.push_str("Operations:\n");
+        let steps = plan.get("Operations").and_then(|o| o.as_array()).cloned().unwrap_or_default();
+        for (step, ops) in steps.iter().enumerate() {
+            let ops = ops.as_array().cloned().unwrap_or_default();
+            let id = |op: &serde_json::Value| op.get("id").and_then(|i| i.as_i64());
+            let parent = |op: &serde_json::Value| {
+                op.get("parentOperators")
+                    .and_then(|p| p.as_array())
+                    .and_then(|p| p.first())
+                    .and_then(|p| p.as_i64())
+            };
+            // Depth-first from the roots; an operator with several parents
+            // is listed under the first one
+            let mut stack: Vec<(usize, &serde_json::Value)> = ops.iter()
+                .rev()
+                .filter(|op| parent(op).is_none())
+                .map(|op| (0, op))
+                .collect();
+            while let Some((depth, op)) = stack.pop() {
+                text.push_str(&format!(
+                    "{:<8}{}->{}\n",
+                    format!("{}:{}", step + 1, id(op).unwrap_or(0)),
+                    " ".repeat(depth * 4),
+                    describe_operation(op),
+                ));
+                let op_id = id(op);
+                stack.extend(
+                    ops.iter()
+                        .rev()
+                        .filter(|child| op_id.is_some() && parent(child) == op_id)
+                        .map(|child| (depth + 1, child)),
+                );
+            }
+        }
+        Ok(Self::from_text(&text))
+    }
+
+    fn has_children(&self, idx: usize) -> bool {
+        self.lines.get(idx + 1).is_some_and(|next| next.indent > self.lines[idx].indent)
+    }
+
+    /// Indices of lines not hidden under a collapsed ancestor
+    fn visible(&self) -> Vec<usize> {
+        let mut visible = Vec::with_capacity(self.lines.len());
+        let mut hide_deeper_than: Option<usize> = None;
+        for (idx, line) in self.lines.iter().enumerate() {
+            if let Some(indent) = hide_deeper_than {
+                if line.indent > indent {
+                    continue;
+                }
+                hide_deeper_than = None;
+            }
+            visible.push(idx);
+            if self.collapsed[idx] && self.has_children(idx) {
+                hide_deeper_than = Some(line.indent);
+            }
+        }
+        visible
+    }
+
+    pub fn move_cursor(&mut self, delta: isize) {
+        let visible = self.visible();
+        let Some(pos) = visible.iter().position(|&i| i == self.cursor) else { return };
+        let pos = (pos as isize + delta).clamp(0, visible.len() as isize - 1) as usize;
+        self.cursor = visible[pos];
+    }
+
+    /// Collapse the selected node, or jump to its parent if it is already collapsed
+    fn collapse_or_parent(&mut self) {
+        if self.has_children(self.cursor) && !self.collapsed[self.cursor] {
+            self.collapsed[self.cursor] = true;
+            return;
+        }
+        let indent = self.lines[self.cursor].indent;
+        if let Some(parent) = (0..self.cursor).rev().find(|&i| self.lines[i].indent < indent) {
+            self.cursor = parent;
+        }
+    }
+
+    fn set_all_collapsed(&mut self, collapsed: bool) {
+        for idx in 0..self.lines.len() {
+            self.collapsed[idx] = collapsed && self.has_children(idx);
+        }
+        // Keep the cursor on a line that is still shown
+        while !self.visible().contains(&self.cursor) {
+            self.collapse_or_parent();
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        if self.lines.is_empty() {
+            return;
+        }
+        let page = self.page_rows.max(1) as isize;
+        match key.code {
+            KeyCode::Up => self.move_cursor(-1),
+            KeyCode::Down => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-page),
+            KeyCode::PageDown => self.move_cursor(page),
+            KeyCode::Home => self.move_cursor(isize::MIN / 2),
+            KeyCode::End => self.move_cursor(isize::MAX / 2),
+            KeyCode::Enter | KeyCode::Char(' ') if self.has_children(self.cursor) => {
+                self.collapsed[self.cursor] = !self.collapsed[self.cursor];
+            }
+            KeyCode::Left => self.collapse_or_parent(),
+            KeyCode::Right => self.collapsed[self.cursor] = false,
+            KeyCode::Char('-') => self.set_all_collapsed(true),
+            KeyCode::Char('+') | KeyCode::Char('=') => self.set_all_collapsed(false),
+            _ => {}
+        }
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, area: Rect, focused: bool) {
+        if area.height == 0 {
+            return;
+        }
+        let visible = self.visible();
+        let height = area.height as usize;
+        self.page_rows = height;
+        let pos = visible.iter().position(|&i| i == self.cursor).unwrap_or(0);
+        if pos < self.scroll {
+            self.scroll = pos;
+        } else if pos >= self.scroll + height {
+            self.scroll = pos + 1 - height;
+        }
+
+        let lines: Vec<Line> = visible.iter()
+            .skip(self.scroll)
+            .take(height)
+            .map(|&idx| {
+                let line = &self.lines[idx];
+                let marker = match (self.has_children(idx), self.collapsed[idx]) {
+                    (false, _) => "  ",
+                    (true, false) => "▾ ",
+                    (true, true) => "▸ ",
+                };
+                let (prefix, body) = line.text.split_at(line.indent.min(line.text.len()));
+                let style = if focused && idx == self.cursor {
+                    Style::default().fg(Color::Black).bg(Color::Cyan)
+                } else if body.starts_with("->") {
+                    Style::default().add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                Line::from(vec![
+                    Span::styled(prefix.to_string(), Style::default().fg(Color::DarkGray)),
+                    Span::styled(marker, Style::default().fg(Color::DarkGray)),
+                    Span::styled(body.to_string(), style),
+                ])
+            })
+            .collect();
+
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+}
+
+/// `Operation  objects  expressions` for one JSON plan operator
+fn describe_operation(op: &serde_json::Value) -> String {
+    let mut parts = vec![op.get("operation").and_then(|o| o.as_str()).unwrap_or("?").to_string()];
+    for key in ["objects", "expressions"] {
+        if let Some(values) = op.get(key).and_then(|v| v.as_array()) {
+            let joined: Vec<&str> = values.iter().filter_map(|v| v.as_str()).collect();
+            if !joined.is_empty() {
+                parts.push(joined.join(", "));
+            }
+        }
+    }
+    parts.join("  ")
+}
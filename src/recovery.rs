@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Unsaved editor content written periodically so a crash or killed
+/// terminal doesn't lose work
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryData {
+    pub filename: Option<PathBuf>,
+    pub saved_at: String,
+    pub content: String,
+}
+
+/// `<data dir>/frost/recovery`, e.g. `~/.local/share/frost/recovery`
+fn recovery_dir() -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", "frost").map(|d| d.data_local_dir().join("recovery"))
+}
+
+/// Recovery file owned by this process
+pub fn own_recovery_path() -> Option<PathBuf> {
+    recovery_dir().map(|d| d.join(format!("autosave-{}.json", std::process::id())))
+}
+
+/// Write `data` atomically (temp file + rename) to `path`
+pub fn write(path: &Path, data: &RecoveryData) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec(data)?)?;
+    fs::rename(&tmp, path)
+}
+
+pub fn read(path: &Path) -> io::Result<RecoveryData> {
+    let bytes = fs::read(path)?;
+    serde_json::from_slice(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn remove(path: &Path) {
+    let _ = fs::remove_file(path);
+}
+
+/// Recovery files left behind by Frost processes that are no longer running
+pub fn find_orphaned() -> Vec<PathBuf> {
+    let Some(dir) = recovery_dir() else { return Vec::new() };
+    let Ok(entries) = fs::read_dir(&dir) else { return Vec::new() };
+    let own_pid = std::process::id();
+    entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|path| {
+            let Some(pid) = path.file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_prefix("autosave-"))
+                .and_then(|n| n.strip_suffix(".json"))
+                .and_then(|n| n.parse::<u32>().ok())
+            else {
+                return false;
+            };
+            pid != own_pid && !process_alive(pid)
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    // Signal 0 only checks that the process exists; another user's process
+    // refuses it but is running
+    let signalled = unsafe { libc::kill(pid as libc::pid_t, 0) == 0 };
+    signalled || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+fn process_alive(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, GetLastError, ERROR_ACCESS_DENIED, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            // Another user's process can't be opened but is running
+            return GetLastError() == ERROR_ACCESS_DENIED;
+        }
+        let mut code = 0u32;
+        let alive = GetExitCodeProcess(handle, &mut code) != 0 && code == STILL_ACTIVE as u32;
+        CloseHandle(handle);
+        alive
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn process_alive(_pid: u32) -> bool {
+    false
+}
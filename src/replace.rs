@@ -0,0 +1,136 @@
+use crate::config::{Dialect, IdentifierQuoting};
+use crate::export::ColumnKind;
+use crate::identifier::quote_identifier;
+use crate::overlay::OverlayOutcome;
+use crate::tile_rowstore::NULL_SENTINEL;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use regex::Regex;
+
+/// Keys listed in one UPDATE's IN list; larger changes are split
+const KEYS_PER_STATEMENT: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaceField {
+    Table,
+    Find,
+    Replace,
+}
+
+/// Rows a replace would touch, computed from the result grid
+#[derive(Debug)]
+pub struct ReplacePreview {
+    /// Key values of matching rows
+    pub keys: Vec<String>,
+    /// Matching rows skipped because their key is NULL
+    pub null_keys: usize,
+}
+
+/// Find-and-replace over one result column. Cells whose whole value equals
+/// `find` get `replace`; the generated UPDATE targets them by key column.
+#[derive(Debug)]
+pub struct ReplaceForm {
+    pub tab_idx: usize,
+    pub column: usize,
+    pub key_column: usize,
+    pub column_name: String,
+    pub key_name: String,
+    /// Number keys go into the IN list bare, others quoted
+    pub key_kind: ColumnKind,
+    pub table: String,
+    pub find: String,
+    pub replace: String,
+    pub field: ReplaceField,
+    /// Set after the first Enter; a second Enter generates the UPDATE
+    pub preview: Option<ReplacePreview>,
+}
+
+impl ReplaceForm {
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        match key.code {
+            KeyCode::Enter => return OverlayOutcome::Submit,
+            KeyCode::Tab | KeyCode::Down => {
+                self.field = match self.field {
+                    ReplaceField::Table => ReplaceField::Find,
+                    ReplaceField::Find => ReplaceField::Replace,
+                    ReplaceField::Replace => ReplaceField::Table,
+                };
+            }
+            KeyCode::BackTab | KeyCode::Up => {
+                self.field = match self.field {
+                    ReplaceField::Table => ReplaceField::Replace,
+                    ReplaceField::Find => ReplaceField::Table,
+                    ReplaceField::Replace => ReplaceField::Find,
+                };
+            }
+            KeyCode::Backspace => {
+                self.field_mut().pop();
+                self.preview = None;
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.field_mut().push(c);
+                self.preview = None;
+            }
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+
+    fn field_mut(&mut self) -> &mut String {
+        match self.field {
+            ReplaceField::Table => &mut self.table,
+            ReplaceField::Find => &mut self.find,
+            ReplaceField::Replace => &mut self.replace,
+        }
+    }
+
+    /// UPDATE statements for the previewed rows, `;`-terminated
+    pub fn update_sql(&self, keys: &[String], quoting: IdentifierQuoting, dialect: Dialect) -> String {
+        let column = quote_identifier(&self.column_name, quoting);
+        let key = quote_identifier(&self.key_name, quoting);
+        let key_literal = |k: &String| if self.key_kind == ColumnKind::Number { k.trim().to_string() } else { sql_literal(k, dialect) };
+        keys.chunks(KEYS_PER_STATEMENT)
+            .map(|chunk| {
+                let list: Vec<String> = chunk.iter().map(key_literal).collect();
+                format!(
+                    "UPDATE {}\nSET {} = {}\nWHERE {} = {}\n  AND {} IN ({});\n",
+                    self.table.trim(),
+                    column,
+                    sql_literal(&self.replace, dialect),
+                    column,
+                    sql_literal(&self.find, dialect),
+                    key,
+                    list.join(", "),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Single-quoted string literal; backslashes are escapes only in Snowflake
+/// strings, so they're doubled there and left alone elsewhere
+fn sql_literal(value: &str, dialect: Dialect) -> String {
+    let value = if dialect == Dialect::Snowflake { value.replace('\\', "\\\\") } else { value.to_string() };
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Key values of rows in `rows` whose `column` equals `find`; NULL keys are
+/// counted separately since they can't be targeted.
+pub fn collect_matches(rows: &[Vec<String>], column: usize, key_column: usize, find: &str, preview: &mut ReplacePreview) {
+    for row in rows {
+        if row.get(column).map(|v| v.as_str()) != Some(find) {
+            continue;
+        }
+        match row.get(key_column) {
+            Some(key) if key != NULL_SENTINEL => preview.keys.push(key.clone()),
+            _ => preview.null_keys += 1,
+        }
+    }
+}
+
+/// First table named after FROM in `sql`, used to prefill the table field
+pub fn guess_table(sql: &str) -> Option<String> {
+    let name = r#"(?:"[^"]+"|[A-Za-z_][A-Za-z0-9_$]*)"#;
+    let from = Regex::new(&format!(r"(?i)\bFROM\s+({name}(?:\.{name})*)")).ok()?;
+    from.captures(sql).map(|c| c[1].to_string())
+}
@@ -0,0 +1,664 @@
+use crate::catalog;
+use crate::config::{Dialect, RestConfig};
+use crate::connection::{CancelHandle, DbWorkerRequest, DbWorkerResponse, QueryExecutor, QueryRequest, WorkerRequests};
+use crate::export::ColumnKind;
+use crate::masking::{ColumnMask, Masker};
+use crate::plan::PlanView;
+use crate::results::ResultsContent;
+use crate::switcher::{ContextKind, SessionContext};
+use crate::tile_rowstore::{TileRowStore, TileStoreOptions, NULL_SENTINEL};
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}, Engine};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs1v15::SigningKey;
+use rsa::pkcs8::{DecodePrivateKey, EncodePublicKey};
+use rsa::sha2::{Digest, Sha256};
+use rsa::signature::{SignatureEncoding, Signer};
+use rsa::RsaPrivateKey;
+use serde_json::{json, Value};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    sync::mpsc::Sender,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Key-pair JWTs are valid for at most an hour; a new one is made before that
+const JWT_LIFETIME: Duration = Duration::from_secs(55 * 60);
+
+/// Longest wait between polls of a running statement
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How requests are authenticated
+enum Auth {
+    /// JWT signed with the user's registered key, cached until it expires
+    KeyPair { key: Box<RsaPrivateKey>, fingerprint: String, token: RefCell<Option<(String, Instant)>> },
+    OAuth(String),
+}
+
+/// Column of a result set as described by `resultSetMetaData.rowType`
+struct Column {
+    name: String,
+    /// Snowflake type in lowercase, e.g. `fixed`, `timestamp_ntz`
+    kind: String,
+    scale: u32,
+}
+
+/// Result set being read partition by partition
+struct OpenResult {
+    handle: String,
+    columns: Vec<Column>,
+    masks: Vec<Option<ColumnMask>>,
+    rows: VecDeque<Vec<Value>>,
+    next_partition: usize,
+    partitions: usize,
+}
+
+/// Runs requests through Snowflake's SQL REST API, without an ODBC driver.
+/// Each statement is its own request, so the session's role, warehouse,
+/// database and schema are kept here and sent with every statement; USE
+/// statements update them.
+pub struct RestExecutor {
+    agent: ureq::Agent,
+    base_url: String,
+    account: String,
+    user: String,
+    auth: Auth,
+    context: SessionContext,
+    store_options: TileStoreOptions,
+    /// Rows fetched per result before stopping (0 fetches everything)
+    max_rows: usize,
+    masker: Masker,
+}
+
+impl RestExecutor {
+    pub fn new(config: &RestConfig, store_options: TileStoreOptions, max_rows: usize, masker: Masker) -> anyhow::Result<Self> {
+        if config.account.is_empty() || config.user.is_empty() {
+            anyhow::bail!("backend = \"rest\" needs account and user in the [rest] section");
+        }
+        let auth = match (&config.private_key_path, &config.oauth_token) {
+            (Some(path), _) => {
+                let pem = std::fs::read_to_string(path)
+                    .map_err(|e| anyhow::anyhow!("Reading private key {}: {}", path.display(), e))?;
+                let key = RsaPrivateKey::from_pkcs8_pem(&pem)
+                    .or_else(|_| RsaPrivateKey::from_pkcs1_pem(&pem))
+                    .map_err(|e| anyhow::anyhow!("Private key {} is not an unencrypted RSA PEM key: {}", path.display(), e))?;
+                let public = key.to_public_key().to_public_key_der()
+                    .map_err(|e| anyhow::anyhow!("Deriving the public key: {}", e))?;
+                let fingerprint = format!("SHA256:{}", STANDARD.encode(Sha256::digest(public.as_bytes())));
+                Auth::KeyPair { key: Box::new(key), fingerprint, token: RefCell::new(None) }
+            }
+            (None, Some(token)) => Auth::OAuth(crate::secrets::interpolate_env(token)?),
+            (None, None) => anyhow::bail!("backend = \"rest\" needs private_key_path or oauth_token in the [rest] section"),
+        };
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(30))
+            .timeout_read(Duration::from_secs(120))
+            .build();
+        Ok(Self {
+            agent,
+            base_url: format!("https://{}.snowflakecomputing.com", config.account.to_lowercase()),
+            account: config.account.clone(),
+            user: config.user.clone(),
+            auth,
+            context: SessionContext {
+                role: config.role.clone(),
+                warehouse: config.warehouse.clone(),
+                database: config.database.clone(),
+                schema: config.schema.clone(),
+                ..Default::default()
+            },
+            store_options,
+            max_rows,
+            masker,
+        })
+    }
+
+    /// Bearer token and its type for the authorization headers
+    fn token(&self) -> Result<(String, &'static str), String> {
+        match &self.auth {
+            Auth::OAuth(token) => Ok((token.clone(), "OAUTH")),
+            Auth::KeyPair { key, fingerprint, token } => {
+                if let Some((jwt, made)) = &*token.borrow() {
+                    if made.elapsed() < JWT_LIFETIME {
+                        return Ok((jwt.clone(), "KEYPAIR_JWT"));
+                    }
+                }
+                // The account locator without region, and the user, in uppercase
+                let account = self.account.split('.').next().unwrap_or_default().to_uppercase();
+                let qualified_user = format!("{}.{}", account, self.user.to_uppercase());
+                let now = Utc::now().timestamp();
+                let header = URL_SAFE_NO_PAD.encode(json!({ "alg": "RS256", "typ": "JWT" }).to_string());
+                let claims = json!({
+                    "iss": format!("{}.{}", qualified_user, fingerprint),
+                    "sub": qualified_user,
+                    "iat": now,
+                    "exp": now + 3600,
+                });
+                let payload = format!("{}.{}", header, URL_SAFE_NO_PAD.encode(claims.to_string()));
+                let signature = SigningKey::<Sha256>::new(RsaPrivateKey::clone(key))
+                    .try_sign(payload.as_bytes())
+                    .map_err(|e| format!("Signing the JWT failed: {}", e))?;
+                let jwt = format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature.to_bytes()));
+                *token.borrow_mut() = Some((jwt.clone(), Instant::now()));
+                Ok((jwt, "KEYPAIR_JWT"))
+            }
+        }
+    }
+
+    /// Send a request; Ok with the status and body for 2xx, Err with
+    /// Snowflake's message otherwise
+    fn call(&self, method: &str, path: &str, body: Option<Value>) -> Result<(u16, Value), String> {
+        let (token, token_type) = self.token()?;
+        let request = self.agent.request(method, &format!("{}{}", self.base_url, path))
+            .set("Authorization", &format!("Bearer {}", token))
+            .set("X-Snowflake-Authorization-Token-Type", token_type)
+            .set("Accept", "application/json")
+            .set("User-Agent", concat!("frost/", env!("CARGO_PKG_VERSION")));
+        let response = match body {
+            Some(body) => request.send_json(body),
+            None => request.call(),
+        };
+        match response {
+            Ok(response) => {
+                let status = response.status();
+                let body = response.into_json().map_err(|e| format!("Reading the response failed: {}", e))?;
+                Ok((status, body))
+            }
+            Err(ureq::Error::Status(status, response)) => {
+                let body: Value = response.into_json().unwrap_or(Value::Null);
+                Err(match body["message"].as_str() {
+                    Some(message) => message.to_string(),
+                    None => format!("Request failed with HTTP {}", status),
+                })
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Submit `sql` on the session context (and `warehouse` instead of the
+    /// session's, if given) and wait for it; a cancel through `cancel`
+    /// stops it on the server too. Returns the finished statement's body.
+    fn run(&self, sql: &str, warehouse: Option<&str>, timeout: Option<Duration>, cancel: Option<&CancelHandle>) -> Result<Value, String> {
+        let mut body = json!({ "statement": sql });
+        let context = [
+            ("role", self.context.role.as_deref()),
+            ("warehouse", warehouse.or(self.context.warehouse.as_deref())),
+            ("database", self.context.database.as_deref()),
+            ("schema", self.context.schema.as_deref()),
+        ];
+        for (key, value) in context {
+            if let Some(value) = value {
+                body[key] = json!(value);
+            }
+        }
+        if let Some(timeout) = timeout {
+            body["timeout"] = json!(timeout.as_secs());
+        }
+        let (mut status, mut response) = self.call("POST", "/api/v2/statements?async=true", Some(body))?;
+        let mut interval = Duration::from_millis(50);
+        while status == 202 {
+            let handle = response["statementHandle"].as_str().unwrap_or_default().to_string();
+            let started = Instant::now();
+            while started.elapsed() < interval {
+                if cancel.is_some_and(|c| c.is_requested()) {
+                    let _ = self.call("POST", &format!("/api/v2/statements/{}/cancel", handle), Some(json!({})));
+                    return Err("Query cancelled".to_string());
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+            interval = (interval * 2).min(MAX_POLL_INTERVAL);
+            (status, response) = self.call("GET", &format!("/api/v2/statements/{}", handle), None)?;
+        }
+        Ok(response)
+    }
+
+    /// Every row of `sql`, fetching all partitions
+    fn run_to_end(&self, sql: &str) -> Result<(Vec<Column>, Vec<Vec<Value>>), String> {
+        let body = self.run(sql, None, None, None)?;
+        let mut result = self.open(body, Vec::new());
+        let mut rows = Vec::new();
+        while let Some(row) = self.next_raw_row(&mut result)? {
+            rows.push(row);
+        }
+        Ok((result.columns, rows))
+    }
+
+    /// The named columns of every row of `sql`, for the lookups in `catalog`
+    fn query_columns(&self, sql: &str, names: &[&str]) -> Result<Vec<Vec<Option<String>>>, String> {
+        let (columns, rows) = self.run_to_end(sql)?;
+        let positions: Vec<Option<usize>> = names.iter()
+            .map(|name| columns.iter().position(|c| c.name.eq_ignore_ascii_case(name)))
+            .collect();
+        Ok(rows.iter()
+            .map(|row| positions.iter()
+                .map(|pos| pos.and_then(|i| display_value(&columns[i], &row[i], self.context.utc_offset.as_deref())))
+                .collect())
+            .collect())
+    }
+
+    fn open(&self, body: Value, masks: Vec<Option<ColumnMask>>) -> OpenResult {
+        let meta = &body["resultSetMetaData"];
+        let columns = meta["rowType"].as_array().map(|row_type| {
+            row_type.iter()
+                .map(|c| Column {
+                    name: c["name"].as_str().unwrap_or_default().to_string(),
+                    kind: c["type"].as_str().unwrap_or_default().to_lowercase(),
+                    scale: c["scale"].as_u64().unwrap_or(0) as u32,
+                })
+                .collect()
+        }).unwrap_or_default();
+        OpenResult {
+            handle: body["statementHandle"].as_str().unwrap_or_default().to_string(),
+            columns,
+            masks,
+            rows: body["data"].as_array().map(|rows| rows.iter().cloned().map(row_values).collect()).unwrap_or_default(),
+            next_partition: 1,
+            partitions: meta["partitionInfo"].as_array().map_or(1, |p| p.len()),
+        }
+    }
+
+    /// Next row as returned, fetching the next partition when the current
+    /// one is used up
+    fn next_raw_row(&self, result: &mut OpenResult) -> Result<Option<Vec<Value>>, String> {
+        while result.rows.is_empty() && result.next_partition < result.partitions {
+            let path = format!("/api/v2/statements/{}?partition={}", result.handle, result.next_partition);
+            let (_, body) = self.call("GET", &path, None)?;
+            result.rows = body["data"].as_array().map(|rows| rows.iter().cloned().map(row_values).collect()).unwrap_or_default();
+            result.next_partition += 1;
+        }
+        Ok(result.rows.pop_front())
+    }
+
+    /// Next row as stored in the tile store: NULLs as the sentinel, masked
+    /// columns masked. None at the end; a partition that can't be fetched
+    /// is an error, and is fetched again by the next call.
+    fn next_row(&self, result: &mut OpenResult) -> Result<Option<Vec<String>>, String> {
+        let Some(row) = self.next_raw_row(result)? else { return Ok(None) };
+        let utc_offset = self.context.utc_offset.as_deref();
+        Ok(Some(result.columns.iter().zip(&row).zip(&result.masks)
+            .map(|((column, value), mask)| {
+                let value = display_value(column, value, utc_offset).unwrap_or_else(|| NULL_SENTINEL.to_string());
+                match mask {
+                    Some(mask) => mask.apply(&value),
+                    None => value,
+                }
+            })
+            .collect()))
+    }
+
+    /// Run statement `idx` of a batch and report its start and outcome.
+    /// Returns the result cut off at the row limit, and whether the
+    /// statement failed.
+    fn run_statement(&mut self, idx: usize, request: QueryRequest, resp_tx: &Sender<DbWorkerResponse>, cancel: &CancelHandle) -> (Option<OpenResult>, bool) {
+        let started = Instant::now();
+        let _ = resp_tx.send(DbWorkerResponse::QueryStarted { query_idx: idx, started, request: request.clone() });
+        let QueryRequest { sql, warehouse, explain, timeout, .. } = request;
+        let _watchdog = timeout.map(|t| cancel.watchdog(t));
+
+        let body = match self.run(&sql, warehouse.as_deref(), timeout, Some(cancel)) {
+            Ok(body) => body,
+            Err(_) if cancel.is_requested() => {
+                let _ = resp_tx.send(cancel.stopped_response(idx, started.elapsed(), timeout));
+                return (None, true);
+            }
+            Err(message) => {
+                let _ = resp_tx.send(DbWorkerResponse::QueryError { query_idx: idx, elapsed: started.elapsed(), query_id: None, message });
+                return (None, true);
+            }
+        };
+        let query_id = body["statementHandle"].as_str().map(|s| s.to_string());
+        apply_use(&mut self.context, &sql);
+
+        if let Some(format) = explain {
+            // The plan comes back as lines of a single text column
+            let mut result = self.open(body, Vec::new());
+            let mut output = String::new();
+            while let Ok(Some(row)) = self.next_raw_row(&mut result) {
+                if let Some(line) = row.first().and_then(|v| v.as_str()) {
+                    output.push_str(line);
+                    output.push('\n');
+                }
+            }
+            let _ = match PlanView::parse(format, &output) {
+                Ok(plan) => resp_tx.send(DbWorkerResponse::QueryFinished {
+                    query_idx: idx,
+                    elapsed: started.elapsed(),
+                    query_id,
+                    result: ResultsContent::Plan(plan),
+                    truncated: false,
+                }),
+                Err(message) => resp_tx.send(DbWorkerResponse::QueryError { query_idx: idx, elapsed: started.elapsed(), query_id, message }),
+            };
+            return (None, false);
+        }
+
+        // DML reports its row counts as stats rather than as a result to show
+        if let Some(stats) = body["stats"].as_object() {
+            let affected: u64 = stats.iter()
+                .filter(|(key, _)| key.starts_with("numRows"))
+                .filter_map(|(_, n)| n.as_u64())
+                .sum();
+            let _ = resp_tx.send(DbWorkerResponse::QueryFinished {
+                query_idx: idx,
+                elapsed: started.elapsed(),
+                query_id,
+                result: ResultsContent::info(
+                    format!("Statement affected {} row{}", affected, if affected == 1 { "" } else { "s" }),
+                    Some(affected),
+                ),
+                truncated: false,
+            });
+            return (None, false);
+        }
+
+        let mut result = self.open(body, Vec::new());
+        let headers: Vec<String> = result.columns.iter().map(|c| c.name.clone()).collect();
+        let kinds: Vec<ColumnKind> = result.columns.iter().map(|c| column_kind(&c.kind)).collect();
+        result.masks = self.masker.for_columns(&headers);
+        let row_limit = if self.max_rows == 0 { usize::MAX } else { self.max_rows };
+        let mut fetch_error = None;
+        let tile_store = TileRowStore::from_rows(
+            &headers,
+            &kinds,
+            std::iter::from_fn(|| {
+                if cancel.is_requested() {
+                    return None;
+                }
+                self.next_row(&mut result).unwrap_or_else(|e| {
+                    fetch_error = Some(e);
+                    None
+                })
+            })
+            .take(row_limit),
+            self.store_options,
+        );
+        if let Some(e) = fetch_error {
+            let _ = resp_tx.send(DbWorkerResponse::QueryError {
+                query_idx: idx,
+                elapsed: started.elapsed(),
+                query_id,
+                message: format!("Fetching the result failed: {}", e),
+            });
+            return (None, true);
+        }
+        let tile_store = match tile_store {
+            Ok(store) => store,
+            Err(e) => {
+                let _ = resp_tx.send(DbWorkerResponse::QueryError {
+                    query_idx: idx,
+                    elapsed: started.elapsed(),
+                    query_id,
+                    message: format!("Failed to create tile store: {:?}", e),
+                });
+                return (None, true);
+            }
+        };
+
+        // Rows fetched before the cancel are discarded
+        if cancel.is_requested() {
+            let _ = resp_tx.send(cancel.stopped_response(idx, started.elapsed(), timeout));
+            return (None, true);
+        }
+        // Anything left past the limit can be fetched with FetchMore
+        let truncated = tile_store.nrows == row_limit && (!result.rows.is_empty() || result.next_partition < result.partitions);
+        let _ = resp_tx.send(DbWorkerResponse::QueryFinished {
+            query_idx: idx,
+            elapsed: started.elapsed(),
+            query_id,
+            result: ResultsContent::table(headers, tile_store, kinds),
+            truncated,
+        });
+        (truncated.then_some(result), false)
+    }
+}
+
+impl QueryExecutor for RestExecutor {
+    fn account(&self) -> Option<(String, String)> {
+        Some((format!("{}.snowflakecomputing.com", self.account), self.user.clone()))
+    }
+
+    fn serve(mut self: Box<Self>, requests: WorkerRequests, resp_tx: Sender<DbWorkerResponse>, cancel: CancelHandle) {
+        // Fill in what the config leaves to the user's defaults; this is
+        // also the check that the account can be reached
+        match self.query_columns("SELECT CURRENT_CLIENT() AS client", &["client"]) {
+            Ok(_) => {
+                let _ = resp_tx.send(DbWorkerResponse::Connected);
+                let current = catalog::session_context(&|sql, names| self.query_columns(sql, names), Dialect::Snowflake);
+                let context = &mut self.context;
+                context.role = context.role.take().or(current.role);
+                context.warehouse = context.warehouse.take().or(current.warehouse);
+                context.database = context.database.take().or(current.database);
+                context.schema = context.schema.take().or(current.schema);
+                context.utc_offset = current.utc_offset;
+                context.client = current.client;
+                let _ = resp_tx.send(DbWorkerResponse::SessionContext(self.context.clone()));
+            }
+            Err(message) => {
+                let _ = resp_tx.send(DbWorkerResponse::ConnectionFailed { message });
+                // Keep thread alive but not connected
+                loop {
+                    match requests.recv(None) {
+                        Ok(DbWorkerRequest::Quit) | Err(_) => break,
+                        _ => continue,
+                    }
+                }
+                return;
+            }
+        }
+
+        let mut open_result: Option<OpenResult> = None;
+        loop {
+            match requests.recv(None) {
+                // Each request is a session of its own, so the parallel
+                // pool has nothing to add; statements run one by one
+                Ok(DbWorkerRequest::RunQueries(queries)) | Ok(DbWorkerRequest::RunParallel { queries, .. }) => {
+                    cancel.reset();
+                    open_result = None;
+                    let mut failed = false;
+                    for (idx, request) in queries.into_iter().enumerate() {
+                        // A cancel or a failed statement also drops the rest of the batch
+                        if cancel.is_requested() || cancel.is_queue_dropped() || failed {
+                            break;
+                        }
+                        let (result, statement_failed) = self.run_statement(idx, request, &resp_tx, &cancel);
+                        open_result = result;
+                        failed = statement_failed;
+                    }
+                    let _ = resp_tx.send(DbWorkerResponse::SessionContext(self.context.clone()));
+                }
+                Ok(DbWorkerRequest::FetchTableStats { database, schema }) => {
+                    let stats = catalog::table_stats(&|sql, names| self.query_columns(sql, names), &database, &schema);
+                    let _ = resp_tx.send(DbWorkerResponse::TableStats { database, schema, stats });
+                }
+                Ok(DbWorkerRequest::ListContext(kind)) => {
+                    let names = catalog::show_names(&|sql, names| self.query_columns(sql, names), kind.show_sql());
+                    let _ = resp_tx.send(DbWorkerResponse::ContextOptions { kind, names });
+                }
+                Ok(DbWorkerRequest::UseContext { kind, name }) => {
+                    // Checked on the server, then remembered for later statements
+                    match self.run(&kind.use_sql(&name), None, None, None) {
+                        Ok(_) => {
+                            let current = match kind {
+                                ContextKind::Role => &mut self.context.role,
+                                ContextKind::Warehouse => &mut self.context.warehouse,
+                                ContextKind::Database => &mut self.context.database,
+                            };
+                            *current = Some(name);
+                            if kind == ContextKind::Database {
+                                self.context.schema = Some("PUBLIC".to_string());
+                            }
+                        }
+                        Err(e) => {
+                            let _ = resp_tx.send(DbWorkerResponse::ContextSwitchFailed {
+                                message: format!("Failed to use {}: {}", name, e),
+                            });
+                        }
+                    }
+                    let _ = resp_tx.send(DbWorkerResponse::SessionContext(self.context.clone()));
+                }
+                Ok(DbWorkerRequest::EstimateCosts(statements)) => {
+                    for statement in statements {
+                        let explain = format!("EXPLAIN USING JSON {}", statement.trim().trim_end_matches(';'));
+                        let plan = self.run_to_end(&explain).ok().map(|(_, rows)| {
+                            rows.iter().filter_map(|row| row.first()?.as_str().map(|s| s.to_string())).collect::<String>()
+                        });
+                        let bytes = plan.and_then(|plan| crate::cost::bytes_assigned(&plan));
+                        let _ = resp_tx.send(DbWorkerResponse::CostEstimate { statement, bytes });
+                    }
+                }
+                Ok(DbWorkerRequest::FetchMore(count)) => {
+                    cancel.reset();
+                    let Some(mut result) = open_result.take() else {
+                        let _ = resp_tx.send(DbWorkerResponse::MoreRows { rows: Vec::new(), more: false, error: None });
+                        continue;
+                    };
+                    let mut rows = Vec::new();
+                    let mut error = None;
+                    while rows.len() < count && !cancel.is_requested() {
+                        match self.next_row(&mut result) {
+                            Ok(Some(row)) => rows.push(row),
+                            Ok(None) => break,
+                            Err(e) => {
+                                error = Some(e);
+                                break;
+                            }
+                        }
+                    }
+                    // A cancel keeps the rows fetched so far but drops the rest
+                    let more = !cancel.is_requested() && (!result.rows.is_empty() || result.next_partition < result.partitions);
+                    if more {
+                        open_result = Some(result);
+                    }
+                    let _ = resp_tx.send(DbWorkerResponse::MoreRows { rows, more, error });
+                }
+                Ok(DbWorkerRequest::ListStages) => {
+                    let _ = resp_tx.send(DbWorkerResponse::Stages(catalog::stages(&|sql, names| self.query_columns(sql, names))));
+                }
+                Ok(DbWorkerRequest::ListStageFiles(stage)) => {
+                    let files = catalog::stage_files(&|sql, names| self.query_columns(sql, names), &stage);
+                    let _ = resp_tx.send(DbWorkerResponse::StageFiles { stage, files });
+                }
+                Ok(DbWorkerRequest::StageTransfer { .. }) => {
+                    let result = Err("PUT and GET are not supported by the SQL API; use backend = \"odbc\"".to_string());
+                    let _ = resp_tx.send(DbWorkerResponse::StageTransferDone { result });
+                }
+                Ok(DbWorkerRequest::FetchDdl(name)) => {
+                    let ddl = catalog::ddl(&|sql, names| self.query_columns(sql, names), &name);
+                    let _ = resp_tx.send(DbWorkerResponse::Ddl { name, ddl });
+                }
+                Ok(DbWorkerRequest::ListMetadataDatabases) => {
+                    let names = catalog::show_names(&|sql, names| self.query_columns(sql, names), "SHOW DATABASES");
+                    let _ = resp_tx.send(DbWorkerResponse::MetadataDatabases(names));
+                }
+                Ok(DbWorkerRequest::FetchMetadata(database)) => {
+                    let columns = catalog::columns(&|sql, names| self.query_columns(sql, names), &database);
+                    let _ = resp_tx.send(DbWorkerResponse::Metadata { database, columns });
+                }
+                Ok(DbWorkerRequest::Quit) | Err(_) => break,
+            }
+        }
+    }
+}
+
+fn row_values(row: Value) -> Vec<Value> {
+    match row {
+        Value::Array(values) => values,
+        _ => Vec::new(),
+    }
+}
+
+/// How JSON export writes a column of this Snowflake type
+fn column_kind(kind: &str) -> ColumnKind {
+    match kind {
+        "fixed" | "real" => ColumnKind::Number,
+        "boolean" => ColumnKind::Boolean,
+        _ => ColumnKind::Text,
+    }
+}
+
+/// A value as the ODBC driver would show it. The SQL API sends dates,
+/// times and timestamps as numbers since the epoch (timestamps with time
+/// zone followed by the offset in minutes plus 1440); LTZ timestamps are
+/// shown in the session's time zone. None for NULL.
+fn display_value(column: &Column, value: &Value, utc_offset: Option<&str>) -> Option<String> {
+    let raw = match value {
+        Value::Null => return None,
+        Value::String(s) => s.as_str(),
+        other => return Some(other.to_string()),
+    };
+    let epoch = |s: &str| -> Option<DateTime<Utc>> {
+        let (secs, frac) = s.split_once('.').unwrap_or((s, "0"));
+        let nanos = format!("{:0<9}", frac).get(..9)?.parse().ok()?;
+        Utc.timestamp_opt(secs.parse().ok()?, nanos).single()
+    };
+    let fraction = if column.scale > 0 { "%.f" } else { "" };
+    let formatted = match column.kind.as_str() {
+        "date" => raw.parse::<i64>().ok()
+            .and_then(|days| NaiveDate::from_ymd_opt(1970, 1, 1)?.checked_add_signed(chrono::Duration::days(days)))
+            .map(|date| date.to_string()),
+        "time" => epoch(raw).map(|t| {
+            let time = NaiveTime::from_num_seconds_from_midnight_opt(t.timestamp() as u32, t.timestamp_subsec_nanos());
+            time.map(|t| t.format(&format!("%H:%M:%S{}", fraction)).to_string()).unwrap_or_default()
+        }),
+        "timestamp_ntz" => epoch(raw).map(|t| t.naive_utc().format(&format!("%Y-%m-%d %H:%M:%S{}", fraction)).to_string()),
+        "timestamp_ltz" => epoch(raw).map(|t| {
+            let offset = utc_offset.and_then(parse_offset).unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+            t.with_timezone(&offset).format(&format!("%Y-%m-%d %H:%M:%S{} %:z", fraction)).to_string()
+        }),
+        "timestamp_tz" => raw.split_once(' ').and_then(|(stamp, minutes)| {
+            let offset = FixedOffset::east_opt((minutes.parse::<i32>().ok()? - 1440) * 60)?;
+            Some(epoch(stamp)?.with_timezone(&offset).format(&format!("%Y-%m-%d %H:%M:%S{} %:z", fraction)).to_string())
+        }),
+        _ => None,
+    };
+    Some(formatted.unwrap_or_else(|| raw.to_string()))
+}
+
+/// `+02:00` as an offset
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let (hours, minutes) = offset.trim_start_matches(['+', '-']).split_once(':')?;
+    FixedOffset::east_opt(sign * (hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60))
+}
+
+/// Track a `USE ROLE/WAREHOUSE/DATABASE/SCHEMA` statement that succeeded,
+/// since the next request would not remember it
+fn apply_use(context: &mut SessionContext, sql: &str) {
+    let words: Vec<&str> = sql.trim().trim_end_matches(';').split_whitespace().collect();
+    if words.first().is_none_or(|w| !w.eq_ignore_ascii_case("use")) {
+        return;
+    }
+    let unquote = |name: &str| match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(quoted) => quoted.replace("\"\"", "\""),
+        None => name.to_uppercase(),
+    };
+    let (kind, name) = match words.as_slice() {
+        [_, kind, name] => (kind.to_lowercase(), *name),
+        [_, name] => ("database".to_string(), *name),
+        _ => return,
+    };
+    match kind.as_str() {
+        "role" => context.role = Some(unquote(name)),
+        "warehouse" => context.warehouse = Some(unquote(name)),
+        "database" | "schema" => {
+            // `USE db.schema`, `USE SCHEMA db.schema` or `USE SCHEMA schema`
+            let parts: Vec<String> = name.splitn(2, '.').map(unquote).collect();
+            match (kind.as_str(), parts.as_slice()) {
+                (_, [database, schema]) => {
+                    context.database = Some(database.clone());
+                    context.schema = Some(schema.clone());
+                }
+                ("schema", [schema]) => context.schema = Some(schema.clone()),
+                (_, [database]) => {
+                    context.database = Some(database.clone());
+                    context.schema = Some("PUBLIC".to_string());
+                }
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+}
+
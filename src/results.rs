@@ -1,19 +1,74 @@
-use crate::tile_rowstore::TileRowStore;
-use crossterm::event::{KeyCode, KeyEvent};
+use crate::chart::{ChartView, MAX_CHART_ROWS};
+use crate::colstats::{ColumnStats, SelectionStats};
+use crate::config::IdentifierQuoting;
+use crate::connection::QueryRequest;
+use crate::distribution::Distribution;
+use crate::export::{ColumnKind, ExportFormat, ExportPrompt, RowWriter};
+use crate::export_job::{ExportJob, TileSource};
+use crate::identifier::quote_identifier;
+use crate::json_view::JsonView;
+use crate::message_text::MessageText;
+use crate::parquet_export::{ParquetWriter, ValueType};
+use crate::plan::PlanView;
+use crate::replace::{collect_matches, ReplaceField, ReplaceForm, ReplacePreview};
+use crate::row_detail::RowDetail;
+use crate::row_view::{RowView, ViewStep};
+use crate::saved_result::{self, ResultFilePrompt, SavedMeta};
+use crate::source_sql::SourceView;
+use crate::tab_compare::{CompareForm, CompareJob, CompareSide};
+use crate::table_search::TableSearch;
+use crate::tile_rowstore::{TileRowStore, TileStoreOptions, NULL_SENTINEL, TILE_SIZE};
+use crate::xlsx_export::XlsxWriter;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{
     Frame,
     layout::Rect,
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
 };
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
 use std::time::{Duration, Instant};
+use unicode_width::UnicodeWidthStr;
+
+/// Widest a column is allowed to grow when auto-sizing
+const MAX_COL_WIDTH: usize = 40;
+
+/// Rows from the top of a table its columns are auto-sized to
+const WIDTH_SAMPLE_ROWS: usize = 200;
+
+/// Widest a column can be made by hand or fitted to its content
+const MAX_FIT_WIDTH: usize = 400;
+
+/// Digits typed within this long of each other make one row number
+const ROW_JUMP_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Most closed tabs kept for reopening, regardless of grace period
+const MAX_CLOSED_TABS: usize = 10;
+
+/// Id of the next tab opened
+static NEXT_TAB_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Selected (rows, display columns), both inclusive
+type SelectionBounds = ((usize, usize), (usize, usize));
 
 #[derive(Debug)]
 pub enum ResultsContent {
     Table {
         headers: Vec<String>,
         tile_store: TileRowStore,
+        /// Per header, from the result set's column metadata
+        kinds: Vec<ColumnKind>,
+        /// Sort, filter or dedup over the stored rows; the grid, copies and
+        /// exports all read through it
+        row_view: RowView,
     },
+    /// `cursor` and `selection` are byte offsets into the message, moved
+    /// with the arrows to copy part of it
     Error {
         message: String,
         cursor: usize,
@@ -21,10 +76,41 @@ pub enum ResultsContent {
     },
     Info {
         message: String,
+        /// Rows changed by DML; None when the driver doesn't report it
+        rows_affected: Option<u64>,
+        cursor: usize,
+        selection: Option<(usize, usize)>,
+    },
+    /// Output of `EXPLAIN`, shown as a collapsible operator tree
+    Plan(PlanView),
+    /// Query stopped by the user; partial rows are not kept
+    Cancelled {
+        elapsed: Duration,
     },
     Pending,
 }
 
+impl ResultsContent {
+    /// A table showing every row in fetch order
+    pub fn table(headers: Vec<String>, tile_store: TileRowStore, kinds: Vec<ColumnKind>) -> Self {
+        ResultsContent::Table { headers, tile_store, kinds, row_view: RowView::default() }
+    }
+
+    /// A message with no caret in it yet
+    pub fn info(message: String, rows_affected: Option<u64>) -> Self {
+        ResultsContent::Info { message, rows_affected, cursor: 0, selection: None }
+    }
+
+    /// The message of an error or info tab, with its caret and selection
+    pub fn message_text(&mut self) -> Option<MessageText<'_>> {
+        match self {
+            ResultsContent::Error { message, cursor, selection }
+            | ResultsContent::Info { message, cursor, selection, .. } => Some(MessageText { text: message, cursor, selection }),
+            _ => None,
+        }
+    }
+}
+
 pub struct ResultsTab {
     pub content: ResultsContent,
     pub cursor_row: usize,
@@ -32,9 +118,57 @@ pub struct ResultsTab {
     pub view_row: usize,
     pub view_col: usize,
     pub running: bool,
+    /// Cancel requested, waiting for the worker to stop
+    pub cancelling: bool,
+    /// Unique for the session; statement results find their tab by it
+    pub id: u64,
     pub elapsed: Option<Duration>,
     pub run_started: Option<Instant>,
     pub query_context: String,
+    /// The statement as sent to the worker, for running it again
+    pub request: Option<QueryRequest>,
+    /// Role · warehouse · database the query ran with
+    pub session_context: Option<String>,
+    /// Columns compared in diff mode (baseline, compared)
+    pub diff_cols: Option<(usize, usize)>,
+    /// Baseline column picked, waiting for the compared column
+    pub diff_pending: Option<usize>,
+    /// Column identifying rows in generated UPDATE statements
+    pub key_col: Option<usize>,
+    /// Snowflake query ID, for finding the query in Snowsight history
+    pub query_id: Option<String>,
+    /// The fetch stopped at `max_rows`; more rows exist
+    pub truncated: bool,
+    /// The worker still holds the cursor, so the fetch can be resumed
+    pub resumable: bool,
+    /// Waiting behind earlier statements of the batch
+    pub queued: bool,
+    /// Index of the statement in the batch that filled this tab
+    pub batch_idx: Option<usize>,
+    /// Kept by close-all
+    pub pinned: bool,
+    /// Shown columns in display order (indices into headers); empty shows all
+    col_order: Vec<usize>,
+    /// Leading display columns kept on screen during horizontal scrolling
+    pinned_cols: usize,
+    /// Other corner of the block selection (row, display column)
+    selection_anchor: Option<(usize, usize)>,
+    /// Aggregates of the selection, with the bounds they were computed for
+    selection_stats: Option<(SelectionBounds, SelectionStats)>,
+    /// Active `/` search; n/N step through its matches
+    search: Option<TableSearch>,
+    /// Auto widths by column (indices into headers) and the number of rows
+    /// they were sampled from
+    auto_widths: (Vec<usize>, usize),
+    /// Widths set with - / + or fitted to content, by column
+    width_overrides: HashMap<usize, usize>,
+    /// Column being fitted to its content on a background thread
+    width_fit: Option<(usize, Receiver<std::io::Result<usize>>)>,
+    /// Sort, filter or dedup being built on a background thread
+    view_build: Option<Receiver<std::io::Result<RowView>>>,
+    /// Show the cursor row as a column name / value line per column, with
+    /// `view_col` the first one shown
+    transposed: bool,
 }
 
 impl ResultsTab {
@@ -46,20 +180,1085 @@ impl ResultsTab {
         Self {
             content: ResultsContent::Pending,
             cursor_row: 0,
-            cursor_col: 1,
+            cursor_col: 0,
             view_row: 0,
             view_col: 0,
             running: true,
+            cancelling: false,
+            id: NEXT_TAB_ID.fetch_add(1, Ordering::Relaxed),
             elapsed: None,
             run_started: Some(started),
             query_context,
+            request: None,
+            session_context: None,
+            diff_cols: None,
+            diff_pending: None,
+            key_col: None,
+            query_id: None,
+            truncated: false,
+            resumable: false,
+            queued: false,
+            batch_idx: None,
+            pinned: false,
+            col_order: Vec::new(),
+            pinned_cols: 0,
+            selection_anchor: None,
+            selection_stats: None,
+            search: None,
+            auto_widths: (Vec::new(), 0),
+            width_overrides: HashMap::new(),
+            width_fit: None,
+            view_build: None,
+            transposed: false,
+        }
+    }
+
+    /// Tab for statement `batch_idx` of a batch, which has not started yet
+    pub fn new_queued(query_context: String, batch_idx: usize) -> Self {
+        let mut tab = Self::new_pending(query_context);
+        tab.running = false;
+        tab.run_started = None;
+        tab.queued = true;
+        tab.batch_idx = Some(batch_idx);
+        tab
+    }
+
+    /// `1,204 rows x 12 columns | 3.4s | Query ID: ...` summary shown under
+    /// finished results; None while the query is still pending
+    pub fn footer(&self) -> Option<String> {
+        let plural = |n: usize, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+        let summary = match &self.content {
+            ResultsContent::Pending => return None,
+            ResultsContent::Table { headers, tile_store, row_view, .. } => {
+                let shown = self.view_columns().len();
+                let columns = if shown < headers.len() {
+                    format!("{} of {} columns", shown, headers.len())
+                } else {
+                    plural(headers.len(), "column")
+                };
+                let mut rows = match (self.truncated, self.resumable) {
+                    (true, true) => format!("{} (truncated, M fetches more)", plural(tile_store.nrows, "row")),
+                    (true, false) => format!("{} (truncated)", plural(tile_store.nrows, "row")),
+                    _ => plural(tile_store.nrows, "row"),
+                };
+                if let Some(steps) = row_view.describe() {
+                    rows = format!("{} of {}, {}", row_view.len(tile_store), rows, steps);
+                }
+                format!("{} x {}", rows, columns)
+            }
+            ResultsContent::Info { rows_affected: Some(n), .. } => format!("{} affected", plural(*n as usize, "row")),
+            // Statements dropped from the queue never started
+            ResultsContent::Info { .. } if self.run_started.is_none() => "Not run".to_string(),
+            ResultsContent::Info { .. } => "Done".to_string(),
+            ResultsContent::Plan(_) => "Plan".to_string(),
+            ResultsContent::Error { .. } => "Failed".to_string(),
+            ResultsContent::Cancelled { .. } => "Cancelled, rows discarded".to_string(),
+        };
+        let elapsed = match &self.content {
+            ResultsContent::Cancelled { elapsed } => Some(*elapsed),
+            _ => self.elapsed,
+        };
+        let mut parts = vec![summary];
+        if self.selection_anchor.is_some() {
+            parts.extend(self.selection_stats.as_ref().map(|(_, stats)| format!("selection: {}", stats.summary())));
+        }
+        parts.extend(elapsed.map(|e| format!("{:.1}s", e.as_secs_f32())));
+        parts.extend(self.query_id.as_ref().map(|id| format!("Query ID: {}", id)));
+        Some(format!(" {}", parts.join(" | ")))
+    }
+
+    /// Add rows fetched after the result was first stored. A sort or filter
+    /// doesn't cover them, so the view goes back to every row; true if one
+    /// was dropped.
+    pub fn append_rows(&mut self, rows: Vec<Vec<String>>) -> Result<bool, String> {
+        let ResultsContent::Table { tile_store, row_view, .. } = &mut self.content else {
+            return Err("not a table".to_string());
+        };
+        let dropped = !row_view.is_all() || self.view_build.is_some();
+        tile_store.append_rows(rows).map_err(|e| e.to_string())?;
+        if dropped {
+            self.set_row_view(Ok(RowView::default()));
+        }
+        Ok(dropped)
+    }
+
+    /// (rows, cols) of the table, or (0, 0) for non-table content
+    pub fn dims(&self) -> (usize, usize) {
+        match &self.content {
+            ResultsContent::Table { tile_store, row_view, .. } => (row_view.len(tile_store), tile_store.ncols),
+            _ => (0, 0),
+        }
+    }
+
+    /// Columns shown in the grid, in display order. `cursor_col` and
+    /// `view_col` are positions in this list.
+    pub fn view_columns(&self) -> Vec<usize> {
+        if self.col_order.is_empty() {
+            (0..self.dims().1).collect()
+        } else {
+            self.col_order.clone()
+        }
+    }
+
+    /// Underlying column under the cursor
+    pub fn cursor_column(&self) -> usize {
+        self.view_columns().get(self.cursor_col).copied().unwrap_or(0)
+    }
+
+    fn move_cursor(&mut self, drow: isize, dcol: isize) {
+        let nrows = self.dims().0;
+        let ncols = self.view_columns().len();
+        if nrows == 0 || ncols == 0 {
+            return;
+        }
+        self.cursor_row = (self.cursor_row as isize + drow).clamp(0, nrows as isize - 1) as usize;
+        self.cursor_col = (self.cursor_col as isize + dcol).clamp(0, ncols as isize - 1) as usize;
+    }
+
+    /// Move the cursor half a page and scroll the view by as much, so the
+    /// cursor keeps its place on screen
+    fn half_page(&mut self, page: isize, down: bool) {
+        let rows = (page / 2).max(1);
+        self.scroll_by(if down { rows } else { -rows }, 0);
+    }
+
+    /// Move the cursor and scroll the view by as many rows
+    fn scroll_by(&mut self, drow: isize, dcol: isize) {
+        let before = self.cursor_row;
+        self.move_cursor(drow, dcol);
+        if self.cursor_row >= before {
+            self.view_row += self.cursor_row - before;
+        } else {
+            self.view_row = self.view_row.saturating_sub(before - self.cursor_row);
+        }
+    }
+
+    /// Move the cursor to the edge of the data, as Ctrl+arrows do in a
+    /// spreadsheet: from a filled cell next to another to the last filled
+    /// one before a NULL or empty cell, otherwise to the next filled one (or
+    /// the last row or column)
+    fn jump_to_edge(&mut self, drow: isize, dcol: isize) -> std::io::Result<()> {
+        let cols = self.view_columns();
+        let (row, col) = (self.cursor_row, self.cursor_col);
+        let ResultsContent::Table { tile_store, row_view, .. } = &mut self.content else { return Ok(()) };
+        let filled = |value: Option<&String>| value.is_some_and(|v| !v.is_empty() && v != NULL_SENTINEL);
+        if dcol != 0 {
+            let values = row_view.get_rows(tile_store, row, 1)?.into_iter().next().unwrap_or_default();
+            self.cursor_col = data_edge(col, dcol > 0, cols.len(), |p| Ok(filled(values.get(cols[p]))))?;
+        } else if let Some(&c) = cols.get(col) {
+            // Rows are read a tile at a time rather than one by one
+            let mut chunk: (usize, Vec<Vec<String>>) = (0, Vec::new());
+            let nrows = row_view.len(tile_store);
+            self.cursor_row = data_edge(row, drow > 0, nrows, |r| {
+                if !(chunk.0..chunk.0 + chunk.1.len()).contains(&r) {
+                    let start = r / TILE_SIZE * TILE_SIZE;
+                    chunk = (start, row_view.get_rows(tile_store, start, TILE_SIZE)?);
+                }
+                Ok(filled(chunk.1.get(r - chunk.0).and_then(|values| values.get(c))))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Load the tiles around the cursor row ahead of the next draw, after a
+    /// jump away from the rows on screen
+    fn prefetch_around_cursor(&mut self, page: usize) {
+        let row = self.cursor_row;
+        if let ResultsContent::Table { tile_store, row_view, .. } = &mut self.content {
+            row_view.prefetch(tile_store, row.saturating_sub(page / 2), page.max(1));
+        }
+    }
+
+    /// Move the cursor, growing the block selection from where it started
+    fn extend_selection(&mut self, drow: isize, dcol: isize) {
+        if self.selection_anchor.is_none() {
+            self.selection_anchor = Some((self.cursor_row, self.cursor_col));
+        }
+        self.move_cursor(drow, dcol);
+    }
+
+    /// Selected rows and display columns (inclusive), or the cursor cell
+    fn selection_bounds(&self) -> SelectionBounds {
+        let (arow, acol) = self.selection_anchor.unwrap_or((self.cursor_row, self.cursor_col));
+        (
+            (arow.min(self.cursor_row), arow.max(self.cursor_row)),
+            (acol.min(self.cursor_col), acol.max(self.cursor_col)),
+        )
+    }
+
+    fn hide_column(&mut self) {
+        let mut cols = self.view_columns();
+        if cols.len() <= 1 {
+            return;
+        }
+        cols.remove(self.cursor_col);
+        if self.cursor_col < self.pinned_cols {
+            self.pinned_cols -= 1;
+        }
+        self.col_order = cols;
+        self.cursor_col = self.cursor_col.min(self.col_order.len() - 1);
+        self.view_col = self.view_col.min(self.cursor_col);
+        self.selection_anchor = None;
+    }
+
+    /// Pin the columns up to and including the cursor column, or unpin
+    fn toggle_pinned_columns(&mut self) {
+        self.pinned_cols = if self.pinned_cols > 0 { 0 } else { self.cursor_col + 1 };
+    }
+
+    /// Swap the cursor column with its neighbour; the cursor moves with it
+    fn move_column(&mut self, delta: isize) {
+        let mut cols = self.view_columns();
+        let target = self.cursor_col as isize + delta;
+        if target < 0 || target as usize >= cols.len() {
+            return;
+        }
+        cols.swap(self.cursor_col, target as usize);
+        self.col_order = cols;
+        self.cursor_col = target as usize;
+        self.selection_anchor = None;
+    }
+
+    fn show_all_columns(&mut self) {
+        let current = self.cursor_column();
+        self.col_order.clear();
+        self.cursor_col = current;
+        self.selection_anchor = None;
+    }
+
+    /// Copy the selected block as tab-separated text. By default this is what
+    /// the grid shows: visible columns in display order. With `full_rows`
+    /// the selected rows are copied with every column in query order.
+    fn copy_selection(&mut self, full_rows: bool) -> String {
+        let ((row_start, row_end), (col_start, col_end)) = self.selection_bounds();
+        let cols: Vec<usize> = if full_rows {
+            (0..self.dims().1).collect()
+        } else {
+            self.view_columns()[col_start..=col_end].to_vec()
+        };
+        let ResultsContent::Table { tile_store, row_view, .. } = &mut self.content else {
+            return "Nothing to copy".to_string();
+        };
+        let rows = match row_view.get_rows(tile_store, row_start, row_end - row_start + 1) {
+            Ok(rows) => rows,
+            Err(e) => return format!("Copy failed: {}", e),
+        };
+        let text: Vec<String> = rows.iter()
+            .map(|row| {
+                cols.iter()
+                    .map(|&c| row.get(c).map(|v| if v == NULL_SENTINEL { "" } else { v.as_str() }).unwrap_or(""))
+                    .collect::<Vec<_>>()
+                    .join("\t")
+            })
+            .collect();
+        match crate::clipboard::set_text(text.join("\n")) {
+            Ok(()) => format!(
+                "Copied {} row{} x {} column{}{}",
+                rows.len(),
+                if rows.len() == 1 { "" } else { "s" },
+                cols.len(),
+                if cols.len() == 1 { "" } else { "s" },
+                if full_rows { " (full rows)" } else { "" },
+            ),
+            Err(e) => format!("Copy failed: {}", e),
+        }
+    }
+
+    /// Recompute the selection aggregates if the selection changed since
+    /// they were last computed
+    fn update_selection_stats(&mut self) {
+        if self.selection_anchor.is_none() {
+            self.selection_stats = None;
+            return;
+        }
+        let bounds = self.selection_bounds();
+        if self.selection_stats.as_ref().map_or(false, |(b, _)| *b == bounds) {
+            return;
+        }
+        let ((row_start, row_end), (col_start, col_end)) = bounds;
+        let cols = self.view_columns()[col_start..=col_end].to_vec();
+        let ResultsContent::Table { tile_store, row_view, .. } = &mut self.content else { return };
+        let mut stats = SelectionStats::default();
+        let mut start = row_start;
+        while start <= row_end {
+            let Ok(rows) = row_view.get_rows(tile_store, start, TILE_SIZE.min(row_end + 1 - start)) else { return };
+            if rows.is_empty() {
+                break;
+            }
+            for row in &rows {
+                for &c in &cols {
+                    stats.add(row.get(c).map(|v| v.as_str()).unwrap_or(NULL_SENTINEL));
+                }
+            }
+            start += rows.len();
+        }
+        self.selection_stats = Some((bounds, stats));
+    }
+
+    /// Copy the selection aggregates as a short report for tickets and chat,
+    /// with the query and the time they were taken
+    fn copy_selection_stats(&mut self) -> String {
+        if self.selection_anchor.is_none() {
+            return "Select cells first (Shift+arrows)".to_string();
+        }
+        self.update_selection_stats();
+        let Some((((row_start, row_end), (col_start, col_end)), stats)) = &self.selection_stats else {
+            return "Selection stats failed".to_string();
+        };
+        let ResultsContent::Table { headers, .. } = &self.content else {
+            return "Nothing to copy".to_string();
+        };
+        let columns: Vec<&str> = self.view_columns()[*col_start..=*col_end]
+            .iter()
+            .map(|&c| headers[c].as_str())
+            .collect();
+        let mut report = format!(
+            "Selection stats ({})\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S %:z"),
+        );
+        if let Some(context) = &self.session_context {
+            report.push_str(&format!("Context: {}\n", context));
+        }
+        if !self.query_context.is_empty() {
+            report.push_str(&format!("Query:   {}\n", self.query_context));
+        }
+        if let Some(id) = &self.query_id {
+            report.push_str(&format!("Query ID: {}\n", id));
+        }
+        report.push_str(&format!("Columns: {}\n", columns.join(", ")));
+        report.push_str(&format!("Rows:    {}-{} ({})\n", row_start + 1, row_end + 1, row_end - row_start + 1));
+        for (label, value) in stats.pairs() {
+            report.push_str(&format!("{:<8} {}\n", format!("{}:", label), value));
+        }
+        match crate::clipboard::set_text(report) {
+            Ok(()) => "Copied selection stats".to_string(),
+            Err(e) => format!("Copy failed: {}", e),
+        }
+    }
+
+    /// Copy the distinct non-NULL values of the cursor column (within the
+    /// selected rows, if any) as `col IN (...)` batches joined with OR
+    fn copy_in_list(&mut self, batch: usize, quoting: IdentifierQuoting) -> String {
+        let column = self.cursor_column();
+        let rows = if self.selection_anchor.is_some() {
+            let ((start, end), _) = self.selection_bounds();
+            start..end + 1
+        } else {
+            0..self.dims().0
+        };
+        let ResultsContent::Table { headers, tile_store, kinds, row_view } = &mut self.content else {
+            return "Nothing to copy".to_string();
+        };
+        let kind = kinds.get(column).copied().unwrap_or(ColumnKind::Text);
+        let mut seen = HashSet::new();
+        let mut values = Vec::new();
+        let mut start = rows.start;
+        while start < rows.end {
+            let chunk = match row_view.get_rows(tile_store, start, TILE_SIZE.min(rows.end - start)) {
+                Ok(chunk) if !chunk.is_empty() => chunk,
+                Ok(_) => break,
+                Err(e) => return format!("Copy failed: {}", e),
+            };
+            for value in chunk.iter().filter_map(|row| row.get(column)) {
+                if value != NULL_SENTINEL && seen.insert(value.clone()) {
+                    values.push(value.clone());
+                }
+            }
+            start += chunk.len();
+        }
+        if values.is_empty() {
+            return "No non-NULL values to copy".to_string();
+        }
+        let sql = crate::export::in_list(&quote_identifier(&headers[column], quoting), kind, &values, batch);
+        match crate::clipboard::set_text(sql) {
+            Ok(()) => format!(
+                "Copied {} value{} as IN list ({} batch{})",
+                values.len(),
+                if values.len() == 1 { "" } else { "s" },
+                values.len().div_ceil(batch.max(1)),
+                if values.len() <= batch { "" } else { "es" },
+            ),
+            Err(e) => format!("Copy failed: {}", e),
+        }
+    }
+
+    /// Copy the selection (or the cursor cell) to the clipboard as `format`
+    pub fn copy_as(&mut self, format: &ExportFormat, typed: bool) -> String {
+        let cols = self.view_columns();
+        let ((row_start, row_end), (col_start, col_end)) = self.selection_bounds();
+        let cols = cols[col_start..=col_end].to_vec();
+        let ResultsContent::Table { headers, tile_store, kinds, row_view } = &mut self.content else {
+            return "Nothing to copy".to_string();
+        };
+        let headers: Vec<String> = cols.iter().map(|&c| headers[c].clone()).collect();
+        let kinds: Vec<ColumnKind> = cols.iter()
+            .map(|&c| kinds.get(c).copied().filter(|_| typed).unwrap_or(ColumnKind::Text))
+            .collect();
+        let mut buf = Vec::new();
+        let written = (|| {
+            let mut writer = RowWriter::new(&mut buf, format, &headers)?.with_kinds(&kinds);
+            let mut start = row_start;
+            while start <= row_end {
+                let chunk = row_view.get_rows(tile_store, start, TILE_SIZE.min(row_end + 1 - start))?;
+                if chunk.is_empty() {
+                    break;
+                }
+                let projected: Vec<Vec<String>> = chunk.iter()
+                    .map(|row| cols.iter().map(|&c| row.get(c).cloned().unwrap_or_default()).collect())
+                    .collect();
+                writer.write_rows(&projected)?;
+                start += chunk.len();
+            }
+            writer.finish()
+        })();
+        let copied = written
+            .map_err(|e| e.to_string())
+            .and_then(|n| {
+                crate::clipboard::set_text(String::from_utf8_lossy(&buf).into_owned())
+                    .map(|_| n)
+                    .map_err(|e| e.to_string())
+            });
+        match copied {
+            Ok(n) => format!("Copied {} row{} as {}", n, if n == 1 { "" } else { "s" }, format.name()),
+            Err(e) => format!("Copy failed: {}", e),
         }
     }
+
+    /// The visible columns' headers and kinds (all text unless `typed`),
+    /// and their rows to read on another thread
+    fn export_source(&self, typed: bool) -> Result<(Vec<String>, Vec<ColumnKind>, TileSource), String> {
+        let cols = self.view_columns();
+        let ResultsContent::Table { headers, tile_store, kinds, row_view } = &self.content else {
+            return Err("Nothing to export".to_string());
+        };
+        let headers: Vec<String> = cols.iter().map(|&c| headers[c].clone()).collect();
+        let kinds: Vec<ColumnKind> = cols.iter()
+            .map(|&c| kinds.get(c).copied().filter(|_| typed).unwrap_or(ColumnKind::Text))
+            .collect();
+        let reader = row_view.reader(tile_store).map_err(|e| format!("Export failed: {}", e))?;
+        Ok((headers, kinds, TileSource::new(reader, cols, row_view.len(tile_store))))
+    }
+
+    /// Export every row of the visible columns, in display order, to a
+    /// timestamped file on a worker thread. Without `typed` JSON values are
+    /// all strings and Parquet columns text.
+    pub fn export_job(&self, format: &ExportFormat, typed: bool) -> Result<ExportJob, String> {
+        let (headers, kinds, mut source) = self.export_source(typed)?;
+        let path = crate::export::timestamped_path("results", format.extension());
+        let format = format.clone();
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+
+        if format == ExportFormat::Parquet {
+            // A first pass settles each column's type, so a NUMBER column
+            // with decimals or huge values isn't forced into integers
+            let total = source.nrows * 2;
+            return Ok(ExportJob::start(format.name().to_string(), path.clone(), total, move |progress| {
+                let mut types: Vec<ValueType> = kinds.iter().map(|&k| ValueType::from_kind(k)).collect();
+                source.scan_batches(progress, false, &mut |batch| {
+                    types.iter_mut().zip(batch.columns()).for_each(|(ty, column)| ty.widen_column(column.as_ref()));
+                    Ok(())
+                })?;
+                let file = std::io::BufWriter::new(progress.count(std::fs::File::create(&path)?));
+                let mut writer = ParquetWriter::new(file, &headers, types)?;
+                source.scan_batches(progress, true, &mut |batch| writer.write_batch(batch))?;
+                let n = writer.finish()?;
+                Ok(format!("Exported {} row{} as Parquet to {}", n, plural(n), path.display()))
+            }));
+        }
+
+        let gzip = matches!(format, ExportFormat::JsonLines { gzip: true });
+        let total = source.nrows;
+        Ok(ExportJob::start(format.name().to_string(), path.clone(), total, move |progress| {
+            let file = std::io::BufWriter::new(progress.count(std::fs::File::create(&path)?));
+            let n = crate::export::write_maybe_gzip(file, gzip, |out| {
+                let mut writer = RowWriter::new(out, &format, &headers)?.with_kinds(&kinds);
+                source.scan(progress, true, &mut |rows| writer.write_rows(rows))?;
+                writer.finish()
+            })?;
+            Ok(format!("Exported {} row{} as {} to {}", n, plural(n), format.name(), path.display()))
+        }))
+    }
+
+    /// Export the rows in view to CSV on a worker thread: the selected cells,
+    /// or every row the sort, filter or dedup left, in display order
+    pub fn export_view_job(&self) -> Result<ExportJob, String> {
+        let cols = self.view_columns();
+        let ResultsContent::Table { headers, tile_store, row_view, .. } = &self.content else {
+            return Err("Nothing to export".to_string());
+        };
+        let nrows = row_view.len(tile_store);
+        let (rows, cols, label) = if self.selection_anchor.is_some() {
+            let ((row_start, row_end), (col_start, col_end)) = self.selection_bounds();
+            (row_start..row_end + 1, cols[col_start..=col_end].to_vec(), "selected")
+        } else {
+            (0..nrows, cols, "visible")
+        };
+        let headers: Vec<String> = cols.iter().map(|&c| headers[c].clone()).collect();
+        let reader = row_view.reader(tile_store).map_err(|e| format!("Export failed: {}", e))?;
+        let mut source = TileSource::new(reader, cols, nrows).with_rows(rows);
+        let path = crate::export::timestamped_path("view", "csv");
+        let total = source.nrows;
+        Ok(ExportJob::start("CSV".to_string(), path.clone(), total, move |progress| {
+            let file = std::io::BufWriter::new(progress.count(std::fs::File::create(&path)?));
+            let mut writer = RowWriter::new(file, &ExportFormat::Csv, &headers)?;
+            source.scan(progress, true, &mut |rows| writer.write_rows(rows))?;
+            let n = writer.finish()?;
+            Ok(format!("Exported {} {} row{} to {}", n, label, if n == 1 { "" } else { "s" }, path.display()))
+        }))
+    }
+
+    /// Scan the whole table for rows whose `column` equals `find`
+    pub fn replace_preview(&mut self, column: usize, key_column: usize, find: &str) -> std::io::Result<ReplacePreview> {
+        let mut preview = ReplacePreview { keys: Vec::new(), null_keys: 0 };
+        let ResultsContent::Table { tile_store, row_view, .. } = &mut self.content else { return Ok(preview) };
+        let mut start = 0;
+        while start < row_view.len(tile_store) {
+            let rows = row_view.get_rows(tile_store, start, TILE_SIZE)?;
+            if rows.is_empty() {
+                break;
+            }
+            collect_matches(&rows, column, key_column, find, &mut preview);
+            start += rows.len();
+        }
+        Ok(preview)
+    }
+
+    /// Sort the rows on the cursor column, ascending unless they are already
+    /// sorted on it that way. Returns the message to show.
+    fn sort_rows(&mut self) -> String {
+        let column = self.cursor_column();
+        let ResultsContent::Table { headers, kinds, row_view, .. } = &self.content else {
+            return "Sorting works on table results only".to_string();
+        };
+        let descending = row_view.sort_column() == Some((column, false));
+        let numeric = kinds.get(column) == Some(&ColumnKind::Number);
+        let message = format!("Sorting by {}...", headers[column]);
+        let step = ViewStep::Sort { column, name: headers[column].clone(), descending, numeric };
+        self.build_view(step, message)
+    }
+
+    /// Keep the rows whose cursor column holds the value under the cursor
+    fn filter_rows(&mut self) -> String {
+        let (row, column) = (self.cursor_row, self.cursor_column());
+        let ResultsContent::Table { headers, tile_store, row_view, .. } = &mut self.content else {
+            return "Filtering works on table results only".to_string();
+        };
+        let value = match row_view.get_rows(tile_store, row, 1) {
+            Ok(rows) => rows.into_iter().next().and_then(|r| r.into_iter().nth(column)),
+            Err(e) => return format!("Filter failed: {}", e),
+        };
+        let Some(value) = value else { return "No row to filter on".to_string() };
+        let message = format!("Filtering on {}...", headers[column]);
+        let step = ViewStep::Filter { column, name: headers[column].clone(), value };
+        self.build_view(step, message)
+    }
+
+    /// Drop rows repeating an earlier row in every column
+    fn dedup_rows(&mut self) -> String {
+        if !matches!(self.content, ResultsContent::Table { .. }) {
+            return "Dropping repeats works on table results only".to_string();
+        }
+        self.build_view(ViewStep::Dedup, "Dropping repeated rows...".to_string())
+    }
+
+    /// Start building the view one `step` further on a background thread;
+    /// `poll_view_build` swaps it in once it is done. Returns `message`.
+    fn build_view(&mut self, step: ViewStep, message: String) -> String {
+        let ResultsContent::Table { tile_store, row_view, .. } = &self.content else {
+            return String::new();
+        };
+        match row_view.build(tile_store, step) {
+            Ok(rx) => {
+                self.view_build = Some(rx);
+                message
+            }
+            Err(e) => format!("Reading rows failed: {}", e),
+        }
+    }
+
+    /// Show a finished sort, filter or dedup, with its message
+    fn poll_view_build(&mut self) -> Option<String> {
+        let rx = self.view_build.as_ref()?;
+        let view = match rx.try_recv() {
+            Ok(view) => view,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(std::io::Error::other("scan stopped")),
+        };
+        Some(self.set_row_view(view))
+    }
+
+    /// Show the rows as built by a sort, filter or dedup (every row for
+    /// the default view), from the top. Search matches and the selection
+    /// are positions in the old view, so they go, as does a view still
+    /// being built from it.
+    fn set_row_view(&mut self, view: std::io::Result<RowView>) -> String {
+        self.view_build = None;
+        let ResultsContent::Table { tile_store, row_view, .. } = &mut self.content else {
+            return String::new();
+        };
+        match view {
+            Ok(view) => *row_view = view,
+            Err(e) => return format!("Reading rows failed: {}", e),
+        }
+        let shown = row_view.len(tile_store);
+        let message = match row_view.describe() {
+            Some(steps) => format!("{} row{}, {}", shown, if shown == 1 { "" } else { "s" }, steps),
+            None => format!("All {} rows in fetch order", tile_store.nrows),
+        };
+        self.cursor_row = 0;
+        self.view_row = 0;
+        self.selection_anchor = None;
+        self.selection_stats = None;
+        self.search = None;
+        message
+    }
+
+    /// Mark the current column as diff baseline, or as the compared column
+    /// if a baseline is already picked.
+    fn toggle_diff_column(&mut self) {
+        let column = self.cursor_column();
+        match self.diff_pending.take() {
+            Some(base) if base != column => {
+                self.diff_cols = Some((base, column));
+            }
+            Some(_) => {}
+            None => {
+                self.diff_cols = None;
+                self.diff_pending = Some(column);
+            }
+        }
+    }
+
+    /// Move the cursor to the next (or previous) row where the diff columns differ.
+    /// Returns false when there is no further mismatch.
+    fn jump_to_mismatch(&mut self, forward: bool) -> bool {
+        let Some((a, b)) = self.diff_cols else { return false };
+        let ResultsContent::Table { tile_store, row_view, .. } = &mut self.content else { return false };
+        let nrows = row_view.len(tile_store);
+        if forward {
+            let mut start = self.cursor_row + 1;
+            while start < nrows {
+                let Ok(rows) = row_view.get_rows(tile_store, start, TILE_SIZE) else { return false };
+                if let Some(i) = rows.iter().position(|r| r.get(a) != r.get(b)) {
+                    self.cursor_row = start + i;
+                    return true;
+                }
+                start += rows.len().max(1);
+            }
+        } else {
+            let mut end = self.cursor_row;
+            while end > 0 {
+                let start = end.saturating_sub(TILE_SIZE);
+                let Ok(rows) = row_view.get_rows(tile_store, start, end - start) else { return false };
+                if let Some(i) = rows.iter().rposition(|r| r.get(a) != r.get(b)) {
+                    self.cursor_row = start + i;
+                    return true;
+                }
+                end = start;
+            }
+        }
+        false
+    }
+
+    /// Start scanning the table for `query` in the background
+    fn start_search(&mut self, query: String) -> Result<(), String> {
+        let ResultsContent::Table { tile_store, row_view, .. } = &self.content else {
+            return Err("Search works on table results only".to_string());
+        };
+        let reader = row_view.reader(tile_store).map_err(|e| format!("Search failed: {}", e))?;
+        self.search = Some(TableSearch::start(reader, query));
+        Ok(())
+    }
+
+    /// Move the cursor to the next (or previous) search match in the shown
+    /// columns; the viewport follows the cursor
+    fn step_search(&mut self, forward: bool) -> bool {
+        let cols = self.view_columns();
+        let Some(search) = &mut self.search else { return false };
+        let from = (self.cursor_row, self.cursor_col);
+        let Some((row, pos)) = search.step(from, forward, |c| cols.iter().position(|&v| v == c)) else {
+            return false;
+        };
+        search.current = Some((row, cols[pos]));
+        self.cursor_row = row;
+        self.cursor_col = pos;
+        self.selection_anchor = None;
+        true
+    }
+
+    /// Width the column at `column` (an index into headers) is drawn at
+    fn column_width(&self, column: usize) -> usize {
+        self.width_overrides.get(&column).or(self.auto_widths.0.get(column)).copied().unwrap_or(1)
+    }
+
+    /// Make the cursor column `delta` columns wider or narrower
+    fn resize_column(&mut self, delta: isize) {
+        let column = self.cursor_column();
+        let width = self.column_width(column).saturating_add_signed(delta).clamp(1, MAX_FIT_WIDTH);
+        self.width_overrides.insert(column, width);
+    }
+
+    /// Start measuring the widest value of the cursor column over the
+    /// whole table, through a separate handle on its tiles
+    fn fit_column(&mut self) -> Result<String, String> {
+        let column = self.cursor_column();
+        let ResultsContent::Table { headers, tile_store, row_view, .. } = &self.content else {
+            return Err("Fitting works on table results only".to_string());
+        };
+        let mut reader = row_view.reader(tile_store).map_err(|e| format!("Fit failed: {}", e))?;
+        let header_width = printable(&headers[column]).width();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let widest = (0..reader.tile_count()).try_fold(header_width, |widest, tile| {
+                let rows = reader.read_tile(tile)?;
+                Ok(rows.iter().filter_map(|r| r.get(column)).map(|v| display_value(v).width()).fold(widest, usize::max))
+            });
+            let _ = tx.send(widest);
+        });
+        self.width_fit = Some((column, rx));
+        Ok(format!("Fitting {} to its content", headers[column]))
+    }
+
+    /// Draw the cursor row transposed: each shown column's name beside its
+    /// value, the cursor on the current column
+    fn render_transposed(&mut self, frame: &mut Frame, area: Rect, focused: bool, null_style: Style) {
+        let cols = self.view_columns();
+        let ResultsContent::Table { headers, tile_store, row_view, .. } = &mut self.content else { return };
+        let height = area.height as usize;
+        if height == 0 || area.width == 0 {
+            return;
+        }
+        let row = row_view.get_rows(tile_store, self.cursor_row, 1).ok().and_then(|mut rows| rows.pop()).unwrap_or_default();
+        if self.cursor_col < self.view_col {
+            self.view_col = self.cursor_col;
+        } else if self.cursor_col >= self.view_col + height {
+            self.view_col = self.cursor_col + 1 - height;
+        }
+        let name_width = cols.iter()
+            .map(|&c| printable(&headers[c]).width())
+            .max()
+            .unwrap_or(1)
+            .clamp(1, MAX_COL_WIDTH);
+        let separator = Style::default().fg(Color::DarkGray);
+        let lines: Vec<Line> = cols.iter().enumerate().skip(self.view_col).take(height)
+            .map(|(p, &c)| {
+                let value = row.get(c).map(String::as_str).unwrap_or("");
+                let style = if focused && p == self.cursor_col {
+                    Style::default().fg(Color::Black).bg(Color::Cyan)
+                } else if value == NULL_SENTINEL {
+                    null_style
+                } else {
+                    Style::default()
+                };
+                Line::from(vec![
+                    Span::styled(fit(&printable(&headers[c]), name_width), Style::default().add_modifier(Modifier::BOLD)),
+                    Span::styled(" │ ", separator),
+                    Span::styled(display_value(value).into_owned(), style),
+                ])
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+
+    /// Apply a finished column fit, with a message saying how it went
+    fn poll_width_fit(&mut self) -> Option<String> {
+        let (column, rx) = self.width_fit.as_ref()?;
+        let column = *column;
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(std::io::Error::other("scan stopped")),
+        };
+        self.width_fit = None;
+        let ResultsContent::Table { headers, .. } = &self.content else { return None };
+        Some(match result {
+            Ok(widest) => {
+                let width = widest.clamp(1, MAX_FIT_WIDTH);
+                self.width_overrides.insert(column, width);
+                format!("{} fitted to {} columns", headers[column], width)
+            }
+            Err(e) => format!("Fit failed: {}", e),
+        })
+    }
+
+    /// Collect new search matches, jumping to the first one once it arrives
+    fn poll_search(&mut self) -> bool {
+        let Some(search) = &mut self.search else { return false };
+        let changed = search.poll();
+        if changed && search.current.is_none() && !search.matches.is_empty() {
+            self.step_search(true);
+        }
+        changed
+    }
+
+    fn render_table(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        focused: bool,
+        find_styles: (Style, Style),
+        null_style: Style,
+        scroll: (usize, bool),
+    ) {
+        let cols = self.view_columns();
+        let selection = self.selection_anchor.map(|_| self.selection_bounds());
+        let ResultsContent::Table { headers, tile_store, row_view, .. } = &mut self.content else { return };
+        if area.height == 0 || area.width == 0 {
+            return;
+        }
+        let body_rows = area.height as usize - 1;
+
+        // Keep the cursor row inside the viewport, `scrolloff` rows from its
+        // edges; a jump more than a row off screen re-centers instead
+        let (scrolloff, center_on_jump) = scroll;
+        if body_rows > 0 {
+            let margin = scrolloff.min((body_rows - 1) / 2);
+            let jumped = self.cursor_row + 1 < self.view_row || self.cursor_row > self.view_row + body_rows;
+            if center_on_jump && jumped {
+                self.view_row = self.cursor_row.saturating_sub(body_rows / 2);
+            } else if self.cursor_row < self.view_row + margin {
+                self.view_row = self.cursor_row.saturating_sub(margin);
+            } else if self.cursor_row + margin >= self.view_row + body_rows {
+                self.view_row = self.cursor_row + margin + 1 - body_rows;
+            }
+            self.view_row = self.view_row.min(row_view.len(tile_store).saturating_sub(body_rows));
+        }
+
+        if body_rows > 0 {
+            row_view.prefetch(tile_store, self.view_row, body_rows);
+        }
+        let rows = row_view.get_rows(tile_store, self.view_row, body_rows).unwrap_or_default();
+        let gutter = row_view.len(tile_store).max(1).to_string().len() + 1;
+        // Row number, the mismatch marker and a space
+        let row_prefix = gutter + 2;
+
+        // Auto widths follow the sample until it is full, so they hold
+        // still while scrolling
+        let sampled = row_view.len(tile_store).min(WIDTH_SAMPLE_ROWS);
+        if self.auto_widths.0.len() != headers.len() || self.auto_widths.1 < sampled {
+            let sample = row_view.get_rows(tile_store, 0, sampled).unwrap_or_default();
+            self.auto_widths = (auto_widths(headers, &sample), sampled);
+        }
+        // Indexed by display position
+        let widths: Vec<usize> = cols.iter()
+            .map(|&c| self.width_overrides.get(&c).copied().unwrap_or(self.auto_widths.0[c]))
+            .collect();
+
+        // Pinned columns and their separator come off the scrolling width
+        let pinned = self.pinned_cols.min(cols.len());
+        let pinned_width = if pinned > 0 {
+            widths[..pinned].iter().map(|w| w + 1).sum::<usize>() + 2
+        } else {
+            0
+        };
+
+        // Keep the cursor column inside the viewport
+        let avail = (area.width as usize).saturating_sub(row_prefix + pinned_width);
+        self.view_col = self.view_col.max(pinned);
+        if self.cursor_col >= pinned && self.cursor_col < self.view_col {
+            self.view_col = self.cursor_col;
+        }
+        while self.view_col < self.cursor_col
+            && widths[self.view_col..=self.cursor_col].iter().map(|w| w + 1).sum::<usize>() > avail
+        {
+            self.view_col += 1;
+        }
+        // Display positions drawn, pinned ones first
+        let shown: Vec<usize> = (0..pinned).chain(self.view_col..cols.len()).collect();
+        let separator = Style::default().fg(Color::DarkGray);
+
+        let diff_style = Style::default().fg(Color::Red).add_modifier(Modifier::BOLD);
+        let mut lines = Vec::with_capacity(rows.len() + 1);
+
+        let mut header_spans = vec![Span::raw(" ".repeat(row_prefix))];
+        for (i, &p) in shown.iter().enumerate() {
+            let c = cols[p];
+            let style = if self.diff_pending == Some(c)
+                || self.diff_cols.map_or(false, |(a, b)| a == c || b == c)
+            {
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().add_modifier(Modifier::BOLD)
+            };
+            header_spans.push(Span::styled(fit(&printable(&headers[c]), widths[p]), style));
+            header_spans.push(Span::raw(" "));
+            if i + 1 == pinned {
+                header_spans.push(Span::styled("│ ", separator));
+            }
+        }
+        lines.push(Line::from(header_spans));
+
+        for (i, row) in rows.iter().enumerate() {
+            let row_idx = self.view_row + i;
+            let mismatch = self.diff_cols.map_or(false, |(a, b)| row.get(a) != row.get(b));
+            let marker = if mismatch { "!" } else { " " };
+            let mut spans = vec![Span::styled(
+                format!("{:>width$}{}", row_idx + 1, marker, width = gutter),
+                Style::default().fg(Color::DarkGray),
+            )];
+            spans.push(Span::raw(" "));
+            for (i, &p) in shown.iter().enumerate() {
+                let c = cols[p];
+                let value = row.get(c).map(|v| display_value(v)).unwrap_or_default();
+                let is_diff_col = self.diff_cols.map_or(false, |(a, b)| a == c || b == c);
+                let selected = selection.map_or(false, |((r0, r1), (c0, c1))| {
+                    (r0..=r1).contains(&row_idx) && (c0..=c1).contains(&p)
+                });
+                let search_hit = self.search.as_ref().map_or(false, |s| s.is_match(row_idx, c));
+                let style = if focused && row_idx == self.cursor_row && p == self.cursor_col {
+                    Style::default().fg(Color::Black).bg(Color::Cyan)
+                } else if search_hit && self.search.as_ref().and_then(|s| s.current) == Some((row_idx, c)) {
+                    find_styles.1
+                } else if search_hit {
+                    find_styles.0
+                } else if selected {
+                    Style::default().bg(Color::DarkGray)
+                } else if mismatch && is_diff_col {
+                    diff_style
+                } else if row_idx == self.cursor_row {
+                    Style::default().add_modifier(Modifier::UNDERLINED)
+                } else {
+                    Style::default()
+                };
+                // NULL stands apart from the text 'NULL' unless the cell is highlighted
+                let style = if row.get(c).is_some_and(|v| v == NULL_SENTINEL) && style.fg.is_none() {
+                    style.patch(null_style)
+                } else {
+                    style
+                };
+                spans.push(Span::styled(fit(&value, widths[p]), style));
+                spans.push(Span::raw(" "));
+                if i + 1 == pinned {
+                    spans.push(Span::styled("│ ", separator));
+                }
+            }
+            lines.push(Line::from(spans));
+        }
+
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+}
+
+/// Where a Ctrl+arrow from `cur` lands among `len` cells, stepping
+/// forwards or backwards: the last filled cell of the run `cur` is in, or
+/// else the next filled cell, or the end. `filled` says whether a cell holds
+/// a value.
+fn data_edge(
+    cur: usize,
+    forward: bool,
+    len: usize,
+    mut filled: impl FnMut(usize) -> std::io::Result<bool>,
+) -> std::io::Result<usize> {
+    let next = |i: usize| if forward { (i + 1 < len).then_some(i + 1) } else { i.checked_sub(1) };
+    let Some(mut at) = next(cur) else { return Ok(cur) };
+    if filled(cur)? && filled(at)? {
+        while let Some(n) = next(at) {
+            if !filled(n)? {
+                break;
+            }
+            at = n;
+        }
+        return Ok(at);
+    }
+    loop {
+        if filled(at)? {
+            return Ok(at);
+        }
+        match next(at) {
+            Some(n) => at = n,
+            None => return Ok(at),
+        }
+    }
+}
+
+/// Width of each column to fit its header and `rows`, up to MAX_COL_WIDTH
+fn auto_widths(headers: &[String], rows: &[Vec<String>]) -> Vec<usize> {
+    (0..headers.len())
+        .map(|c| {
+            rows.iter()
+                .map(|r| r.get(c).map(|v| display_value(v).width()).unwrap_or(0))
+                .chain(std::iter::once(printable(&headers[c]).width()))
+                .max()
+                .unwrap_or(1)
+                .clamp(1, MAX_COL_WIDTH)
+        })
+        .collect()
+}
+
+/// Text shown for a stored cell value
+fn display_value(value: &str) -> Cow<'_, str> {
+    if value == NULL_SENTINEL { Cow::Borrowed("NULL") } else { printable(value) }
+}
+
+/// `text` on one row: line breaks as ↵, tabs as → and other control
+/// characters as their control pictures, which would otherwise break up
+/// the grid
+pub fn printable(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(char::is_control) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.replace("\r\n", "\n").chars().map(|ch| match ch {
+        '\n' => '↵',
+        '\t' => '→',
+        '\u{7f}' => '␡',
+        ch if (ch as u32) < 0x20 => char::from_u32(0x2400 + ch as u32).unwrap_or('?'),
+        ch if ch.is_control() => '?',
+        ch => ch,
+    }).collect())
+}
+
+/// Pad or truncate `text` to exactly `width` display columns
+pub fn fit(text: &str, width: usize) -> String {
+    let mut out = String::with_capacity(width);
+    let mut used = 0;
+    for ch in text.chars() {
+        let w = ch.to_string().width();
+        if used + w > width {
+            break;
+        }
+        out.push(ch);
+        used += w;
+    }
+    out.push_str(&" ".repeat(width - used));
+    out
 }
 
 pub struct Results {
     pub tabs: Vec<ResultsTab>,
     pub tab_idx: usize,
+    /// Data rows visible in the last render, used for paging
+    page_rows: usize,
+    /// Width error and info messages were last wrapped to
+    text_width: usize,
+    /// Row number typed so far and when its last digit came
+    row_jump: Option<(String, Instant)>,
+    /// File export running in the background
+    export_job: Option<ExportJob>,
+    /// Tab comparison running in the background
+    compare_job: Option<CompareJob>,
+    /// Transient status shown in the pane title (export results etc.)
+    pub message: Option<String>,
+    /// Recently closed tabs (most recent first) with their original index and
+    /// close time; their temp files live until the tab is purged
+    closed_tabs: VecDeque<(Instant, usize, ResultsTab)>,
+    closed_tab_grace: Duration,
+    /// Show a ticking elapsed time on running tabs (off in reduced redraw mode)
+    animate_timer: bool,
+    /// Text typed after `/`, until Enter starts the search
+    search_input: Option<String>,
+    /// Cell styles for search matches and the current match
+    find_styles: (Style, Style),
+    /// Style of NULL cells
+    null_style: Style,
+    /// Values per IN clause when copying a column as an IN list
+    in_list_batch: usize,
+    identifier_quoting: IdentifierQuoting,
+    /// Default of the export prompt's typed JSON toggle
+    typed_json: bool,
+    /// Tab marked with B as the base of a comparison
+    compare_base: Option<usize>,
+    /// Grid scrolloff and whether jumps re-center the view
+    scroll: (usize, bool),
 }
 
 impl Results {
@@ -67,86 +1266,974 @@ impl Results {
         Self {
             tabs: Vec::new(),
             tab_idx: 0,
+            page_rows: 1,
+            text_width: 80,
+            row_jump: None,
+            export_job: None,
+            compare_job: None,
+            message: None,
+            closed_tabs: VecDeque::new(),
+            closed_tab_grace: Duration::from_secs(300),
+            animate_timer: true,
+            search_input: None,
+            find_styles: (
+                Style::default().fg(Color::Black).bg(Color::Yellow),
+                Style::default().fg(Color::Black).bg(Color::LightRed),
+            ),
+            null_style: Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+            in_list_batch: 1000,
+            identifier_quoting: IdentifierQuoting::default(),
+            typed_json: true,
+            compare_base: None,
+            scroll: (2, true),
         }
     }
     
-    pub fn add_result(&mut self, result: ResultsContent) {
-        // Find the pending tab and update it
+    /// Rows kept visible around the cursor, and whether far jumps center it
+    pub fn set_scroll_options(&mut self, scrolloff: usize, center_on_jump: bool) {
+        self.scroll = (scrolloff, center_on_jump);
+    }
+    
+    pub fn set_animate_timer(&mut self, animate: bool) {
+        self.animate_timer = animate;
+    }
+    
+    /// Cell styles for search matches and the current match
+    pub fn set_find_styles(&mut self, matched: Style, current: Style) {
+        self.find_styles = (matched, current);
+    }
+    
+    /// NULL cells are drawn dim and italic in `color`
+    pub fn set_null_color(&mut self, color: Color) {
+        self.null_style = Style::default().fg(color).add_modifier(Modifier::DIM | Modifier::ITALIC);
+    }
+    
+    /// Typing a `/` search query; keys should all come here
+    pub fn is_search_input_active(&self) -> bool {
+        self.search_input.is_some()
+    }
+    
+    /// Pick up matches from running searches, finished column fits and
+    /// finished sorts or filters; true if any tab changed
+    pub fn poll_searches(&mut self) -> bool {
+        let mut changed = false;
         for tab in &mut self.tabs {
-            if matches!(tab.content, ResultsContent::Pending) {
+            changed |= tab.poll_search();
+            if let Some(message) = tab.poll_width_fit() {
+                self.message = Some(message);
+                changed = true;
+            }
+            if let Some(message) = tab.poll_view_build() {
+                self.message = Some(message);
+                changed = true;
+            }
+        }
+        changed
+    }
+    
+    fn handle_search_input(&mut self, key: KeyEvent) {
+        let Some(input) = &mut self.search_input else { return };
+        match key.code {
+            KeyCode::Esc => self.search_input = None,
+            KeyCode::Backspace => { input.pop(); }
+            KeyCode::Enter => {
+                let query = self.search_input.take().unwrap_or_default();
+                let Some(tab) = self.tabs.get_mut(self.tab_idx) else { return };
+                if query.is_empty() {
+                    tab.search = None;
+                } else if let Err(message) = tab.start_search(query) {
+                    self.message = Some(message);
+                }
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => input.push(c),
+            _ => {}
+        }
+    }
+    
+    /// Batch size and column quoting for IN-list copies
+    pub fn set_in_list_options(&mut self, batch: usize, quoting: IdentifierQuoting) {
+        self.in_list_batch = batch.max(1);
+        self.identifier_quoting = quoting;
+    }
+    
+    /// Export the current tab, or every table tab, to an Excel workbook with
+    /// a sheet per tab, on a worker thread
+    fn xlsx_job(&self, all_tabs: bool, typed: bool) -> Result<ExportJob, String> {
+        let tabs: Vec<usize> = if all_tabs { (0..self.tabs.len()).collect() } else { vec![self.tab_idx] };
+        let mut sheets = Vec::new();
+        for i in tabs {
+            let Some(tab) = self.tabs.get(i) else { continue };
+            if !matches!(tab.content, ResultsContent::Table { .. }) {
+                continue;
+            }
+            // Unsampled tabs come out at Excel's default width
+            let widths: Vec<usize> = tab.view_columns().iter().map(|&c| tab.column_width(c).clamp(8, 60)).collect();
+            let (headers, kinds, source) = tab.export_source(typed)?;
+            sheets.push((format!("Result {}", i + 1), headers, kinds, widths, source));
+        }
+        if sheets.is_empty() {
+            return Err("No table results to export".to_string());
+        }
+        let path = crate::export::timestamped_path("results", "xlsx");
+        let total = sheets.iter().map(|sheet| sheet.4.nrows).sum();
+        Ok(ExportJob::start("Excel".to_string(), path.clone(), total, move |progress| {
+            let mut xlsx = XlsxWriter::new();
+            let (mut rows, mut truncated) = (0, false);
+            let count = sheets.len();
+            for (name, headers, kinds, widths, mut source) in sheets {
+                let mut sheet = xlsx.add_sheet(&name, &headers, &kinds, &widths)?;
+                source.scan(progress, true, &mut |chunk| {
+                    // Rows past Excel's limit are read but left out
+                    if sheet.is_full() {
+                        sheet.truncated |= !chunk.is_empty();
+                        return Ok(());
+                    }
+                    sheet.write_rows(chunk)
+                })?;
+                truncated |= sheet.truncated;
+                rows += sheet.finish()?;
+            }
+            xlsx.save(&path)?;
+            let plural = |n: usize| if n == 1 { "" } else { "s" };
+            Ok(format!(
+                "Exported {} row{} in {} sheet{} to {}{}",
+                rows, plural(rows), count, plural(count), path.display(),
+                if truncated { " (cut at Excel's row limit)" } else { "" },
+            ))
+        }))
+    }
+
+    /// Start writing `format` to a file in the background; its progress
+    /// shows under the results until the outcome replaces it
+    pub fn start_export(&mut self, tab_idx: usize, format: &ExportFormat, typed: bool) {
+        if self.export_job.is_some() {
+            self.message = Some("An export is already running; c cancels it".to_string());
+            return;
+        }
+        let job = match format {
+            ExportFormat::Xlsx { all_tabs } => self.xlsx_job(*all_tabs, typed),
+            _ => match self.tabs.get(tab_idx) {
+                Some(tab) => tab.export_job(format, typed),
+                None => Err("Nothing to export".to_string()),
+            },
+        };
+        match job {
+            Ok(job) => self.export_job = Some(job),
+            Err(message) => self.message = Some(message),
+        }
+    }
+
+    /// Take the running export's progress; true if anything changed
+    pub fn poll_export(&mut self) -> bool {
+        let Some(job) = &mut self.export_job else { return false };
+        let changed = job.poll();
+        if let Some(outcome) = job.outcome.take() {
+            self.message = Some(outcome);
+            self.export_job = None;
+        }
+        changed
+    }
+
+    /// Stop the running export; false if there is none
+    pub fn cancel_export(&mut self) -> bool {
+        match &self.export_job {
+            Some(job) => {
+                job.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// File name prompt for saving the current tab, or with `save` false
+    /// for opening a saved result
+    pub fn result_file_prompt(&self, save: bool) -> Result<ResultFilePrompt, String> {
+        if !save {
+            return Ok(ResultFilePrompt { save_tab: None, path: String::new(), error: None });
+        }
+        let tab = self.tabs.get(self.tab_idx).ok_or("No results to save")?;
+        if !matches!(tab.content, ResultsContent::Table { .. }) {
+            return Err("Only table results can be saved".to_string());
+        }
+        let path = crate::export::timestamped_path("results", saved_result::EXTENSION);
+        Ok(ResultFilePrompt { save_tab: Some(self.tab_idx), path: path.display().to_string(), error: None })
+    }
+
+    /// Write tab `tab_idx`, with its headers and the query it came from, to
+    /// `path` in the background
+    pub fn save_result(&mut self, tab_idx: usize, path: PathBuf) -> Result<(), String> {
+        if self.export_job.is_some() {
+            return Err("An export is already running; c cancels it".to_string());
+        }
+        let tab = self.tabs.get(tab_idx).ok_or("That tab was closed")?;
+        let ResultsContent::Table { headers, tile_store, kinds, row_view } = &tab.content else {
+            return Err("Only table results can be saved".to_string());
+        };
+        let reader = row_view.reader(tile_store).map_err(|e| format!("Save failed: {}", e))?;
+        let meta = SavedMeta {
+            headers: headers.clone(),
+            kinds: kinds.clone(),
+            sql: tab.request.as_ref().map(|r| r.sql.clone()),
+            context: tab.query_context.clone(),
+            warehouse: tab.request.as_ref().and_then(|r| r.warehouse.clone()),
+            timeout: tab.request.as_ref().and_then(|r| r.timeout),
+            session_context: tab.session_context.clone(),
+            query_id: tab.query_id.clone(),
+            elapsed: tab.elapsed,
+            truncated: tab.truncated,
+            saved_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            rows: row_view.len(tile_store),
+        };
+        let mut source = TileSource::new(reader, (0..headers.len()).collect(), row_view.len(tile_store));
+        let total = source.nrows;
+        self.export_job = Some(ExportJob::start("saved result".to_string(), path.clone(), total, move |progress| {
+            let n = saved_result::write(&path, &meta, &mut source, progress)?;
+            Ok(format!("Saved {} row{} to {}", n, if n == 1 { "" } else { "s" }, path.display()))
+        }));
+        Ok(())
+    }
+
+    /// Open a saved result in a new tab; its query can be shown and run
+    /// again as if it had just run. Returns the message to show.
+    pub fn open_result(&mut self, path: &Path, options: TileStoreOptions) -> Result<String, String> {
+        let (meta, tile_store) = saved_result::read(path, options)
+            .map_err(|e| format!("Can't open {}: {}", path.display(), e))?;
+        let mut tab = ResultsTab::new_pending(meta.context.clone());
+        tab.running = false;
+        tab.run_started = None;
+        tab.elapsed = meta.elapsed;
+        tab.request = meta.sql.map(|sql| QueryRequest {
+            sql,
+            context: meta.context,
+            warehouse: meta.warehouse,
+            explain: None,
+            timeout: meta.timeout,
+        });
+        tab.session_context = meta.session_context;
+        tab.query_id = meta.query_id;
+        tab.truncated = meta.truncated;
+        let rows = tile_store.nrows;
+        tab.content = ResultsContent::table(meta.headers, tile_store, meta.kinds);
+        self.tabs.push(tab);
+        self.tab_idx = self.tabs.len() - 1;
+        Ok(format!("Opened {} row{} saved {} from {}", rows, if rows == 1 { "" } else { "s" }, meta.saved_at, path.display()))
+    }
+    
+    /// Whether JSON exports write numbers and booleans unquoted by default
+    pub fn set_typed_json(&mut self, typed: bool) {
+        self.typed_json = typed;
+    }
+    
+    /// How long closed tabs (and their temp files) can still be reopened
+    pub fn set_closed_tab_grace(&mut self, grace: Duration) {
+        self.closed_tab_grace = grace;
+    }
+    
+    /// Close the current tab, keeping it around for `reopen_closed_tab`.
+    /// Tabs still waiting for their query can't be closed.
+    pub fn close_current_tab(&mut self) {
+        if self.tab_idx >= self.tabs.len() || self.tabs[self.tab_idx].running {
+            return;
+        }
+        let tab = self.tabs.remove(self.tab_idx);
+        self.closed_tabs.push_front((Instant::now(), self.tab_idx, tab));
+        self.closed_tabs.truncate(MAX_CLOSED_TABS);
+        if self.tab_idx >= self.tabs.len() {
+            self.tab_idx = self.tabs.len().saturating_sub(1);
+        }
+    }
+    
+    /// Close every tab that isn't pinned or still waiting for its query,
+    /// keeping them around for `reopen_closed_tab`
+    pub fn close_unpinned_tabs(&mut self) {
+        let now = Instant::now();
+        let current = self.tab_idx;
+        let mut kept = Vec::new();
+        let mut closed = 0;
+        for (idx, tab) in std::mem::take(&mut self.tabs).into_iter().enumerate() {
+            if tab.pinned || tab.running || tab.queued {
+                if idx <= current {
+                    self.tab_idx = kept.len();
+                }
+                kept.push(tab);
+            } else {
+                self.closed_tabs.push_front((now, idx, tab));
+                closed += 1;
+            }
+        }
+        self.closed_tabs.truncate(MAX_CLOSED_TABS);
+        self.tabs = kept;
+        if self.tab_idx >= self.tabs.len() {
+            self.tab_idx = self.tabs.len().saturating_sub(1);
+        }
+        self.compare_base = None;
+        let pinned = self.tabs.iter().filter(|tab| tab.pinned).count();
+        self.message = Some(format!(
+            "Closed {} tab{}{}",
+            closed,
+            if closed == 1 { "" } else { "s" },
+            if pinned > 0 { format!(", kept {} pinned", pinned) } else { String::new() },
+        ));
+    }
+    
+    /// Restore the most recently closed tab at its old position
+    pub fn reopen_closed_tab(&mut self) {
+        self.purge_closed_tabs();
+        match self.closed_tabs.pop_front() {
+            Some((_, idx, tab)) => {
+                let idx = idx.min(self.tabs.len());
+                self.tabs.insert(idx, tab);
+                self.tab_idx = idx;
+            }
+            None => self.message = Some("No closed tab to reopen".to_string()),
+        }
+    }
+    
+    /// Drop closed tabs older than the grace period, deleting their temp files
+    pub fn purge_closed_tabs(&mut self) {
+        let grace = self.closed_tab_grace;
+        self.closed_tabs.retain(|(closed_at, _, _)| closed_at.elapsed() < grace);
+    }
+    
+    /// The cursor row of the current tab as a row detail popup, on its
+    /// cursor column; None unless the tab holds a table with rows
+    pub fn row_detail(&mut self) -> Option<RowDetail> {
+        let tab = self.tabs.get_mut(self.tab_idx)?;
+        let cols = tab.view_columns();
+        let (row, selected) = (tab.cursor_row, tab.cursor_col);
+        let ResultsContent::Table { headers, tile_store, row_view, .. } = &mut tab.content else { return None };
+        let values = row_view.get_rows(tile_store, row, 1).ok()?.into_iter().next()?;
+        let fields = cols.iter()
+            .map(|&c| (headers[c].clone(), values.get(c).cloned().unwrap_or_default()))
+            .collect();
+        Some(RowDetail::new(row, fields, selected))
+    }
+
+    /// The statement the current tab came from
+    pub fn source_view(&self) -> Result<SourceView, String> {
+        let tab = self.tabs.get(self.tab_idx).ok_or("No results")?;
+        let request = tab.request.as_ref().ok_or("This tab didn't come from a query")?;
+        Ok(SourceView::new(self.tab_idx, request.sql.clone()))
+    }
+    
+    /// Chart of the current tab. A block selection picks the rows and, when
+    /// it spans columns, the x column (its first) and the y columns; without
+    /// one the cursor column is plotted over the first rows.
+    pub fn chart_view(&mut self) -> Result<ChartView, String> {
+        let tab = self.tabs.get_mut(self.tab_idx).ok_or("No results to chart")?;
+        let cols = tab.view_columns();
+        let ((row_start, row_end), (col_start, col_end)) = tab.selection_bounds();
+        let (first, count, cols, ys) = match tab.selection_anchor {
+            Some(_) if col_end > col_start => {
+                (row_start, row_end + 1 - row_start, cols[col_start..=col_end].to_vec(), (1..=col_end - col_start).collect())
+            }
+            Some(_) => (row_start, row_end + 1 - row_start, cols, vec![tab.cursor_col]),
+            None => (0, MAX_CHART_ROWS, cols, vec![tab.cursor_col]),
+        };
+        let ResultsContent::Table { headers, tile_store, row_view, .. } = &mut tab.content else {
+            return Err("Charts work on table results only".to_string());
+        };
+        let rows = row_view.get_rows(tile_store, first, count.min(MAX_CHART_ROWS)).map_err(|e| e.to_string())?;
+        let rows = rows.into_iter()
+            .map(|row| cols.iter().map(|&c| row.get(c).cloned().unwrap_or_default()).collect())
+            .collect();
+        let headers = cols.iter().map(|&c| headers[c].clone()).collect();
+        ChartView::new(headers, rows, first, ys)
+    }
+    
+    /// Tree of the JSON object or array in the cell under the cursor
+    pub fn json_view(&mut self) -> Result<JsonView, String> {
+        let quoting = self.identifier_quoting;
+        let tab = self.tabs.get_mut(self.tab_idx).ok_or("No results")?;
+        let (row, column) = (tab.cursor_row, tab.cursor_column());
+        let ResultsContent::Table { headers, tile_store, row_view, .. } = &mut tab.content else {
+            return Err("Not JSON".to_string());
+        };
+        let cell = row_view.get_rows(tile_store, row, 1).ok()
+            .and_then(|rows| rows.into_iter().next())
+            .and_then(|r| r.into_iter().nth(column))
+            .unwrap_or_default();
+        JsonView::parse(quote_identifier(&headers[column], quoting), &cell)
+            .ok_or_else(|| "Not JSON".to_string())
+    }
+    
+    /// Find-and-replace form for the cursor column of the current tab,
+    /// prefilled with the cell under the cursor. Needs a key column marked with K.
+    pub fn replace_form(&mut self) -> Result<ReplaceForm, String> {
+        let tab_idx = self.tab_idx;
+        let tab = self.tabs.get_mut(tab_idx).ok_or("No results to replace in")?;
+        let (row, column) = (tab.cursor_row, tab.cursor_column());
+        let key_column = tab.key_col.ok_or("Mark a key column with K first")?;
+        if key_column == column {
+            return Err("Can't replace in the key column".to_string());
+        }
+        let ResultsContent::Table { headers, tile_store, row_view, kinds } = &mut tab.content else {
+            return Err("Replace works on table results only".to_string());
+        };
+        let find = row_view.get_rows(tile_store, row, 1).ok()
+            .and_then(|rows| rows.into_iter().next())
+            .and_then(|r| r.into_iter().nth(column))
+            .filter(|v| v != NULL_SENTINEL)
+            .unwrap_or_default();
+        Ok(ReplaceForm {
+            tab_idx,
+            column,
+            key_column,
+            column_name: headers[column].clone(),
+            key_name: headers[key_column].clone(),
+            key_kind: kinds.get(key_column).copied().unwrap_or(ColumnKind::Text),
+            table: String::new(),
+            find,
+            replace: String::new(),
+            field: ReplaceField::Replace,
+            preview: None,
+        })
+    }
+    
+    /// Scan the cursor column of the current tab; returns the column name
+    /// and the stats as label/value pairs
+    pub fn column_stats(&mut self) -> Result<(String, Vec<(&'static str, String)>), String> {
+        let tab = self.tabs.get_mut(self.tab_idx).ok_or("No results to profile")?;
+        let column = tab.cursor_column();
+        let ResultsContent::Table { headers, tile_store, row_view, .. } = &mut tab.content else {
+            return Err("Column stats work on table results only".to_string());
+        };
+        let mut stats = ColumnStats::default();
+        let mut start = 0;
+        while start < row_view.len(tile_store) {
+            let rows = row_view.get_rows(tile_store, start, TILE_SIZE).map_err(|e| format!("Column stats failed: {}", e))?;
+            if rows.is_empty() {
+                break;
+            }
+            for row in &rows {
+                stats.add(row.get(column).map(|v| v.as_str()).unwrap_or(NULL_SENTINEL));
+            }
+            start += rows.len();
+        }
+        Ok((headers[column].clone(), stats.finish()))
+    }
+    
+    /// The first B marks the current tab as the base of a comparison; B on
+    /// another table tab then asks for the key columns to match rows on.
+    /// Err carries the message to show instead.
+    pub fn compare_form(&mut self) -> Result<CompareForm, String> {
+        let tab = self.tabs.get(self.tab_idx).ok_or("No results to compare")?;
+        let ResultsContent::Table { headers, .. } = &tab.content else {
+            return Err("Compare works on table results only".to_string());
+        };
+        let base = self.compare_base.take();
+        let base_tab = base.filter(|&b| b != self.tab_idx).and_then(|b| self.tabs.get(b));
+        let Some(ResultsTab { content: ResultsContent::Table { headers: base_headers, .. }, key_col, .. }) = base_tab else {
+            if base == Some(self.tab_idx) {
+                return Err("Comparison cancelled".to_string());
+            }
+            self.compare_base = Some(self.tab_idx);
+            return Err(format!("Tab {} is the base; press B on the tab to compare with it", self.tab_idx + 1));
+        };
+        // The base tab's key column if one is marked, else the first shared column
+        let keys = key_col.map(|k| base_headers[k].clone())
+            .or_else(|| base_headers.iter().find(|h| headers.iter().any(|o| o.eq_ignore_ascii_case(h))).cloned())
+            .unwrap_or_default();
+        Ok(CompareForm { base: base.unwrap_or_default(), other: self.tab_idx, keys, error: None })
+    }
+    
+    /// Start comparing the tabs of `form` on a background thread;
+    /// `poll_compare` opens the differing rows in a new tab
+    pub fn compare_tabs(&mut self, form: &CompareForm, options: TileStoreOptions) -> Result<String, String> {
+        if self.compare_job.is_some() {
+            return Err("A comparison is already running".to_string());
+        }
+        let side = |idx: usize| -> Result<CompareSide, String> {
+            let tab = self.tabs.get(idx).ok_or("That tab was closed")?;
+            let ResultsContent::Table { headers, tile_store, row_view, .. } = &tab.content else {
+                return Err(format!("Tab {} has no table to compare", idx + 1));
+            };
+            let reader = row_view.reader(tile_store).map_err(|e| format!("Compare failed: {}", e))?;
+            Ok(CompareSide { headers: headers.clone(), reader, rows: row_view.len(tile_store) })
+        };
+        let (base, other) = (side(form.base)?, side(form.other)?);
+        let has = |headers: &[String], key: &str| headers.iter().any(|h| h.eq_ignore_ascii_case(key));
+        let keys: Vec<String> = form.key_names().into_iter().map(str::to_string).collect();
+        if let Some(key) = keys.iter().find(|key| !has(&base.headers, key) || !has(&other.headers, key)) {
+            return Err(format!("Both tabs need a column named {}", key));
+        }
+        let title = format!("Tab {} compared with tab {} on {}", form.other + 1, form.base + 1, form.keys.trim());
+        self.compare_job = Some(CompareJob::start(base, other, keys, options, title));
+        Ok(format!("Comparing tab {} with tab {}...", form.other + 1, form.base + 1))
+    }
+
+    /// Take the running comparison's progress, opening its tab once it is
+    /// done; true if anything changed
+    pub fn poll_compare(&mut self) -> bool {
+        let Some(job) = &mut self.compare_job else { return false };
+        let scanned = job.scanned;
+        let Some(result) = job.poll() else {
+            if job.scanned == scanned {
+                return false;
+            }
+            let percent = job.scanned * 100 / job.total.max(1);
+            self.message = Some(format!("{} ({}%)", job.title, percent.min(100)));
+            return true;
+        };
+        let title = job.title.clone();
+        self.compare_job = None;
+        self.message = Some(match result {
+            Ok(comparison) => {
+                let kinds = vec![ColumnKind::Text; comparison.headers.len()];
+                let mut tab = ResultsTab::new_pending(title.clone());
+                tab.running = false;
+                tab.elapsed = Some(Duration::ZERO);
+                tab.content = ResultsContent::table(comparison.headers, comparison.rows, kinds);
+                self.tabs.push(tab);
+                self.tab_idx = self.tabs.len() - 1;
+                format!("{}: {}", title, comparison.summary)
+            }
+            Err(e) => e,
+        });
+        true
+    }
+    
+    /// Start counting the values of the cursor column of the current tab
+    pub fn distribution(&mut self) -> Result<Distribution, String> {
+        let tab = self.tabs.get(self.tab_idx).ok_or("No results to count")?;
+        let column = tab.cursor_column();
+        let ResultsContent::Table { headers, tile_store, row_view, .. } = &tab.content else {
+            return Err("Value counts work on table results only".to_string());
+        };
+        let reader = row_view.reader(tile_store).map_err(|e| format!("Value counts failed: {}", e))?;
+        Ok(Distribution::start(reader, headers[column].clone(), column, row_view.len(tile_store)))
+    }
+    
+    /// Export format picker for the current tab; exports the selection if
+    /// one is active, otherwise the whole result
+    pub fn export_prompt(&self) -> Result<ExportPrompt, String> {
+        let tab = self.tabs.get(self.tab_idx).ok_or("No results to export")?;
+        if !matches!(tab.content, ResultsContent::Table { .. }) {
+            return Err("Export works on table results only".to_string());
+        }
+        Ok(ExportPrompt {
+            tab_idx: self.tab_idx,
+            to_clipboard: tab.selection_anchor.is_some(),
+            table_guess: String::new(),
+            format: None,
+            typed: self.typed_json,
+        })
+    }
+
+    /// Fill the running tab with id `tab_id` with `result`, or open a new
+    /// tab if it is gone; returns its index
+    pub fn add_tab_result(&mut self, tab_id: Option<u64>, result: ResultsContent) -> usize {
+        let tab = self.tabs.iter_mut().enumerate().find(|(_, tab)| {
+            Some(tab.id) == tab_id && tab.running && matches!(tab.content, ResultsContent::Pending)
+        });
+        match tab {
+            Some((idx, tab)) => {
                 tab.content = result;
                 tab.running = false;
+                tab.cancelling = false;
                 tab.elapsed = tab.run_started.map(|s| s.elapsed());
-                return;
+                idx
             }
+            None => self.add_result(result),
         }
-        
-        // If no pending tab, create a new one
+    }
+    
+    /// Open a new tab with `result`; returns its index. Pending tabs are
+    /// left to the statements they were opened for.
+    pub fn add_result(&mut self, result: ResultsContent) -> usize {
         let mut tab = ResultsTab::new_pending(String::new());
         tab.content = result;
         tab.running = false;
         self.tabs.push(tab);
         self.tab_idx = self.tabs.len() - 1;
+        self.tab_idx
+    }
+    
+    /// Start the queued tab with id `tab_id`; returns its index, None if it
+    /// is gone
+    pub fn start_queued(&mut self, tab_id: u64, started: Instant) -> Option<usize> {
+        let idx = self.tabs.iter().position(|tab| tab.queued && tab.id == tab_id)?;
+        let tab = &mut self.tabs[idx];
+        tab.queued = false;
+        tab.running = true;
+        tab.run_started = Some(started);
+        Some(idx)
+    }
+    
+    /// Queue the current tab's statement to run again, in a new tab or in
+    /// place of the current one; returns the request to send
+    pub fn rerun(&mut self, new_tab: bool) -> Result<QueryRequest, String> {
+        let tab = self.tabs.get(self.tab_idx).ok_or("No results to re-run")?;
+        let request = tab.request.clone().ok_or("This tab didn't come from a query")?;
+        let mut rerun = ResultsTab::new_queued(request.context.clone(), 0);
+        rerun.request = Some(request.clone());
+        rerun.pinned = tab.pinned;
+        if new_tab {
+            self.tabs.push(rerun);
+            self.tab_idx = self.tabs.len() - 1;
+        } else {
+            self.tabs[self.tab_idx] = rerun;
+        }
+        Ok(request)
+    }
+    
+    pub fn has_queued(&self) -> bool {
+        self.tabs.iter().any(|tab| tab.queued)
+    }
+    
+    /// Statements of the batch are still queued or running
+    pub fn has_unfinished(&self) -> bool {
+        self.tabs.iter().any(|tab| tab.queued || (tab.running && matches!(tab.content, ResultsContent::Pending)))
+    }
+    
+    /// Close out the tabs of statements that will not run with `reason`;
+    /// returns how many there were
+    pub fn drop_queued(&mut self, reason: &str) -> usize {
+        let mut dropped = 0;
+        for tab in self.tabs.iter_mut().filter(|tab| tab.queued) {
+            tab.queued = false;
+            tab.content = ResultsContent::info(reason.to_string(), None);
+            dropped += 1;
+        }
+        dropped
+    }
+    
+    /// Show the running query's tab as cancelling until the worker confirms
+    pub fn mark_cancelling(&mut self) {
+        for tab in &mut self.tabs {
+            if tab.running {
+                tab.cancelling = true;
+            }
+        }
+    }
+    
+    /// Query ID of the current tab, if the query reached Snowflake
+    pub fn current_query_id(&self) -> Option<&str> {
+        self.tabs.get(self.tab_idx).and_then(|t| t.query_id.as_deref())
+    }
+    
+    fn copy_query_id(&mut self) {
+        let Some(id) = self.current_query_id().map(|id| id.to_string()) else {
+            self.message = Some("No query ID for this tab".to_string());
+            return;
+        };
+        self.message = Some(match crate::clipboard::set_text(id.clone()) {
+            Ok(()) => format!("Copied query ID {}", id),
+            Err(e) => format!("Copy failed: {}", e),
+        });
     }
     
     pub fn handle_key(&mut self, key: KeyEvent) {
+        let page = self.page_rows.max(1) as isize;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        self.message = None;
+        if self.search_input.is_some() {
+            self.handle_search_input(key);
+            return;
+        }
         match key.code {
             KeyCode::Tab => {
                 if !self.tabs.is_empty() && self.tabs.len() > 1 {
                     self.tab_idx = (self.tab_idx + 1) % self.tabs.len();
                 }
+                return;
+            }
+            KeyCode::Char('w') if ctrl => {
+                self.close_current_tab();
+                return;
+            }
+            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                self.copy_query_id();
+                return;
+            }
+            KeyCode::Char('O') => {
+                self.close_unpinned_tabs();
+                return;
             }
             _ => {}
         }
+        let Some(tab) = self.tabs.get_mut(self.tab_idx) else { return };
+        if let ResultsContent::Plan(plan) = &mut tab.content {
+            plan.handle_key(key);
+            return;
+        }
+        if let Some(mut text) = tab.content.message_text() {
+            if ctrl && key.code == KeyCode::Char('c') {
+                let what = if text.selection.is_some() { "selection" } else { "message" };
+                self.message = Some(match crate::clipboard::set_text(text.selected().to_string()) {
+                    Ok(()) => format!("Copied {}", what),
+                    Err(e) => format!("Copy failed: {}", e),
+                });
+                return;
+            }
+            if text.handle_key(key, self.text_width, self.page_rows.max(1)) {
+                return;
+            }
+        }
+        if ctrl && matches!(key.code, KeyCode::Char('d') | KeyCode::Char('u')) {
+            tab.selection_anchor = None;
+            tab.half_page(page, key.code == KeyCode::Char('d'));
+            return;
+        }
+        if ctrl && matches!(tab.content, ResultsContent::Table { .. }) {
+            let edge = match key.code {
+                KeyCode::Up => Some((-1, 0)),
+                KeyCode::Down => Some((1, 0)),
+                KeyCode::Left => Some((0, -1)),
+                KeyCode::Right => Some((0, 1)),
+                _ => None,
+            };
+            if let Some((drow, dcol)) = edge {
+                let (drow, dcol) = if tab.transposed { (dcol, drow) } else { (drow, dcol) };
+                if !key.modifiers.contains(KeyModifiers::SHIFT) {
+                    tab.selection_anchor = None;
+                } else if tab.selection_anchor.is_none() {
+                    tab.selection_anchor = Some((tab.cursor_row, tab.cursor_col));
+                }
+                if let Err(e) = tab.jump_to_edge(drow, dcol) {
+                    self.message = Some(format!("Read failed: {}", e));
+                }
+                tab.prefetch_around_cursor(page as usize);
+                return;
+            }
+        }
+        let motion = match key.code {
+            KeyCode::Up => Some((-1, 0)),
+            KeyCode::Down => Some((1, 0)),
+            KeyCode::Left => Some((0, -1)),
+            KeyCode::Right => Some((0, 1)),
+            KeyCode::PageUp => Some((-page, 0)),
+            KeyCode::PageDown => Some((page, 0)),
+            KeyCode::Home if ctrl => Some((isize::MIN / 2, isize::MIN / 2)),
+            KeyCode::End if ctrl => Some((isize::MAX / 2, isize::MAX / 2)),
+            KeyCode::Home => Some((0, isize::MIN / 2)),
+            KeyCode::End => Some((0, isize::MAX / 2)),
+            _ => None,
+        };
+        if let Some((drow, dcol)) = motion {
+            // Transposed, the columns run down the screen
+            let (drow, dcol) = if tab.transposed { (dcol, drow) } else { (drow, dcol) };
+            if key.modifiers.contains(KeyModifiers::SHIFT) {
+                tab.extend_selection(drow, dcol);
+            } else {
+                tab.selection_anchor = None;
+                tab.move_cursor(drow, dcol);
+            }
+            if drow.abs() > 1 {
+                tab.prefetch_around_cursor(page as usize);
+            }
+            return;
+        }
+        match key.code {
+            KeyCode::Esc => {
+                tab.selection_anchor = None;
+                tab.search = None;
+            }
+            KeyCode::Char('/') if matches!(tab.content, ResultsContent::Table { .. }) => {
+                self.search_input = Some(String::new());
+            }
+            KeyCode::Char('c') if ctrl => self.message = Some(tab.copy_selection(false)),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.message = Some(tab.copy_selection(true));
+            }
+            KeyCode::Char('h') => tab.hide_column(),
+            KeyCode::Char('H') => tab.show_all_columns(),
+            KeyCode::Char('f') | KeyCode::Char('F') => tab.toggle_pinned_columns(),
+            KeyCode::Char('<') => tab.move_column(-1),
+            KeyCode::Char('>') => tab.move_column(1),
+            KeyCode::Char('d') => tab.toggle_diff_column(),
+            KeyCode::Char('D') => {
+                tab.diff_cols = None;
+                tab.diff_pending = None;
+            }
+            KeyCode::Char('-') => tab.resize_column(-1),
+            KeyCode::Char('+') | KeyCode::Char('=') => tab.resize_column(1),
+            KeyCode::Char('w') => {
+                self.message = Some(tab.fit_column().unwrap_or_else(|e| e));
+            }
+            KeyCode::Char('W') => tab.width_overrides.clear(),
+            KeyCode::Char('t') | KeyCode::Char('T') if matches!(tab.content, ResultsContent::Table { .. }) => {
+                tab.transposed = !tab.transposed;
+            }
+            KeyCode::Char('l') | KeyCode::Char('L') => tab.pinned = !tab.pinned,
+            KeyCode::Char('o') if !ctrl => self.message = Some(tab.sort_rows()),
+            KeyCode::Char('v') if !ctrl => self.message = Some(tab.filter_rows()),
+            KeyCode::Char('g') if !ctrl => self.message = Some(tab.dedup_rows()),
+            KeyCode::Backspace if matches!(&tab.content, ResultsContent::Table { row_view, .. } if !row_view.is_all()) => {
+                self.message = Some(tab.set_row_view(Ok(RowView::default())));
+            }
+            KeyCode::Char(d) if d.is_ascii_digit() && matches!(tab.content, ResultsContent::Table { .. }) => {
+                let mut digits = match self.row_jump.take() {
+                    Some((digits, at)) if at.elapsed() < ROW_JUMP_TIMEOUT => digits,
+                    _ => String::new(),
+                };
+                digits.push(d);
+                let nrows = tab.dims().0;
+                let row = digits.parse::<usize>().unwrap_or(usize::MAX).clamp(1, nrows.max(1));
+                tab.selection_anchor = None;
+                tab.cursor_row = row - 1;
+                tab.prefetch_around_cursor(page as usize);
+                self.message = Some(format!("Row {} of {}", digits, nrows));
+                self.row_jump = Some((digits, Instant::now()));
+            }
+            KeyCode::Char('k') | KeyCode::Char('K') => {
+                let column = tab.cursor_column();
+                tab.key_col = if tab.key_col == Some(column) { None } else { Some(column) };
+            }
+            KeyCode::Char('e') if self.export_job.is_some() => {
+                self.message = Some("An export is already running; c cancels it".to_string());
+            }
+            KeyCode::Char('e') => match tab.export_view_job() {
+                Ok(job) => self.export_job = Some(job),
+                Err(message) => self.message = Some(message),
+            },
+            KeyCode::Char('a') | KeyCode::Char('A') => self.message = Some(tab.copy_selection_stats()),
+            KeyCode::Char('i') | KeyCode::Char('I') => {
+                self.message = Some(tab.copy_in_list(self.in_list_batch, self.identifier_quoting));
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') if tab.search.is_some() => {
+                if !tab.step_search(key.code == KeyCode::Char('n')) {
+                    self.message = Some("No matches".to_string());
+                }
+            }
+            KeyCode::Char('n') => { tab.jump_to_mismatch(true); }
+            KeyCode::Char('N') => { tab.jump_to_mismatch(false); }
+            _ => {}
+        }
+    }
+    
+    /// Mouse wheel over the pane: the grid scrolls with its cursor, which
+    /// keeps its place on screen, while a plan only moves its cursor
+    pub fn wheel_scroll(&mut self, rows: isize, cols: isize) {
+        let Some(tab) = self.tabs.get_mut(self.tab_idx) else { return };
+        if let ResultsContent::Plan(plan) = &mut tab.content {
+            plan.move_cursor(rows);
+            return;
+        }
+        if tab.transposed {
+            tab.move_cursor(cols, rows);
+        } else {
+            tab.scroll_by(rows, cols);
+        }
     }
     
-    pub fn render(&self, frame: &mut Frame, area: Rect, focused: bool) {
+    pub fn render(&mut self, frame: &mut Frame, area: Rect, focused: bool) {
+        let diff_info = match self.tabs.get(self.tab_idx) {
+            Some(ResultsTab { content: ResultsContent::Table { headers, .. }, diff_cols: Some((a, b)), .. }) => {
+                format!(" diff: {} vs {}", headers[*a], headers[*b])
+            }
+            Some(ResultsTab { content: ResultsContent::Table { headers, .. }, diff_pending: Some(a), .. }) => {
+                format!(" diff: {} vs ?", headers[*a])
+            }
+            _ => String::new(),
+        };
+        let key_info = match self.tabs.get(self.tab_idx) {
+            Some(ResultsTab { content: ResultsContent::Table { headers, .. }, key_col: Some(k), .. }) => {
+                format!(" key: {}", headers[*k])
+            }
+            _ => String::new(),
+        };
+        let pin_info = match self.tabs.get(self.tab_idx) {
+            Some(tab) if tab.transposed && matches!(tab.content, ResultsContent::Table { .. }) => {
+                format!(" transposed: row {}/{}", tab.cursor_row + 1, tab.dims().0)
+            }
+            Some(tab) if tab.pinned_cols > 0 && matches!(tab.content, ResultsContent::Table { .. }) => {
+                format!(" pinned: {}", tab.pinned_cols)
+            }
+            _ => String::new(),
+        };
         let block = Block::default()
             .borders(Borders::ALL)
-            .title(format!("Results {}", 
-                if self.tabs.is_empty() { 
-                    String::new() 
-                } else { 
-                    format!("({}/{})", self.tab_idx + 1, self.tabs.len()) 
-                }
+            .title(format!("Results {}{}{}{}", 
+                match self.tabs.get(self.tab_idx) {
+                    None => String::new(),
+                    Some(tab) if tab.pinned => format!("({}/{}, pinned)", self.tab_idx + 1, self.tabs.len()),
+                    Some(_) => format!("({}/{})", self.tab_idx + 1, self.tabs.len()),
+                },
+                diff_info,
+                key_info,
+                pin_info
             ))
+            .title_bottom(self.search_input.as_ref().map(|input| format!(" /{}_ ", input))
+                .or_else(|| self.message.clone())
+                .or_else(|| self.export_job.as_ref().map(|job| job.status()))
+                .or_else(|| self.tabs.get(self.tab_idx).and_then(|t| t.search.as_ref()).map(|s| s.status()))
+                .unwrap_or_default())
             .border_style(if focused {
                 Style::default().fg(Color::Cyan)
             } else {
                 Style::default().fg(Color::Gray)
             });
         
-        let inner = block.inner(area);
+        let mut inner = block.inner(area);
         frame.render_widget(block, area);
         
+        // Summary line kept at the bottom of finished tabs
+        if let Some(tab) = self.tabs.get_mut(self.tab_idx) {
+            tab.update_selection_stats();
+        }
+        if let Some(footer) = self.tabs.get(self.tab_idx).and_then(|t| t.footer()) {
+            if inner.height >= 2 {
+                inner.height -= 1;
+                let footer_area = Rect { y: inner.y + inner.height, height: 1, ..inner };
+                let truncated = self.tabs.get(self.tab_idx).map_or(false, |t| t.truncated);
+                let style = Style::default().fg(if truncated { Color::Yellow } else { Color::DarkGray });
+                frame.render_widget(Paragraph::new(footer).style(style), footer_area);
+            }
+        }
+        
         if self.tabs.is_empty() {
             let paragraph = Paragraph::new("No results yet. Press Ctrl+Enter to run a query.");
             frame.render_widget(paragraph, inner);
-        } else if let Some(tab) = self.tabs.get(self.tab_idx) {
+        } else if let Some(tab) = self.tabs.get_mut(self.tab_idx) {
             match &tab.content {
                 ResultsContent::Pending => {
-                    let msg = if tab.running {
-                        format!("Running query... ({:.1}s)", 
-                            tab.run_started.map(|s| s.elapsed().as_secs_f32()).unwrap_or(0.0))
+                    let secs = tab.run_started.map(|s| s.elapsed().as_secs_f32()).unwrap_or(0.0);
+                    let msg = if tab.cancelling && self.animate_timer {
+                        format!("Cancelling query... ({:.1}s)", secs)
+                    } else if tab.cancelling {
+                        "Cancelling query...".to_string()
+                    } else if tab.running && self.animate_timer {
+                        format!("Running query... ({:.1}s)", secs)
+                    } else if tab.running {
+                        "Running query...".to_string()
+                    } else if tab.queued {
+                        format!("Queued: {}", tab.query_context)
                     } else {
                         "Query pending...".to_string()
                     };
                     let paragraph = Paragraph::new(msg);
                     frame.render_widget(paragraph, inner);
                 }
-                ResultsContent::Info { message } => {
-                    let paragraph = Paragraph::new(message.as_str());
-                    frame.render_widget(paragraph, inner);
+                ResultsContent::Info { .. } | ResultsContent::Error { .. } => {
+                    self.page_rows = inner.height as usize;
+                    self.text_width = inner.width as usize;
+                    let style = if matches!(tab.content, ResultsContent::Error { .. }) {
+                        Style::default().fg(Color::Red)
+                    } else {
+                        Style::default()
+                    };
+                    if let Some(text) = tab.content.message_text() {
+                        text.render(frame, inner, focused, style, &mut tab.view_row);
+                    }
                 }
-                ResultsContent::Error { message, .. } => {
-                    let paragraph = Paragraph::new(message.as_str())
-                        .style(Style::default().fg(Color::Red));
+                ResultsContent::Cancelled { elapsed } => {
+                    let paragraph = Paragraph::new(format!("Cancelled after {:.1}s", elapsed.as_secs_f32()))
+                        .style(Style::default().fg(Color::Yellow));
                     frame.render_widget(paragraph, inner);
                 }
+                ResultsContent::Plan(_) => {
+                    if let ResultsContent::Plan(plan) = &mut tab.content {
+                        plan.render(frame, inner, focused);
+                    }
+                }
                 ResultsContent::Table { .. } => {
-                    // TODO: Render actual table results
-                    let paragraph = Paragraph::new("Table results will be displayed here");
-                    frame.render_widget(paragraph, inner);
+                    self.page_rows = inner.height.saturating_sub(1) as usize;
+                    if tab.transposed {
+                        tab.render_transposed(frame, inner, focused, self.null_style);
+                    } else {
+                        tab.render_table(frame, inner, focused, self.find_styles, self.null_style, self.scroll);
+                    }
                 }
             }
         }
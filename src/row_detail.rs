@@ -0,0 +1,100 @@
+use crate::overlay::OverlayOutcome;
+use crate::results::printable;
+use crate::tile_rowstore::NULL_SENTINEL;
+use crossterm::event::{KeyCode, KeyEvent};
+use unicode_width::UnicodeWidthStr;
+
+/// Longer column names are cut off
+const MAX_NAME_WIDTH: usize = 30;
+
+/// One result row as a list of column names and values, long values
+/// wrapped; c copies the selected value and C the whole row
+#[derive(Debug)]
+pub struct RowDetail {
+    /// 0-based row in the result
+    pub row: usize,
+    /// Column names and stored values, in display order
+    pub fields: Vec<(String, String)>,
+    pub selected: usize,
+    /// First line shown; drawing keeps the selected field in view
+    pub scroll: usize,
+    /// Submitted with C, to copy every field rather than the selected one
+    pub copy_all: bool,
+}
+
+impl RowDetail {
+    pub fn new(row: usize, fields: Vec<(String, String)>, selected: usize) -> Self {
+        Self { row, fields, selected, scroll: 0, copy_all: false }
+    }
+
+    /// Columns the names are shown in, up to MAX_NAME_WIDTH
+    pub fn name_width(&self) -> usize {
+        self.fields.iter().map(|(name, _)| printable(name).width()).max().unwrap_or(1).clamp(1, MAX_NAME_WIDTH)
+    }
+
+    /// Text copied: the selected value, or `name: value` lines for the
+    /// row. NULL copies as nothing, as in the grid.
+    pub fn copy_text(&self) -> String {
+        let value = |v: &str| if v == NULL_SENTINEL { String::new() } else { v.to_string() };
+        if self.copy_all {
+            self.fields.iter().map(|(name, v)| format!("{}: {}", name, value(v))).collect::<Vec<_>>().join("\n")
+        } else {
+            self.fields.get(self.selected).map(|(_, v)| value(v)).unwrap_or_default()
+        }
+    }
+
+    /// Lines of the values at `width` columns, each with the field it
+    /// belongs to. Line breaks in a value are kept; NULL is None.
+    pub fn layout(&self, width: usize) -> Vec<(usize, Option<String>)> {
+        let mut lines = Vec::new();
+        for (i, (_, value)) in self.fields.iter().enumerate() {
+            if value == NULL_SENTINEL {
+                lines.push((i, None));
+                continue;
+            }
+            for line in value.split('\n') {
+                for piece in wrap(&printable(line.trim_end_matches('\r')), width) {
+                    lines.push((i, Some(piece)));
+                }
+            }
+        }
+        lines
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        let last = self.fields.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('c') => return OverlayOutcome::Submit,
+            KeyCode::Char('C') => {
+                self.copy_all = true;
+                return OverlayOutcome::Submit;
+            }
+            KeyCode::Enter | KeyCode::Char('q') => return OverlayOutcome::Close,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+            KeyCode::PageDown => self.selected = (self.selected + 10).min(last),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+}
+
+/// `text` cut into pieces of at most `width` columns; an empty text is
+/// one empty piece
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut pieces = vec![String::new()];
+    let mut used = 0;
+    for ch in text.chars() {
+        let w = ch.encode_utf8(&mut [0; 4]).width();
+        if used + w > width.max(1) && used > 0 {
+            pieces.push(String::new());
+            used = 0;
+        }
+        pieces.last_mut().expect("starts with a piece").push(ch);
+        used += w;
+    }
+    pieces
+}
@@ -0,0 +1,342 @@
+use crate::tile_rowstore::{TileReader, TileRowStore, NULL_SENTINEL, TILE_SIZE};
+use arrow_array::RecordBatch;
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fs::File,
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    sync::{
+        mpsc::{self, Receiver},
+        Arc,
+    },
+};
+use tempfile::NamedTempFile;
+
+/// Views of more rows than this keep their positions in a temp file
+const SPILL_POSITIONS: usize = 1 << 20;
+
+/// View rows read together on a scan. Sorted rows are spread over the
+/// store, so each store tile is loaded once per window rather than once per
+/// row; a tile's worth at a time would load most tiles for every view tile.
+const WINDOW_ROWS: usize = 64 * TILE_SIZE;
+
+/// The rows of a result as a sort, filter or dedup left them: positions
+/// into the tile store, so none of them rewrites the rows. Without one
+/// every row shows, in the order it was fetched.
+#[derive(Debug, Default)]
+pub struct RowView {
+    index: Option<Box<RowIndex>>,
+}
+
+#[derive(Debug)]
+struct RowIndex {
+    positions: Positions,
+    /// What made the view, first step first, e.g. `sorted by id desc`
+    steps: Vec<String>,
+    /// Column and direction (true descending) of a sort that was the last step
+    sort: Option<(usize, bool)>,
+}
+
+/// Store rows in view order
+#[derive(Debug)]
+enum Positions {
+    Memory(Arc<Vec<u64>>),
+    /// Little-endian u64s, read through a handle of its own
+    File { temp: Arc<NamedTempFile>, file: File, len: usize },
+}
+
+impl Positions {
+    fn new(positions: Vec<u64>) -> io::Result<Self> {
+        if positions.len() <= SPILL_POSITIONS {
+            return Ok(Positions::Memory(Arc::new(positions)));
+        }
+        let mut temp = NamedTempFile::new()?;
+        let mut out = BufWriter::new(temp.as_file_mut());
+        for &position in &positions {
+            out.write_u64::<LittleEndian>(position)?;
+        }
+        out.flush()?;
+        drop(out);
+        let file = File::open(temp.path())?;
+        Ok(Positions::File { temp: Arc::new(temp), file, len: positions.len() })
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Positions::Memory(positions) => positions.len(),
+            Positions::File { len, .. } => *len,
+        }
+    }
+
+    /// The same positions through a new handle, for another thread
+    fn reopen(&self) -> io::Result<Self> {
+        Ok(match self {
+            Positions::Memory(positions) => Positions::Memory(positions.clone()),
+            Positions::File { temp, len, .. } => Positions::File {
+                temp: temp.clone(),
+                file: File::open(temp.path())?,
+                len: *len,
+            },
+        })
+    }
+
+    /// Positions of view rows start..start+count, cut off at the end
+    fn read(&mut self, start: usize, count: usize) -> io::Result<Vec<u64>> {
+        let end = self.len().min(start.saturating_add(count));
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        match self {
+            Positions::Memory(positions) => Ok(positions[start..end].to_vec()),
+            Positions::File { file, .. } => {
+                let mut buf = vec![0u8; (end - start) * 8];
+                file.seek(SeekFrom::Start(start as u64 * 8))?;
+                file.read_exact(&mut buf)?;
+                Ok(buf.chunks_exact(8).map(LittleEndian::read_u64).collect())
+            }
+        }
+    }
+}
+
+impl RowView {
+    /// No sort, filter or dedup applied
+    pub fn is_all(&self) -> bool {
+        self.index.is_none()
+    }
+
+    /// Rows in the view
+    pub fn len(&self, store: &TileRowStore) -> usize {
+        self.index.as_ref().map_or(store.nrows, |index| index.positions.len())
+    }
+
+    /// `sorted by id desc, status = 'OPEN'`; None for every row
+    pub fn describe(&self) -> Option<String> {
+        self.index.as_ref().map(|index| index.steps.join(", "))
+    }
+
+    /// Column and direction of the sort that made the view, if that was the
+    /// last step
+    pub fn sort_column(&self) -> Option<(usize, bool)> {
+        self.index.as_ref().and_then(|index| index.sort)
+    }
+
+    /// View rows start..start+count with their store positions
+    fn positioned_rows(&mut self, store: &mut TileRowStore, start: usize, count: usize) -> io::Result<Vec<(u64, Vec<String>)>> {
+        let Some(index) = &mut self.index else {
+            let rows = store.get_rows(start, count)?;
+            return Ok(rows.into_iter().zip(start as u64..).map(|(row, p)| (p, row)).collect());
+        };
+        let positions = index.positions.read(start, count)?;
+        // Read in store order so each tile is loaded once, then put back
+        let mut order: Vec<usize> = (0..positions.len()).collect();
+        order.sort_by_key(|&i| positions[i]);
+        let mut rows = vec![Vec::new(); positions.len()];
+        for i in order {
+            rows[i] = store.get_rows(positions[i] as usize, 1)?.pop().unwrap_or_default();
+        }
+        Ok(positions.into_iter().zip(rows).collect())
+    }
+
+    /// View rows start..start+count
+    pub fn get_rows(&mut self, store: &mut TileRowStore, start: usize, count: usize) -> io::Result<Vec<Vec<String>>> {
+        if self.index.is_none() {
+            return store.get_rows(start, count);
+        }
+        Ok(self.positioned_rows(store, start, count)?.into_iter().map(|(_, row)| row).collect())
+    }
+
+    /// Load the tiles of the view rows from `view_row` ahead of a draw;
+    /// only the fetch order has them together
+    pub fn prefetch(&self, store: &mut TileRowStore, view_row: usize, max_rows: usize) {
+        if self.index.is_none() {
+            store.prefetch_for_view(view_row, max_rows);
+        }
+    }
+
+    /// Sequential access to the view's rows from another thread
+    pub fn reader(&self, store: &TileRowStore) -> io::Result<ViewReader> {
+        let positions = match &self.index {
+            Some(index) => Some(index.positions.reopen()?),
+            None => None,
+        };
+        Ok(ViewReader { reader: store.reader()?, positions, window: (0, Vec::new()) })
+    }
+
+    /// Build the view one `step` further than this one on a background
+    /// thread, reading the rows through a separate handle on the store; the
+    /// view arrives on the returned channel
+    pub fn build(&self, store: &TileRowStore, step: ViewStep) -> io::Result<Receiver<io::Result<RowView>>> {
+        let mut reader = self.reader(store)?;
+        let steps = self.index.as_ref().map(|index| index.steps.clone()).unwrap_or_default();
+        let last_sort = self.sort_column();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = tx.send(step.run(&mut reader, steps, last_sort));
+        });
+        Ok(rx)
+    }
+}
+
+/// A view with `positions`, one step further than one made by `steps`
+/// that ended in `last_sort`
+fn then(mut steps: Vec<String>, last_sort: Option<(usize, bool)>, positions: Vec<u64>, step: String, sort: Option<(usize, bool)>) -> io::Result<RowView> {
+    // Sorting again on the same column replaces the sort
+    if let (Some((last, _)), Some((column, _))) = (last_sort, sort) {
+        if last == column {
+            steps.pop();
+        }
+    }
+    steps.push(step);
+    let positions = Positions::new(positions)?;
+    Ok(RowView { index: Some(Box::new(RowIndex { positions, steps, sort })) })
+}
+
+/// How a view is narrowed or reordered from the one before it
+#[derive(Debug)]
+pub enum ViewStep {
+    /// Order on `column`, NULLs last. The sort is stable, so sorting a
+    /// sorted view on another column breaks its ties.
+    Sort { column: usize, name: String, descending: bool, numeric: bool },
+    /// Keep the rows whose `column` holds `value`
+    Filter { column: usize, name: String, value: String },
+    /// Drop the rows repeating an earlier row exactly
+    Dedup,
+}
+
+impl ViewStep {
+    /// The view this step makes of the one `reader` reads, which was made
+    /// by `steps` ending in `last_sort`
+    fn run(self, reader: &mut ViewReader, steps: Vec<String>, last_sort: Option<(usize, bool)>) -> io::Result<RowView> {
+        match self {
+            ViewStep::Sort { column, name, descending, numeric } => {
+                let mut keyed: Vec<(u64, String)> = Vec::new();
+                reader.scan(&mut |position, mut row| {
+                    keyed.push((position, if column < row.len() { row.swap_remove(column) } else { String::new() }));
+                })?;
+                keyed.sort_by(|(_, a), (_, b)| compare_values(a, b, descending, numeric));
+                let step = format!("sorted by {}{}", name, if descending { " desc" } else { "" });
+                then(steps, last_sort, keyed.into_iter().map(|(p, _)| p).collect(), step, Some((column, descending)))
+            }
+            ViewStep::Filter { column, name, value } => {
+                let mut kept = Vec::new();
+                reader.scan(&mut |position, row| {
+                    if row.get(column) == Some(&value) {
+                        kept.push(position);
+                    }
+                })?;
+                let shown = if value == NULL_SENTINEL { "NULL".to_string() } else { format!("'{}'", value) };
+                then(steps, last_sort, kept, format!("{} = {}", name, shown), None)
+            }
+            ViewStep::Dedup => {
+                let mut seen = HashSet::new();
+                let mut kept = Vec::new();
+                reader.scan(&mut |position, row| {
+                    if seen.insert(row) {
+                        kept.push(position);
+                    }
+                })?;
+                then(steps, last_sort, kept, "repeated rows dropped".to_string(), None)
+            }
+        }
+    }
+}
+
+/// Order of two values for a sort; NULLs go last either way, and numbers
+/// that don't parse sort after the ones that do
+fn compare_values(a: &str, b: &str, descending: bool, numeric: bool) -> Ordering {
+    match (a == NULL_SENTINEL, b == NULL_SENTINEL) {
+        (true, true) => return Ordering::Equal,
+        (true, false) => return Ordering::Greater,
+        (false, true) => return Ordering::Less,
+        _ => {}
+    }
+    let order = if numeric {
+        match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+            (Ok(x), Ok(y)) => x.total_cmp(&y),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            _ => a.cmp(b),
+        }
+    } else {
+        a.cmp(b)
+    };
+    if descending { order.reverse() } else { order }
+}
+
+/// Uncached, sequential access to a view's rows, a tile's worth of view
+/// rows at a time, through a separate handle on the store's file
+#[derive(Debug)]
+pub struct ViewReader {
+    reader: TileReader,
+    /// None reads the store's tiles as they are
+    positions: Option<Positions>,
+    /// First view row and the rows of the window last read
+    window: (usize, Vec<Vec<String>>),
+}
+
+impl ViewReader {
+    pub fn tile_count(&self) -> usize {
+        match &self.positions {
+            Some(positions) => positions.len().div_ceil(TILE_SIZE),
+            None => self.reader.tile_count(),
+        }
+    }
+
+    pub fn read_tile(&mut self, idx: usize) -> io::Result<Vec<Vec<String>>> {
+        let Some(positions) = &mut self.positions else {
+            return self.reader.read_tile(idx);
+        };
+        let start = idx * TILE_SIZE;
+        let (window_start, window) = &self.window;
+        if start < *window_start || start >= window_start + window.len() {
+            let window_start = start / WINDOW_ROWS * WINDOW_ROWS;
+            let positions = positions.read(window_start, WINDOW_ROWS)?;
+            let mut order: Vec<usize> = (0..positions.len()).collect();
+            order.sort_by_key(|&i| positions[i]);
+            let mut rows = vec![Vec::new(); positions.len()];
+            let mut tile: Option<(usize, Vec<Vec<String>>)> = None;
+            for i in order {
+                let position = positions[i] as usize;
+                let tile_idx = position / TILE_SIZE;
+                if tile.as_ref().is_none_or(|(t, _)| *t != tile_idx) {
+                    tile = Some((tile_idx, self.reader.read_tile(tile_idx)?));
+                }
+                rows[i] = tile.as_ref()
+                    .and_then(|(_, tile_rows)| tile_rows.get(position % TILE_SIZE).cloned())
+                    .unwrap_or_default();
+            }
+            self.window = (window_start, rows);
+        }
+        let (window_start, window) = &self.window;
+        let from = (start - window_start).min(window.len());
+        Ok(window[from..(from + TILE_SIZE).min(window.len())].to_vec())
+    }
+
+    /// Hand every view row to `each` with its store position, a tile at a
+    /// time
+    fn scan(&mut self, each: &mut dyn FnMut(u64, Vec<String>)) -> io::Result<()> {
+        for tile in 0..self.tile_count() {
+            let rows = self.read_tile(tile)?;
+            let start = tile * TILE_SIZE;
+            let positions = match &mut self.positions {
+                Some(positions) => positions.read(start, rows.len())?,
+                None => (start as u64..(start + rows.len()) as u64).collect(),
+            };
+            for (position, row) in positions.into_iter().zip(rows) {
+                each(position, row);
+            }
+        }
+        Ok(())
+    }
+
+    /// View tile `idx` as a record batch: the store's own tile where the
+    /// view keeps the fetch order, else one built from the view's rows
+    pub fn read_batch(&mut self, idx: usize) -> io::Result<RecordBatch> {
+        if self.positions.is_none() {
+            return self.reader.read_batch(idx);
+        }
+        let rows = self.read_tile(idx)?;
+        self.reader.rows_batch(&rows)
+    }
+}
@@ -0,0 +1,29 @@
+use crate::overlay::OverlayOutcome;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// File name to save an editor buffer that has none yet to
+#[derive(Debug)]
+pub struct SaveAsPrompt {
+    pub path: String,
+    /// Why the last Enter didn't save
+    pub error: Option<String>,
+}
+
+impl SaveAsPrompt {
+    pub fn new(path: String) -> Self {
+        SaveAsPrompt { path, error: None }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        match key.code {
+            KeyCode::Enter if !self.path.trim().is_empty() => return OverlayOutcome::Submit,
+            KeyCode::Backspace => {
+                self.path.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => self.path.push(c),
+            _ => return OverlayOutcome::Consumed,
+        }
+        self.error = None;
+        OverlayOutcome::Consumed
+    }
+}
@@ -0,0 +1,142 @@
+use crate::export::ColumnKind;
+use crate::export_job::{JobProgress, TileSource};
+use crate::overlay::OverlayOutcome;
+use crate::tile_rowstore::{TileRowStore, TileStoreOptions};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub const EXTENSION: &str = "frostres";
+
+/// Magic header for file sanity
+const MAGIC: &[u8; 4] = b"FRES";
+
+/// On-disk format version, written right after the magic
+const FORMAT_VERSION: u8 = 1;
+
+/// What a saved result came from, stored as JSON ahead of the rows
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedMeta {
+    pub headers: Vec<String>,
+    pub kinds: Vec<ColumnKind>,
+    /// The statement as sent, None for tabs that didn't come from one
+    pub sql: Option<String>,
+    pub context: String,
+    pub warehouse: Option<String>,
+    pub timeout: Option<Duration>,
+    pub session_context: Option<String>,
+    pub query_id: Option<String>,
+    pub elapsed: Option<Duration>,
+    /// The fetch had stopped at `max_rows`
+    pub truncated: bool,
+    pub saved_at: String,
+    pub rows: usize,
+}
+
+/// Write `meta` and every row of `source` to `path` as a `.frostres` file:
+/// `FRES`, the version, the JSON length (u64) and JSON, then each value as
+/// its length (u64) and bytes, row by row
+pub fn write(path: &Path, meta: &SavedMeta, source: &mut TileSource, progress: &mut JobProgress) -> io::Result<usize> {
+    let mut out = BufWriter::new(progress.count(File::create(path)?));
+    out.write_all(MAGIC)?;
+    out.write_u8(FORMAT_VERSION)?;
+    let json = serde_json::to_vec(meta)?;
+    out.write_u64::<LittleEndian>(json.len() as u64)?;
+    out.write_all(&json)?;
+    let mut written = 0;
+    source.scan(progress, true, &mut |rows| {
+        for value in rows.iter().flatten() {
+            out.write_u64::<LittleEndian>(value.len() as u64)?;
+            out.write_all(value.as_bytes())?;
+        }
+        written += rows.len();
+        Ok(())
+    })?;
+    out.flush()?;
+    Ok(written)
+}
+
+/// Read a file written by `write` back into a tile store
+pub fn read(path: &Path, options: TileStoreOptions) -> io::Result<(SavedMeta, TileRowStore)> {
+    let invalid = |why: &str| io::Error::new(io::ErrorKind::InvalidData, format!("not a saved result ({})", why));
+    let file = File::open(path)?;
+    // No length in the file can be longer than the file itself, so a corrupt
+    // one fails before anything is allocated for it
+    let size = file.metadata()?.len();
+    let checked = |len: u64| match usize::try_from(len) {
+        Ok(len) if len as u64 <= size => Ok(len),
+        _ => Err(invalid("length past the end of the file")),
+    };
+    let mut file = BufReader::new(file);
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic).map_err(|_| invalid("too short"))?;
+    if &magic != MAGIC {
+        return Err(invalid("bad header"));
+    }
+    let version = file.read_u8()?;
+    if version != FORMAT_VERSION {
+        return Err(invalid(&format!("format version {}", version)));
+    }
+    let len = checked(file.read_u64::<LittleEndian>()?)?;
+    let mut json = vec![0u8; len];
+    file.read_exact(&mut json)?;
+    let meta: SavedMeta = serde_json::from_slice(&json).map_err(|e| invalid(&e.to_string()))?;
+
+    // from_rows takes plain rows, so a read error ends them early and is
+    // reported after
+    let ncols = meta.headers.len();
+    let mut error = None;
+    let rows = (0..meta.rows).map_while(|_| {
+        let row: io::Result<Vec<String>> = (0..ncols)
+            .map(|_| {
+                let len = checked(file.read_u64::<LittleEndian>()?)?;
+                let mut buf = vec![0u8; len];
+                file.read_exact(&mut buf)?;
+                Ok(String::from_utf8_lossy(&buf).into_owned())
+            })
+            .collect();
+        row.map_err(|e| error = Some(e)).ok()
+    });
+    let store = TileRowStore::from_rows(&meta.headers, &meta.kinds, rows, options)?;
+    match error {
+        Some(e) => Err(e),
+        None => Ok((meta, store)),
+    }
+}
+
+/// `name` with the `.frostres` extension unless it has one
+pub fn with_extension(name: &str) -> PathBuf {
+    let path = PathBuf::from(name.trim());
+    if path.extension().is_some() { path } else { path.with_extension(EXTENSION) }
+}
+
+/// File name to save a results tab to, or to open a saved one from
+#[derive(Debug)]
+pub struct ResultFilePrompt {
+    /// Tab to save; None opens a file into a new tab
+    pub save_tab: Option<usize>,
+    pub path: String,
+    /// Why the last Enter didn't save or open
+    pub error: Option<String>,
+}
+
+impl ResultFilePrompt {
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        match key.code {
+            KeyCode::Enter if !self.path.trim().is_empty() => return OverlayOutcome::Submit,
+            KeyCode::Backspace => {
+                self.path.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => self.path.push(c),
+            _ => return OverlayOutcome::Consumed,
+        }
+        self.error = None;
+        OverlayOutcome::Consumed
+    }
+}
@@ -0,0 +1,110 @@
+/// Replace every `${NAME}` in `input` with the value of environment variable NAME.
+/// A missing variable is an error rather than an empty string, so a typo never
+/// turns into a silently blank password.
+pub fn interpolate_env(input: &str) -> anyhow::Result<String> {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find('}')
+            .ok_or_else(|| anyhow::anyhow!("Unterminated ${{...}} in connection_string"))?;
+        let name = &after[..end];
+        let value = std::env::var(name)
+            .map_err(|_| anyhow::anyhow!("Environment variable {} referenced in connection_string is not set", name))?;
+        out.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Look up a password in the OS keyring.
+///
+/// Linux uses the Secret Service via `secret-tool`, macOS the login keychain via
+/// `security`, Windows a generic credential named `<service>/<account>` in the
+/// Credential Manager. Store it first with e.g.
+/// `secret-tool store --label=frost service frost account <user>`,
+/// `security add-generic-password -s frost -a <user> -w` or
+/// `cmdkey /generic:frost/<user> /user:<user> /pass`.
+pub fn keyring_password(service: &str, account: &str) -> anyhow::Result<String> {
+    #[cfg(windows)]
+    let password = credential_manager_password(service, account)?;
+    #[cfg(not(windows))]
+    let password = keyring_command_password(service, account)?;
+    if password.is_empty() {
+        return Err(anyhow::anyhow!("Keyring entry for '{}' is empty", account));
+    }
+    Ok(password)
+}
+
+#[cfg(not(windows))]
+fn keyring_command_password(service: &str, account: &str) -> anyhow::Result<String> {
+    use std::process::Command;
+
+    #[cfg(target_os = "macos")]
+    let output = Command::new("security")
+        .args(["find-generic-password", "-s", service, "-a", account, "-w"])
+        .output();
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let output = Command::new("secret-tool")
+        .args(["lookup", "service", service, "account", account])
+        .output();
+    #[cfg(not(unix))]
+    let output: std::io::Result<std::process::Output> = {
+        let _ = (service, account, Command::new);
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "keyring lookup is not supported on this platform; use ${ENV_VAR} interpolation instead",
+        ))
+    };
+
+    let output = output.map_err(|e| anyhow::anyhow!("Keyring lookup failed: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "No keyring entry for service '{}' account '{}'", service, account
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(windows)]
+fn credential_manager_password(service: &str, account: &str) -> anyhow::Result<String> {
+    use windows_sys::Win32::Foundation::{GetLastError, ERROR_NOT_FOUND};
+    use windows_sys::Win32::Security::Credentials::{CredFree, CredReadW, CREDENTIALW, CRED_TYPE_GENERIC};
+
+    let target: Vec<u16> = format!("{}/{}", service, account).encode_utf16().chain(std::iter::once(0)).collect();
+    let mut credential: *mut CREDENTIALW = std::ptr::null_mut();
+    unsafe {
+        if CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &mut credential) == 0 {
+            let error = GetLastError();
+            if error == ERROR_NOT_FOUND {
+                return Err(anyhow::anyhow!(
+                    "No keyring entry for service '{}' account '{}'", service, account
+                ));
+            }
+            return Err(anyhow::anyhow!(
+                "Keyring lookup failed: {}", std::io::Error::from_raw_os_error(error as i32)
+            ));
+        }
+        let blob = &*credential;
+        let bytes = if blob.CredentialBlob.is_null() {
+            &[][..]
+        } else {
+            std::slice::from_raw_parts(blob.CredentialBlob, blob.CredentialBlobSize as usize)
+        };
+        // cmdkey and the Credential Manager UI store the password as UTF-16
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+        let password = String::from_utf16_lossy(&units);
+        CredFree(credential as *const _);
+        Ok(password)
+    }
+}
+
+/// Value of `key` in an ODBC `Key=Value;` connection string (case-insensitive key)
+pub fn connection_string_value(conn_str: &str, key: &str) -> Option<String> {
+    conn_str.split(';')
+        .filter_map(|part| part.split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case(key))
+        .map(|(_, v)| v.trim().to_string())
+}
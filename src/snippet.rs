@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+/// Snippets available without configuration; `[snippets]` entries with the
+/// same trigger replace them
+const BUILTIN: &[(&str, &str)] = &[
+    ("sel", "SELECT * FROM $1 WHERE $2 LIMIT 100"),
+    (
+        "merge",
+        "MERGE INTO ${1:target} t\nUSING ${2:source} s\n    ON ${3:t.id = s.id}\n\
+         WHEN MATCHED THEN UPDATE SET $4\nWHEN NOT MATCHED THEN INSERT ($5) VALUES ($6);",
+    ),
+    (
+        "copy",
+        "COPY INTO ${1:table}\nFROM @${2:stage}/$3\n\
+         FILE_FORMAT = (TYPE = ${4:CSV} SKIP_HEADER = 1)\nON_ERROR = ${5:ABORT_STATEMENT};",
+    ),
+    (
+        "task",
+        "CREATE OR REPLACE TASK ${1:name}\n    WAREHOUSE = ${2:warehouse}\n    SCHEDULE = \
+         '${3:USING CRON 0 * * * * UTC}'\nAS\n$0;",
+    ),
+];
+
+/// Built-in snippets overlaid with the configured ones, by trigger
+pub fn with_builtins(configured: &HashMap<String, String>) -> HashMap<String, String> {
+    let mut snippets: HashMap<String, String> = BUILTIN.iter()
+        .map(|(trigger, body)| (trigger.to_string(), body.to_string()))
+        .collect();
+    snippets.extend(configured.iter().map(|(trigger, body)| (trigger.clone(), body.clone())));
+    snippets
+}
+
+/// Text of a snippet body and its tab stops as byte ranges, in the order
+/// Tab visits them. `$N` is an empty stop, `${N:text}` one filled with
+/// text, and `$0` (or else the end) comes last; a number used twice is
+/// a stop only the first time. Any other `$` is literal, so `$$` blocks
+/// survive. Lines after the first get `indent` in front.
+pub fn expand(body: &str, indent: &str) -> (String, Vec<(usize, usize)>) {
+    let body = body.replace('\n', &format!("\n{}", indent));
+    let mut text = String::new();
+    let mut stops: Vec<(u32, usize, usize)> = Vec::new();
+    let mut rest = body.as_str();
+    while let Some(i) = rest.find('$') {
+        text.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let Some((number, default, len)) = parse_stop(rest) else {
+            text.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+        let start = text.len();
+        text.push_str(default);
+        if !stops.iter().any(|&(n, ..)| n == number) {
+            stops.push((number, start, text.len()));
+        }
+        rest = &rest[len..];
+    }
+    text.push_str(rest);
+    if !stops.iter().any(|&(n, ..)| n == 0) {
+        stops.push((0, text.len(), text.len()));
+    }
+    stops.sort_by_key(|&(n, ..)| if n == 0 { u32::MAX } else { n });
+    (text, stops.into_iter().map(|(_, start, end)| (start, end)).collect())
+}
+
+/// `$N` or `${N:text}` at the start of `s`: its number, text and length
+fn parse_stop(s: &str) -> Option<(u32, &str, usize)> {
+    let s = s.strip_prefix('$')?;
+    if let Some(inner) = s.strip_prefix('{') {
+        let end = inner.find('}')?;
+        let (number, default) = inner[..end].split_once(':').unwrap_or((&inner[..end], ""));
+        return Some((number.parse().ok()?, default, end + 3));
+    }
+    let digits = s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    Some((s[..digits].parse().ok()?, "", digits + 1))
+}
@@ -0,0 +1,39 @@
+use crate::overlay::OverlayOutcome;
+use crossterm::event::{KeyCode, KeyEvent};
+
+/// The statement that filled a results tab, as it was sent; c copies it,
+/// e puts it in the editor at the caret
+#[derive(Debug)]
+pub struct SourceView {
+    /// 0-based index of the tab
+    pub tab: usize,
+    pub sql: String,
+    pub scroll: usize,
+    /// Submitted with e rather than c
+    pub to_editor: bool,
+}
+
+impl SourceView {
+    pub fn new(tab: usize, sql: String) -> Self {
+        Self { tab, sql, scroll: 0, to_editor: false }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        match key.code {
+            KeyCode::Char('c') | KeyCode::Char('C') => return OverlayOutcome::Submit,
+            KeyCode::Char('e') | KeyCode::Char('E') => {
+                self.to_editor = true;
+                return OverlayOutcome::Submit;
+            }
+            KeyCode::Char('q') | KeyCode::Char('Q') => return OverlayOutcome::Close,
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Down => self.scroll += 1,
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::PageDown => self.scroll += 10,
+            KeyCode::Home => self.scroll = 0,
+            KeyCode::End => self.scroll = usize::MAX,
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+}
@@ -0,0 +1,245 @@
+use crate::health::human_bytes;
+use crate::overlay::OverlayOutcome;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::path::Path;
+
+/// One file listed by `LIST @stage`
+#[derive(Debug, Clone)]
+pub struct StageFile {
+    /// As listed: `stage_name/path/file.csv.gz` for internal stages, a URL
+    /// for external ones
+    pub name: String,
+    pub size: u64,
+}
+
+impl StageFile {
+    /// Path relative to the stage root, as GET expects it; None for files
+    /// of external stages, which GET can't download
+    pub fn relative_path(&self) -> Option<&str> {
+        if self.name.contains("://") {
+            return None;
+        }
+        self.name.split_once('/').map(|(_, rest)| rest)
+    }
+}
+
+/// Worker round trip the browser is waiting to have sent
+#[derive(Debug, Clone, PartialEq)]
+pub enum StageRequest {
+    ListStages,
+    ListFiles(String),
+    Put { stage: String, path: String },
+    Get { stage: String, file: String },
+}
+
+impl StageRequest {
+    /// `PUT` / `GET` statement for a transfer; None for listings
+    pub fn transfer_sql(&self, download_dir: &Path) -> Option<String> {
+        match self {
+            StageRequest::Put { stage, path } => Some(format!(
+                "PUT '{}' @{} AUTO_COMPRESS=TRUE",
+                file_url(path),
+                stage,
+            )),
+            StageRequest::Get { stage, file } => Some(format!(
+                "GET '@{}/{}' '{}/'",
+                stage,
+                file.replace('\'', "\\'"),
+                file_url(&download_dir.to_string_lossy()),
+            )),
+            StageRequest::ListStages | StageRequest::ListFiles(_) => None,
+        }
+    }
+}
+
+/// `file://` URL for PUT/GET; forward slashes work on Windows too and
+/// avoid backslash escapes inside the quoted literal
+fn file_url(path: &str) -> String {
+    format!("file://{}", path.replace('\\', "/").replace('\'', "\\'"))
+}
+
+#[derive(Debug)]
+enum StageView {
+    Stages,
+    Files { stage: String },
+}
+
+/// Stage browser overlay: lists the stages of the current schema, then the
+/// files of one stage; `u` uploads a local file with PUT, `d` downloads the
+/// selected one with GET into the working directory.
+#[derive(Debug)]
+pub struct StageBrowser {
+    view: StageView,
+    /// None while loading
+    stages: Option<Vec<String>>,
+    files: Option<Vec<StageFile>>,
+    pub selected: usize,
+    /// Local path being typed after `u`
+    pub upload_input: Option<String>,
+    /// Last error or transfer result
+    pub status: Option<String>,
+    /// A transfer is running; further ones wait for it
+    pub busy: bool,
+    request: Option<StageRequest>,
+}
+
+impl StageBrowser {
+    pub fn new() -> Self {
+        Self {
+            view: StageView::Stages,
+            stages: None,
+            files: None,
+            selected: 0,
+            upload_input: None,
+            status: None,
+            busy: false,
+            request: Some(StageRequest::ListStages),
+        }
+    }
+
+    /// Request for the worker, if one is waiting to be sent
+    pub fn take_request(&mut self) -> Option<StageRequest> {
+        self.request.take()
+    }
+
+    /// Stage whose files are shown
+    pub fn current_stage(&self) -> Option<&str> {
+        match &self.view {
+            StageView::Files { stage } => Some(stage),
+            StageView::Stages => None,
+        }
+    }
+
+    pub fn set_stages(&mut self, stages: Result<Vec<String>, String>) {
+        self.stages = Some(stages.unwrap_or_else(|e| {
+            self.status = Some(e);
+            Vec::new()
+        }));
+    }
+
+    pub fn set_files(&mut self, stage: &str, files: Result<Vec<StageFile>, String>) {
+        if self.current_stage() != Some(stage) {
+            return;
+        }
+        self.files = Some(files.unwrap_or_else(|e| {
+            self.status = Some(e);
+            Vec::new()
+        }));
+        self.selected = self.selected.min(self.len().saturating_sub(1));
+    }
+
+    /// Transfer finished; refresh the listing to show an upload
+    pub fn transfer_done(&mut self, status: String) {
+        self.busy = false;
+        self.status = Some(status);
+        if let Some(stage) = self.current_stage() {
+            self.request = Some(StageRequest::ListFiles(stage.to_string()));
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        match self.view {
+            StageView::Stages => self.stages.is_none(),
+            StageView::Files { .. } => self.files.is_none(),
+        }
+    }
+
+    /// `(name, detail)` rows of the current list
+    pub fn rows(&self) -> Vec<(String, String)> {
+        match self.view {
+            StageView::Stages => self.stages.iter()
+                .flatten()
+                .map(|s| (s.clone(), String::new()))
+                .collect(),
+            StageView::Files { .. } => self.files.iter()
+                .flatten()
+                .map(|f| (f.name.clone(), human_bytes(f.size)))
+                .collect(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self.view {
+            StageView::Stages => self.stages.as_ref().map_or(0, |s| s.len()),
+            StageView::Files { .. } => self.files.as_ref().map_or(0, |f| f.len()),
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match &self.view {
+            StageView::Stages => " Stages - Enter: list files, Esc: close ".to_string(),
+            StageView::Files { stage } => format!(" @{} - u: upload, d: download, Backspace: back ", stage),
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        if let Some(input) = &mut self.upload_input {
+            match key.code {
+                KeyCode::Enter => {
+                    let path = self.upload_input.take().unwrap_or_default();
+                    let path = path.trim();
+                    if let (false, Some(stage)) = (path.is_empty(), self.current_stage()) {
+                        self.request = Some(StageRequest::Put { stage: stage.to_string(), path: path.to_string() });
+                        self.busy = true;
+                        self.status = Some(format!("Uploading {}...", path));
+                    }
+                }
+                KeyCode::Backspace => { input.pop(); }
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => input.push(c),
+                _ => {}
+            }
+            return OverlayOutcome::Consumed;
+        }
+        let last = self.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            KeyCode::Enter => {
+                let stage = match &self.view {
+                    StageView::Stages => self.stages.as_ref().and_then(|s| s.get(self.selected)).cloned(),
+                    StageView::Files { .. } => None,
+                };
+                if let Some(stage) = stage {
+                    self.request = Some(StageRequest::ListFiles(stage.clone()));
+                    self.view = StageView::Files { stage };
+                    self.files = None;
+                    self.selected = 0;
+                }
+            }
+            KeyCode::Backspace | KeyCode::Left if self.current_stage().is_some() => {
+                let stage = self.current_stage().map(|s| s.to_string());
+                self.view = StageView::Stages;
+                self.selected = stage
+                    .and_then(|stage| self.stages.iter().flatten().position(|s| *s == stage))
+                    .unwrap_or(0);
+            }
+            KeyCode::Char('r') => {
+                self.request = Some(match self.current_stage() {
+                    Some(stage) => StageRequest::ListFiles(stage.to_string()),
+                    None => StageRequest::ListStages,
+                });
+            }
+            KeyCode::Char('u') if self.current_stage().is_some() && !self.busy => {
+                self.upload_input = Some(String::new());
+            }
+            KeyCode::Char('d') if !self.busy => {
+                let file = self.files.as_ref().and_then(|f| f.get(self.selected));
+                if let (Some(stage), Some(file)) = (self.current_stage(), file) {
+                    match file.relative_path() {
+                        Some(path) => {
+                            self.request = Some(StageRequest::Get { stage: stage.to_string(), file: path.to_string() });
+                            self.busy = true;
+                            self.status = Some(format!("Downloading {}...", path));
+                        }
+                        None => self.status = Some("GET only downloads from internal stages".to_string()),
+                    }
+                }
+            }
+            KeyCode::Char('q') => return OverlayOutcome::Close,
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+}
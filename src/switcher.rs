@@ -0,0 +1,183 @@
+use crate::overlay::OverlayOutcome;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Session setting the switcher changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContextKind {
+    Role,
+    Warehouse,
+    Database,
+}
+
+impl ContextKind {
+    pub const ALL: [ContextKind; 3] = [ContextKind::Role, ContextKind::Warehouse, ContextKind::Database];
+
+    /// Position in `ALL`
+    fn index(self) -> usize {
+        match self {
+            ContextKind::Role => 0,
+            ContextKind::Warehouse => 1,
+            ContextKind::Database => 2,
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            ContextKind::Role => "Roles",
+            ContextKind::Warehouse => "Warehouses",
+            ContextKind::Database => "Databases",
+        }
+    }
+
+    /// `SHOW ...` statement listing the choices
+    pub fn show_sql(self) -> &'static str {
+        match self {
+            ContextKind::Role => "SHOW ROLES",
+            ContextKind::Warehouse => "SHOW WAREHOUSES",
+            ContextKind::Database => "SHOW DATABASES",
+        }
+    }
+
+    /// `USE ...` statement switching to `name` (as listed by SHOW, so quoted)
+    pub fn use_sql(self, name: &str) -> String {
+        let keyword = match self {
+            ContextKind::Role => "ROLE",
+            ContextKind::Warehouse => "WAREHOUSE",
+            ContextKind::Database => "DATABASE",
+        };
+        format!("USE {} \"{}\"", keyword, name.replace('"', "\"\""))
+    }
+}
+
+/// Current role, warehouse, database and schema of the worker's session
+#[derive(Debug, Clone, Default)]
+pub struct SessionContext {
+    pub role: Option<String>,
+    pub warehouse: Option<String>,
+    pub database: Option<String>,
+    pub schema: Option<String>,
+    /// Current UTC offset of the session TIMEZONE, e.g. `+02:00`
+    pub utc_offset: Option<String>,
+    /// Driver name and version as seen by the server, e.g. `ODBC 3.1.0`
+    pub client: Option<String>,
+}
+
+impl SessionContext {
+    /// `ROLE · WAREHOUSE · DB.SCHEMA` for the status bar
+    pub fn summary(&self) -> String {
+        let unset = || "-".to_string();
+        let location = match (&self.database, &self.schema) {
+            (Some(db), Some(schema)) => format!("{}.{}", db, schema),
+            (Some(db), None) => db.clone(),
+            _ => unset(),
+        };
+        format!(
+            "{} · {} · {}",
+            self.role.clone().unwrap_or_else(unset),
+            self.warehouse.clone().unwrap_or_else(unset),
+            location,
+        )
+    }
+
+    /// `utc_offset` parsed for converting timestamps to session time
+    pub fn offset(&self) -> Option<chrono::FixedOffset> {
+        let text = self.utc_offset.as_deref()?.trim();
+        let (sign, rest) = match text.as_bytes().first()? {
+            b'+' => (1, &text[1..]),
+            b'-' => (-1, &text[1..]),
+            _ => (1, text),
+        };
+        let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+        let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+        chrono::FixedOffset::east_opt(sign * seconds)
+    }
+}
+
+/// Quick-switch overlay: pick a role, warehouse or database from the SHOW
+/// output and the workspace issues the matching USE through the worker.
+#[derive(Debug)]
+pub struct Switcher {
+    pub kind: ContextKind,
+    /// Names per kind (indexed like `ContextKind::ALL`); None until loaded
+    options: [Option<Vec<String>>; 3],
+    /// Kinds whose SHOW has been sent to the worker
+    requested: [bool; 3],
+    /// Typed text narrowing the list
+    pub filter: String,
+    pub selected: usize,
+}
+
+impl Switcher {
+    pub fn new(kind: ContextKind) -> Self {
+        Self {
+            kind,
+            options: [None, None, None],
+            requested: [false; 3],
+            filter: String::new(),
+            selected: 0,
+        }
+    }
+
+    /// The shown kind if its list still has to be fetched; marks it requested
+    pub fn take_load_request(&mut self) -> Option<ContextKind> {
+        let idx = self.kind.index();
+        if self.options[idx].is_some() || self.requested[idx] {
+            return None;
+        }
+        self.requested[idx] = true;
+        Some(self.kind)
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.options[self.kind.index()].is_none()
+    }
+
+    pub fn set_options(&mut self, kind: ContextKind, names: Vec<String>) {
+        self.options[kind.index()] = Some(names);
+    }
+
+    /// Names of the current kind matching the filter (case-insensitive)
+    pub fn visible(&self) -> Vec<&str> {
+        let filter = self.filter.to_lowercase();
+        self.options[self.kind.index()].iter()
+            .flatten()
+            .filter(|n| n.to_lowercase().contains(&filter))
+            .map(|n| n.as_str())
+            .collect()
+    }
+
+    pub fn selected_name(&self) -> Option<String> {
+        self.visible().get(self.selected).map(|n| n.to_string())
+    }
+
+    fn cycle_kind(&mut self, forward: bool) {
+        let n = ContextKind::ALL.len();
+        let current = self.kind.index();
+        let idx = if forward { (current + 1) % n } else { (current + n - 1) % n };
+        self.kind = ContextKind::ALL[idx];
+        self.filter.clear();
+        self.selected = 0;
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        match key.code {
+            KeyCode::Enter => return OverlayOutcome::Submit,
+            KeyCode::Tab | KeyCode::Right => self.cycle_kind(true),
+            KeyCode::BackTab | KeyCode::Left => self.cycle_kind(false),
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => {
+                self.selected = (self.selected + 1).min(self.visible().len().saturating_sub(1));
+            }
+            KeyCode::Backspace => {
+                self.filter.pop();
+                self.selected = 0;
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.filter.push(c);
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+}
@@ -0,0 +1,289 @@
+use crate::export::ColumnKind;
+use crate::overlay::OverlayOutcome;
+use crate::row_view::ViewReader;
+use crate::tile_rowstore::{TileRowStore, TileStoreOptions, NULL_SENTINEL, TILE_SIZE};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{hash_map::{DefaultHasher, Entry}, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, TryRecvError},
+    Arc,
+};
+
+/// Key columns for comparing two results tabs. The base tab is the one
+/// marked first; rows are matched between the tabs on the key columns.
+#[derive(Debug)]
+pub struct CompareForm {
+    pub base: usize,
+    pub other: usize,
+    /// Comma-separated key column names
+    pub keys: String,
+    /// Why the last Enter didn't compare
+    pub error: Option<String>,
+}
+
+impl CompareForm {
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        match key.code {
+            KeyCode::Enter if !self.keys.trim().is_empty() => return OverlayOutcome::Submit,
+            KeyCode::Backspace => {
+                self.keys.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => self.keys.push(c),
+            _ => return OverlayOutcome::Consumed,
+        }
+        self.error = None;
+        OverlayOutcome::Consumed
+    }
+
+    /// The key column names as typed, blanks dropped
+    pub fn key_names(&self) -> Vec<&str> {
+        self.keys.split(',').map(str::trim).filter(|k| !k.is_empty()).collect()
+    }
+}
+
+/// Rows that differ between two tabs, as a table: a change column (ADDED,
+/// REMOVED or CHANGED), the keys, the names of the changed columns, then
+/// the other shared columns. Changed cells read `old → new`. Added rows
+/// come first in the other tab's order, then the changed and removed ones
+/// in the base tab's.
+#[derive(Debug)]
+pub struct Comparison {
+    pub headers: Vec<String>,
+    pub rows: TileRowStore,
+    /// `3 added, 1 removed, 2 changed, 40 unchanged` plus any caveats
+    pub summary: String,
+}
+
+/// One side of a comparison: a tab's headers and a reader of its rows
+pub struct CompareSide {
+    pub headers: Vec<String>,
+    pub reader: ViewReader,
+    /// Rows in the tab's view
+    pub rows: usize,
+}
+
+#[derive(Debug)]
+enum CompareEvent {
+    /// Rows read so far, over all passes
+    Progress(usize),
+    Done(Box<Result<Comparison, String>>),
+}
+
+/// A comparison running on a background thread. The base tab is read
+/// twice and the other once, a tile at a time, so only the base's keys
+/// and the changed rows are held in memory.
+#[derive(Debug)]
+pub struct CompareJob {
+    /// Title of the tab the comparison opens in
+    pub title: String,
+    /// Rows to read over all passes, and read so far
+    pub total: usize,
+    pub scanned: usize,
+    rx: Receiver<CompareEvent>,
+    stop: Arc<AtomicBool>,
+}
+
+impl CompareJob {
+    /// Compare `other` against `base` on the `keys` columns
+    pub fn start(mut base: CompareSide, mut other: CompareSide, keys: Vec<String>, options: TileStoreOptions, title: String) -> Self {
+        let total = 2 * base.rows + other.rows;
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        std::thread::spawn(move || {
+            let mut scanned = 0;
+            let mut progress = |rows: usize| {
+                scanned += rows;
+                !thread_stop.load(Ordering::Relaxed) && tx.send(CompareEvent::Progress(scanned)).is_ok()
+            };
+            let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+            let result = compare(&mut base, &mut other, &keys, options, &mut progress);
+            let _ = tx.send(CompareEvent::Done(Box::new(result)));
+        });
+        Self { title, total, scanned: 0, rx, stop }
+    }
+
+    /// Take progress sent since the last call; the comparison once it ends
+    pub fn poll(&mut self) -> Option<Result<Comparison, String>> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(CompareEvent::Progress(scanned)) => self.scanned = scanned,
+                Ok(CompareEvent::Done(result)) => return Some(*result),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => return Some(Err("Compare stopped".to_string())),
+            }
+        }
+    }
+}
+
+impl Drop for CompareJob {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Hand each of `reader`'s rows to `each` with its view row, a tile at a
+/// time; `progress` gets the rows of each tile and returns false to stop
+fn scan(
+    reader: &mut ViewReader,
+    progress: &mut dyn FnMut(usize) -> bool,
+    each: &mut dyn FnMut(usize, Vec<String>) -> io::Result<()>,
+) -> Result<(), String> {
+    let failed = |e: io::Error| format!("Compare failed: {}", e);
+    for tile in 0..reader.tile_count() {
+        let rows = reader.read_tile(tile).map_err(failed)?;
+        let count = rows.len();
+        for (i, row) in rows.into_iter().enumerate() {
+            each(tile * TILE_SIZE + i, row).map_err(failed)?;
+        }
+        if !progress(count) {
+            return Err("Compare stopped".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Compare `other` against `base` on the `keys` columns. Only columns the
+/// tabs share (by name, ignoring case) are compared; a repeated key is
+/// matched once and counted.
+fn compare(
+    base: &mut CompareSide,
+    other: &mut CompareSide,
+    keys: &[&str],
+    options: TileStoreOptions,
+    progress: &mut dyn FnMut(usize) -> bool,
+) -> Result<Comparison, String> {
+    let find = |headers: &[String], name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    // (base index, other index) of each shared column, keys first
+    let mut key_cols = Vec::new();
+    for &key in keys {
+        match (find(&base.headers, key), find(&other.headers, key)) {
+            (Some(b), Some(o)) => key_cols.push((b, o)),
+            _ => return Err(format!("Both tabs need a column named {}", key)),
+        }
+    }
+    let value_cols: Vec<(usize, usize)> = base.headers.iter().enumerate()
+        .filter_map(|(b, name)| Some((b, find(&other.headers, name)?)))
+        .filter(|col| !key_cols.contains(col))
+        .collect();
+    let unshared = (base.headers.len() + other.headers.len()).saturating_sub(2 * (key_cols.len() + value_cols.len()));
+
+    let key_of = |row: &[String], side: fn(&(usize, usize)) -> usize| -> Vec<String> {
+        key_cols.iter().map(|col| row.get(side(col)).cloned().unwrap_or_default()).collect()
+    };
+    let cell = |row: &[String], i: usize| row.get(i).cloned().unwrap_or_default();
+    // Rows with the same hash of their shared values are taken as unchanged
+    let values_hash = |row: &[String], side: fn(&(usize, usize)) -> usize| -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for col in &value_cols {
+            row.get(side(col)).map_or("", String::as_str).hash(&mut hasher);
+        }
+        hasher.finish()
+    };
+
+    let mut headers = vec!["change".to_string()];
+    headers.extend(key_cols.iter().map(|&(b, _)| base.headers[b].clone()));
+    headers.push("changed columns".to_string());
+    headers.extend(value_cols.iter().map(|&(b, _)| base.headers[b].clone()));
+    let kinds = vec![ColumnKind::Text; headers.len()];
+    let mut store = TileRowStore::from_rows(&headers, &kinds, std::iter::empty(), options)
+        .map_err(|e| format!("Compare failed: {}", e))?;
+    let mut out: Vec<Vec<String>> = Vec::new();
+    let mut emit = |row: Vec<String>, store: &mut TileRowStore| -> io::Result<()> {
+        out.push(row);
+        if out.len() >= TILE_SIZE {
+            store.append_rows(std::mem::take(&mut out))?;
+        }
+        Ok(())
+    };
+
+    // Base keys with the view row and values hash of their first row
+    let mut base_rows: HashMap<Vec<String>, (usize, u64)> = HashMap::new();
+    let mut duplicates = 0;
+    scan(&mut base.reader, progress, &mut |idx, row| {
+        match base_rows.entry(key_of(&row, |c| c.0)) {
+            Entry::Occupied(_) => duplicates += 1,
+            Entry::Vacant(slot) => {
+                slot.insert((idx, values_hash(&row, |c| c.0)));
+            }
+        }
+        Ok(())
+    })?;
+
+    // Other rows: added ones go out now, changed ones wait for their base row
+    let mut matched = HashSet::new();
+    let mut changed_rows: HashMap<usize, Vec<String>> = HashMap::new();
+    let (mut added, mut unchanged) = (0, 0);
+    scan(&mut other.reader, progress, &mut |_, row| {
+        let key = key_of(&row, |c| c.1);
+        if !matched.insert(key.clone()) {
+            duplicates += 1;
+            return Ok(());
+        }
+        match base_rows.get(&key) {
+            None => {
+                added += 1;
+                let mut out = vec!["ADDED".to_string()];
+                out.extend(key);
+                out.push(String::new());
+                out.extend(value_cols.iter().map(|&(_, o)| cell(&row, o)));
+                emit(out, &mut store)
+            }
+            Some(&(_, hash)) if hash == values_hash(&row, |c| c.1) => {
+                unchanged += 1;
+                Ok(())
+            }
+            Some(&(idx, _)) => {
+                changed_rows.insert(idx, row);
+                Ok(())
+            }
+        }
+    })?;
+
+    // Base rows again: changed and removed ones go out in base order
+    let (mut changed, mut removed) = (0, 0);
+    scan(&mut base.reader, progress, &mut |idx, old| {
+        let key = key_of(&old, |c| c.0);
+        // Only a key's first row was matched
+        if base_rows.get(&key).map(|&(first, _)| first) != Some(idx) {
+            return Ok(());
+        }
+        if !matched.contains(&key) {
+            removed += 1;
+            let mut out = vec!["REMOVED".to_string()];
+            out.extend(key);
+            out.push(String::new());
+            out.extend(value_cols.iter().map(|&(b, _)| cell(&old, b)));
+            return emit(out, &mut store);
+        }
+        let Some(row) = changed_rows.remove(&idx) else { return Ok(()) };
+        let differing: Vec<&(usize, usize)> = value_cols.iter().filter(|&&(b, o)| cell(&old, b) != cell(&row, o)).collect();
+        changed += 1;
+        let mut out = vec!["CHANGED".to_string()];
+        out.extend(key);
+        out.push(differing.iter().map(|&&(b, _)| base.headers[b].as_str()).collect::<Vec<_>>().join(", "));
+        out.extend(value_cols.iter().map(|col| {
+            let (old, new) = (cell(&old, col.0), cell(&row, col.1));
+            if differing.contains(&col) { format!("{} → {}", shown(&old), shown(&new)) } else { new }
+        }));
+        emit(out, &mut store)
+    })?;
+    store.append_rows(out).map_err(|e| format!("Compare failed: {}", e))?;
+
+    let mut summary = format!("{} added, {} removed, {} changed, {} unchanged", added, removed, changed, unchanged);
+    if duplicates > 0 {
+        summary.push_str(&format!("; {} repeated key{} skipped", duplicates, if duplicates == 1 { "" } else { "s" }));
+    }
+    if unshared > 0 {
+        summary.push_str(&format!("; {} column{} in one tab only", unshared, if unshared == 1 { "" } else { "s" }));
+    }
+    Ok(Comparison { headers, rows: store, summary })
+}
+
+/// A stored value as it reads inside a changed cell
+fn shown(value: &str) -> &str {
+    if value == NULL_SENTINEL { "NULL" } else { value }
+}
@@ -0,0 +1,154 @@
+use crate::row_view::ViewReader;
+use crate::tile_rowstore::{NULL_SENTINEL, TILE_SIZE};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, TryRecvError},
+    Arc,
+};
+
+/// Matches kept per search; scanning stops once this many are found
+const MAX_MATCHES: usize = 100_000;
+
+/// Progress sent by the scan thread, one message per tile with hits
+#[derive(Debug)]
+enum SearchEvent {
+    Matches(Vec<(usize, usize)>),
+    Failed(String),
+}
+
+/// Case-insensitive substring search over a result table. The tiles are
+/// scanned on a background thread through a separate file handle; matches
+/// arrive as (row, column) pairs in row order and are collected by `poll`.
+#[derive(Debug)]
+pub struct TableSearch {
+    pub query: String,
+    /// Sorted by (row, column); columns are indices into the headers
+    pub matches: Vec<(usize, usize)>,
+    /// Match the cursor was last moved to
+    pub current: Option<(usize, usize)>,
+    pub done: bool,
+    pub error: Option<String>,
+    rx: Receiver<SearchEvent>,
+    stop: Arc<AtomicBool>,
+}
+
+impl TableSearch {
+    pub fn start(mut reader: ViewReader, query: String) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let needle = query.to_lowercase();
+        let thread_stop = stop.clone();
+        std::thread::spawn(move || {
+            let mut found = 0;
+            for tile in 0..reader.tile_count() {
+                if thread_stop.load(Ordering::Relaxed) || found >= MAX_MATCHES {
+                    break;
+                }
+                let rows = match reader.read_tile(tile) {
+                    Ok(rows) => rows,
+                    Err(e) => {
+                        let _ = tx.send(SearchEvent::Failed(e.to_string()));
+                        break;
+                    }
+                };
+                let mut hits = Vec::new();
+                for (i, row) in rows.iter().enumerate() {
+                    for (col, value) in row.iter().enumerate() {
+                        if value != NULL_SENTINEL && value.to_lowercase().contains(&needle) {
+                            hits.push((tile * TILE_SIZE + i, col));
+                        }
+                    }
+                }
+                hits.truncate(MAX_MATCHES - found);
+                found += hits.len();
+                if !hits.is_empty() && tx.send(SearchEvent::Matches(hits)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            query,
+            matches: Vec::new(),
+            current: None,
+            done: false,
+            error: None,
+            rx,
+            stop,
+        }
+    }
+
+    /// Collect matches sent since the last call; true if anything changed
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            match self.rx.try_recv() {
+                Ok(SearchEvent::Matches(hits)) => {
+                    self.matches.extend(hits);
+                    changed = true;
+                }
+                Ok(SearchEvent::Failed(e)) => {
+                    self.error = Some(e);
+                    changed = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    changed |= !self.done;
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        changed
+    }
+
+    pub fn is_match(&self, row: usize, col: usize) -> bool {
+        self.matches.binary_search(&(row, col)).is_ok()
+    }
+
+    /// Next (or previous) match after `from` in (row, display position)
+    /// order, wrapping around. `positions` maps a column to its display
+    /// position; matches in hidden columns are skipped.
+    pub fn step(&self, from: (usize, usize), forward: bool, positions: impl Fn(usize) -> Option<usize>) -> Option<(usize, usize)> {
+        let mut shown: Vec<(usize, usize)> = self.matches.iter()
+            .filter_map(|&(row, col)| positions(col).map(|pos| (row, pos)))
+            .collect();
+        shown.sort_unstable();
+        let found = if forward {
+            shown.iter().find(|&&m| m > from).or(shown.first())
+        } else {
+            shown.iter().rev().find(|&&m| m < from).or(shown.last())
+        };
+        found.copied()
+    }
+
+    /// `/query  3/41 matches` style summary for the results pane
+    pub fn status(&self) -> String {
+        let count = self.matches.len();
+        let position = self.current
+            .and_then(|c| self.matches.binary_search(&c).ok())
+            .map(|i| format!("{}/", i + 1))
+            .unwrap_or_default();
+        let progress = if let Some(e) = &self.error {
+            format!(" (failed: {})", e)
+        } else if !self.done {
+            " (searching...)".to_string()
+        } else {
+            String::new()
+        };
+        format!(
+            " /{}  {}{}{} match{}{} ",
+            self.query,
+            position,
+            count,
+            if count >= MAX_MATCHES { "+" } else { "" },
+            if count == 1 { "" } else { "es" },
+            progress,
+        )
+    }
+}
+
+impl Drop for TableSearch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
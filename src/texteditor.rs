@@ -1,4 +1,3 @@
-use arboard::Clipboard;
 use crossterm::{
     cursor::SetCursorStyle,
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseEventKind, MouseButton},
@@ -23,15 +22,22 @@ use ratatui::{
     Frame, Terminal,
 };
 use ropey::Rope;
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     env,
+    ops::Range,
     error::Error,
     fs,
     io,
-    path::PathBuf,
+    path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 use unicode_width::UnicodeWidthStr;
+use crate::clipboard;
+use crate::snippet;
+use crate::config::{Dialect, IdentifierQuoting, KeywordCase};
+use crate::highlight;
 
 #[derive(Debug, Clone, Copy)]
 struct VisualLine {
@@ -40,19 +46,85 @@ struct VisualLine {
     is_continuation: bool,
     indent: usize,
     logical_line: usize,
+    /// Lines hidden by a fold after this one, the last row of its line
+    folded: usize,
 }
 
-#[derive(Clone, Debug)]
+/// How one line lays out, kept between rebuilds until an edit touches it
+#[derive(Debug, Clone)]
+struct LineLayout {
+    /// Bytes of the line with its line break
+    len_bytes: usize,
+    /// Rows of the line as byte ranges within it
+    segments: Vec<(usize, usize)>,
+    /// Indent of its continuation rows
+    indent: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 enum EditOp {
     Insert { pos: usize, text: String },
     Delete { pos: usize, text: String },
 }
 
+/// What an undo step did; runs of typing and of deleting are undone
+/// together, anything else on its own
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+enum EditKind {
+    Typing,
+    Delete,
+    Paste,
+    /// Indenting, moving lines, find-and-replace
+    Other,
+}
+
+/// Caret and selection anchor, put back by undo and redo
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct CaretState {
+    caret: usize,
+    anchor: Option<usize>,
+}
+
+/// A pause in typing or deleting this long starts a new undo step
+const UNDO_PAUSE: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize)]
 struct UndoGroup {
-    ops: Vec<(EditOp, usize, usize)>,
+    kind: EditKind,
+    ops: Vec<EditOp>,
+    before: CaretState,
+    after: CaretState,
+    /// Time of the last edit in the group
+    #[serde(skip, default = "Instant::now")]
     timestamp: Instant,
 }
 
+impl UndoGroup {
+    /// Whether an edit of `kind` made at `now` continues this step: typing
+    /// and deleting go on while contiguous and without a pause, and typing
+    /// or pasting over a selection joins the deletion of it
+    fn continues_with(&self, kind: EditKind, op: &EditOp, now: Instant) -> bool {
+        if now.duration_since(self.timestamp) > UNDO_PAUSE {
+            return false;
+        }
+        let selection_deleted = self.before.anchor.is_some() && self.ops.len() == 1;
+        match (self.kind, kind, self.ops.last(), op) {
+            (EditKind::Delete, EditKind::Typing | EditKind::Paste, Some(EditOp::Delete { pos, .. }), EditOp::Insert { pos: at, .. }) => {
+                selection_deleted && pos == at
+            }
+            // A newline ends the run, so lines are undone one at a time
+            (EditKind::Typing, EditKind::Typing, Some(EditOp::Insert { pos, text }), EditOp::Insert { pos: at, text: typed }) => {
+                *at == pos + text.len() && !typed.starts_with('\n')
+            }
+            // Backspace deletes before the last deletion, Delete at it
+            (EditKind::Delete, EditKind::Delete, Some(EditOp::Delete { pos, .. }), EditOp::Delete { pos: at, text }) => {
+                !selection_deleted && (at == pos || at + text.len() == *pos)
+            }
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 enum PromptType {
     SaveAs,
@@ -60,13 +132,12 @@ enum PromptType {
     FindReplace,
 }
 
-struct Prompt {
+pub struct Prompt {
     prompt_type: PromptType,
     message: String,
     input: String,
     cursor_pos: usize,
     selection_anchor: Option<usize>,
-    clipboard: Clipboard,
     replace_input: String,
     replace_cursor_pos: usize,
     replace_selection_anchor: Option<usize>,
@@ -131,7 +202,6 @@ impl Prompt {
             input: default_path,
             cursor_pos,
             selection_anchor: None,
-            clipboard: Clipboard::new().unwrap(),
             replace_input: String::new(),
             replace_cursor_pos: 0,
             replace_selection_anchor: None,
@@ -149,7 +219,6 @@ impl Prompt {
             input: String::new(),
             cursor_pos: 0,
             selection_anchor: None,
-            clipboard: Clipboard::new().unwrap(),
             replace_input: String::new(),
             replace_cursor_pos: 0,
             replace_selection_anchor: None,
@@ -167,7 +236,6 @@ impl Prompt {
             input: String::new(),
             cursor_pos: 0,
             selection_anchor: None,
-            clipboard: Clipboard::new().unwrap(),
             replace_input: String::new(),
             replace_cursor_pos: 0,
             replace_selection_anchor: None,
@@ -306,7 +374,7 @@ impl Prompt {
                     }
                     _ => self.input[start..end].to_string(),
                 };
-                if let Err(_) = self.clipboard.set_text(text) {
+                if clipboard::set_text(text).is_err() {
                     return false;
                 }
                 return true;
@@ -326,14 +394,14 @@ impl Prompt {
     fn paste(&mut self) {
         match self.prompt_type {
             PromptType::SaveAs => {
-                if let Ok(text) = self.clipboard.get_text() {
+                if let Some(text) = clipboard::get_text() {
                     self.delete_selection();
                     self.input.insert_str(self.cursor_pos, &text);
                     self.cursor_pos += text.len();
                 }
             }
             PromptType::FindReplace => {
-                if let Ok(text) = self.clipboard.get_text() {
+                if let Some(text) = clipboard::get_text() {
                     self.delete_selection();
                     match self.active_field {
                         FindReplaceField::Find => {
@@ -849,32 +917,142 @@ pub struct Editor {
     caret: usize,
     selection_anchor: Option<usize>,
     preferred_col: usize,
+    /// Set when an edit left `preferred_col` to `update_viewport`
+    preferred_col_stale: bool,
     viewport_offset: (usize, usize),
     word_wrap: bool,
     visual_lines: Vec<Option<VisualLine>>,
     visual_lines_valid: bool,
+    /// Layout of each line at `wrap_width` (0 without word wrap); None for
+    /// lines edited since, which are laid out again on the next rebuild
+    line_layouts: Vec<Option<LineLayout>>,
+    wrap_width: usize,
     logical_line_map: Vec<(usize, usize)>,
     scrolloff: usize,
+    /// Re-center the view when the caret lands well outside it
+    center_on_jump: bool,
+    /// Pair brackets, quotes and `$$` as they are typed
+    auto_close: bool,
+    /// Case keywords get once typed, and the dialect whose keywords they are
+    keyword_case: KeywordCase,
+    dialect: Dialect,
+    /// Draw tabs, trailing spaces and non-breaking spaces as glyphs
+    show_whitespace: bool,
+    /// Columns between tab stops
+    tab_width: usize,
+    /// Indent with spaces rather than tabs
+    insert_spaces: bool,
     virtual_lines: usize,
     pub filename: Option<PathBuf>,
     modified: bool,
+    /// Edits are refused, for generated text that is only to be read
+    read_only: bool,
     undo_stack: Vec<UndoGroup>,
     redo_stack: Vec<UndoGroup>,
     current_group: Option<UndoGroup>,
-    last_edit_time: Option<Instant>,
+    /// Set between `begin_undo_group` and `end_undo_group`: every edit
+    /// joins the current step, which has this kind
+    forced_group: Option<EditKind>,
+    undo_limit: usize,
+    /// Undo steps that lead back to the text on disk; None once that text
+    /// can't be reached by undo or redo any more
+    saved_depth: Option<usize>,
+    /// Size limit of the undo history kept per file between sessions;
+    /// None keeps it in memory only
+    undo_file_limit: Option<usize>,
+    /// Carets besides `caret`, added with Ctrl+D or Alt+Click; typing,
+    /// deleting and the arrow keys act at all of them
+    extra_carets: Vec<CaretState>,
+    /// Snippet bodies by trigger
+    snippets: HashMap<String, String>,
+    /// Stops of the expanded snippet that Tab has yet to visit, kept in
+    /// place as the text around them changes
+    snippet_stops: Vec<(usize, usize)>,
+    /// Folded parenthesis regions as the byte offsets of their `(` and `)`;
+    /// the lines between the two are left out of the visual lines
+    folds: Vec<(usize, usize)>,
     is_dragging: bool,
-    clipboard: Clipboard,
     current_dir: PathBuf,
     pub app_state: AppState,
+    /// Session role / warehouse / database, shown at the end of the status bar
+    pub status_context: Option<String>,
+    /// (byte offset, text) shown dimmed after the line containing the offset;
+    /// set by the workspace from its statement cost cache
+    pub cost_annotations: Vec<(usize, String)>,
+    /// Byte range the last query error points at; cleared by any edit
+    pub error_span: Option<(usize, usize)>,
+    error_style: Style,
+    /// Background of the other occurrences of the name under the caret
+    occurrence_bg: Color,
     find_matches: Vec<(usize, usize)>,
     current_match_index: Option<usize>,
     viewport_follows_caret: bool,
+    /// Center the view on the caret at the next viewport update, however
+    /// near it is; set by `goto_position`
+    center_requested: bool,
     #[cfg(target_os = "windows")]
     previous_viewport_offset: (usize, usize),
     #[cfg(target_os = "windows")]
     modal_just_dismissed: bool,
 }
 
+/// Whether the end of `line_prefix` is inside a string, a quoted name or a
+/// `--` comment
+fn in_string_or_comment(line_prefix: &str) -> bool {
+    let mut quote = None;
+    let mut chars = line_prefix.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '-') if chars.peek() == Some(&'-') => return true,
+            _ => {}
+        }
+    }
+    quote.is_some()
+}
+
+/// Case the selection or word at the caret is converted to
+#[derive(Debug, Clone, Copy)]
+enum TextCase {
+    Upper,
+    Lower,
+    /// First letter of each word up, the rest down
+    Title,
+}
+
+impl TextCase {
+    fn apply(self, text: &str) -> String {
+        match self {
+            TextCase::Upper => text.to_uppercase(),
+            TextCase::Lower => text.to_lowercase(),
+            TextCase::Title => {
+                let mut out = String::with_capacity(text.len());
+                let mut word_start = true;
+                for c in text.chars() {
+                    if word_start {
+                        out.extend(c.to_uppercase());
+                    } else {
+                        out.extend(c.to_lowercase());
+                    }
+                    word_start = !c.is_alphanumeric();
+                }
+                out
+            }
+        }
+    }
+}
+
+/// Character that closes a pair opened by `ch`
+fn closer_for(ch: char) -> Option<char> {
+    match ch {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '\'' | '"' => Some(ch),
+        _ => None,
+    }
+}
+
 impl Editor {
     pub fn new() -> Self {
         let current_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
@@ -883,26 +1061,49 @@ impl Editor {
             caret: 0,
             selection_anchor: None,
             preferred_col: 0,
+            preferred_col_stale: false,
             viewport_offset: (0, 0),
             word_wrap: true,
             visual_lines: Vec::new(),
             visual_lines_valid: false,
+            line_layouts: Vec::new(),
+            wrap_width: 0,
             logical_line_map: Vec::new(),
             scrolloff: 3,
+            center_on_jump: true,
+            auto_close: true,
+            keyword_case: KeywordCase::Off,
+            dialect: Dialect::Snowflake,
+            show_whitespace: false,
+            tab_width: 4,
+            insert_spaces: true,
             virtual_lines: 2,
             filename: None,
             modified: false,
+            read_only: false,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             current_group: None,
-            last_edit_time: None,
+            undo_limit: 1000,
+            forced_group: None,
+            saved_depth: Some(0),
+            undo_file_limit: None,
+            extra_carets: Vec::new(),
+            snippets: HashMap::new(),
+            snippet_stops: Vec::new(),
+            folds: Vec::new(),
             is_dragging: false,
-            clipboard: Clipboard::new().unwrap(),
             current_dir,
             app_state: AppState::Editing,
+            status_context: None,
+            cost_annotations: Vec::new(),
+            error_span: None,
+            error_style: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            occurrence_bg: Color::DarkGray,
             find_matches: Vec::new(),
             current_match_index: None,
             viewport_follows_caret: true,
+            center_requested: false,
             #[cfg(target_os = "windows")]
             previous_viewport_offset: (0, 0),
             #[cfg(target_os = "windows")]
@@ -912,26 +1113,46 @@ impl Editor {
         editor
     }
 
-    fn save(&mut self) -> io::Result<()> {
-        if let Some(ref path) = self.filename {
+    /// Write the buffer to its file
+    pub fn save(&mut self) -> io::Result<()> {
+        if let Some(path) = self.filename.clone() {
             let content = self.rope.to_string();
-            fs::write(path, content)?;
-            self.modified = false;
+            fs::write(&path, &content)?;
+            self.mark_saved(&path, &content);
             Ok(())
         } else {
             Err(io::Error::new(io::ErrorKind::Other, "No filename"))
         }
     }
 
-    fn save_as(&mut self, path: PathBuf) -> io::Result<()> {
+    /// Write the buffer to `path`, which becomes its file
+    pub fn save_as(&mut self, path: PathBuf) -> io::Result<()> {
         let content = self.rope.to_string();
-        fs::write(&path, content)?;
+        fs::write(&path, &content)?;
+        self.mark_saved(&path, &content);
         self.filename = Some(path);
-        self.modified = false;
         Ok(())
     }
 
-    fn get_save_path_suggestion(&self) -> String {
+    /// The buffer now matches `path` on disk; its undo history is kept for
+    /// the next time the file is opened. Failing to write it only loses
+    /// that history.
+    fn mark_saved(&mut self, path: &Path, content: &str) {
+        self.finalize_undo_group();
+        self.saved_depth = Some(self.undo_stack.len());
+        self.modified = false;
+        if let Some(limit) = self.undo_file_limit {
+            let _ = crate::undo_file::write(path, content, &self.undo_stack, &self.redo_stack, limit);
+        }
+    }
+
+    /// Keep the undo history of saved files between sessions, up to
+    /// `max_bytes` per file
+    pub fn set_persistent_undo(&mut self, max_bytes: Option<usize>) {
+        self.undo_file_limit = max_bytes;
+    }
+
+    pub fn get_save_path_suggestion(&self) -> String {
         if let Some(ref path) = self.filename {
             path.to_string_lossy().to_string()
         } else {
@@ -959,13 +1180,121 @@ impl Editor {
         self.logical_line_map.clear();
         self.undo_stack.clear();
         self.redo_stack.clear();
+        self.current_group = None;
+        self.extra_carets.clear();
+        self.snippet_stops.clear();
+        self.folds.clear();
+        self.line_layouts.clear();
+        if self.undo_file_limit.is_some() {
+            if let Some((undo, redo)) = crate::undo_file::read(&path, &content) {
+                self.undo_stack = undo;
+                self.redo_stack = redo;
+                self.trim_undo_stack();
+            }
+        }
+        self.saved_depth = Some(self.undo_stack.len());
+        Ok(())
+    }
+
+    /// Open `path` in place of the buffer, with the caret on `line` (0-based)
+    pub fn open_at_line(&mut self, path: PathBuf, line: usize) -> io::Result<()> {
+        self.load_file(path)?;
+        self.goto_line(line);
         Ok(())
     }
 
+    /// Put the caret at the start of `line` (0-based), clamped to the buffer
+    pub fn goto_line(&mut self, line: usize) {
+        let line = line.min(self.rope.len_lines().saturating_sub(1));
+        self.extra_carets.clear();
+        self.snippet_stops.clear();
+        self.caret = self.rope.char_to_byte(self.rope.line_to_char(line));
+        self.selection_anchor = None;
+        self.preferred_col = 0;
+        self.enable_viewport_following();
+    }
+
+    /// Put the caret at character `col` of `line` (both 0-based and clamped
+    /// to the buffer) and center the view on it
+    pub fn goto_position(&mut self, line: usize, col: usize, viewport_width: usize) {
+        self.goto_line(line);
+        let line = self.rope.byte_to_line(self.caret);
+        let text = self.rope.line(line);
+        let line_len = text.chars().take_while(|&c| c != '\n' && c != '\r').count();
+        self.caret = self.rope.char_to_byte(self.rope.line_to_char(line) + col.min(line_len));
+        let (_, visual_col) = self.get_visual_position(self.caret, viewport_width);
+        self.preferred_col = visual_col;
+        self.center_requested = true;
+    }
+
+    /// 0-based line of the caret
+    pub fn caret_line(&self) -> usize {
+        self.rope.byte_to_line(self.caret)
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Replace the buffer with recovered autosave content. The buffer stays
+    /// marked modified since the content was never saved to `filename`.
+    pub fn restore_recovered(&mut self, content: &str, filename: Option<PathBuf>) {
+        self.rope = Rope::from_str(content);
+        self.filename = filename;
+        self.caret = 0;
+        self.selection_anchor = None;
+        self.preferred_col = 0;
+        self.invalidate_visual_lines();
+        self.logical_line_map.clear();
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.current_group = None;
+        self.extra_carets.clear();
+        self.snippet_stops.clear();
+        self.folds.clear();
+        self.line_layouts.clear();
+        self.saved_depth = None;
+        self.error_span = None;
+        self.modified = true;
+    }
+
+    /// Show `content` in a new, unnamed read-only buffer
+    pub fn view_text(&mut self, content: &str) {
+        self.restore_recovered(content, None);
+        self.modified = false;
+        self.read_only = true;
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
     pub fn has_selection(&self) -> bool {
         self.selection_anchor.is_some()
     }
 
+    /// Selected text, or the (possibly qualified and quoted) object name
+    /// under the caret
+    pub fn identifier_at_caret(&self) -> Option<String> {
+        if let Some((start, end)) = self.get_selection_range().filter(|(s, e)| s < e) {
+            let text = self.rope.byte_slice(start..end).to_string();
+            return Some(text.trim().to_string()).filter(|t| !t.is_empty());
+        }
+        let line_idx = self.rope.byte_to_line(self.caret);
+        let line_start = self.rope.line_to_byte(line_idx);
+        let line = self.rope.line(line_idx).to_string();
+        let col = self.caret - line_start;
+        let is_name = |c: char| c.is_alphanumeric() || matches!(c, '_' | '$' | '.' | '"');
+        let start = line[..col].char_indices().rev().find(|&(_, c)| !is_name(c)).map_or(0, |(i, c)| i + c.len_utf8());
+        let end = line[col..].find(|c: char| !is_name(c)).map_or(line.len(), |i| col + i);
+        let name = line[start..end].trim_matches('.');
+        Some(name.to_string()).filter(|n| !n.is_empty())
+    }
+
     pub fn get_selection_range(&self) -> Option<(usize, usize)> {
         self.selection_anchor.map(|anchor| {
             if anchor <= self.caret {
@@ -984,49 +1313,521 @@ impl Editor {
         if let Some((start, end)) = self.get_selection_range() {
             if start < end {
                 let text = self.rope.byte_slice(start..end).to_string();
-                let before = self.caret;
+                let before = self.caret_state();
                 
                 let start_char = self.rope.byte_to_char(start);
                 let end_char = self.rope.byte_to_char(end);
                 self.rope.remove(start_char..end_char);
                 
                 self.caret = start;
-                self.push_op(EditOp::Delete { pos: start, text }, before, self.caret);
+                self.clear_selection();
+                self.push_op(EditOp::Delete { pos: start, text }, EditKind::Delete, before);
                 
                 self.invalidate_visual_lines();
-                self.clear_selection();
                 return true;
             }
         }
         false
     }
 
-    fn push_op(&mut self, op: EditOp, caret_before: usize, caret_after: usize) {
-        let now = Instant::now();
-        let new_group = self.last_edit_time
-            .map_or(true, |t| now.duration_since(t) > Duration::from_secs(1));
+    /// Rows kept visible around the caret, and whether far jumps center it
+    pub fn set_scroll_options(&mut self, scrolloff: usize, center_on_jump: bool) {
+        self.scrolloff = scrolloff;
+        self.center_on_jump = center_on_jump;
+    }
 
-        if new_group {
-            if let Some(group) = self.current_group.take() {
-                self.undo_stack.push(group);
+    pub fn set_snippets(&mut self, snippets: HashMap<String, String>) {
+        self.snippets = snippets;
+    }
+
+    pub fn set_keyword_case(&mut self, keyword_case: KeywordCase, dialect: Dialect) {
+        self.keyword_case = keyword_case;
+        self.dialect = dialect;
+    }
+
+    pub fn set_auto_close(&mut self, auto_close: bool) {
+        self.auto_close = auto_close;
+    }
+
+    pub fn set_show_whitespace(&mut self, show: bool) {
+        self.show_whitespace = show;
+    }
+
+    /// Tab stop width and whether indenting inserts spaces or tabs
+    pub fn set_tab_options(&mut self, tab_width: usize, insert_spaces: bool) {
+        self.tab_width = tab_width.max(1);
+        self.insert_spaces = insert_spaces;
+        self.invalidate_visual_lines();
+    }
+
+    /// Wrap long lines, or let them run on and scroll the view sideways
+    pub fn set_word_wrap(&mut self, wrap: bool) {
+        self.word_wrap = wrap;
+        self.viewport_offset.1 = 0;
+        self.invalidate_visual_lines();
+        self.enable_viewport_following();
+    }
+
+    pub fn set_error_color(&mut self, color: Color) {
+        self.error_style = self.error_style.fg(color);
+    }
+
+    pub fn set_occurrence_color(&mut self, color: Color) {
+        self.occurrence_bg = color;
+    }
+
+    pub fn set_undo_limit(&mut self, limit: usize) {
+        self.undo_limit = limit.max(1);
+        self.trim_undo_stack();
+    }
+
+    fn trim_undo_stack(&mut self) {
+        if self.undo_stack.len() > self.undo_limit {
+            let excess = self.undo_stack.len() - self.undo_limit;
+            self.undo_stack.drain(..excess);
+            self.saved_depth = self.saved_depth.and_then(|depth| depth.checked_sub(excess));
+        }
+    }
+
+    fn caret_state(&self) -> CaretState {
+        CaretState { caret: self.caret, anchor: self.selection_anchor }
+    }
+
+    /// Record an edit already applied to the rope; `before` is the caret
+    /// and selection it was made from, and the current ones are the result
+    fn push_op(&mut self, op: EditOp, kind: EditKind, before: CaretState) {
+        self.shift_snippet_stops(&op);
+        match &op {
+            EditOp::Insert { pos, text } => self.note_edit(*pos, "", text),
+            EditOp::Delete { pos, text } => self.note_edit(*pos, text, ""),
+        }
+        let now = Instant::now();
+        let after = self.caret_state();
+        match self.current_group.as_mut() {
+            Some(group) if self.forced_group.is_some() || group.continues_with(kind, &op, now) => {
+                // Typing over a selection makes the step a typing run
+                if self.forced_group.is_none() {
+                    group.kind = kind;
+                }
+                group.ops.push(op);
+                group.after = after;
+                group.timestamp = now;
+            }
+            _ => {
+                self.finalize_undo_group();
+                // Editing after undoing past the save drops the redo steps
+                // that led back to it
+                if self.saved_depth.is_some_and(|depth| depth > self.undo_stack.len()) {
+                    self.saved_depth = None;
+                }
+                self.current_group = Some(UndoGroup {
+                    kind: self.forced_group.unwrap_or(kind),
+                    ops: vec![op],
+                    before,
+                    after,
+                    timestamp: now,
+                });
             }
-            self.current_group = Some(UndoGroup {
-                ops: vec![(op, caret_before, caret_after)],
-                timestamp: now,
-            });
-        } else if let Some(ref mut group) = self.current_group {
-            group.ops.push((op, caret_before, caret_after));
         }
 
         self.redo_stack.clear();
-        self.last_edit_time = Some(now);
+        self.error_span = None;
         self.modified = true;
     }
 
+    /// Make the edits up to `end_undo_group` one undo step, which undoes
+    /// to the caret and selection of the first of them
+    fn begin_undo_group(&mut self, kind: EditKind) {
+        self.finalize_undo_group();
+        self.forced_group = Some(kind);
+    }
+
+    fn end_undo_group(&mut self) {
+        let after = self.caret_state();
+        if let Some(group) = self.current_group.as_mut() {
+            group.after = after;
+        }
+        self.forced_group = None;
+        self.finalize_undo_group();
+    }
+
+    /// Run `edit` at every caret as one undo step. Carets are visited from
+    /// the last to the first, so an edit only moves the carets after it,
+    /// which are shifted by the length it added or removed. Typing or
+    /// deleting at several carets continues the step of the previous key.
+    fn edit_at_carets(&mut self, kind: EditKind, mut edit: impl FnMut(&mut Self)) {
+        if self.extra_carets.is_empty() {
+            edit(self);
+            return;
+        }
+        let continues = matches!(kind, EditKind::Typing | EditKind::Delete)
+            && self.current_group.as_ref().is_some_and(|g| g.kind == kind && g.timestamp.elapsed() <= UNDO_PAUSE);
+        if !continues {
+            self.finalize_undo_group();
+        }
+        self.forced_group = Some(kind);
+
+        let mut carets = std::mem::take(&mut self.extra_carets);
+        carets.push(self.caret_state());
+        let primary = carets.len() - 1;
+        let start_of = |c: &CaretState| c.anchor.map_or(c.caret, |a| a.min(c.caret));
+        let mut order: Vec<usize> = (0..carets.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(start_of(&carets[i])));
+        for (n, &i) in order.iter().enumerate() {
+            let len_before = self.rope.len_bytes();
+            self.restore_caret(carets[i]);
+            edit(self);
+            carets[i] = self.caret_state();
+            let delta = self.rope.len_bytes() as isize - len_before as isize;
+            let shift = |pos: usize| pos.saturating_add_signed(delta);
+            for &j in &order[..n] {
+                carets[j].caret = shift(carets[j].caret);
+                carets[j].anchor = carets[j].anchor.map(shift);
+            }
+        }
+
+        let CaretState { caret, anchor } = carets.swap_remove(primary);
+        self.caret = caret;
+        self.selection_anchor = anchor;
+        self.set_extra_carets(carets);
+        let after = self.caret_state();
+        if let Some(group) = self.current_group.as_mut() {
+            group.after = after;
+        }
+        self.forced_group = None;
+        self.invalidate_visual_lines();
+    }
+
+    /// Move every caret with `motion`, each from its own column
+    fn move_carets(&mut self, viewport_width: usize, mut motion: impl FnMut(&mut Self)) {
+        let primary_col = self.preferred_col;
+        let mut carets = std::mem::take(&mut self.extra_carets);
+        let primary = self.caret_state();
+        for extra in &mut carets {
+            self.restore_caret(*extra);
+            self.preferred_col = self.get_visual_position(self.caret, viewport_width).1;
+            motion(self);
+            *extra = self.caret_state();
+        }
+        self.restore_caret(primary);
+        self.preferred_col = primary_col;
+        motion(self);
+        self.set_extra_carets(carets);
+    }
+
+    /// Keep the carets that don't coincide with the primary or each other
+    fn set_extra_carets(&mut self, mut carets: Vec<CaretState>) {
+        let len = self.rope.len_bytes();
+        let mut seen = vec![self.caret];
+        carets.retain_mut(|c| {
+            c.caret = c.caret.min(len);
+            c.anchor = c.anchor.map(|a| a.min(len)).filter(|&a| a != c.caret);
+            let fresh = !seen.contains(&c.caret);
+            seen.push(c.caret);
+            fresh
+        });
+        self.extra_carets = carets;
+    }
+
+    /// Back to the primary caret alone (Esc)
+    /// Tab in the editor: select the next stop of the snippet being filled
+    /// in or expand the snippet whose trigger is right before the caret.
+    /// False when there is neither, leaving the key to the workspace.
+    pub fn snippet_tab(&mut self, viewport_width: usize) -> bool {
+        if self.read_only {
+            return false;
+        }
+        if !self.snippet_stops.is_empty() {
+            self.next_snippet_stop(viewport_width);
+            return true;
+        }
+        if !self.extra_carets.is_empty() || self.selection_anchor.is_some_and(|a| a != self.caret) {
+            return false;
+        }
+        let line_start = self.rope.line_to_byte(self.rope.byte_to_line(self.caret));
+        let before_caret = self.rope.byte_slice(line_start..self.caret).to_string();
+        let trigger_len = before_caret.len()
+            - before_caret.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_').len();
+        if trigger_len == 0 {
+            return false;
+        }
+        let Some(body) = self.snippets.get(&before_caret[before_caret.len() - trigger_len..]) else {
+            return false;
+        };
+        let indent_len = before_caret.len() - before_caret.trim_start_matches([' ', '\t']).len();
+        let (text, stops) = snippet::expand(body, &before_caret[..indent_len]);
+        
+        self.enable_viewport_following();
+        let start = self.caret - trigger_len;
+        let before = self.caret_state();
+        self.begin_undo_group(EditKind::Other);
+        let trigger = self.rope.byte_slice(start..self.caret).to_string();
+        self.rope.remove(self.rope.byte_to_char(start)..self.rope.byte_to_char(self.caret));
+        self.caret = start;
+        self.push_op(EditOp::Delete { pos: start, text: trigger }, EditKind::Other, before);
+        self.rope.insert(self.rope.byte_to_char(start), &text);
+        self.caret = start + text.len();
+        self.push_op(EditOp::Insert { pos: start, text }, EditKind::Other, before);
+        self.snippet_stops = stops.into_iter().map(|(s, e)| (start + s, start + e)).collect();
+        self.next_snippet_stop(viewport_width);
+        self.end_undo_group();
+        self.invalidate_visual_lines();
+        true
+    }
+
+    /// Select the next snippet stop, or put the caret there if it is empty
+    fn next_snippet_stop(&mut self, viewport_width: usize) {
+        let (start, end) = self.snippet_stops.remove(0);
+        self.enable_viewport_following();
+        self.caret = end;
+        self.selection_anchor = (start < end).then_some(start);
+        let (_, col) = self.get_visual_position(self.caret, viewport_width);
+        self.preferred_col = col;
+    }
+
+    /// Move the snippet stops still ahead along with an edit
+    fn shift_snippet_stops(&mut self, op: &EditOp) {
+        for (start, end) in &mut self.snippet_stops {
+            match op {
+                EditOp::Insert { pos, text } => {
+                    if *pos <= *start {
+                        *start += text.len();
+                        *end += text.len();
+                    } else if *pos <= *end {
+                        *end += text.len();
+                    }
+                }
+                EditOp::Delete { pos, text } => {
+                    // Offsets inside the removed text collapse onto its start
+                    let shift = |at: usize| if at <= *pos { at } else { at.saturating_sub(text.len()).max(*pos) };
+                    *start = shift(*start);
+                    *end = shift(*end);
+                }
+            }
+        }
+    }
+
+    /// Lines hidden by the fold `(open, close)`: those between its
+    /// parentheses' lines
+    fn folded_lines(&self, (open, close): (usize, usize)) -> Range<usize> {
+        self.rope.byte_to_line(open) + 1..self.rope.byte_to_line(close)
+    }
+
+    /// The parentheses to fold at the caret: the last `(` on the caret's
+    /// line that is closed lines later (a CTE or subquery opening there),
+    /// else the innermost pair around the caret with lines between them.
+    /// Parentheses in strings and `--` comments don't count.
+    fn fold_region_at_caret(&self) -> Option<(usize, usize)> {
+        let text = self.rope.to_string();
+        let mut pairs = Vec::new();
+        let mut open = Vec::new();
+        let mut quote = None;
+        let mut comment = false;
+        let mut bytes = text.bytes().enumerate().peekable();
+        while let Some((i, b)) = bytes.next() {
+            match (quote, b) {
+                _ if comment => comment = b != b'\n',
+                (Some(q), _) => quote = (b != q).then_some(q),
+                (None, b'\'' | b'"') => quote = Some(b),
+                (None, b'-') if bytes.peek().is_some_and(|&(_, next)| next == b'-') => comment = true,
+                (None, b'(') => open.push(i),
+                (None, b')') => pairs.extend(open.pop().map(|start| (start, i))),
+                _ => {}
+            }
+        }
+        let caret_line = self.rope.byte_to_line(self.caret);
+        let foldable = |&&fold: &&(usize, usize)| !self.folded_lines(fold).is_empty();
+        pairs.iter()
+            .filter(foldable)
+            .filter(|&&(open, _)| self.rope.byte_to_line(open) == caret_line)
+            .max_by_key(|&&(open, _)| open)
+            .or_else(|| pairs.iter()
+                .filter(foldable)
+                .filter(|&&(open, close)| open < self.caret && self.caret <= close)
+                .max_by_key(|&&(open, _)| open))
+            .copied()
+    }
+
+    /// Collapse the region at the caret to its first line (Alt+-)
+    fn fold_at_caret(&mut self) {
+        let Some(fold) = self.fold_region_at_caret() else { return };
+        if self.folds.contains(&fold) {
+            return;
+        }
+        self.folds.push(fold);
+        if self.folded_lines(fold).contains(&self.rope.byte_to_line(self.caret)) {
+            self.caret = fold.0 + 1;
+            self.selection_anchor = None;
+        }
+        self.invalidate_visual_lines();
+    }
+
+    /// Expand the folds starting on the caret's line or around the caret
+    /// (Alt+=), or every fold (Alt+0)
+    fn unfold(&mut self, all: bool) {
+        let caret_line = self.rope.byte_to_line(self.caret);
+        let caret = self.caret;
+        let before = self.folds.len();
+        if all {
+            self.folds.clear();
+        } else {
+            let rope = &self.rope;
+            self.folds.retain(|&(open, close)| rope.byte_to_line(open) != caret_line && !(open < caret && caret <= close));
+        }
+        if self.folds.len() != before {
+            self.invalidate_visual_lines();
+        }
+    }
+
+    /// Expand folds that hide a caret, so it never sits on a line that
+    /// isn't shown
+    fn unfold_around_carets(&mut self) {
+        if self.folds.is_empty() {
+            return;
+        }
+        let carets: Vec<usize> = self.extra_carets.iter().map(|c| c.caret).chain([self.caret]).collect();
+        let before = self.folds.len();
+        let folds = std::mem::take(&mut self.folds);
+        self.folds = folds.into_iter()
+            .filter(|&fold| {
+                let lines = self.folded_lines(fold);
+                !carets.iter().any(|&caret| lines.contains(&self.rope.byte_to_line(caret)))
+            })
+            .collect();
+        if self.folds.len() != before {
+            self.invalidate_visual_lines();
+        }
+    }
+
+    /// Keep the layout state in step with an edit just made to the rope:
+    /// `removed` replaced by `inserted` at `pos`
+    fn note_edit(&mut self, pos: usize, removed: &str, inserted: &str) {
+        self.shift_folds(pos, removed.len(), inserted.len());
+        // The lines the edit touched are laid out again; the rest keep theirs
+        if !self.line_layouts.is_empty() {
+            let line = self.rope.byte_to_line(pos.min(self.rope.len_bytes())).min(self.line_layouts.len());
+            let old_end = (line + removed.matches('\n').count() + 1).min(self.line_layouts.len());
+            let new_lines = inserted.matches('\n').count() + 1;
+            self.line_layouts.splice(line..old_end, std::iter::repeat_n(None, new_lines));
+        }
+    }
+
+    /// Keep folds on their parentheses as `removed` bytes at `pos` are
+    /// replaced by `inserted` ones; a fold whose parenthesis is deleted goes
+    fn shift_folds(&mut self, pos: usize, removed: usize, inserted: usize) {
+        if self.folds.is_empty() {
+            return;
+        }
+        let removed_end = pos + removed;
+        self.folds.retain_mut(|(open, close)| {
+            if (pos..removed_end).contains(open) || (pos..removed_end).contains(close) {
+                return false;
+            }
+            for offset in [open, close] {
+                if *offset >= pos {
+                    *offset = *offset - removed + inserted;
+                }
+            }
+            true
+        });
+        self.invalidate_visual_lines();
+    }
+
+    pub fn clear_extra_carets(&mut self) {
+        self.extra_carets.clear();
+    }
+
+    /// With a selection, select the next occurrence of its text as well,
+    /// wrapping at the end; without one, select the word at the caret
+    /// (Ctrl+D). The new selection becomes the primary caret.
+    /// Byte range of the word (letters, digits and `_`) the caret is in or
+    /// next to
+    fn word_at_caret(&self) -> Option<(usize, usize)> {
+        let line_idx = self.rope.byte_to_line(self.caret);
+        let line_start = self.rope.line_to_byte(line_idx);
+        let line = self.rope.line(line_idx).to_string();
+        let offset = self.caret - line_start;
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let word_start = line[..offset].char_indices().rev().find(|&(_, c)| !is_word(c)).map_or(0, |(i, c)| i + c.len_utf8());
+        let word_end = line[offset..].find(|c| !is_word(c)).map_or(line.len(), |i| offset + i);
+        (word_start < word_end).then_some((line_start + word_start, line_start + word_end))
+    }
+
+    /// Other occurrences between `from` and `to` of the name the caret is
+    /// on; none with a selection or on a keyword. Case is ignored, as for
+    /// unquoted names.
+    fn word_occurrences(&self, from: usize, to: usize) -> Vec<(usize, usize)> {
+        if self.get_selection_range().is_some_and(|(s, e)| s < e) {
+            return Vec::new();
+        }
+        let Some((start, end)) = self.word_at_caret() else { return Vec::new() };
+        let word = self.rope.byte_slice(start..end).to_string().to_ascii_lowercase();
+        if word.starts_with(|c: char| c.is_ascii_digit()) || highlight::is_keyword(&word.to_uppercase(), self.dialect) {
+            return Vec::new();
+        }
+        // ASCII lowercasing keeps byte offsets, so matches map back as-is
+        let text = self.rope.byte_slice(from..to).to_string().to_ascii_lowercase();
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        text.match_indices(&word)
+            .map(|(i, _)| (i, i + word.len()))
+            .filter(|&(s, e)| from + s != start && !text[..s].ends_with(is_word) && !text[e..].starts_with(is_word))
+            .map(|(s, e)| (from + s, from + e))
+            .collect()
+    }
+
+    fn select_next_occurrence(&mut self) {
+        self.finalize_undo_group();
+        let Some((start, end)) = self.get_selection_range().filter(|(s, e)| s < e) else {
+            if let Some((word_start, word_end)) = self.word_at_caret() {
+                self.selection_anchor = Some(word_start);
+                self.caret = word_end;
+            }
+            return;
+        };
+        let needle = self.rope.byte_slice(start..end).to_string();
+        let text = self.rope.to_string();
+        let selected: Vec<usize> = self.extra_carets.iter()
+            .filter_map(|c| c.anchor.map(|a| a.min(c.caret)))
+            .chain([start])
+            .collect();
+        let matches: Vec<usize> = text.match_indices(&needle).map(|(pos, _)| pos).collect();
+        let next = matches.iter()
+            .filter(|&&pos| pos >= end)
+            .chain(matches.iter().filter(|&&pos| pos < start))
+            .find(|pos| !selected.contains(pos));
+        if let Some(&pos) = next {
+            self.extra_carets.push(self.caret_state());
+            self.selection_anchor = Some(pos);
+            self.caret = pos + needle.len();
+            self.enable_viewport_following();
+        }
+    }
+
+    /// Add a caret at a screen position of the text area `area` (Alt+Click)
+    pub fn add_caret_at(&mut self, col: u16, row: u16, area: Rect) {
+        let viewport_width = area.width as usize;
+        if let Some((pos, _)) = self.byte_at_screen(col, row, area, viewport_width) {
+            self.finalize_undo_group();
+            let mut carets = std::mem::take(&mut self.extra_carets);
+            carets.push(self.caret_state());
+            self.caret = pos;
+            self.selection_anchor = None;
+            self.set_extra_carets(carets);
+        }
+    }
+
+    /// Put the caret alone at a screen position of the text area `area`
+    pub fn click_at(&mut self, col: u16, row: u16, area: Rect) {
+        self.extra_carets.clear();
+        self.snippet_stops.clear();
+        self.handle_click(col, row, area, area.width as usize, false);
+    }
+
     fn finalize_undo_group(&mut self) {
         if let Some(group) = self.current_group.take() {
             if !group.ops.is_empty() {
                 self.undo_stack.push(group);
+                self.trim_undo_stack();
             }
         }
     }
@@ -1035,9 +1836,7 @@ impl Editor {
         self.finalize_undo_group();
         
         if let Some(group) = self.undo_stack.pop() {
-            let mut caret = self.caret;
-            
-            for (op, before, _) in group.ops.iter().rev() {
+            for op in group.ops.iter().rev() {
                 match op {
                     EditOp::Insert { pos, text } => {
                         // Ensure positions are within bounds
@@ -1047,35 +1846,36 @@ impl Editor {
                             let char_pos = self.rope.byte_to_char(safe_pos);
                             let char_end = self.rope.byte_to_char(safe_end);
                             self.rope.remove(char_pos..char_end);
+                            self.note_edit(safe_pos, text, "");
                         }
                     }
                     EditOp::Delete { pos, text } => {
                         let safe_pos = (*pos).min(self.rope.len_bytes());
                         self.rope.insert(self.rope.byte_to_char(safe_pos), text);
+                        self.note_edit(safe_pos, "", text);
                     }
                 }
-                caret = *before;
             }
             
-            // Ensure caret is within valid bounds
-            self.caret = caret.min(self.rope.len_bytes());
-            self.clear_selection();
+            self.extra_carets.clear();
+            self.snippet_stops.clear();
+            self.restore_caret(group.before);
             self.invalidate_visual_lines();
             self.logical_line_map.clear();
             self.redo_stack.push(group);
-            self.modified = !self.undo_stack.is_empty();
+            self.error_span = None;
+            self.modified = Some(self.undo_stack.len()) != self.saved_depth;
         }
     }
 
     fn redo(&mut self) {
         if let Some(group) = self.redo_stack.pop() {
-            let mut caret = self.caret;
-            
-            for (op, _, after) in &group.ops {
+            for op in &group.ops {
                 match op {
                     EditOp::Insert { pos, text } => {
                         let safe_pos = (*pos).min(self.rope.len_bytes());
                         self.rope.insert(self.rope.byte_to_char(safe_pos), text);
+                        self.note_edit(safe_pos, "", text);
                     }
                     EditOp::Delete { pos, text } => {
                         // Ensure positions are within bounds
@@ -1085,25 +1885,33 @@ impl Editor {
                             let char_pos = self.rope.byte_to_char(safe_pos);
                             let char_end = self.rope.byte_to_char(safe_end);
                             self.rope.remove(char_pos..char_end);
+                            self.note_edit(safe_pos, text, "");
                         }
                     }
                 }
-                caret = *after;
             }
             
-            // Ensure caret is within valid bounds
-            self.caret = caret.min(self.rope.len_bytes());
-            self.clear_selection();
+            self.extra_carets.clear();
+            self.snippet_stops.clear();
+            self.restore_caret(group.after);
             self.invalidate_visual_lines();
             self.logical_line_map.clear();
             self.undo_stack.push(group);
-            self.modified = true;
+            self.error_span = None;
+            self.modified = Some(self.undo_stack.len()) != self.saved_depth;
         }
     }
 
-    fn calculate_indent(line: &str) -> usize {
+    /// Put the caret and selection back, kept within the text
+    fn restore_caret(&mut self, state: CaretState) {
+        let len = self.rope.len_bytes();
+        self.caret = state.caret.min(len);
+        self.selection_anchor = state.anchor.map(|anchor| anchor.min(len)).filter(|&anchor| anchor != self.caret);
+    }
+
+    fn calculate_indent(line: &str, tab_width: usize) -> usize {
         let trimmed = line.trim_start();
-        let base_indent = line.len() - trimmed.len();
+        let base_indent = text_cols(&line[..line.len() - trimmed.len()], tab_width);
         
         if trimmed.starts_with("- ") || trimmed.starts_with("* ") || trimmed.starts_with("+ ")  || trimmed.starts_with("▢ ")  || trimmed.starts_with("◪ ")  || trimmed.starts_with("■ ") {
             return base_indent + 4;
@@ -1130,62 +1938,63 @@ impl Editor {
     fn rebuild_visual_lines(&mut self, viewport_width: usize) {
         self.visual_lines.clear();
         self.logical_line_map.clear();
+        // A new width relays everything out, as does an edit that wasn't noted
+        let wrap_width = if self.word_wrap { viewport_width } else { 0 };
+        if wrap_width != self.wrap_width || self.line_layouts.len() != self.rope.len_lines() {
+            self.line_layouts = vec![None; self.rope.len_lines()];
+            self.wrap_width = wrap_width;
+        }
         
         for _ in 0..self.virtual_lines {
             self.visual_lines.push(None);
         }
         
         let mut byte_pos = 0;
+        let hidden: Vec<Range<usize>> = self.folds.iter().map(|&fold| self.folded_lines(fold)).collect();
         
         for line_idx in 0..self.rope.len_lines() {
             let line_start_idx = self.visual_lines.len();
-            let line = self.rope.line(line_idx);
-            let line_str = line.to_string();
-            let line_bytes = line.len_bytes();
+            let layout = match self.line_layouts[line_idx].take() {
+                Some(layout) => layout,
+                None => self.layout_line(line_idx, viewport_width),
+            };
             
-            if !self.word_wrap {
-                let has_newline = line_str.ends_with('\n');
-                let end = byte_pos + line_bytes.saturating_sub(if has_newline { 1 } else { 0 });
-                
+            if hidden.iter().any(|lines| lines.contains(&line_idx)) {
+                self.logical_line_map.push((line_start_idx, 0));
+                byte_pos += layout.len_bytes;
+                self.line_layouts[line_idx] = Some(layout);
+                continue;
+            }
+            
+            for (i, &(start, end)) in layout.segments.iter().enumerate() {
                 self.visual_lines.push(Some(VisualLine {
-                    start_byte: byte_pos,
-                    end_byte: end,
-                    is_continuation: false,
-                    indent: 0,
+                    start_byte: byte_pos + start,
+                    end_byte: byte_pos + end,
+                    is_continuation: i > 0,
+                    indent: if i > 0 { layout.indent } else { 0 },
                     logical_line: line_idx,
+                    folded: 0,
                 }));
-            } else {
-                let has_newline = line_str.ends_with('\n');
-                let content = if has_newline { &line_str[..line_str.len() - 1] } else { &line_str };
-                
-                if content.is_empty() {
-                    self.visual_lines.push(Some(VisualLine {
-                        start_byte: byte_pos,
-                        end_byte: byte_pos,
-                        is_continuation: false,
-                        indent: 0,
-                        logical_line: line_idx,
-                    }));
-                } else {
-                    let indent = Self::calculate_indent(&line_str);
-                    let segments = self.wrap_line(content, viewport_width, indent);
-                    
-                    for (i, (start, end)) in segments.into_iter().enumerate() {
-                        self.visual_lines.push(Some(VisualLine {
-                            start_byte: byte_pos + start,
-                            end_byte: byte_pos + end,
-                            is_continuation: i > 0,
-                            indent: if i > 0 { indent } else { 0 },
-                            logical_line: line_idx,
-                        }));
-                    }
-                }
+            }
+            
+            // Hidden lines of nested folds are counted once
+            let folded = hidden.iter().filter(|lines| lines.start == line_idx + 1).map(|lines| lines.len()).max();
+            if let (Some(folded), Some(Some(last))) = (folded, self.visual_lines.last_mut()) {
+                last.folded = folded;
             }
             
             let line_visual_count = self.visual_lines.len() - line_start_idx;
             self.logical_line_map.push((line_start_idx, line_visual_count));
             
-            byte_pos += line_bytes;
+            byte_pos += layout.len_bytes;
+            self.line_layouts[line_idx] = Some(layout);
+        }
+        
+        // Kept layouts that no longer add up to the text are all redone
+        if byte_pos != self.rope.len_bytes() {
+            self.line_layouts.clear();
+            self.rebuild_visual_lines(viewport_width);
+            return;
         }
         
         for _ in 0..self.virtual_lines {
@@ -1195,12 +2004,30 @@ impl Editor {
         self.visual_lines_valid = true;
     }
 
+    /// Rows of line `line_idx`: one for the whole line, or its wrap
+    /// segments with word wrap on
+    fn layout_line(&self, line_idx: usize, viewport_width: usize) -> LineLayout {
+        let line = self.rope.line(line_idx);
+        let line_str = line.to_string();
+        let content = line_str.strip_suffix('\n').unwrap_or(&line_str);
+        if !self.word_wrap || content.is_empty() {
+            return LineLayout { len_bytes: line.len_bytes(), segments: vec![(0, content.len())], indent: 0 };
+        }
+        let indent = Self::calculate_indent(&line_str, self.tab_width);
+        LineLayout {
+            len_bytes: line.len_bytes(),
+            segments: self.wrap_line(content, viewport_width, indent),
+            indent,
+        }
+    }
+
     fn invalidate_visual_lines(&mut self) {
         self.visual_lines_valid = false;
     }
 
     fn ensure_visual_lines(&mut self, viewport_width: usize) {
-        if !self.visual_lines_valid || self.visual_lines.is_empty() {
+        let resized = self.word_wrap && viewport_width != self.wrap_width;
+        if !self.visual_lines_valid || self.visual_lines.is_empty() || resized {
             self.rebuild_visual_lines(viewport_width);
         }
     }
@@ -1222,79 +2049,28 @@ impl Editor {
             }
             
             let mut width = 0;
-            let mut end = start;
+            let mut end = content.len();
             let mut last_break = start;
             
-            let slice = if start <= content.len() {
-                content.chars().skip(content[..start].chars().count())
-            } else {
-                break;
-            };
-            
-            let mut char_offset = 0;
-            for ch in slice {
-                let ch_width = ch.to_string().width();
-                if width + ch_width > available_width && char_offset > 0 {
-                    end = if last_break > start { 
-                        last_break 
-                    } else {
-                        // Calculate the byte position for char_offset characters from start
-                        let mut byte_pos = start;
-                        for (idx, ch) in content[start..].chars().enumerate() {
-                            if idx >= char_offset {
-                                break;
-                            }
-                            byte_pos += ch.len_utf8();
-                        }
-                        byte_pos
-                    };
+            for (offset, ch) in content[start..].char_indices() {
+                let pos = start + offset;
+                let ch_width = char_cols(ch, width, self.tab_width);
+                if width + ch_width > available_width && pos > start {
+                    end = if last_break > start { last_break } else { pos };
                     break;
                 }
                 
                 width += ch_width;
                 if ch == ' ' || ch == '-' || ch == '/' {
-                    // Calculate byte position for the break point
-                    let mut byte_pos = start;
-                    for (idx, c) in content[start..].chars().enumerate() {
-                        if idx == char_offset {
-                            byte_pos += c.len_utf8();
-                            break;
-                        }
-                        byte_pos += c.len_utf8();
-                    }
-                    last_break = byte_pos;
+                    last_break = pos + ch.len_utf8();
                 }
-                
-                // Calculate end byte position
-                let mut byte_pos = start;
-                for (idx, c) in content[start..].chars().enumerate() {
-                    if idx == char_offset {
-                        byte_pos += c.len_utf8();
-                        break;
-                    }
-                    byte_pos += c.len_utf8();
-                }
-                end = byte_pos;
-                
-                char_offset += 1;
             }
             
             segments.push((start, end));
-            start = end;
             is_first = false;
             
-            // Skip spaces at the beginning of the next line, respecting UTF-8 boundaries
-            while start < content.len() {
-                if let Some(ch) = content[start..].chars().next() {
-                    if ch == ' ' {
-                        start += ch.len_utf8();
-                    } else {
-                        break;
-                    }
-                } else {
-                    break;
-                }
-            }
+            // Skip spaces at the beginning of the next line
+            start = content.len() - content[end..].trim_start_matches(' ').len();
         }
         
         if segments.is_empty() {
@@ -1319,7 +2095,7 @@ impl Editor {
                 
                 if byte_pos >= vl.start_byte && byte_pos <= vl.end_byte {
                     let text = &self.rope.byte_slice(vl.start_byte..byte_pos).to_string();
-                    let col = vl.indent + text.width();
+                    let col = vl.indent + text_cols(text, self.tab_width);
                     return (row, col);
                 }
             }
@@ -1350,7 +2126,7 @@ impl Editor {
                 if width >= adjusted_col {
                     break;
                 }
-                width += ch.to_string().width();
+                width += char_cols(ch, width, self.tab_width);
                 byte_offset += ch.len_utf8();
             }
             
@@ -1425,6 +2201,51 @@ impl Editor {
         }
     }
 
+    /// Home goes to the start of a wrapped row, then to the line's first
+    /// non-blank character, then to column 0; pressed again from there it
+    /// goes back to the first non-blank
+    fn move_home(&mut self, viewport_width: usize, extend_selection: bool) {
+        self.enable_viewport_following();
+        if extend_selection && self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.caret);
+        } else if !extend_selection {
+            self.clear_selection();
+        }
+        
+        let (row, _) = self.get_visual_position(self.caret, viewport_width);
+        let row_start = match self.visual_lines.get(row) {
+            Some(Some(vl)) if vl.is_continuation => Some(vl.start_byte),
+            _ => None,
+        };
+        let line = self.rope.byte_to_line(self.caret);
+        let line_start = self.rope.line_to_byte(line);
+        let text = self.rope.line(line).to_string();
+        let first_non_blank = line_start + text.len() - text.trim_start_matches([' ', '\t']).len();
+        
+        self.caret = match row_start {
+            Some(start) if self.caret != start => start,
+            _ if self.caret != first_non_blank => first_non_blank,
+            _ => line_start,
+        };
+        let (_, col) = self.get_visual_position(self.caret, viewport_width);
+        self.preferred_col = col;
+    }
+    
+    fn move_end(&mut self, viewport_width: usize, extend_selection: bool) {
+        self.enable_viewport_following();
+        if extend_selection && self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.caret);
+        } else if !extend_selection {
+            self.clear_selection();
+        }
+        
+        let line = self.rope.byte_to_line(self.caret);
+        let text = self.rope.line(line).to_string();
+        self.caret = self.rope.line_to_byte(line) + text.trim_end_matches(['\n', '\r']).len();
+        let (_, col) = self.get_visual_position(self.caret, viewport_width);
+        self.preferred_col = col;
+    }
+
     fn move_right(&mut self, viewport_width: usize, extend_selection: bool) {
         self.enable_viewport_following();
         if !extend_selection && self.has_selection() {
@@ -1457,14 +2278,188 @@ impl Editor {
         self.enable_viewport_following();
         self.delete_selection();
 
-        let before = self.caret;
+        let before = self.caret_state();
         self.rope.insert_char(self.rope.byte_to_char(self.caret), ch);
         self.caret += ch.len_utf8();
         
-        self.push_op(EditOp::Insert { pos: before, text: ch.to_string() }, before, self.caret);
+        self.push_op(EditOp::Insert { pos: before.caret, text: ch.to_string() }, EditKind::Typing, before);
+        
+        self.invalidate_visual_lines();
+        
+        let (_, col) = self.get_visual_position(self.caret, viewport_width);
+        self.preferred_col = col;
+    }
+
+    /// The character `offset` characters after the caret (negative:
+    /// before it), if there is one
+    fn char_near_caret(&self, offset: isize) -> Option<char> {
+        let idx = self.rope.byte_to_char(self.caret).checked_add_signed(offset)?;
+        self.rope.get_char(idx)
+    }
+
+    /// Type `ch`, pairing it when `auto_close` is on: an opener gets its
+    /// closer after the caret, a closer already there is stepped over, an
+    /// opener typed over a selection wraps it, and a second `$` gets a
+    /// closing `$$`
+    fn type_char(&mut self, ch: char, viewport_width: usize) {
+        if !(ch.is_alphanumeric() || ch == '_') {
+            self.recase_keyword_before_caret();
+        }
+        if !self.auto_close {
+            return self.insert_char(ch, viewport_width);
+        }
+        let closer = closer_for(ch);
+        if let Some((start, end)) = self.get_selection_range().filter(|(start, end)| start < end) {
+            match closer {
+                Some(closer) => self.wrap_selection(start, end, ch, closer, viewport_width),
+                None => self.insert_char(ch, viewport_width),
+            }
+            return;
+        }
         
+        let prev = self.char_near_caret(-1);
+        let next = self.char_near_caret(0);
+        if matches!(ch, ')' | ']' | '\'' | '"' | '$') && next == Some(ch) {
+            self.enable_viewport_following();
+            self.caret += ch.len_utf8();
+            let (_, col) = self.get_visual_position(self.caret, viewport_width);
+            self.preferred_col = col;
+            return;
+        }
+        // Only before nothing, space or a closer, so typing in front of a
+        // word doesn't leave a stray closer
+        let open_context = next.map_or(true, |c| c.is_whitespace() || matches!(c, ')' | ']' | ',' | ';'));
+        // A quote after a word is an apostrophe or ends a string, and one
+        // after another quote escapes it
+        let word_before = prev.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '\'' || c == '"');
+        let pair = match ch {
+            '$' => (prev == Some('$') && self.char_near_caret(-2) != Some('$')).then(|| "$$".to_string()),
+            '\'' | '"' if word_before => None,
+            _ => closer.map(String::from),
+        };
+        match pair {
+            Some(pair) if open_context => {
+                self.enable_viewport_following();
+                let before = self.caret_state();
+                let text = format!("{}{}", ch, pair);
+                self.rope.insert(self.rope.byte_to_char(self.caret), &text);
+                self.caret += ch.len_utf8();
+                self.push_op(EditOp::Insert { pos: before.caret, text }, EditKind::Typing, before);
+                self.invalidate_visual_lines();
+                let (_, col) = self.get_visual_position(self.caret, viewport_width);
+                self.preferred_col = col;
+            }
+            _ => self.insert_char(ch, viewport_width),
+        }
+    }
+
+    /// Change the keyword just finished before the caret to `keyword_case`,
+    /// unless it is in a string, quoted name or comment
+    fn recase_keyword_before_caret(&mut self) {
+        if self.keyword_case == KeywordCase::Off || self.get_selection_range().is_some_and(|(s, e)| s < e) {
+            return;
+        }
+        let line_start = self.rope.line_to_byte(self.rope.byte_to_line(self.caret));
+        let before_caret = self.rope.byte_slice(line_start..self.caret).to_string();
+        let word = before_caret.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_');
+        let prefix_len = word.len();
+        let word = &before_caret[prefix_len..];
+        // `$var`, `:var` and `t.col` aren't keywords whatever their name
+        let is_name = before_caret[..prefix_len].ends_with(['$', ':', '.', '@']);
+        if word.is_empty() || is_name || in_string_or_comment(&before_caret[..prefix_len]) {
+            return;
+        }
+        let upper = word.to_uppercase();
+        if !highlight::is_keyword(&upper, self.dialect) {
+            return;
+        }
+        let cased = if self.keyword_case == KeywordCase::Upper { upper } else { word.to_lowercase() };
+        if cased != word {
+            self.replace_range(line_start + prefix_len, self.caret, cased);
+        }
+    }
+
+    /// Replace `start..end` with `text` as one undo step, keeping the caret
+    /// and selection on the same text. Inside a step already open (typing
+    /// at several carets) the edit joins it.
+    fn replace_range(&mut self, start: usize, end: usize, text: String) {
+        let before = self.caret_state();
+        let own_group = self.forced_group.is_none();
+        if own_group {
+            self.begin_undo_group(EditKind::Other);
+        }
+        let old = self.rope.byte_slice(start..end).to_string();
+        let delta = text.len() as isize - old.len() as isize;
+        let map = |pos: usize| if pos >= end { pos.saturating_add_signed(delta) } else { pos.min(start + text.len()) };
+        self.rope.remove(self.rope.byte_to_char(start)..self.rope.byte_to_char(end));
+        self.push_op(EditOp::Delete { pos: start, text: old }, EditKind::Other, before);
+        self.rope.insert(self.rope.byte_to_char(start), &text);
+        self.caret = map(self.caret);
+        self.selection_anchor = self.selection_anchor.map(map);
+        self.push_op(EditOp::Insert { pos: start, text }, EditKind::Other, before);
+        if own_group {
+            self.end_undo_group();
+        }
+        self.invalidate_visual_lines();
+    }
+
+    /// Rewrite the leading whitespace of every line in the configured
+    /// style: spaces only, or tabs with spaces for a part stop. Returns
+    /// the number of lines changed, as one undo step.
+    pub fn convert_indentation(&mut self) -> usize {
+        if self.read_only {
+            return 0;
+        }
+        let mut changed = 0;
+        self.clear_extra_carets();
+        self.begin_undo_group(EditKind::Other);
+        // From the last line up, so the earlier ones keep their offsets
+        for line_idx in (0..self.rope.len_lines()).rev() {
+            let line = self.rope.line(line_idx).to_string();
+            let lead = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+            let cols = text_cols(lead, self.tab_width);
+            let converted = if self.insert_spaces {
+                " ".repeat(cols)
+            } else {
+                "\t".repeat(cols / self.tab_width) + &" ".repeat(cols % self.tab_width)
+            };
+            if converted != lead {
+                let line_start = self.rope.line_to_byte(line_idx);
+                self.replace_range(line_start, line_start + lead.len(), converted);
+                changed += 1;
+            }
+        }
+        self.end_undo_group();
+        changed
+    }
+
+    /// Convert the selection, or the word at the caret, to `case`
+    fn convert_case(&mut self, case: TextCase) {
+        let Some((start, end)) = self.get_selection_range().filter(|(s, e)| s < e).or_else(|| self.word_at_caret()) else {
+            return;
+        };
+        let text = self.rope.byte_slice(start..end).to_string();
+        let converted = case.apply(&text);
+        if converted != text {
+            self.enable_viewport_following();
+            self.replace_range(start, end, converted);
+        }
+    }
+
+    /// Put `open` before and `close` after the selection, which stays on
+    /// the text inside
+    fn wrap_selection(&mut self, start: usize, end: usize, open: char, close: char, viewport_width: usize) {
+        self.enable_viewport_following();
+        let before = self.caret_state();
+        self.begin_undo_group(EditKind::Other);
+        self.rope.insert_char(self.rope.byte_to_char(end), close);
+        self.push_op(EditOp::Insert { pos: end, text: close.to_string() }, EditKind::Other, before);
+        self.rope.insert_char(self.rope.byte_to_char(start), open);
+        self.caret += open.len_utf8();
+        self.selection_anchor = self.selection_anchor.map(|anchor| anchor + open.len_utf8());
+        self.push_op(EditOp::Insert { pos: start, text: open.to_string() }, EditKind::Other, before);
+        self.end_undo_group();
         self.invalidate_visual_lines();
-        
         let (_, col) = self.get_visual_position(self.caret, viewport_width);
         self.preferred_col = col;
     }
@@ -1479,10 +2474,10 @@ impl Editor {
             let char_idx = self.rope.byte_to_char(self.caret);
             
             if let Some(ch) = self.rope.get_char(char_idx) {
-                let before = self.caret;
+                let before = self.caret_state();
                 self.rope.remove(char_idx..char_idx + 1);
                 
-                self.push_op(EditOp::Delete { pos: self.caret, text: ch.to_string() }, before, self.caret);
+                self.push_op(EditOp::Delete { pos: self.caret, text: ch.to_string() }, EditKind::Delete, before);
                 
                 self.invalidate_visual_lines();
             }
@@ -1494,25 +2489,39 @@ impl Editor {
         if self.delete_selection() {
             return;
         }
+        // Backspace in an empty pair takes the closer too
+        let prev = self.char_near_caret(-1);
+        if self.auto_close && prev.and_then(closer_for).is_some_and(|c| self.char_near_caret(0) == Some(c)) {
+            let before = self.caret_state();
+            let char_idx = self.rope.byte_to_char(self.caret);
+            let text = self.rope.slice(char_idx - 1..char_idx + 1).to_string();
+            self.rope.remove(char_idx - 1..char_idx + 1);
+            self.caret -= prev.map_or(0, char::len_utf8);
+            self.push_op(EditOp::Delete { pos: self.caret, text }, EditKind::Delete, before);
+            self.invalidate_visual_lines();
+            return;
+        }
 
         if self.caret > 0 {
             let char_idx = self.rope.byte_to_char(self.caret);
             if char_idx > 0 {
                 let ch = self.rope.char(char_idx - 1);
                 let ch_bytes = ch.len_utf8();
-                let before = self.caret;
+                let before = self.caret_state();
                 
                 self.rope.remove(char_idx - 1..char_idx);
                 self.caret -= ch_bytes;
                 
-                self.push_op(EditOp::Delete { pos: self.caret, text: ch.to_string() }, before, self.caret);
+                self.push_op(EditOp::Delete { pos: self.caret, text: ch.to_string() }, EditKind::Delete, before);
                 
                 self.invalidate_visual_lines();
             }
         }
     }
 
-    fn indent(&mut self, viewport_width: usize) {
+    /// Indent the caret's line or every selected line by one level
+    pub fn indent(&mut self, viewport_width: usize) {
+        let unit = self.indent_unit();
         if let Some((start, end)) = self.get_selection_range() {
             // Handle selection - indent all lines in selection
             let start_char = self.rope.byte_to_char(start);
@@ -1520,29 +2529,30 @@ impl Editor {
             let start_line = self.rope.char_to_line(start_char);
             let end_line = self.rope.char_to_line(end_char);
             
-            let before_caret = self.caret;
+            let before = self.caret_state();
             let mut caret_adjustment = 0;
             let mut anchor_adjustment = 0;
             
             // Process lines from end to start to avoid offset issues
+            self.begin_undo_group(EditKind::Other);
             for line_idx in (start_line..=end_line).rev() {
                 let line_start = self.rope.line_to_char(line_idx);
                 let line_byte = self.rope.char_to_byte(line_start);
                 
-                self.rope.insert(line_start, "    ");
+                self.rope.insert(line_start, &unit);
                 
                 // Track adjustments for caret and anchor
                 if self.caret >= line_byte {
-                    caret_adjustment += 4;
+                    caret_adjustment += unit.len();
                 }
                 
                 if let Some(anchor) = self.selection_anchor {
                     if anchor >= line_byte {
-                        anchor_adjustment += 4;
+                        anchor_adjustment += unit.len();
                     }
                 }
                 
-                self.push_op(EditOp::Insert { pos: line_byte, text: "    ".to_string() }, before_caret, self.caret);
+                self.push_op(EditOp::Insert { pos: line_byte, text: unit.clone() }, EditKind::Other, before);
             }
             
             // Apply adjustments
@@ -1550,6 +2560,7 @@ impl Editor {
             if let Some(anchor) = self.selection_anchor {
                 self.selection_anchor = Some(anchor + anchor_adjustment);
             }
+            self.end_undo_group();
             
             self.invalidate_visual_lines();
             let (_, col) = self.get_visual_position(self.caret, viewport_width);
@@ -1561,13 +2572,13 @@ impl Editor {
             let line_start = self.rope.line_to_char(line_idx);
             let line_byte = self.rope.char_to_byte(line_start);
             
-            let before = self.caret;
-            self.rope.insert(line_start, "    ");
+            let before = self.caret_state();
+            self.rope.insert(line_start, &unit);
             if self.caret >= line_byte {
-                self.caret += 4;
+                self.caret += unit.len();
             }
             
-            self.push_op(EditOp::Insert { pos: line_byte, text: "    ".to_string() }, before, self.caret);
+            self.push_op(EditOp::Insert { pos: line_byte, text: unit.clone() }, EditKind::Other, before);
             
             self.invalidate_visual_lines();
             
@@ -1576,7 +2587,9 @@ impl Editor {
         }
     }
 
-    fn dedent(&mut self, viewport_width: usize) {
+    /// Take one level of indentation off the caret's line or every
+    /// selected line
+    pub fn dedent(&mut self, viewport_width: usize) {
         if let Some((start, end)) = self.get_selection_range() {
             // Handle selection - dedent all lines in selection
             let start_char = self.rope.byte_to_char(start);
@@ -1584,27 +2597,20 @@ impl Editor {
             let start_line = self.rope.char_to_line(start_char);
             let end_line = self.rope.char_to_line(end_char);
             
-            let before_caret = self.caret;
+            let before = self.caret_state();
             let mut caret_adjustment = 0;
             let mut anchor_adjustment = 0;
             
             // Process lines from end to start to avoid offset issues
+            self.begin_undo_group(EditKind::Other);
             for line_idx in (start_line..=end_line).rev() {
-                let line = self.rope.line(line_idx);
-                
-                let mut spaces = 0;
-                for ch in line.chars().take(4) {
-                    if ch == ' ' {
-                        spaces += 1;
-                    } else {
-                        break;
-                    }
-                }
+                let spaces = self.outdent_len(line_idx);
                 
                 if spaces > 0 {
                     let line_start = self.rope.line_to_char(line_idx);
                     let line_byte = self.rope.char_to_byte(line_start);
                     
+                    let removed = self.rope.byte_slice(line_byte..line_byte + spaces).to_string();
                     self.rope.remove(line_start..line_start + spaces);
                     
                     // Track adjustments for caret and anchor
@@ -1626,7 +2632,7 @@ impl Editor {
                         }
                     }
                     
-                    self.push_op(EditOp::Delete { pos: line_byte, text: " ".repeat(spaces) }, before_caret, self.caret);
+                    self.push_op(EditOp::Delete { pos: line_byte, text: removed }, EditKind::Other, before);
                 }
             }
             
@@ -1635,6 +2641,7 @@ impl Editor {
             if let Some(anchor) = self.selection_anchor {
                 self.selection_anchor = Some(anchor - anchor_adjustment);
             }
+            self.end_undo_group();
             
             self.invalidate_visual_lines();
             let (_, col) = self.get_visual_position(self.caret, viewport_width);
@@ -1643,22 +2650,14 @@ impl Editor {
             // No selection - dedent current line only
             let char_idx = self.rope.byte_to_char(self.caret);
             let line_idx = self.rope.char_to_line(char_idx);
-            let line = self.rope.line(line_idx);
-            
-            let mut spaces = 0;
-            for ch in line.chars().take(4) {
-                if ch == ' ' {
-                    spaces += 1;
-                } else {
-                    break;
-                }
-            }
+            let spaces = self.outdent_len(line_idx);
             
             if spaces > 0 {
                 let line_start = self.rope.line_to_char(line_idx);
                 let line_byte = self.rope.char_to_byte(line_start);
-                let before = self.caret;
+                let before = self.caret_state();
                 
+                let removed = self.rope.byte_slice(line_byte..line_byte + spaces).to_string();
                 self.rope.remove(line_start..line_start + spaces);
                 
                 if self.caret >= line_byte + spaces {
@@ -1667,7 +2666,7 @@ impl Editor {
                     self.caret = line_byte;
                 }
                 
-                self.push_op(EditOp::Delete { pos: line_byte, text: " ".repeat(spaces) }, before, self.caret);
+                self.push_op(EditOp::Delete { pos: line_byte, text: removed }, EditKind::Other, before);
                 
                 self.invalidate_visual_lines();
                 
@@ -1677,24 +2676,127 @@ impl Editor {
         }
     }
 
+    /// One level of indentation: `tab_width` spaces, or a tab
+    fn indent_unit(&self) -> String {
+        if self.insert_spaces { " ".repeat(self.tab_width) } else { "\t".to_string() }
+    }
+
+    /// Bytes dedenting takes off the front of a line: a tab, or up to
+    /// `tab_width` spaces
+    fn outdent_len(&self, line_idx: usize) -> usize {
+        let line = self.rope.line(line_idx);
+        if line.chars().next() == Some('\t') {
+            return 1;
+        }
+        line.chars().take(self.tab_width).take_while(|&ch| ch == ' ').count()
+    }
+
+    /// First and last line of the selection, or the caret's line twice
+    fn selected_lines(&self) -> (usize, usize) {
+        match self.get_selection_range() {
+            Some((start, end)) => (self.rope.byte_to_line(start), self.rope.byte_to_line(end)),
+            None => {
+                let line_idx = self.rope.byte_to_line(self.caret);
+                (line_idx, line_idx)
+            }
+        }
+    }
+
+    /// Insert a copy of the caret's line, or of the selected lines, below
+    /// them and move the caret and selection onto it (Ctrl+Shift+D)
+    fn duplicate_lines(&mut self, viewport_width: usize) {
+        let (start_line, end_line) = self.selected_lines();
+        let before = self.caret_state();
+        let block_start = self.rope.line_to_byte(start_line);
+        let block_end = self.rope.line_to_byte(end_line + 1);
+        let block = self.rope.byte_slice(block_start..block_end).to_string();
+        // The last line has no line break of its own to copy
+        let text = if block.ends_with('\n') { block } else { format!("\n{}", block) };
+        
+        self.rope.insert(self.rope.byte_to_char(block_end), &text);
+        self.caret += text.len();
+        self.selection_anchor = self.selection_anchor.map(|anchor| anchor + text.len());
+        self.push_op(EditOp::Insert { pos: block_end, text }, EditKind::Other, before);
+        
+        self.invalidate_visual_lines();
+        let (_, col) = self.get_visual_position(self.caret, viewport_width);
+        self.preferred_col = col;
+    }
+
+    /// Delete the caret's line or the selected lines (Ctrl+Shift+K)
+    fn delete_lines(&mut self, viewport_width: usize) {
+        let (start_line, end_line) = self.selected_lines();
+        let before = self.caret_state();
+        let mut start = self.rope.line_to_byte(start_line);
+        let end = self.rope.line_to_byte(end_line + 1);
+        // Without a line break after them, the one before goes instead
+        if start > 0 && !self.rope.byte_slice(start..end).to_string().ends_with('\n') {
+            start -= 1;
+        }
+        if start == end {
+            return;
+        }
+        let text = self.rope.byte_slice(start..end).to_string();
+        
+        self.rope.remove(self.rope.byte_to_char(start)..self.rope.byte_to_char(end));
+        self.caret = self.rope.line_to_byte(self.rope.byte_to_line(start));
+        self.selection_anchor = None;
+        self.push_op(EditOp::Delete { pos: start, text }, EditKind::Other, before);
+        
+        self.invalidate_visual_lines();
+        let (_, col) = self.get_visual_position(self.caret, viewport_width);
+        self.preferred_col = col;
+    }
+
+    /// Join the next line, or the selected lines, onto the caret's line:
+    /// each line break and the indentation after it becomes one space, or
+    /// nothing before an empty line (Ctrl+J)
+    fn join_lines(&mut self, viewport_width: usize) {
+        let (start_line, end_line) = self.selected_lines();
+        let end_line = end_line.max(start_line + 1).min(self.rope.len_lines().saturating_sub(1));
+        if end_line <= start_line {
+            return;
+        }
+        let before = self.caret_state();
+        self.begin_undo_group(EditKind::Other);
+        // From the last break back, so the earlier ones keep their offsets
+        let mut first_join = self.caret;
+        for line_idx in (start_line + 1..=end_line).rev() {
+            let line_start = self.rope.line_to_byte(line_idx);
+            let line = self.rope.line(line_idx).to_string();
+            let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+            let mut break_start = line_start - 1;
+            if break_start > 0 && self.rope.byte(break_start - 1) == b'\r' {
+                break_start -= 1;
+            }
+            let removed = self.rope.byte_slice(break_start..line_start + indent).to_string();
+            self.rope.remove(self.rope.byte_to_char(break_start)..self.rope.byte_to_char(line_start + indent));
+            self.push_op(EditOp::Delete { pos: break_start, text: removed }, EditKind::Other, before);
+            
+            let rest_empty = line[indent..].trim_end_matches(['\r', '\n']).is_empty();
+            if !rest_empty {
+                self.rope.insert(self.rope.byte_to_char(break_start), " ");
+                self.push_op(EditOp::Insert { pos: break_start, text: " ".to_string() }, EditKind::Other, before);
+            }
+            first_join = break_start;
+        }
+        self.caret = first_join;
+        self.selection_anchor = None;
+        self.end_undo_group();
+        
+        self.invalidate_visual_lines();
+        let (_, col) = self.get_visual_position(self.caret, viewport_width);
+        self.preferred_col = col;
+    }
+
     fn move_lines_up(&mut self, viewport_width: usize) {
-        let (start_line, end_line) = if let Some((start, end)) = self.get_selection_range() {
-            let start_char = self.rope.byte_to_char(start);
-            let end_char = self.rope.byte_to_char(end);
-            let start_line = self.rope.char_to_line(start_char);
-            let end_line = self.rope.char_to_line(end_char);
-            (start_line, end_line)
-        } else {
-            let char_idx = self.rope.byte_to_char(self.caret);
-            let line_idx = self.rope.char_to_line(char_idx);
-            (line_idx, line_idx)
-        };
+        let (start_line, end_line) = self.selected_lines();
         
         if start_line == 0 {
             return;
         }
         
-        let before_caret = self.caret;
+        let before = self.caret_state();
         let _before_anchor = self.selection_anchor;
         
         // Store original line count to detect if we're at the end
@@ -1754,8 +2856,10 @@ impl Editor {
         }
         
         // Record the operation for undo
-        self.push_op(EditOp::Delete { pos: prev_line_start_byte, text: original_content }, before_caret, self.caret);
-        self.push_op(EditOp::Insert { pos: prev_line_start_byte, text: new_content }, before_caret, self.caret);
+        self.begin_undo_group(EditKind::Other);
+        self.push_op(EditOp::Delete { pos: prev_line_start_byte, text: original_content }, EditKind::Other, before);
+        self.push_op(EditOp::Insert { pos: prev_line_start_byte, text: new_content }, EditKind::Other, before);
+        self.end_undo_group();
         
         self.invalidate_visual_lines();
         let (_, col) = self.get_visual_position(self.caret, viewport_width);
@@ -1763,17 +2867,7 @@ impl Editor {
     }
     
     fn move_lines_down(&mut self, viewport_width: usize) {
-        let (start_line, end_line) = if let Some((start, end)) = self.get_selection_range() {
-            let start_char = self.rope.byte_to_char(start);
-            let end_char = self.rope.byte_to_char(end);
-            let start_line = self.rope.char_to_line(start_char);
-            let end_line = self.rope.char_to_line(end_char);
-            (start_line, end_line)
-        } else {
-            let char_idx = self.rope.byte_to_char(self.caret);
-            let line_idx = self.rope.char_to_line(char_idx);
-            (line_idx, line_idx)
-        };
+        let (start_line, end_line) = self.selected_lines();
         
         let total_lines = self.rope.len_lines();
         
@@ -1782,7 +2876,7 @@ impl Editor {
             return;
         }
         
-        let before_caret = self.caret;
+        let before = self.caret_state();
         let _before_anchor = self.selection_anchor;
         
         let at_end_of_doc = end_line + 2 == total_lines;
@@ -1849,8 +2943,10 @@ impl Editor {
         }
         
         // Record the operation for undo
-        self.push_op(EditOp::Delete { pos: block_start_byte, text: original_content }, before_caret, self.caret);
-        self.push_op(EditOp::Insert { pos: block_start_byte, text: new_content }, before_caret, self.caret);
+        self.begin_undo_group(EditKind::Other);
+        self.push_op(EditOp::Delete { pos: block_start_byte, text: original_content }, EditKind::Other, before);
+        self.push_op(EditOp::Insert { pos: block_start_byte, text: new_content }, EditKind::Other, before);
+        self.end_undo_group();
         
         self.invalidate_visual_lines();
         let (_, col) = self.get_visual_position(self.caret, viewport_width);
@@ -1866,7 +2962,7 @@ impl Editor {
         if let Some((start, end)) = self.get_selection_range() {
             if start < end {
                 let text = self.rope.byte_slice(start..end).to_string();
-                if let Err(_) = self.clipboard.set_text(text) {
+                if clipboard::set_text(text).is_err() {
                     return false;
                 }
                 return true;
@@ -1883,23 +2979,81 @@ impl Editor {
         false
     }
 
-    fn paste(&mut self, viewport_width: usize) {
-        if let Ok(text) = self.clipboard.get_text() {
-            self.delete_selection();
-            
-            let before = self.caret;
-            let char_pos = self.rope.byte_to_char(self.caret);
-            let bytes_inserted = text.len();
-            self.rope.insert(char_pos, &text);
-            self.caret += bytes_inserted;
-            
-            self.push_op(EditOp::Insert { pos: before, text: text.clone() }, before, self.caret);
-            
-            self.invalidate_visual_lines();
-            
-            let (_, col) = self.get_visual_position(self.caret, viewport_width);
-            self.preferred_col = col;
+    fn paste(&mut self) {
+        if let Some(text) = clipboard::get_text() {
+            self.insert_pasted(text);
+        }
+    }
+
+    /// Replace the selection with `text` in one rope insert and one undo
+    /// step. The layout isn't touched here: the lines it spans are laid
+    /// out by the next `update_viewport`, which also takes the caret's
+    /// column as the preferred one.
+    fn insert_pasted(&mut self, text: String) {
+        self.delete_selection();
+        
+        let before = self.caret_state();
+        self.rope.insert(self.rope.byte_to_char(self.caret), &text);
+        self.caret += text.len();
+        
+        self.push_op(EditOp::Insert { pos: before.caret, text }, EditKind::Paste, before);
+        
+        self.invalidate_visual_lines();
+        self.preferred_col_stale = true;
+    }
+
+    /// Insert an object name coming from the object tree or autocomplete,
+    /// quoting each part of the qualified name as `mode` requires. The
+    /// `replace_len` bytes before the caret (a partly typed name) go.
+    pub fn insert_identifier(&mut self, replace_len: usize, parts: &[&str], mode: IdentifierQuoting, viewport_width: usize) {
+        let text = crate::identifier::quote_qualified(parts, mode);
+        self.replace_before_caret(replace_len, text, viewport_width);
+    }
+
+    /// Text of the caret's line up to the caret
+    pub fn line_before_caret(&self) -> String {
+        let line_start = self.rope.line_to_byte(self.rope.byte_to_line(self.caret));
+        self.rope.byte_slice(line_start..self.caret).to_string()
+    }
+
+    /// Replace the `len` bytes before the caret with `text`, e.g. a partly
+    /// typed name with its completion
+    pub fn replace_before_caret(&mut self, len: usize, text: String, viewport_width: usize) {
+        if self.read_only {
+            return;
+        }
+        self.selection_anchor = Some(self.caret.saturating_sub(len));
+        self.insert_text(text, viewport_width);
+    }
+
+    /// Insert generated text (e.g. SQL built from the results grid) at the
+    /// caret as a single undoable edit, replacing any selection.
+    pub fn insert_text(&mut self, text: String, viewport_width: usize) {
+        if self.read_only {
+            return;
+        }
+        self.extra_carets.clear();
+        self.enable_viewport_following();
+        self.insert_pasted(text);
+        
+        let (_, col) = self.get_visual_position(self.caret, viewport_width);
+        self.preferred_col = col;
+    }
+
+    /// Insert text pasted into the terminal at every caret as one undo
+    /// step. It goes in as it is, without the auto-indent and pairing of
+    /// typed keys; the carriage returns terminals send for line breaks
+    /// become newlines.
+    pub fn paste_text(&mut self, text: &str, viewport_width: usize, viewport_height: usize) {
+        if self.read_only {
+            return;
         }
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.enable_viewport_following();
+        self.snippet_stops.clear();
+        self.edit_at_carets(EditKind::Paste, |e| e.insert_pasted(text.clone()));
+        self.refresh_find_matches_if_active();
+        self.update_viewport(viewport_height, viewport_width);
     }
 
     fn enable_viewport_following(&mut self) {
@@ -1907,6 +3061,82 @@ impl Editor {
     }
     
     fn page_up(&mut self, viewport_width: usize, viewport_height: usize, extend_selection: bool) {
+        self.move_rows_up(viewport_width, viewport_height.saturating_sub(2), extend_selection);
+    }
+    
+    fn page_down(&mut self, viewport_width: usize, viewport_height: usize, extend_selection: bool) {
+        self.move_rows_down(viewport_width, viewport_height.saturating_sub(2), extend_selection);
+    }
+    
+    /// Move the caret half a page and scroll the view by as much, so the
+    /// caret keeps its place on screen (Alt+D / Alt+U or Ctrl+U)
+    fn half_page(&mut self, viewport_width: usize, viewport_height: usize, down: bool, extend_selection: bool) {
+        let rows = (viewport_height / 2).max(1);
+        let (before, _) = self.get_visual_position(self.caret, viewport_width);
+        if down {
+            self.move_rows_down(viewport_width, rows, extend_selection);
+        } else {
+            self.move_rows_up(viewport_width, rows, extend_selection);
+        }
+        let (after, _) = self.get_visual_position(self.caret, viewport_width);
+        let max_offset = self.visual_lines.len().saturating_sub(viewport_height);
+        self.viewport_offset.0 = if after >= before {
+            (self.viewport_offset.0 + (after - before)).min(max_offset)
+        } else {
+            self.viewport_offset.0.saturating_sub(before - after)
+        };
+    }
+    
+    /// Scroll the view `rows` down (negative: up) and leave the caret where
+    /// it is, unless that puts it within `scrolloff` of an edge; then it is
+    /// pulled along
+    fn scroll_view(&mut self, viewport_width: usize, viewport_height: usize, rows: isize) {
+        self.ensure_visual_lines(viewport_width);
+        let Some(last) = self.visual_lines.iter().rposition(|vl| vl.is_some()) else { return };
+        let max_offset = self.visual_lines.len().saturating_sub(viewport_height);
+        self.viewport_offset.0 = self.viewport_offset.0.saturating_add_signed(rows).min(max_offset);
+        
+        let offset = self.viewport_offset.0;
+        let margin = self.scrolloff.min(viewport_height.saturating_sub(1) / 2);
+        let top = if offset == 0 { 0 } else { offset + margin };
+        let bottom = if offset == max_offset { last } else { (offset + viewport_height).saturating_sub(margin + 1) };
+        let (row, _) = self.get_visual_position(self.caret, viewport_width);
+        let target = row.clamp(top.max(self.virtual_lines), bottom.min(last).max(self.virtual_lines));
+        if target != row {
+            self.caret = self.visual_to_byte(target, self.preferred_col, viewport_width);
+        }
+    }
+    
+    /// Scroll the view by mouse wheel, leaving the caret where it is until
+    /// the next motion or edit. Columns only scroll with word wrap off, as
+    /// far as the widest row shown.
+    pub fn wheel_scroll(&mut self, rows: isize, cols: isize, viewport_width: usize, viewport_height: usize) {
+        self.ensure_visual_lines(viewport_width);
+        self.viewport_follows_caret = false;
+        let max_offset = self.visual_lines.len().saturating_sub(viewport_height);
+        self.viewport_offset.0 = self.viewport_offset.0.saturating_add_signed(rows).min(max_offset);
+        if self.word_wrap || cols == 0 {
+            return;
+        }
+        let start = self.viewport_offset.0.min(self.visual_lines.len());
+        let end = (start + viewport_height).min(self.visual_lines.len());
+        let widest = self.visual_lines[start..end].iter().flatten()
+            .map(|vl| text_cols(&self.rope.byte_slice(vl.start_byte..vl.end_byte).to_string(), self.tab_width))
+            .max()
+            .unwrap_or(0);
+        let max_col = widest.saturating_sub(viewport_width.saturating_sub(1));
+        let col = self.viewport_offset.1.saturating_add_signed(cols);
+        // Never further right than it already was, if the caret took it past
+        self.viewport_offset.1 = if cols > 0 { col.min(max_col.max(self.viewport_offset.1)) } else { col };
+    }
+
+    /// Put the caret's row in the middle of the view
+    fn center_caret(&mut self) {
+        self.enable_viewport_following();
+        self.center_requested = true;
+    }
+    
+    fn move_rows_up(&mut self, viewport_width: usize, page_size: usize, extend_selection: bool) {
         self.enable_viewport_following();
         
         if extend_selection && self.selection_anchor.is_none() {
@@ -1916,7 +3146,6 @@ impl Editor {
         }
         
         let (current_row, _) = self.get_visual_position(self.caret, viewport_width);
-        let page_size = viewport_height.saturating_sub(2);
         
         if current_row <= self.virtual_lines {
             // Already at top, move to beginning of document
@@ -1927,7 +3156,7 @@ impl Editor {
         }
     }
     
-    fn page_down(&mut self, viewport_width: usize, viewport_height: usize, extend_selection: bool) {
+    fn move_rows_down(&mut self, viewport_width: usize, page_size: usize, extend_selection: bool) {
         self.enable_viewport_following();
         
         if extend_selection && self.selection_anchor.is_none() {
@@ -1937,7 +3166,6 @@ impl Editor {
         }
         
         let (current_row, _) = self.get_visual_position(self.caret, viewport_width);
-        let page_size = viewport_height.saturating_sub(2);
         let total_lines = self.visual_lines.len();
         let last_content_line = total_lines.saturating_sub(self.virtual_lines + 1);
         
@@ -1951,22 +3179,33 @@ impl Editor {
     }
     
     fn update_viewport(&mut self, height: usize, width: usize) {
+        self.unfold_around_carets();
         self.ensure_visual_lines(width);
+        if std::mem::take(&mut self.preferred_col_stale) {
+            self.preferred_col = self.get_visual_position(self.caret, width).1;
+        }
         
-        if self.viewport_follows_caret {
+        if self.viewport_follows_caret && height > 0 && width > 0 {
             let (row, col) = self.get_visual_position(self.caret, width);
+            // A scrolloff over half the view would never let the caret settle
+            let margin = self.scrolloff.min((height - 1) / 2);
+            let h_margin = self.scrolloff.min((width - 1) / 2);
             
-            if row < self.viewport_offset.0 + self.scrolloff {
-                self.viewport_offset.0 = row.saturating_sub(self.scrolloff);
-            } else if row >= self.viewport_offset.0 + height - self.scrolloff {
-                self.viewport_offset.0 = row + self.scrolloff + 1 - height;
+            // More than a row outside the view is a jump, not a step
+            let jumped = row + 1 < self.viewport_offset.0 || row > self.viewport_offset.0 + height;
+            if std::mem::take(&mut self.center_requested) || (self.center_on_jump && jumped) {
+                self.viewport_offset.0 = row.saturating_sub(height / 2);
+            } else if row < self.viewport_offset.0 + margin {
+                self.viewport_offset.0 = row.saturating_sub(margin);
+            } else if row >= self.viewport_offset.0 + height - margin {
+                self.viewport_offset.0 = row + margin + 1 - height;
             }
             
             if !self.word_wrap {
-                if col < self.viewport_offset.1 + self.scrolloff {
-                    self.viewport_offset.1 = col.saturating_sub(self.scrolloff);
-                } else if col >= self.viewport_offset.1 + width - self.scrolloff {
-                    self.viewport_offset.1 = col + self.scrolloff + 1 - width;
+                if col < self.viewport_offset.1 + h_margin {
+                    self.viewport_offset.1 = col.saturating_sub(h_margin);
+                } else if col >= self.viewport_offset.1 + width - h_margin {
+                    self.viewport_offset.1 = col + h_margin + 1 - width;
                 }
             } else {
                 self.viewport_offset.1 = 0;
@@ -1976,33 +3215,38 @@ impl Editor {
 
     fn handle_click(&mut self, col: u16, row: u16, area: Rect, viewport_width: usize, shift_held: bool) {
         self.enable_viewport_following();
+        if let Some((new_pos, actual_col)) = self.byte_at_screen(col, row, area, viewport_width) {
+            if shift_held {
+                if self.selection_anchor.is_none() {
+                    self.selection_anchor = Some(self.caret);
+                }
+                self.caret = new_pos;
+            } else {
+                self.clear_selection();
+                self.caret = new_pos;
+            }
+            
+            self.preferred_col = actual_col;
+        }
+    }
+
+    /// Byte offset and visual column of a screen position in `area`, if it
+    /// is on a line of the buffer
+    fn byte_at_screen(&mut self, col: u16, row: u16, area: Rect, viewport_width: usize) -> Option<(usize, usize)> {
         self.ensure_visual_lines(viewport_width);
         let click_row = self.viewport_offset.0 + row.saturating_sub(area.y) as usize;
         let click_col = self.viewport_offset.1 + col.saturating_sub(area.x) as usize;
         
-        if click_row >= self.virtual_lines && 
-           click_row < self.visual_lines.len() - self.virtual_lines {
-            if let Some(Some(vline)) = self.visual_lines.get(click_row) {
-                let actual_col = if vline.is_continuation {
-                    click_col.max(vline.indent)
-                } else {
-                    click_col
-                };
-                let new_pos = self.visual_to_byte(click_row, actual_col, viewport_width);
-                
-                if shift_held {
-                    if self.selection_anchor.is_none() {
-                        self.selection_anchor = Some(self.caret);
-                    }
-                    self.caret = new_pos;
-                } else {
-                    self.clear_selection();
-                    self.caret = new_pos;
-                }
-                
-                self.preferred_col = actual_col;
-            }
+        if click_row < self.virtual_lines || click_row >= self.visual_lines.len() - self.virtual_lines {
+            return None;
         }
+        let Some(Some(vline)) = self.visual_lines.get(click_row) else { return None };
+        let actual_col = if vline.is_continuation {
+            click_col.max(vline.indent)
+        } else {
+            click_col
+        };
+        Some((self.visual_to_byte(click_row, actual_col, viewport_width), actual_col))
     }
 
     fn get_display_name(&self) -> String {
@@ -2102,9 +3346,7 @@ impl Editor {
     fn replace_current(&mut self, replacement: &str, viewport_width: usize) {
         if let Some(idx) = self.current_match_index {
             if let Some(&(start, end)) = self.find_matches.get(idx) {
-                // Finalize any pending undo group before starting replace
-                self.finalize_undo_group();
-                
+                self.begin_undo_group(EditKind::Other);
                 self.caret = start;
                 self.selection_anchor = Some(end);
                 
@@ -2113,10 +3355,7 @@ impl Editor {
                     self.insert_char(ch, viewport_width);
                 }
                 
-                // Finalize the replace operation as its own undo group
-                self.finalize_undo_group();
-                // Reset last edit time to prevent timing issues with immediate undo
-                self.last_edit_time = None;
+                self.end_undo_group();
                 
                 let query = if let AppState::Prompting(ref prompt) = self.app_state {
                     prompt.input.clone()
@@ -2162,8 +3401,7 @@ impl Editor {
             return;
         }
 
-        // Finalize any pending undo group before starting replace all
-        self.finalize_undo_group();
+        self.begin_undo_group(EditKind::Other);
 
         self.update_find_matches(query);
         
@@ -2183,10 +3421,7 @@ impl Editor {
             }
         }
         
-        // Finalize the replace all operation as its own undo group
-        self.finalize_undo_group();
-        // Reset last edit time to prevent timing issues with immediate undo
-        self.last_edit_time = None;
+        self.end_undo_group();
     }
 
     fn refresh_find_matches_if_active(&mut self) {
@@ -2836,9 +4071,58 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
 }
 */
 
+/// Columns `ch` takes when it starts at column `col` of its row; a tab
+/// runs to the next multiple of `tab_width`
+fn char_cols(ch: char, col: usize, tab_width: usize) -> usize {
+    if ch == '\t' {
+        tab_width - col % tab_width
+    } else {
+        ch.encode_utf8(&mut [0; 4]).width()
+    }
+}
+
+/// Columns `text` takes from the start of a row
+fn text_cols(text: &str, tab_width: usize) -> usize {
+    text.chars().fold(0, |col, ch| col + char_cols(ch, col, tab_width))
+}
+
+/// Glyph `ch` is drawn as when whitespace is shown, `trailing` when no
+/// visible character follows it on its line
+fn whitespace_glyph(ch: char, trailing: bool) -> Option<char> {
+    match ch {
+        '\t' => Some('→'),
+        '\u{a0}' => Some('␣'),
+        ' ' if trailing => Some('·'),
+        _ => None,
+    }
+}
+
+/// Keys that only move, select, copy or change the view, which are all a
+/// read-only buffer takes
+fn is_view_key(key: &event::KeyEvent) -> bool {
+    let ctrl = key.modifiers.contains(event::KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(event::KeyModifiers::ALT);
+    let shift = key.modifiers.contains(event::KeyModifiers::SHIFT);
+    match key.code {
+        // Alt+Shift+Up / Down move lines
+        KeyCode::Up | KeyCode::Down => !(alt && shift),
+        KeyCode::Left | KeyCode::Right | KeyCode::Home | KeyCode::End
+        | KeyCode::PageUp | KeyCode::PageDown | KeyCode::Esc => true,
+        // Ctrl+Shift+D duplicates lines
+        KeyCode::Char('a') | KeyCode::Char('c') | KeyCode::Char('d') | KeyCode::Char('l') | KeyCode::Char('u') if ctrl && !shift => true,
+        KeyCode::Char('d') | KeyCode::Char('u') | KeyCode::Char('w') | KeyCode::Char('z')
+        | KeyCode::Char('-') | KeyCode::Char('=') | KeyCode::Char('0') => alt,
+        _ => false,
+    }
+}
+
 pub fn handle_editor_key(editor: &mut Editor, key: event::KeyEvent, viewport_width: usize, viewport_height: usize) -> io::Result<()> {
+    if editor.read_only && !is_view_key(&key) {
+        return Ok(());
+    }
     match key.code {
         KeyCode::Char('a') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+            editor.clear_extra_carets();
             editor.select_all();
             editor.update_viewport(viewport_height, viewport_width);
         }
@@ -2846,13 +4130,14 @@ pub fn handle_editor_key(editor: &mut Editor, key: event::KeyEvent, viewport_wid
             editor.copy();
         }
         KeyCode::Char('x') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+            editor.clear_extra_carets();
             if editor.cut() {
                 editor.refresh_find_matches_if_active();
                 editor.update_viewport(viewport_height, viewport_width);
             }
         }
         KeyCode::Char('v') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
-            editor.paste(viewport_width);
+            editor.edit_at_carets(EditKind::Paste, |e| e.paste());
             editor.refresh_find_matches_if_active();
             editor.update_viewport(viewport_height, viewport_width);
         }
@@ -2866,48 +4151,147 @@ pub fn handle_editor_key(editor: &mut Editor, key: event::KeyEvent, viewport_wid
             editor.refresh_find_matches_if_active();
             editor.update_viewport(viewport_height, viewport_width);
         }
+        KeyCode::Char('d') | KeyCode::Char('D') | KeyCode::Char('k') | KeyCode::Char('K')
+            if key.modifiers.contains(event::KeyModifiers::CONTROL | event::KeyModifiers::SHIFT) =>
+        {
+            editor.clear_extra_carets();
+            if matches!(key.code, KeyCode::Char('d') | KeyCode::Char('D')) {
+                editor.duplicate_lines(viewport_width);
+            } else {
+                editor.delete_lines(viewport_width);
+            }
+            editor.refresh_find_matches_if_active();
+            editor.update_viewport(viewport_height, viewport_width);
+        }
+        KeyCode::Char('U') | KeyCode::Char('L') | KeyCode::Char('T') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+            editor.clear_extra_carets();
+            editor.convert_case(match key.code {
+                KeyCode::Char('U') => TextCase::Upper,
+                KeyCode::Char('L') => TextCase::Lower,
+                _ => TextCase::Title,
+            });
+            editor.refresh_find_matches_if_active();
+            editor.update_viewport(viewport_height, viewport_width);
+        }
+        KeyCode::Char('-') | KeyCode::Char('=') | KeyCode::Char('0') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+            match key.code {
+                KeyCode::Char('-') => editor.fold_at_caret(),
+                KeyCode::Char('=') => editor.unfold(false),
+                _ => editor.unfold(true),
+            }
+            editor.update_viewport(viewport_height, viewport_width);
+        }
+        KeyCode::Char('w') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+            editor.show_whitespace = !editor.show_whitespace;
+        }
+        KeyCode::Char('z') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+            editor.set_word_wrap(!editor.word_wrap);
+            editor.update_viewport(viewport_height, viewport_width);
+        }
+        KeyCode::Char('j') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+            editor.clear_extra_carets();
+            editor.join_lines(viewport_width);
+            editor.refresh_find_matches_if_active();
+            editor.update_viewport(viewport_height, viewport_width);
+        }
+        KeyCode::Up | KeyCode::Down if key.modifiers.contains(event::KeyModifiers::ALT | event::KeyModifiers::SHIFT) => {
+            editor.clear_extra_carets();
+            if key.code == KeyCode::Up {
+                editor.move_lines_up(viewport_width);
+            } else {
+                editor.move_lines_down(viewport_width);
+            }
+            editor.refresh_find_matches_if_active();
+            editor.update_viewport(viewport_height, viewport_width);
+        }
+        KeyCode::Char('d') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+            editor.select_next_occurrence();
+            editor.update_viewport(viewport_height, viewport_width);
+        }
+        // Ctrl+D selects the next occurrence, so half a page down is Alt+D
+        // only; half a page up keeps Ctrl+U as well
+        KeyCode::Char('d') | KeyCode::Char('u') if key.modifiers.contains(event::KeyModifiers::ALT) => {
+            editor.clear_extra_carets();
+            let down = key.code == KeyCode::Char('d');
+            editor.half_page(viewport_width, viewport_height, down, key.modifiers.contains(event::KeyModifiers::SHIFT));
+            editor.update_viewport(viewport_height, viewport_width);
+        }
+        KeyCode::Char('u') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+            editor.clear_extra_carets();
+            editor.half_page(viewport_width, viewport_height, false, key.modifiers.contains(event::KeyModifiers::SHIFT));
+            editor.update_viewport(viewport_height, viewport_width);
+        }
+        KeyCode::Char('l') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+            editor.center_caret();
+            editor.update_viewport(viewport_height, viewport_width);
+        }
         KeyCode::Char(c) => {
-            editor.insert_char(c, viewport_width);
+            editor.edit_at_carets(EditKind::Typing, |e| e.type_char(c, viewport_width));
             editor.refresh_find_matches_if_active();
             editor.update_viewport(viewport_height, viewport_width);
         }
         KeyCode::Enter => {
-            editor.insert_char('\n', viewport_width);
+            editor.edit_at_carets(EditKind::Typing, |e| e.type_char('\n', viewport_width));
             editor.preferred_col = 0;
             editor.refresh_find_matches_if_active();
             editor.update_viewport(viewport_height, viewport_width);
         }
         KeyCode::Backspace => {
-            editor.backspace(viewport_width);
+            editor.edit_at_carets(EditKind::Delete, |e| e.backspace(viewport_width));
             editor.refresh_find_matches_if_active();
             editor.update_viewport(viewport_height, viewport_width);
         }
         KeyCode::Delete => {
-            editor.delete(viewport_width);
+            editor.edit_at_carets(EditKind::Delete, |e| e.delete(viewport_width));
             editor.refresh_find_matches_if_active();
             editor.update_viewport(viewport_height, viewport_width);
         }
         KeyCode::Left => {
-            editor.move_left(viewport_width, key.modifiers.contains(event::KeyModifiers::SHIFT));
+            let shift = key.modifiers.contains(event::KeyModifiers::SHIFT);
+            editor.move_carets(viewport_width, |e| e.move_left(viewport_width, shift));
             editor.update_viewport(viewport_height, viewport_width);
         }
         KeyCode::Right => {
-            editor.move_right(viewport_width, key.modifiers.contains(event::KeyModifiers::SHIFT));
+            let shift = key.modifiers.contains(event::KeyModifiers::SHIFT);
+            editor.move_carets(viewport_width, |e| e.move_right(viewport_width, shift));
             editor.update_viewport(viewport_height, viewport_width);
         }
+        KeyCode::Up | KeyCode::Down if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+            editor.clear_extra_carets();
+            let rows = if key.code == KeyCode::Up { -1 } else { 1 };
+            editor.scroll_view(viewport_width, viewport_height, rows);
+        }
         KeyCode::Up => {
-            editor.move_up(viewport_width, key.modifiers.contains(event::KeyModifiers::SHIFT));
+            let shift = key.modifiers.contains(event::KeyModifiers::SHIFT);
+            editor.move_carets(viewport_width, |e| e.move_up(viewport_width, shift));
             editor.update_viewport(viewport_height, viewport_width);
         }
         KeyCode::Down => {
-            editor.move_down(viewport_width, key.modifiers.contains(event::KeyModifiers::SHIFT));
+            let shift = key.modifiers.contains(event::KeyModifiers::SHIFT);
+            editor.move_carets(viewport_width, |e| e.move_down(viewport_width, shift));
+            editor.update_viewport(viewport_height, viewport_width);
+        }
+        KeyCode::Home => {
+            let shift = key.modifiers.contains(event::KeyModifiers::SHIFT);
+            editor.move_carets(viewport_width, |e| e.move_home(viewport_width, shift));
+            editor.update_viewport(viewport_height, viewport_width);
+        }
+        KeyCode::End => {
+            let shift = key.modifiers.contains(event::KeyModifiers::SHIFT);
+            editor.move_carets(viewport_width, |e| e.move_end(viewport_width, shift));
             editor.update_viewport(viewport_height, viewport_width);
         }
+        KeyCode::Esc => {
+            editor.clear_extra_carets();
+            editor.snippet_stops.clear();
+        }
         KeyCode::PageUp => {
+            editor.clear_extra_carets();
             editor.page_up(viewport_width, viewport_height, key.modifiers.contains(event::KeyModifiers::SHIFT));
             editor.update_viewport(viewport_height, viewport_width);
         }
         KeyCode::PageDown => {
+            editor.clear_extra_carets();
             editor.page_down(viewport_width, viewport_height, key.modifiers.contains(event::KeyModifiers::SHIFT));
             editor.update_viewport(viewport_height, viewport_width);
         }
@@ -2978,11 +4362,19 @@ fn draw_ui_with_cursor_in_area(f: &mut Frame, editor: &mut Editor, show_cursor:
     let start = editor.viewport_offset.0;
     let end = (start + viewport_height).min(editor.visual_lines.len());
     
+    let visible = editor.visual_lines.get(start..end).unwrap_or_default();
+    let occurrences = match (visible.iter().flatten().next(), visible.iter().flatten().last()) {
+        (Some(first), Some(last)) => editor.word_occurrences(first.start_byte, last.end_byte),
+        _ => Vec::new(),
+    };
+    
     for row in start..end {
         if let Some(vline_opt) = editor.visual_lines.get(row) {
             if let Some(vline) = vline_opt {
                 let text = editor.rope.byte_slice(vline.start_byte..vline.end_byte).to_string();
                 
+                // Columns left blank where a wide character is cut by the left edge
+                let mut clipped_cols = 0;
                 let (display_text, display_start_offset) = if editor.word_wrap || editor.viewport_offset.1 == 0 {
                     (text, 0)
                 } else {
@@ -2993,10 +4385,12 @@ fn draw_ui_with_cursor_in_area(f: &mut Frame, editor: &mut Editor, show_cursor:
                     let mut found_start = false;
                     
                     for ch in text.chars() {
-                        let ch_width = ch.to_string().width();
-                        width += ch_width;
+                        let starts_before = width < editor.viewport_offset.1;
+                        width += char_cols(ch, width, editor.tab_width);
                         
-                        if width > editor.viewport_offset.1 {
+                        if width > editor.viewport_offset.1 && starts_before {
+                            clipped_cols = width - editor.viewport_offset.1;
+                        } else if width > editor.viewport_offset.1 {
                             if !found_start {
                                 display_start_offset = byte_offset;
                                 found_start = true;
@@ -3010,13 +4404,28 @@ fn draw_ui_with_cursor_in_area(f: &mut Frame, editor: &mut Editor, show_cursor:
                 };
                 
                 let mut spans = vec![];
-                if vline.indent > 0 {
-                    spans.push(Span::raw(" ".repeat(vline.indent)));
+                if vline.indent + clipped_cols > 0 {
+                    spans.push(Span::raw(" ".repeat(vline.indent + clipped_cols)));
                 }
                 
                 // Check for find matches in this line
                 let mut char_styles = vec![Style::default(); display_text.len()];
                 
+                // Other uses of the name under the caret, beneath the rest
+                for &(occ_start, occ_end) in &occurrences {
+                    if occ_end <= vline.start_byte || occ_start >= vline.end_byte {
+                        continue;
+                    }
+                    let mut byte_pos = display_start_offset;
+                    for (i, ch) in display_text.chars().enumerate() {
+                        let global_pos = vline.start_byte + byte_pos;
+                        if global_pos >= occ_start && global_pos < occ_end {
+                            char_styles[i] = char_styles[i].bg(editor.occurrence_bg);
+                        }
+                        byte_pos += ch.len_utf8();
+                    }
+                }
+                
                 // Apply selection highlighting
                 if let Some((sel_start, sel_end)) = selection_range {
                     let line_start = vline.start_byte;
@@ -3034,6 +4443,28 @@ fn draw_ui_with_cursor_in_area(f: &mut Frame, editor: &mut Editor, show_cursor:
                     }
                 }
                 
+                // Extra carets show as a reversed cell, with their selections
+                for extra in &editor.extra_carets {
+                    let (sel_start, sel_end) = match extra.anchor {
+                        Some(anchor) => (anchor.min(extra.caret), anchor.max(extra.caret)),
+                        None => (extra.caret, extra.caret),
+                    };
+                    if sel_end < vline.start_byte || sel_start > vline.end_byte {
+                        continue;
+                    }
+                    let mut byte_pos = display_start_offset;
+                    for (i, ch) in display_text.chars().enumerate() {
+                        let global_pos = vline.start_byte + byte_pos;
+                        if global_pos >= sel_start && global_pos < sel_end {
+                            char_styles[i] = Style::default().bg(Color::Blue).fg(Color::White);
+                        }
+                        if global_pos == extra.caret {
+                            char_styles[i] = char_styles[i].add_modifier(Modifier::REVERSED);
+                        }
+                        byte_pos += ch.len_utf8();
+                    }
+                }
+                
                 // Apply find match highlighting
                 for (match_start, match_end) in &editor.find_matches {
                     let line_start = vline.start_byte;
@@ -3068,24 +4499,77 @@ fn draw_ui_with_cursor_in_area(f: &mut Frame, editor: &mut Editor, show_cursor:
                     }
                 }
                 
+                // Mark the position of the last query error
+                if let Some((err_start, err_end)) = editor.error_span {
+                    if err_end > vline.start_byte && err_start < vline.end_byte {
+                        let mut byte_pos = display_start_offset;
+                        for (i, ch) in display_text.chars().enumerate() {
+                            let global_pos = vline.start_byte + byte_pos;
+                            if global_pos >= err_start && global_pos < err_end {
+                                char_styles[i] = char_styles[i].patch(editor.error_style);
+                            }
+                            byte_pos += ch.len_utf8();
+                        }
+                    }
+                }
+                
+                // Spaces from here to the end of the line are trailing
+                let trailing_from = if editor.show_whitespace {
+                    let line = editor.rope.line(vline.logical_line).to_string();
+                    let content = line.trim_end_matches(['\n', '\r']);
+                    editor.rope.line_to_byte(vline.logical_line) + content.trim_end_matches(' ').len()
+                } else {
+                    usize::MAX
+                };
+                
                 let mut current_style = Style::default();
                 let mut current_text = String::new();
+                let mut byte_pos = display_start_offset;
+                // Column in the row, for the tab stops
+                let mut col = if editor.word_wrap { 0 } else { editor.viewport_offset.1 + clipped_cols };
                 
                 for (ch, style) in display_text.chars().zip(char_styles.iter()) {
-                    if *style != current_style {
+                    let global_pos = vline.start_byte + byte_pos;
+                    byte_pos += ch.len_utf8();
+                    let cols = char_cols(ch, col, editor.tab_width);
+                    col += cols;
+                    let tab = ch == '\t';
+                    let glyph = whitespace_glyph(ch, global_pos >= trailing_from).filter(|_| editor.show_whitespace);
+                    let (ch, style) = match glyph {
+                        Some(glyph) if style.fg.is_none() => (glyph, style.fg(Color::DarkGray)),
+                        _ => (glyph.unwrap_or(ch), *style),
+                    };
+                    if style != current_style {
                         if !current_text.is_empty() {
                             spans.push(Span::styled(current_text.clone(), current_style));
                             current_text.clear();
                         }
-                        current_style = *style;
+                        current_style = style;
+                    }
+                    // A tab fills the cells to the next stop, after its glyph if shown
+                    current_text.push(if tab && glyph.is_none() { ' ' } else { ch });
+                    if tab {
+                        current_text.extend(std::iter::repeat_n(' ', cols - 1));
                     }
-                    current_text.push(ch);
                 }
                 
                 if !current_text.is_empty() {
                     spans.push(Span::styled(current_text, current_style));
                 }
                 
+                if vline.folded > 0 {
+                    spans.push(Span::styled(
+                        format!(" ⋯ {} lines ", vline.folded),
+                        Style::default().fg(Color::DarkGray).add_modifier(Modifier::REVERSED),
+                    ));
+                }
+                
+                let annotation = editor.cost_annotations.iter()
+                    .find(|(offset, _)| *offset >= vline.start_byte && *offset < vline.end_byte);
+                if let Some((_, annotation)) = annotation {
+                    spans.push(Span::styled(format!("  -- {}", annotation), Style::default().fg(Color::DarkGray)));
+                }
+                
                 lines.push(Line::from(spans));
             } else {
                 lines.push(Line::from(""));
@@ -3109,9 +4593,10 @@ fn draw_ui_with_cursor_in_area(f: &mut Frame, editor: &mut Editor, show_cursor:
         ])
         .split(chunks[1]);
     
-    let status_left = format!("{} {}", 
+    let status_left = format!("{} {}{}", 
         editor.get_display_name(),
-        if editor.modified { "[modified]" } else { "" }
+        if editor.modified { "[modified]" } else { "" },
+        if editor.read_only { "[RO]" } else { "" }
     );
     
     let status_center = format!("Ln {}, Col {}", caret_row + 1, caret_col + 1);
@@ -3129,15 +4614,18 @@ fn draw_ui_with_cursor_in_area(f: &mut Frame, editor: &mut Editor, show_cursor:
     );
     
     if show_cursor && matches!(editor.app_state, AppState::Editing) {
-        let cursor_display_row = caret_row.saturating_sub(editor.viewport_offset.0);
+        // The wheel can scroll the caret out of view on any side
+        let cursor_display_row = caret_row.checked_sub(editor.viewport_offset.0);
         let cursor_display_col = if caret_row < editor.visual_lines.len() && editor.visual_lines[caret_row].is_some() {
             let vline = editor.visual_lines[caret_row].as_ref().unwrap();
-            caret_col + vline.indent - editor.viewport_offset.1
+            (caret_col + vline.indent).checked_sub(editor.viewport_offset.1)
         } else {
-            caret_col - editor.viewport_offset.1
+            caret_col.checked_sub(editor.viewport_offset.1)
         };
         
-        if cursor_display_row < viewport_height && cursor_display_col < viewport_width {
+        let shown = cursor_display_row.zip(cursor_display_col)
+            .filter(|&(row, col)| row < viewport_height && col < viewport_width);
+        if let Some((cursor_display_row, cursor_display_col)) = shown {
             f.set_cursor_position((
                 chunks[0].x + cursor_display_col as u16,
                 chunks[0].y + cursor_display_row as u16,
@@ -3727,15 +5215,20 @@ fn draw_ui_with_cursor(f: &mut Frame, editor: &mut Editor, show_cursor: bool) {
         String::new()
     };
     
+    let context_info = editor.status_context.as_ref()
+        .map(|c| format!(" | {}", c))
+        .unwrap_or_default();
+    
     let total_lines = editor.rope.len_lines();
     let status_text = format!(
-        " {} | {} | {}/{}:{}{} ",
+        " {} | {} | {}/{}:{}{}{} ",
         editor.get_display_name(),
         if editor.word_wrap { "Wrap" } else { "No-Wrap" },
         line,
         total_lines,
         col,
-        selection_info
+        selection_info,
+        context_info
     );
     
     let status = Paragraph::new(Line::from(vec![Span::raw(status_text)]))
@@ -1,11 +1,27 @@
 use std::{
+    collections::HashSet,
     fs::{File, OpenOptions},
     io::{self, Read, Write, Seek, SeekFrom,BufWriter},
+    ops::Range,
+    ptr::NonNull,
     sync::Arc,
 };
 use tempfile::NamedTempFile;
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, WriteBytesExt};
 use lru::LruCache;
+use arrow_array::{
+    types::Int32Type, Array, ArrayRef, BooleanArray, DictionaryArray, Float64Array, Int64Array, RecordBatch,
+    RecordBatchOptions, StringArray,
+};
+use arrow_buffer::Buffer;
+use arrow_ipc::{
+    reader::FileDecoder,
+    writer::{write_message, DictionaryTracker, IpcDataGenerator, IpcWriteOptions},
+    Block, CompressionType, MetadataVersion,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use serde::{Deserialize, Serialize};
+use crate::export::ColumnKind;
 
 
 pub const NULL_SENTINEL: &str = "<Frost-NULL>";
@@ -13,28 +29,197 @@ pub const NULL_SENTINEL: &str = "<Frost-NULL>";
 /// Number of rows per tile (can be made configurable)
 pub const TILE_SIZE: usize = 1_000;
 
+/// Cache and buffer sizing for a TileRowStore
+#[derive(Debug, Clone, Copy)]
+pub struct TileStoreOptions {
+    /// Tiles kept in the LRU cache
+    pub cache_tiles: usize,
+    /// Keep the first/last tiles resident; results with more tiles than this
+    /// don't pin them (None = always pin)
+    pub pin_max_tiles: Option<usize>,
+    /// Write buffer size for the backing file
+    pub io_buffer: usize,
+    /// Results with at most this many rows and value bytes stay in memory;
+    /// past either they are written to a temp file
+    pub memory_rows: usize,
+    pub memory_bytes: usize,
+    /// How tiles written to the temp file are compressed
+    pub compression: TileCompression,
+}
+
+/// Compression of the Arrow buffers of tiles on disk. Uncompressed tiles
+/// are read straight from the mapped file; compressed ones are decoded
+/// into memory, but take less temp space and I/O.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TileCompression {
+    None,
+    /// Fast enough to decode on every scroll to an uncached tile
+    #[default]
+    Lz4,
+    /// Smaller than LZ4, slower to write
+    Zstd,
+}
+
+impl TileCompression {
+    fn ipc(self) -> Option<CompressionType> {
+        match self {
+            TileCompression::None => None,
+            TileCompression::Lz4 => Some(CompressionType::LZ4_FRAME),
+            TileCompression::Zstd => Some(CompressionType::ZSTD),
+        }
+    }
+}
+
+impl Default for TileStoreOptions {
+    fn default() -> Self {
+        Self {
+            cache_tiles: 6,
+            pin_max_tiles: None,
+            io_buffer: 256 * 1024,
+            memory_rows: 10_000,
+            memory_bytes: 4 * 1024 * 1024,
+            compression: TileCompression::default(),
+        }
+    }
+}
+
+impl TileStoreOptions {
+    /// Smaller cache and buffers for constrained environments
+    pub fn low_memory() -> Self {
+        Self {
+            cache_tiles: 2,
+            pin_max_tiles: Some(8),
+            io_buffer: 32 * 1024,
+            memory_rows: 1_000,
+            memory_bytes: 512 * 1024,
+            compression: TileCompression::default(),
+        }
+    }
+
+    /// `rows` holding `bytes` of values fit in memory
+    fn fits_in_memory(&self, rows: usize, bytes: usize) -> bool {
+        rows <= self.memory_rows && bytes <= self.memory_bytes
+    }
+}
+
+/// Rows of a result small enough to skip the temp file
+#[derive(Debug)]
+struct MemoryRows {
+    /// Shared with readers on other threads
+    rows: Arc<Vec<Vec<String>>>,
+    /// Bytes of the values
+    bytes: usize,
+    /// For writing the rows out once they outgrow the limits
+    options: TileStoreOptions,
+}
+
 /// Magic header for file sanity
 const MAGIC: &[u8; 4] = b"SNTR";
 
+/// On-disk format version, written right after the magic.
+/// v2: row counts, tile counts and value lengths are u64.
+/// v3: tiles are Arrow IPC record batch messages.
+/// v4: number and boolean columns are typed, buffers may be compressed, and
+/// the tile table gives each column's encoding.
+const FORMAT_VERSION: u8 = 4;
+
+/// Tiles start on multiples of this, so their Arrow buffers can be used
+/// where they lie
+const TILE_ALIGNMENT: u64 = 64;
+
+/// A tile's column is dictionary-encoded when it has at most one distinct
+/// value per this many rows
+const ROWS_PER_DISTINCT: usize = 4;
+
+/// A tile as held in memory: its record batch as decoded, the buffers of
+/// an uncompressed one still in the mapped file. Rows become strings only
+/// when they are asked for.
+type Tile = RecordBatch;
+
+/// How a tile stores one of its columns. A number or boolean column is
+/// typed only if every value in the tile reads back as the exact text it
+/// came as; otherwise it is text, like the rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnEncoding {
+    Text,
+    /// Text with one dictionary entry per distinct value
+    Dictionary,
+    Int,
+    Double,
+    Boolean,
+}
+
+impl ColumnEncoding {
+    /// Tag of the encoding in the tile table
+    fn tag(self) -> u8 {
+        match self {
+            ColumnEncoding::Text => 0,
+            ColumnEncoding::Dictionary => 1,
+            ColumnEncoding::Int => 2,
+            ColumnEncoding::Double => 3,
+            ColumnEncoding::Boolean => 4,
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            ColumnEncoding::Text => DataType::Utf8,
+            ColumnEncoding::Dictionary => DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+            ColumnEncoding::Int => DataType::Int64,
+            ColumnEncoding::Double => DataType::Float64,
+            ColumnEncoding::Boolean => DataType::Boolean,
+        }
+    }
+}
+
+/// Where a tile's Arrow IPC messages lie in the file
+#[derive(Debug, Clone)]
+struct TileBlocks {
+    /// How each column is stored
+    encodings: Vec<ColumnEncoding>,
+    /// Dictionary of each dictionary-encoded column, in column order
+    dictionaries: Vec<Block>,
+    batch: Block,
+}
+
+impl TileBlocks {
+    /// Offset just past the tile
+    fn end(&self) -> u64 {
+        self.batch.offset() as u64 + block_len(&self.batch)
+    }
+}
+
 //------- TileRowStore definition --------
 #[derive(Debug)]
 pub struct TileRowStore {
+    /// Set while the result is small; the other fields are then unused
+    memory: Option<Box<MemoryRows>>,
     /// Temp file (auto cleaned up)
     temp_file: Option<NamedTempFile>,
     /// We need a persistent file handle for reading (can be reopened by path if needed)
-    file: std::io::BufReader<File>,
-    /// Offsets of each tile block
-    tile_offsets: Vec<u64>,
+    file: Option<File>,
+    /// The file mapped into memory, remapped as tiles are appended; None
+    /// where mapping isn't available, and tiles are read with seek+read
+    map: Option<Arc<FileMap>>,
+    /// Arrow IPC messages of each tile: offsets, metadata and body lengths
+    tile_offsets: Vec<TileBlocks>,
     /// Row count for each tile (last tile may be short)
-    tile_row_counts: Vec<u32>,
+    tile_row_counts: Vec<u64>,
     /// Total cols, total rows
     pub ncols: usize,
     pub nrows: usize,
-    /// Tile LRU: tile index -> Arc<Vec<Vec<String>>>
-    cache: LruCache<usize, Arc<Vec<Vec<String>>>>,
+    /// Kind of each column, which decides the types tiles try
+    kinds: Vec<ColumnKind>,
+    compression: TileCompression,
+    /// Tile LRU: tile index -> Tile
+    cache: LruCache<usize, Tile>,
     /// Always hold first/last tile in memory
-    first_tile: Option<Arc<Vec<Vec<String>>>>,
-    last_tile: Option<Arc<Vec<Vec<String>>>>,
+    first_tile: Option<Tile>,
+    last_tile: Option<Tile>,
+    /// Tile lookups in `get_rows` served from memory (pinned or cached) / from disk
+    cache_hits: u64,
+    cache_misses: u64,
 }
 
 impl TileRowStore {
@@ -50,32 +235,83 @@ impl TileRowStore {
         let prefetch_start = start_tile.saturating_sub(1);
         let prefetch_end = (end_tile+1).min(tile_count-1);
         for t in prefetch_start..=prefetch_end {
-            let _ = self.load_tile_arc(t);
+            let _ = self.load_tile(t);
         }
     }
 
+    /// Keep the rows in memory while they stay within the options' limits,
+    /// otherwise write them all to a temp file, typing the columns of each
+    /// tile by `kinds` where their values allow
     pub fn from_rows<I>(
         headers: &[String],
-        rows_iter: I,
+        kinds: &[ColumnKind],
+        mut rows_iter: I,
+        options: TileStoreOptions,
     ) -> io::Result<Self>
     where
         I: Iterator<Item = Vec<String>>,
     {
+        let kinds = column_kinds(headers.len(), kinds);
+        let mut rows = Vec::new();
+        let mut bytes = 0;
+        for row in rows_iter.by_ref() {
+            bytes += row_bytes(&row);
+            rows.push(row);
+            if !options.fits_in_memory(rows.len(), bytes) {
+                return Self::spill(kinds, rows.into_iter().chain(rows_iter), options);
+            }
+        }
+        let mut store = Self::empty(kinds, &options);
+        store.nrows = rows.len();
+        store.memory = Some(Box::new(MemoryRows { rows: Arc::new(rows), bytes, options }));
+        Ok(store)
+    }
+
+    /// A store of `kinds` columns with no rows, no file and no memory rows
+    fn empty(kinds: Vec<ColumnKind>, options: &TileStoreOptions) -> Self {
+        TileRowStore {
+            memory: None,
+            temp_file: None,
+            file: None,
+            map: None,
+            tile_offsets: Vec::new(),
+            tile_row_counts: Vec::new(),
+            ncols: kinds.len(),
+            nrows: 0,
+            kinds,
+            compression: options.compression,
+            cache: LruCache::new(std::num::NonZeroUsize::new(options.cache_tiles.max(1)).unwrap()),
+            first_tile: None,
+            last_tile: None,
+            cache_hits: 0,
+            cache_misses: 0,
+        }
+    }
+
+    /// Write entire rowset from an iterator to a temp file, one column per kind
+    fn spill<I>(kinds: Vec<ColumnKind>, rows_iter: I, options: TileStoreOptions) -> io::Result<Self>
+    where
+        I: Iterator<Item = Vec<String>>,
+    {
+        let ncols = kinds.len();
         let mut temp_file = NamedTempFile::new()?;
-        let mut file = BufWriter::with_capacity(256 * 1024, temp_file.as_file_mut());
+        let mut file = BufWriter::with_capacity(options.io_buffer, temp_file.as_file_mut());
         // Write header
         file.write_all(MAGIC)?;
+        file.write_u8(FORMAT_VERSION)?;
         file.write_u32::<LittleEndian>(TILE_SIZE as u32)?;
-        file.write_u32::<LittleEndian>(headers.len() as u32)?;
+        file.write_u32::<LittleEndian>(ncols as u32)?;
         // Placeholders:
         let row_count_pos = file.stream_position()?;
-        file.write_u32::<LittleEndian>(0)?;
+        file.write_u64::<LittleEndian>(0)?;
         let tile_count_pos = file.stream_position()?;
-        file.write_u32::<LittleEndian>(0)?;
+        file.write_u64::<LittleEndian>(0)?;
+        let header_end = file.stream_position()?;
+        let mut offset = pad_to_alignment(&mut file, header_end)?;
 
         // Tiles:
-        let mut tile_offsets: Vec<u64> = Vec::new();
-        let mut tile_row_counts: Vec<u32> = Vec::new();
+        let mut tile_offsets: Vec<TileBlocks> = Vec::new();
+        let mut tile_row_counts: Vec<u64> = Vec::new();
         let mut buf_tile: Vec<Vec<String>> = Vec::with_capacity(TILE_SIZE);
 
         let mut nrows = 0usize;
@@ -83,35 +319,41 @@ impl TileRowStore {
             buf_tile.push(row);
             nrows += 1;
             if buf_tile.len() == TILE_SIZE {
-                tile_offsets.push(file.stream_position()?);
-                Self::write_tile(&mut file, &buf_tile)?;
-                tile_row_counts.push(buf_tile.len() as u32);
+                let tile = write_tile(&mut file, &kinds, options.compression, offset, &buf_tile)?;
+                offset = tile.end();
+                tile_offsets.push(tile);
+                tile_row_counts.push(buf_tile.len() as u64);
                 buf_tile.clear();
             }
         }
 
         // Write last (possibly short) tile
         if !buf_tile.is_empty() {
-            tile_offsets.push(file.stream_position()?);
-            Self::write_tile(&mut file, &buf_tile)?;
-            tile_row_counts.push(buf_tile.len() as u32);
+            let tile = write_tile(&mut file, &kinds, options.compression, offset, &buf_tile)?;
+            tile_offsets.push(tile);
+            tile_row_counts.push(buf_tile.len() as u64);
             buf_tile.clear();
         }
 
-        // After data, write the tile offset table:
-        let _tile_table_pos = file.stream_position()?;
-        for &offset in &tile_offsets {
-            file.write_u64::<LittleEndian>(offset)?;
-        }
-        for &row_count in &tile_row_counts {
-            file.write_u32::<LittleEndian>(row_count)?;
+        // After data, write the tile table. Per tile: row count, batch
+        // block, the encoding tag of each column, then the block of each
+        // dictionary; a block is offset, metadata length and body length.
+        for (tile, &row_count) in tile_offsets.iter().zip(&tile_row_counts) {
+            file.write_u64::<LittleEndian>(row_count)?;
+            write_block(&mut file, &tile.batch)?;
+            for encoding in &tile.encodings {
+                file.write_u8(encoding.tag())?;
+            }
+            for block in &tile.dictionaries {
+                write_block(&mut file, block)?;
+            }
         }
 
         // Patch row count / tile count
         file.seek(SeekFrom::Start(row_count_pos))?;
-        file.write_u32::<LittleEndian>(nrows as u32)?;
+        file.write_u64::<LittleEndian>(nrows as u64)?;
         file.seek(SeekFrom::Start(tile_count_pos))?;
-        file.write_u32::<LittleEndian>(tile_offsets.len() as u32)?;
+        file.write_u64::<LittleEndian>(tile_offsets.len() as u64)?;
 
         // Now re-open as read handle (flush+read)
         drop(file);
@@ -120,65 +362,116 @@ impl TileRowStore {
             .write(true)
             .open(temp_file.path())?;
 
-        let buf_reader = std::io::BufReader::with_capacity(256 * 1024, temp_file_read);
-
+        let map = FileMap::new(&temp_file_read).ok().map(Arc::new);
 
-        let mut store = TileRowStore {
-            temp_file: Some(temp_file),
-            file: buf_reader,
-            tile_offsets,
-            tile_row_counts,
-            ncols: headers.len(),
-            nrows,
-            cache: LruCache::new(std::num::NonZeroUsize::new(6).unwrap()), // cache size of 6
-            first_tile: None,
-            last_tile: None,
-        };
+        let mut store = Self::empty(kinds, &options);
+        store.temp_file = Some(temp_file);
+        store.file = Some(temp_file_read);
+        store.map = map;
+        store.tile_offsets = tile_offsets;
+        store.tile_row_counts = tile_row_counts;
+        store.nrows = nrows;
 
         // Preload first/last tiles
-        if !store.tile_offsets.is_empty() {
-            store.first_tile = store.load_tile_arc(0).ok();
-            store.last_tile = store.load_tile_arc(store.tile_offsets.len() - 1).ok();
+        let tile_count = store.tile_offsets.len();
+        if tile_count > 0 && options.pin_max_tiles.map_or(true, |max| tile_count <= max) {
+            store.first_tile = store.load_tile(0).ok();
+            store.last_tile = store.load_tile(store.tile_offsets.len() - 1).ok();
         }
 
         Ok(store)
     }
 
-    /// Write a full tile (rows) in format:
-    /// [row count: u32][col count: u32] then, for row in rows, col in row: [u32(len)][bytes]
-    fn write_tile<W: Write>(file: &mut W, rows: &[Vec<String>]) -> io::Result<()> {
-        file.write_u32::<LittleEndian>(rows.len() as u32)?;
-        file.write_u32::<LittleEndian>(if rows.is_empty() { 0 } else { rows[0].len() as u32 })?;
-        for row in rows {
-            for col in row {
-                let bytes = col.as_bytes();
-                file.write_u32::<LittleEndian>(bytes.len() as u32)?;
-                file.write_all(bytes)?;
+    /// Loads a tile from file (by tile index)
+    fn load_tile(&mut self, idx: usize) -> io::Result<Tile> {
+        let tile = self.tile_offsets.get(idx)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "tile idx out of range"))?;
+        let file = self.file.as_mut().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no backing file"))?;
+        read_tile(self.map.as_ref(), file, tile)
+    }
+
+    /// Add rows after the existing ones. A short last tile is read back and
+    /// rewritten together with the new rows, so every tile but the last
+    /// stays full; the new tiles go at the end of the file.
+    pub fn append_rows(&mut self, rows: Vec<Vec<String>>) -> io::Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        if let Some(memory) = &mut self.memory {
+            let nrows = memory.rows.len() + rows.len();
+            let bytes = memory.bytes + rows.iter().map(|row| row_bytes(row)).sum::<usize>();
+            if memory.options.fits_in_memory(nrows, bytes) {
+                Arc::make_mut(&mut memory.rows).extend(rows);
+                memory.bytes = bytes;
+                self.nrows = nrows;
+            } else {
+                // Outgrown: the rows so far and the new ones go to a temp file
+                let kept = memory.rows.iter().cloned();
+                *self = Self::spill(self.kinds.clone(), kept.chain(rows), memory.options)?;
             }
+            return Ok(());
+        }
+        let mut pending = Vec::new();
+        if self.tile_row_counts.last().map_or(false, |&n| (n as usize) < TILE_SIZE) {
+            let last = self.tile_offsets.len() - 1;
+            let tile = self.load_tile(last)?;
+            pending = tile_rows(&tile, 0..tile.num_rows());
+            self.tile_offsets.pop();
+            self.tile_row_counts.pop();
+            self.cache.pop(&last);
+        }
+        pending.extend(rows);
+
+        let file = self.file.as_mut().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no backing file"))?;
+        let end = file.seek(SeekFrom::End(0))?;
+        let mut writer = BufWriter::new(&mut *file);
+        let mut offset = pad_to_alignment(&mut writer, end)?;
+        for rows in pending.chunks(TILE_SIZE) {
+            let tile = write_tile(&mut writer, &self.kinds, self.compression, offset, rows)?;
+            offset = tile.end();
+            self.tile_offsets.push(tile);
+            self.tile_row_counts.push(rows.len() as u64);
+        }
+        writer.flush()?;
+        drop(writer);
+        self.nrows = self.tile_row_counts.iter().sum::<u64>() as usize;
+        // Tiles read before keep the old mapping alive as long as they need it
+        self.map = FileMap::new(file).ok().map(Arc::new);
+
+        // The pinned last tile has changed; the first one only if it was short
+        if self.last_tile.is_some() {
+            self.first_tile = self.load_tile(0).ok();
+            self.last_tile = self.load_tile(self.tile_offsets.len() - 1).ok();
         }
         Ok(())
     }
 
-    /// Loads an Arc'd tile from file (by tile index)
-    fn load_tile_arc(&mut self, idx: usize) -> io::Result<Arc<Vec<Vec<String>>>> {
-        let offset = *self.tile_offsets.get(idx)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "tile idx out of range"))?;
-        self.file.seek(SeekFrom::Start(offset))?;
-
-        let row_count = self.file.read_u32::<LittleEndian>()? as usize;
-        let col_count = self.file.read_u32::<LittleEndian>()? as usize;
-        let mut rows = Vec::with_capacity(row_count);
-        for _ in 0..row_count {
-            let mut row = Vec::with_capacity(col_count);
-            for _ in 0..col_count {
-                let len = self.file.read_u32::<LittleEndian>()? as usize;
-                let mut buf = vec![0u8; len];
-                self.file.read_exact(&mut buf)?;
-                row.push(String::from_utf8_lossy(&buf).to_string());
-            }
-            rows.push(row);
+    /// Separate read handle on the backing file, so another thread can scan
+    /// the tiles while the grid keeps using this store
+    pub fn reader(&self) -> io::Result<TileReader> {
+        if let Some(memory) = &self.memory {
+            return Ok(TileReader {
+                memory: Some(memory.rows.clone()),
+                file: None,
+                map: None,
+                tile_offsets: Vec::new(),
+                kinds: self.kinds.clone(),
+            });
         }
-        Ok(Arc::new(rows))
+        let temp_file = self.temp_file.as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no backing file"))?;
+        Ok(TileReader {
+            memory: None,
+            file: Some(File::open(temp_file.path())?),
+            map: self.map.clone(),
+            tile_offsets: self.tile_offsets.clone(),
+            kinds: self.kinds.clone(),
+        })
+    }
+
+    /// (hits, misses) of tile lookups so far, for the perf overlay
+    pub fn cache_stats(&self) -> (u64, u64) {
+        (self.cache_hits, self.cache_misses)
     }
 
     /// Fetches rows from start..(start+count).
@@ -188,36 +481,354 @@ impl TileRowStore {
             return Ok(Vec::new());
         }
         let end = usize::min(self.nrows, start+count);
+        if let Some(memory) = &self.memory {
+            self.cache_hits += 1;
+            return Ok(memory.rows[start..end].to_vec());
+        }
         let mut result = Vec::with_capacity(end-start);
         let mut curr = start;
         while curr < end {
             let tile_idx = curr / TILE_SIZE;
             let in_tile = curr % TILE_SIZE;
-            let tile = if tile_idx == 0 {
-                self.first_tile.as_ref().cloned()
-                    .or_else(|| self.load_tile_arc(0).ok())
+            let pinned = if tile_idx == 0 {
+                self.first_tile.as_ref()
             } else if tile_idx == self.tile_offsets.len()-1 {
-                self.last_tile.as_ref().cloned()
-                    .or_else(|| self.load_tile_arc(tile_idx).ok())
+                self.last_tile.as_ref()
+            } else {
+                None
+            };
+            let tile = if let Some(t) = pinned {
+                self.cache_hits += 1;
+                Some(t.clone())
             } else {
                 if let Some(t) = self.cache.get(&tile_idx) {
+                    self.cache_hits += 1;
                     Some(t.clone())
                 } else {
-                    let t = self.load_tile_arc(tile_idx)?;
+                    self.cache_misses += 1;
+                    let t = self.load_tile(tile_idx)?;
                     self.cache.put(tile_idx, t.clone());
                     Some(t)
                 }
             }.ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Failed to load tile"))?;
-            let end_in_tile = usize::min(tile.len(), in_tile + (end-curr));
-            for row in &tile[in_tile..end_in_tile] {
-                result.push(row.clone());
-            }
+            let end_in_tile = usize::min(tile.num_rows(), in_tile + (end-curr));
+            result.extend(tile_rows(&tile, in_tile..end_in_tile));
             curr += end_in_tile - in_tile;
         }
         Ok(result)
     }
 }
 
+/// Uncached, sequential access to a store's tiles from another thread
+#[derive(Debug)]
+pub struct TileReader {
+    /// Rows of an in-memory store, read a tile's worth at a time
+    memory: Option<Arc<Vec<Vec<String>>>>,
+    file: Option<File>,
+    /// Shared with the store; covers every tile this reader was given
+    map: Option<Arc<FileMap>>,
+    tile_offsets: Vec<TileBlocks>,
+    kinds: Vec<ColumnKind>,
+}
+
+impl TileReader {
+    pub fn tile_count(&self) -> usize {
+        match &self.memory {
+            Some(rows) => rows.len().div_ceil(TILE_SIZE),
+            None => self.tile_offsets.len(),
+        }
+    }
+
+    pub fn read_tile(&mut self, idx: usize) -> io::Result<Vec<Vec<String>>> {
+        let out_of_range = || io::Error::new(io::ErrorKind::InvalidInput, "tile idx out of range");
+        if let Some(rows) = &self.memory {
+            return rows.chunks(TILE_SIZE).nth(idx).map(<[_]>::to_vec).ok_or_else(out_of_range);
+        }
+        let tile = self.read_batch(idx)?;
+        Ok(tile_rows(&tile, 0..tile.num_rows()))
+    }
+
+    /// Tile `idx` as a record batch, the arrays as stored; the rows of an
+    /// in-memory store are typed the way a tile written to disk would be
+    pub fn read_batch(&mut self, idx: usize) -> io::Result<RecordBatch> {
+        let out_of_range = || io::Error::new(io::ErrorKind::InvalidInput, "tile idx out of range");
+        if let Some(rows) = &self.memory {
+            let rows = rows.chunks(TILE_SIZE).nth(idx).ok_or_else(out_of_range)?;
+            return self.rows_batch(rows);
+        }
+        let tile = self.tile_offsets.get(idx).ok_or_else(out_of_range)?;
+        let file = self.file.as_mut().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no backing file"))?;
+        read_tile(self.map.as_ref(), file, tile)
+    }
+
+    /// `rows` of this store's columns as a record batch
+    pub fn rows_batch(&self, rows: &[Vec<String>]) -> io::Result<RecordBatch> {
+        encode_rows(&self.kinds, rows).map(|(batch, _)| batch)
+    }
+}
+
+/// A read-only memory mapping of a store's file. Tiles are decoded from
+/// slices of it, without a seek and read per tile; the Arrow buffers of a
+/// tile hold on to the mapping until they are dropped.
+#[derive(Debug)]
+struct FileMap {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// The mapping is read-only and unmapped only on drop
+unsafe impl Send for FileMap {}
+unsafe impl Sync for FileMap {}
+
+impl FileMap {
+    /// Map all of `file` as it is now
+    #[cfg(unix)]
+    fn new(file: &File) -> io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty file"));
+        }
+        // SAFETY: a fresh shared read-only mapping of an open file; the
+        // store only ever appends to the file, so mapped bytes don't change
+        let ptr = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_SHARED, file.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let ptr = NonNull::new(ptr as *mut u8).ok_or_else(|| io::Error::other("mmap returned null"))?;
+        Ok(Self { ptr, len })
+    }
+
+    #[cfg(not(unix))]
+    fn new(_file: &File) -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "memory mapping needs unix"))
+    }
+
+    /// `block`'s bytes as a buffer borrowing the mapping; None if the
+    /// mapping ends before them
+    fn tile_buffer(self: &Arc<Self>, block: &Block) -> Option<Buffer> {
+        let start = block.offset() as usize;
+        let len = block_len(block) as usize;
+        if start.checked_add(len)? > self.len {
+            return None;
+        }
+        // SAFETY: the range is inside the mapping, which the buffer keeps
+        // alive through its owner
+        let ptr = unsafe { NonNull::new_unchecked(self.ptr.as_ptr().add(start)) };
+        Some(unsafe { Buffer::from_custom_allocation(ptr, len, self.clone()) })
+    }
+}
+
+impl Drop for FileMap {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// Nullable columns stored as `encodings` say, named by position (the
+/// headers live with the results tab)
+fn tile_schema(encodings: &[ColumnEncoding]) -> SchemaRef {
+    let fields: Vec<Field> = encodings.iter()
+        .enumerate()
+        .map(|(c, encoding)| {
+            let name = format!("c{}", c);
+            if *encoding != ColumnEncoding::Dictionary {
+                return Field::new(name, encoding.data_type(), true);
+            }
+            // The IPC reader finds a column's dictionary by the id in the schema
+            #[allow(deprecated)]
+            Field::new_dict(name, encoding.data_type(), true, c as i64, false)
+        })
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+/// Write `block` as offset, metadata length and body length
+fn write_block<W: Write>(file: &mut W, block: &Block) -> io::Result<()> {
+    file.write_u64::<LittleEndian>(block.offset() as u64)?;
+    file.write_u64::<LittleEndian>(block.metaDataLength() as u64)?;
+    file.write_u64::<LittleEndian>(block.bodyLength() as u64)
+}
+
+/// Bytes of a row's values, counted against `TileStoreOptions::memory_bytes`
+fn row_bytes(row: &[String]) -> usize {
+    row.iter().map(String::len).sum()
+}
+
+/// `kinds` padded with text, or cut, to `ncols` columns
+fn column_kinds(ncols: usize, kinds: &[ColumnKind]) -> Vec<ColumnKind> {
+    (0..ncols).map(|c| kinds.get(c).copied().unwrap_or(ColumnKind::Text)).collect()
+}
+
+/// `value` as an integer, if that prints back as `value`
+fn int_value(value: &str) -> Option<i64> {
+    value.parse::<i64>().ok().filter(|n| n.to_string() == value)
+}
+
+/// `value` as a double, if that prints back as `value`
+fn double_value(value: &str) -> Option<f64> {
+    value.parse::<f64>().ok().filter(|n| n.is_finite() && n.to_string() == value)
+}
+
+fn bool_value(value: &str) -> Option<bool> {
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// One column of a tile from its values (None for NULL): typed as `kind`
+/// when every value converts back exactly, else text, dictionary-encoded
+/// when at most one in ROWS_PER_DISTINCT values is distinct
+fn encode_column<'a, I>(kind: ColumnKind, values: impl Fn() -> I, nrows: usize) -> (ColumnEncoding, ArrayRef)
+where
+    I: Iterator<Item = Option<&'a str>>,
+{
+    fn typed<'a, T, A: FromIterator<Option<T>>>(values: impl Iterator<Item = Option<&'a str>>, parse: fn(&str) -> Option<T>) -> Option<A> {
+        values.map(|v| v.map_or(Some(None), |v| parse(v).map(Some))).collect()
+    }
+    match kind {
+        ColumnKind::Number => {
+            if let Some(ints) = typed::<_, Int64Array>(values(), int_value) {
+                return (ColumnEncoding::Int, Arc::new(ints));
+            }
+            if let Some(doubles) = typed::<_, Float64Array>(values(), double_value) {
+                return (ColumnEncoding::Double, Arc::new(doubles));
+            }
+        }
+        ColumnKind::Boolean => {
+            if let Some(bools) = typed::<_, BooleanArray>(values(), bool_value) {
+                return (ColumnEncoding::Boolean, Arc::new(bools));
+            }
+        }
+        ColumnKind::Text => {}
+    }
+    let distinct: HashSet<Option<&str>> = values().collect();
+    if distinct.len() * ROWS_PER_DISTINCT <= nrows {
+        (ColumnEncoding::Dictionary, Arc::new(values().collect::<DictionaryArray<Int32Type>>()))
+    } else {
+        (ColumnEncoding::Text, Arc::new(StringArray::from_iter(values())))
+    }
+}
+
+/// `rows` as a record batch of one column per kind, NULL_SENTINEL stored
+/// as null, and how each column came out
+fn encode_rows(kinds: &[ColumnKind], rows: &[Vec<String>]) -> io::Result<(RecordBatch, Vec<ColumnEncoding>)> {
+    let (encodings, columns): (Vec<ColumnEncoding>, Vec<ArrayRef>) = kinds.iter()
+        .enumerate()
+        .map(|(c, &kind)| {
+            let values = || rows.iter().map(|row| row.get(c).map(String::as_str).filter(|&v| v != NULL_SENTINEL));
+            encode_column(kind, values, rows.len())
+        })
+        .unzip();
+    // The row count keeps results without columns encodable
+    let row_count = RecordBatchOptions::new().with_row_count(Some(rows.len()));
+    let batch = RecordBatch::try_new_with_options(tile_schema(&encodings), columns, &row_count)
+        .map_err(io::Error::other)?;
+    Ok((batch, encodings))
+}
+
+/// Text of value `row` of a tile column, as it was before it was stored;
+/// NULL_SENTINEL for null
+pub fn cell_text(column: &dyn Array, row: usize) -> String {
+    if column.is_null(row) {
+        return NULL_SENTINEL.to_string();
+    }
+    let any = column.as_any();
+    if let Some(dict) = any.downcast_ref::<DictionaryArray<Int32Type>>() {
+        return cell_text(dict.values().as_ref(), dict.keys().value(row) as usize);
+    }
+    if let Some(strings) = any.downcast_ref::<StringArray>() {
+        strings.value(row).to_string()
+    } else if let Some(ints) = any.downcast_ref::<Int64Array>() {
+        ints.value(row).to_string()
+    } else if let Some(doubles) = any.downcast_ref::<Float64Array>() {
+        doubles.value(row).to_string()
+    } else if let Some(bools) = any.downcast_ref::<BooleanArray>() {
+        bools.value(row).to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Rows `rows` of a tile as text
+fn tile_rows(tile: &Tile, rows: Range<usize>) -> Vec<Vec<String>> {
+    rows.map(|r| tile.columns().iter().map(|column| cell_text(column.as_ref(), r)).collect())
+        .collect()
+}
+
+/// Bytes of a tile's message, metadata and body
+fn block_len(block: &Block) -> u64 {
+    block.metaDataLength() as u64 + block.bodyLength() as u64
+}
+
+/// Write zeros from `pos` up to the next multiple of TILE_ALIGNMENT;
+/// returns where that is
+fn pad_to_alignment<W: Write>(file: &mut W, pos: u64) -> io::Result<u64> {
+    let aligned = pos.next_multiple_of(TILE_ALIGNMENT);
+    file.write_all(&[0u8; TILE_ALIGNMENT as usize][..(aligned - pos) as usize])?;
+    Ok(aligned)
+}
+
+/// The bytes of `block`, from `map` when it covers them and otherwise
+/// read from `file`
+fn block_buffer(map: Option<&Arc<FileMap>>, file: &mut File, block: &Block) -> io::Result<Buffer> {
+    if let Some(buffer) = map.and_then(|map| map.tile_buffer(block)) {
+        return Ok(buffer);
+    }
+    file.seek(SeekFrom::Start(block.offset() as u64))?;
+    let mut bytes = vec![0u8; block_len(block) as usize];
+    file.read_exact(&mut bytes)?;
+    Ok(Buffer::from_vec(bytes))
+}
+
+/// Write a tile (rows) at `offset` as Arrow IPC messages: a record batch
+/// of a column per kind, preceded by a dictionary for each column stored
+/// with one, every buffer compressed with `compression`
+fn write_tile<W: Write>(
+    file: &mut W,
+    kinds: &[ColumnKind],
+    compression: TileCompression,
+    offset: u64,
+    rows: &[Vec<String>],
+) -> io::Result<TileBlocks> {
+    let (batch, encodings) = encode_rows(kinds, rows)?;
+    let options = IpcWriteOptions::default().try_with_compression(compression.ipc()).map_err(io::Error::other)?;
+    let (dictionaries, encoded) = IpcDataGenerator::default()
+        .encoded_batch(&batch, &mut DictionaryTracker::new(false), &options)
+        .map_err(io::Error::other)?;
+    // Dictionaries come in column order
+    let mut offset = offset;
+    let mut write = |file: &mut W, encoded| -> io::Result<Block> {
+        let (meta_len, body_len) = write_message(file, encoded, &options).map_err(io::Error::other)?;
+        let block = Block::new(offset as i64, meta_len as i32, body_len as i64);
+        offset += block_len(&block);
+        Ok(block)
+    };
+    let dictionaries = dictionaries.into_iter()
+        .map(|dictionary| write(file, dictionary))
+        .collect::<io::Result<_>>()?;
+    let batch = write(file, encoded)?;
+    Ok(TileBlocks { encodings, dictionaries, batch })
+}
+
+/// Read the tile written by `write_tile` at `tile`
+fn read_tile(map: Option<&Arc<FileMap>>, file: &mut File, tile: &TileBlocks) -> io::Result<Tile> {
+    let mut decoder = FileDecoder::new(tile_schema(&tile.encodings), MetadataVersion::V5);
+    for block in &tile.dictionaries {
+        decoder.read_dictionary(block, &block_buffer(map, file, block)?).map_err(io::Error::other)?;
+    }
+    decoder.read_record_batch(&tile.batch, &block_buffer(map, file, &tile.batch)?)
+        .map_err(io::Error::other)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "tile is not a record batch"))
+}
+
 /// To allow ResultsTab or tile cache to auto-clean up temp files:
 impl Drop for TileRowStore {
     fn drop(&mut self) {
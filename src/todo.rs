@@ -0,0 +1,121 @@
+use crate::overlay::OverlayOutcome;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::path::{Path, PathBuf};
+
+/// Comment tags collected into the list
+const TAGS: [&str; 2] = ["TODO", "FIXME"];
+
+/// Limits for the project folder scan, which runs on the UI thread
+const MAX_FILES: usize = 2_000;
+const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TodoSource {
+    /// The editor buffer
+    Buffer,
+    File(PathBuf),
+}
+
+/// One `-- TODO:` / `-- FIXME:` comment
+#[derive(Debug, Clone)]
+pub struct TodoItem {
+    pub source: TodoSource,
+    /// 0-based line number
+    pub line: usize,
+    pub tag: &'static str,
+    pub text: String,
+}
+
+/// `-- TODO: text` comments in `text`; the tag is case-insensitive and the
+/// colon required, so prose mentioning "todo" isn't picked up
+pub fn scan_text(text: &str, source: &TodoSource) -> Vec<TodoItem> {
+    let mut items = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let Some(start) = line.find("--") else { continue };
+        let comment = line[start + 2..].trim_start_matches('-').trim_start();
+        for tag in TAGS {
+            let matched = comment.get(..tag.len()).is_some_and(|t| t.eq_ignore_ascii_case(tag))
+                && comment[tag.len()..].starts_with(':');
+            if matched {
+                items.push(TodoItem {
+                    source: source.clone(),
+                    line: line_no,
+                    tag,
+                    text: comment[tag.len() + 1..].trim().to_string(),
+                });
+                break;
+            }
+        }
+    }
+    items
+}
+
+/// TODOs in the `.sql` files under `dir`, skipping hidden directories and
+/// `skip` (the file open in the editor, which is scanned from the buffer)
+pub fn scan_folder(dir: &Path, skip: Option<&Path>) -> Vec<TodoItem> {
+    let skip = skip.and_then(|p| p.canonicalize().ok());
+    let mut items = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    let mut files = 0;
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        let mut entries: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+        entries.sort();
+        for path in entries {
+            let hidden = path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if hidden {
+                continue;
+            }
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let is_sql = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("sql"));
+            let small = path.metadata().is_ok_and(|m| m.len() <= MAX_FILE_BYTES);
+            if !is_sql || !small || skip.as_deref() == path.canonicalize().ok().as_deref() {
+                continue;
+            }
+            files += 1;
+            if files > MAX_FILES {
+                return items;
+            }
+            if let Ok(text) = std::fs::read_to_string(&path) {
+                items.extend(scan_text(&text, &TodoSource::File(path)));
+            }
+        }
+    }
+    items
+}
+
+/// Navigable list of TODO comments; Enter jumps to the selected one
+#[derive(Debug)]
+pub struct TodoList {
+    pub items: Vec<TodoItem>,
+    pub selected: usize,
+}
+
+impl TodoList {
+    pub fn new(items: Vec<TodoItem>) -> Self {
+        Self { items, selected: 0 }
+    }
+
+    pub fn selected_item(&self) -> Option<&TodoItem> {
+        self.items.get(self.selected)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> OverlayOutcome {
+        let last = self.items.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Enter if !self.items.is_empty() => return OverlayOutcome::Submit,
+            KeyCode::Char('q') => return OverlayOutcome::Close,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+            KeyCode::PageDown => self.selected = (self.selected + 10).min(last),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            _ => {}
+        }
+        OverlayOutcome::Consumed
+    }
+}
@@ -0,0 +1,63 @@
+use crate::util::fnv1a;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Undo and redo steps of a file as it was last saved
+#[derive(Serialize, Deserialize)]
+struct UndoFile<T> {
+    /// Hash of the saved content; a file changed elsewhere since then
+    /// gets no history
+    content_hash: u64,
+    undo: Vec<T>,
+    redo: Vec<T>,
+}
+
+/// `<data dir>/frost/undo/<hash of the file's path>.json`, e.g.
+/// `~/.local/share/frost/undo/…`
+fn history_path(file: &Path) -> Option<PathBuf> {
+    let file = fs::canonicalize(file).ok()?;
+    let name = format!("{:016x}.json", fnv1a(file.to_string_lossy().as_bytes()));
+    directories::ProjectDirs::from("", "", "frost").map(|d| d.data_local_dir().join("undo").join(name))
+}
+
+/// Keep the history of `file`, just saved as `content`. The oldest undo
+/// steps, then the furthest redo steps, are dropped until it fits in
+/// `max_bytes`.
+pub fn write<T: Serialize>(file: &Path, content: &str, undo: &[T], redo: &[T], max_bytes: usize) -> io::Result<()> {
+    let path = history_path(file).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    let size = |step: &T| serde_json::to_vec(step).map_or(0, |bytes| bytes.len() + 1);
+    let mut total: usize = undo.iter().chain(redo).map(size).sum();
+    let (mut undo_from, mut redo_from) = (0, 0);
+    while total > max_bytes && undo_from < undo.len() {
+        total -= size(&undo[undo_from]);
+        undo_from += 1;
+    }
+    // The redo stack is popped from its end, so its start is furthest away
+    while total > max_bytes && redo_from < redo.len() {
+        total -= size(&redo[redo_from]);
+        redo_from += 1;
+    }
+    let history = UndoFile {
+        content_hash: fnv1a(content.as_bytes()),
+        undo: undo[undo_from..].iter().collect(),
+        redo: redo[redo_from..].iter().collect(),
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec(&history)?)?;
+    fs::rename(&tmp, &path)
+}
+
+/// Undo and redo steps kept for `file`, if they were written for exactly
+/// `content`
+pub fn read<T: DeserializeOwned>(file: &Path, content: &str) -> Option<(Vec<T>, Vec<T>)> {
+    let bytes = fs::read(history_path(file)?).ok()?;
+    let history: UndoFile<T> = serde_json::from_slice(&bytes).ok()?;
+    (history.content_hash == fnv1a(content.as_bytes())).then_some((history.undo, history.redo))
+}
@@ -0,0 +1,4 @@
+/// 64-bit FNV-1a; unlike std's hasher it is the same across runs and builds
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
@@ -1,18 +1,49 @@
 use crate::{
-    config::Config,
-    connection::{DbWorkerRequest, DbWorkerResponse, SafeStmt, start_db_worker},
+    catalog,
+    clipboard::HistoryList,
+    complete::{self, CompletionList},
+    config::{rgb, Config, Dialect, RedrawMode},
+    keymap::{Action, Keymap},
+    metadata::MetadataCache,
+    object_tree::{ObjectTree, TreeNode},
+    cost::{self, CostCache},
+    ddl::DdlView,
+    diff::{self, Change, DiffView},
+    error_pos,
+    export::{ExportFormat, ExportPrompt},
+    goto::GotoPrompt,
+    health::{human_bytes, CheckStatus, HealthReport},
+    highlight::highlight_sql,
+    distribution::bar,
+    json_view::JsonCopy,
+    overlay::{ConfirmAction, Overlay, OverlayOutcome},
+    perf::PerfStats,
+    recovery::{self, RecoveryData},
+    replace::{self, ReplaceField, ReplaceForm},
+    snippet,
+    connection::{CancelHandle, DbWorkerRequest, DbWorkerResponse, QueryExecutor, QueryRequest, RequestSender, TableStats, SCRIPT_WRAPPER_LINES, start_db_worker},
+    directive::Directives,
     focus::Focus,
-    results::{Results, ResultsTab, ResultsContent},
+    guard,
+    plan::PlanFormat,
+    results::{fit, printable, Results, ResultsTab, ResultsContent},
+    save_as::SaveAsPrompt,
+    saved_result,
+    stages::{StageBrowser, StageRequest},
+    switcher::{ContextKind, Switcher},
+    todo::{self, TodoList, TodoSource},
     texteditor::{Editor, AppState},
 };
 use std::{
-    sync::{Arc, Mutex},
-    sync::mpsc::{Receiver, Sender},
+    collections::HashMap,
+    fmt::Write as _,
+    sync::mpsc::Receiver,
     time::{Duration, Instant},
     io,
 };
+use chrono::FixedOffset;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent},
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
     execute,
 };
 #[cfg(target_os = "windows")]
@@ -22,26 +53,95 @@ use ratatui::{
     Terminal,
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
-    widgets::{Block, Borders},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
 };
 
 const MIN_ROWS: i16 = 3;
 
+/// A draw slower than this counts towards switching to reduced redraws
+const SLOW_DRAW: Duration = Duration::from_millis(40);
+
+/// Consecutive slow draws before auto mode switches to reduced redraws
+const SLOW_DRAWS_TO_REDUCE: u32 = 10;
+
+/// In reduced mode, background status changes are drawn at most this often
+const STATUS_BATCH: Duration = Duration::from_millis(500);
+
 pub struct Workspace {
     pub editor: Editor,
     pub results: Results,
     pub focus: Focus,
+    /// Where the editor's text was last drawn, for mouse clicks
+    editor_area: Rect,
+    /// Where the results pane was last drawn, for the mouse wheel
+    results_area: Rect,
     pub running: bool,
     pub run_started: Option<Instant>,
     pub run_duration: Option<Duration>,
     pub error: Option<String>,
     pub connected: bool,
+    config: Config,
+    keymap: Keymap,
+    /// Modal popups, topmost last
+    overlays: Vec<Overlay>,
+    
+    // Redrawing
+    /// Only redraw on input and batched status changes (slow terminals)
+    reduced_redraw: bool,
+    /// Input changed the screen; draw on the next tick
+    needs_redraw: bool,
+    /// Worker responses arrived since the last draw
+    status_changed: bool,
+    last_draw: Instant,
+    slow_draws: u32,
+    /// Frame and event timings, shown by the perf overlay
+    perf: PerfStats,
+    show_perf: bool,
+    
+    // Crash recovery
+    recovery_path: Option<std::path::PathBuf>,
+    last_autosave: Instant,
+    /// Hash of the content last written to the recovery file
+    autosaved_hash: Option<u64>,
     
     // Database communication
-    db_req_tx: Sender<DbWorkerRequest>,
+    db_req_tx: RequestSender,
     db_resp_rx: Receiver<DbWorkerResponse>,
-    current_stmt: Arc<Mutex<Option<SafeStmt>>>,
+    cancel: CancelHandle,
+    /// UTC offset of the session time zone, for session-local timestamps
+    session_offset: Option<FixedOffset>,
+    /// Driver name and version reported by the server, for the health report
+    session_client: Option<String>,
+    /// EXPLAIN bytes and last run times per statement, for the editor's
+    /// cost annotations
+    costs: CostCache,
+    /// The single statement being run or explained, so its cost can be
+    /// recorded when it finishes
+    pending_statement: Option<String>,
+    /// Per statement of the running batch, the buffer offset its text
+    /// starts at and the lines wrapped in front of it, to map error
+    /// positions back into the editor; empty for runs that did not come
+    /// from the editor text
+    error_origins: Vec<(usize, usize)>,
+    /// Per statement of the running batch, the id of the tab its result
+    /// goes to; statements without one open a tab when they start
+    batch_tabs: Vec<u64>,
+    /// The running batch is a `parallel=true` one
+    batch_parallel: bool,
+    /// Table row counts per (database, schema), fetched when a schema is
+    /// expanded in the object tree
+    table_stats: HashMap<(String, String), Vec<TableStats>>,
+    /// Object names for autocomplete, cached on disk per account and user
+    metadata: MetadataCache,
+    /// The refresh in progress was asked for; fetch every database, not
+    /// only stale ones
+    metadata_force: bool,
+    /// Session database and schema, which unqualified completions resolve
+    /// against
+    session_database: Option<String>,
+    session_schema: Option<String>,
     
     // Layout
     split_offset: i16,
@@ -52,21 +152,102 @@ pub struct Workspace {
 }
 
 impl Workspace {
-    pub fn new(config: Config) -> Self {
-        let (db_req_tx, db_resp_rx, current_stmt) = start_db_worker(config.connection_string);
+    /// Workspace whose queries `executor` answers: ODBC or the SQL API
+    /// (see `configured_executor`), or a `FakeExecutor` for tests and demos
+    pub fn new(config: Config, keymap: Keymap, executor: Box<dyn QueryExecutor>) -> Self {
+        let metadata = match executor.account() {
+            Some((server, user)) => MetadataCache::load(&server, &user),
+            None => MetadataCache::default(),
+        };
+        let (db_req_tx, db_resp_rx, cancel) = start_db_worker(executor);
+        
+        let mut editor = Editor::new();
+        editor.set_undo_limit(config.undo_limit());
+        editor.set_persistent_undo(config.persistent_undo_limit());
+        editor.set_scroll_options(config.scrolloff, config.center_on_jump);
+        editor.set_auto_close(config.auto_close);
+        editor.set_show_whitespace(config.show_whitespace);
+        editor.set_word_wrap(config.word_wrap);
+        editor.set_tab_options(config.tab_width, config.insert_spaces);
+        editor.set_read_only(config.read_only);
+        editor.set_keyword_case(config.keyword_case, config.sql_dialect());
+        editor.set_snippets(snippet::with_builtins(&config.snippets));
+        editor.set_error_color(rgb(config.colors.error_fg));
+        editor.set_occurrence_color(rgb(config.colors.word_occurrence_bg));
+        
+        let reduced_redraw = config.redraw_mode == RedrawMode::Reduced;
+        let mut results = Results::new();
+        results.set_animate_timer(!reduced_redraw);
+        results.set_closed_tab_grace(Duration::from_secs(config.closed_tab_grace_secs));
+        results.set_find_styles(
+            Style::default().fg(rgb(config.colors.find_match_fg)).bg(rgb(config.colors.find_match_bg)),
+            Style::default().fg(rgb(config.colors.find_current_fg)).bg(rgb(config.colors.find_current_bg)),
+        );
+        results.set_null_color(rgb(config.colors.null_fg));
+        results.set_in_list_options(config.in_list_batch_size, config.identifier_quoting);
+        results.set_typed_json(config.export_typed_json);
+        results.set_scroll_options(config.grid_scrolloff, config.center_on_jump);
+        
+        let mut overlays = Vec::new();
+        let health = HealthReport::collect(&config);
+        if config.health_report_on_start || health.has_failures() {
+            overlays.push(Overlay::Health(health));
+        }
+        if let Some(path) = recovery::find_orphaned().into_iter().next() {
+            if let Ok(data) = recovery::read(&path) {
+                let name = data.filename.as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|| "[No Name]".to_string());
+                overlays.push(Overlay::Confirm {
+                    title: " Recover unsaved work ".to_string(),
+                    message: format!(
+                        "Unsaved editor content for {} was autosaved at {}.\n\nRestore it? (y/n)",
+                        name, data.saved_at
+                    ),
+                    action: ConfirmAction::RestoreRecovery(path),
+                });
+            }
+        }
         
         Self {
-            editor: Editor::new(),
-            results: Results::new(),
+            editor,
+            results,
             focus: Focus::Editor,
+            editor_area: Rect::default(),
+            results_area: Rect::default(),
             running: false,
             run_started: None,
             run_duration: None,
             error: None,
             connected: false,
+            config,
+            keymap,
+            overlays,
+            reduced_redraw,
+            needs_redraw: true,
+            status_changed: false,
+            last_draw: Instant::now(),
+            slow_draws: 0,
+            perf: PerfStats::default(),
+            show_perf: false,
+            recovery_path: recovery::own_recovery_path(),
+            last_autosave: Instant::now(),
+            autosaved_hash: None,
             db_req_tx,
             db_resp_rx,
-            current_stmt,
+            cancel,
+            session_offset: None,
+            session_client: None,
+            costs: CostCache::default(),
+            pending_statement: None,
+            error_origins: Vec::new(),
+            batch_tabs: Vec::new(),
+            batch_parallel: false,
+            table_stats: HashMap::new(),
+            metadata,
+            metadata_force: false,
+            session_database: None,
+            session_schema: None,
             split_offset: 0,
             min_split_offset: -20,
             max_split_offset: 20,
@@ -85,6 +266,19 @@ impl Workspace {
         // Set title
         execute!(io::stdout(), crossterm::terminal::SetTitle("Minimal Frost"))?;
         
+        self.run_with_events(terminal, |timeout| {
+            if event::poll(timeout)? { event::read().map(Some) } else { Ok(None) }
+        })
+    }
+    
+    /// The event loop with events from `next_event`, which waits up to the
+    /// given time for one; lets tests drive the workspace with synthetic
+    /// events (ending with the quit chord) on a `TestBackend`
+    pub fn run_with_events<B, F>(&mut self, terminal: &mut Terminal<B>, mut next_event: F) -> io::Result<()>
+    where
+        B: Backend,
+        F: FnMut(Duration) -> io::Result<Option<Event>>,
+    {
         loop {
             // Check if editor wants to exit
             if let AppState::Exiting = self.editor.app_state {
@@ -93,13 +287,37 @@ impl Workspace {
             
             // Poll for database responses
             self.poll_db_responses();
+            if self.results.poll_searches() {
+                self.status_changed = true;
+            }
+            if self.results.poll_export() {
+                self.status_changed = true;
+            }
+            if self.results.poll_compare() {
+                self.status_changed = true;
+            }
+            if let Some(Overlay::Distribution(dist)) = self.overlays.last_mut() {
+                self.status_changed |= dist.poll();
+            }
+            
+            self.autosave_tick();
+            self.results.purge_closed_tabs();
             
             // Draw UI
-            terminal.draw(|f| self.draw(f))?;
+            if self.redraw_due() {
+                let started = Instant::now();
+                terminal.draw(|f| self.draw(f))?;
+                let took = started.elapsed();
+                self.perf.record_frame(took);
+                self.record_draw(took);
+            }
             
             // Handle events
-            if event::poll(Duration::from_millis(50))? {
-                match event::read()? {
+            let poll = if self.reduced_redraw { 250 } else { 50 };
+            if let Some(event) = next_event(Duration::from_millis(poll))? {
+                self.needs_redraw = true;
+                let arrived = Instant::now();
+                match event {
                     Event::Key(key) => {
                         // On Windows, ignore key release events
                         #[cfg(target_os = "windows")]
@@ -112,10 +330,15 @@ impl Workspace {
                         if self.handle_key(key, terminal)? {
                             break; // Exit
                         }
+                        self.perf.record_event(arrived.elapsed());
                     }
                     Event::Mouse(mouse) => {
                         self.handle_mouse(mouse);
                     }
+                    Event::Paste(text) => {
+                        self.handle_paste(&text);
+                        self.perf.record_event(arrived.elapsed());
+                    }
                     Event::Resize(_, _) => {
                         #[cfg(target_os = "windows")]
                         terminal.autoresize()?;
@@ -132,11 +355,98 @@ impl Workspace {
             }
         }
         
+        // Clean exit: nothing to recover
+        if let Some(path) = &self.recovery_path {
+            recovery::remove(path);
+        }
+        
         Ok(())
     }
     
+    /// Full mode draws every tick (the running timer animates); reduced mode
+    /// draws after input, or at most every STATUS_BATCH for worker updates.
+    fn redraw_due(&self) -> bool {
+        if !self.reduced_redraw {
+            return true;
+        }
+        self.needs_redraw || (self.status_changed && self.last_draw.elapsed() >= STATUS_BATCH)
+    }
+    
+    /// Reset redraw state after a draw; in auto mode, a run of slow draws
+    /// switches to reduced redraws for the rest of the session.
+    fn record_draw(&mut self, took: Duration) {
+        self.needs_redraw = false;
+        self.status_changed = false;
+        self.last_draw = Instant::now();
+        if self.reduced_redraw || self.config.redraw_mode != RedrawMode::Auto {
+            return;
+        }
+        if took < SLOW_DRAW {
+            self.slow_draws = 0;
+            return;
+        }
+        self.slow_draws += 1;
+        if self.slow_draws >= SLOW_DRAWS_TO_REDUCE {
+            self.reduced_redraw = true;
+            self.results.set_animate_timer(false);
+            self.results.message = Some("Slow terminal detected: reduced redraw mode".to_string());
+            self.needs_redraw = true;
+        }
+    }
+    
+    /// Write unsaved editor content to the recovery file every
+    /// `autosave_interval_secs`, and drop the file once the buffer is saved.
+    fn autosave_tick(&mut self) {
+        let interval = self.config.autosave_interval_secs;
+        let Some(path) = &self.recovery_path else { return };
+        if interval == 0 || self.last_autosave.elapsed() < Duration::from_secs(interval) {
+            return;
+        }
+        self.last_autosave = Instant::now();
+        
+        if !self.editor.is_modified() {
+            if self.autosaved_hash.take().is_some() {
+                recovery::remove(path);
+            }
+            return;
+        }
+        
+        let content = self.editor.rope.to_string();
+        let hash = {
+            use std::hash::{Hash, Hasher};
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            content.hash(&mut hasher);
+            hasher.finish()
+        };
+        if self.autosaved_hash == Some(hash) {
+            return;
+        }
+        let data = RecoveryData {
+            filename: self.editor.filename.clone(),
+            saved_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            content,
+        };
+        if recovery::write(path, &data).is_ok() {
+            self.autosaved_hash = Some(hash);
+        }
+    }
+    
     fn draw(&mut self, f: &mut Frame) {
         let size = f.area();
+        let panes = self.pane_area(size);
+        
+        // Production profiles keep a banner across the top
+        if panes != size {
+            let env = self.config.environment.as_deref().unwrap_or("prod").to_uppercase();
+            let guard = if self.config.prod_writes_need_flag && !self.config.allow_prod_writes {
+                "writes disabled (start with --allow-prod-writes)"
+            } else {
+                "writes need confirmation"
+            };
+            let banner = Paragraph::new(format!(" {} - {}", env, guard))
+                .style(Style::default().bg(Color::Red).fg(Color::White).add_modifier(Modifier::BOLD));
+            f.render_widget(banner, Rect { height: 1, ..size });
+        }
         
         // Calculate constraints based on split_offset
         let editor_percent = ((50 + self.split_offset) as u16).clamp(20, 80);
@@ -157,203 +467,2667 @@ impl Workspace {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints(constraints)
-            .split(size);
+            .split(panes);
         
         // Draw editor if not hidden
+        self.editor_area = Rect::default();
         if !self.editor_hidden && !chunks.is_empty() {
             self.draw_editor(f, chunks[0]);
         }
         
         // Draw results if not hidden
+        self.results_area = Rect::default();
         if !self.results_hidden && chunks.len() > 1 {
-            self.results.render(f, chunks[1], self.focus == Focus::Results);
+            self.results_area = chunks[1];
         } else if !self.results_hidden && self.editor_hidden {
-            self.results.render(f, chunks[0], self.focus == Focus::Results);
+            self.results_area = chunks[0];
+        }
+        if self.results_area != Rect::default() {
+            self.results.render(f, self.results_area, self.focus == Focus::Results);
+        }
+        
+        for idx in 0..self.overlays.len() {
+            self.draw_overlay(f, size, idx);
+        }
+        
+        if self.show_perf {
+            self.draw_perf(f, panes);
         }
     }
     
-    fn draw_editor(&mut self, f: &mut Frame, area: Rect) {
-        // Draw border around editor area
+    /// Frame time panel in the top right corner; it takes no input, so it
+    /// stays up while working
+    fn draw_perf(&self, f: &mut Frame, area: Rect) {
+        let cache = self.results.tabs.get(self.results.tab_idx).and_then(|tab| match &tab.content {
+            ResultsContent::Table { tile_store, .. } => Some(tile_store.cache_stats()),
+            _ => None,
+        });
+        let lines: Vec<Line> = self.perf.lines(cache, self.db_req_tx.queued())
+            .into_iter()
+            .map(|line| Line::from(format!(" {} ", line)))
+            .collect();
+        let width = (lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 2).min(area.width);
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let panel = Rect::new(area.x + area.width - width, area.y, width, height);
+        let colors = &self.config.colors;
         let block = Block::default()
             .borders(Borders::ALL)
-            .title("SQL Editor")
-            .border_style(if self.focus == Focus::Editor {
-                Style::default().fg(Color::Cyan)
-            } else {
-                Style::default().fg(Color::Gray)
-            });
-        
-        // Get the inner area (excluding borders) before rendering
-        let inner = block.inner(area);
-        
-        // Render the block
-        f.render_widget(block, area);
-        
-        // Use texteditor's draw_ui function directly on the inner area
-        crate::texteditor::draw_ui(f, &mut self.editor, inner);
+            .title(" Perf ")
+            .border_style(Style::default().fg(rgb(colors.help_border)))
+            .style(Style::default().bg(rgb(colors.help_bg)));
+        f.render_widget(Clear, panel);
+        f.render_widget(Paragraph::new(lines).block(block), panel);
     }
     
-    fn handle_key<B: Backend>(&mut self, key: KeyEvent, terminal: &mut Terminal<B>) -> io::Result<bool> {
-        // Global keys first
-        match (key.code, key.modifiers) {
-            (KeyCode::Char('q'), KeyModifiers::CONTROL) => {
-                // Set editor to exiting state
-                self.editor.app_state = AppState::Exiting;
-                return Ok(true);
-            }
-            (KeyCode::Tab, KeyModifiers::NONE) => {
-                // Switch focus
-                self.focus = match self.focus {
-                    Focus::Editor => Focus::Results,
-                    Focus::Results => Focus::Editor,
-                    Focus::DbTree => Focus::Editor,
+    fn draw_overlay(&mut self, f: &mut Frame, size: Rect, idx: usize) {
+        match &self.overlays[idx] {
+            Overlay::Help { .. } => self.draw_help(f, size, idx),
+            Overlay::Ddl(_) => self.draw_ddl(f, size, idx),
+            Overlay::Source(_) => self.draw_source(f, size, idx),
+            Overlay::RowDetail(_) => self.draw_row_detail(f, size, idx),
+            Overlay::Json(_) => self.draw_json(f, size, idx),
+            Overlay::Distribution(_) => self.draw_distribution(f, size, idx),
+            Overlay::Compare(form) => {
+                let colors = &self.config.colors;
+                let key_style = Style::default().fg(rgb(colors.info_fg));
+                let mut lines = vec![
+                    Line::from(format!(" Rows of tab {} against tab {}, matched on:", form.other + 1, form.base + 1)),
+                    Line::from(""),
+                    Line::from(vec![Span::styled(" Key columns: ", key_style), Span::raw(format!("{}_", form.keys))]),
+                    Line::from(""),
+                    Line::from(" Enter: compare (names separated by commas)"),
+                ];
+                if let Some(error) = &form.error {
+                    lines.push(Line::from(Span::styled(format!(" {}", error), Style::default().fg(rgb(colors.error_fg)))));
+                }
+                let width = size.width.min(64);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Compare tabs (Esc to cancel) ")
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
+            }
+            Overlay::ResultFile(prompt) => {
+                let colors = &self.config.colors;
+                let key_style = Style::default().fg(rgb(colors.info_fg));
+                let (title, intro) = match prompt.save_tab {
+                    Some(tab) => (" Save result (Esc to cancel) ", format!(" Rows, headers and query of tab {} go to:", tab + 1)),
+                    None => (" Open saved result (Esc to cancel) ", " A result saved with z opens in a new tab:".to_string()),
                 };
-                return Ok(false);
+                let mut lines = vec![
+                    Line::from(intro),
+                    Line::from(""),
+                    Line::from(vec![Span::styled(" File: ", key_style), Span::raw(format!("{}_", prompt.path))]),
+                    Line::from(""),
+                    Line::from(" Enter: confirm (.frostres is added without an extension)"),
+                ];
+                if let Some(error) = &prompt.error {
+                    lines.push(Line::from(Span::styled(format!(" {}", error), Style::default().fg(rgb(colors.error_fg)))));
+                }
+                let width = size.width.min(72);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
             }
-            (KeyCode::Enter, KeyModifiers::CONTROL) => {
-                self.run_query();
-                return Ok(false);
+            Overlay::Chart(chart) => {
+                let colors = &self.config.colors;
+                let area = Rect::new(size.x + 2, size.y + 1, size.width.saturating_sub(4), size.height.saturating_sub(2));
+                let last = chart.first_row + chart.len();
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(
+                        " Chart of rows {}-{} (←/→ column, x: x axis, Space: y series, t: {}, Esc to close) ",
+                        chart.first_row + 1, last, if chart.bars { "lines" } else { "bars" },
+                    ))
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                let inner = block.inner(area);
+                f.render_widget(Clear, area);
+                f.render_widget(block, area);
+                chart.render(f, inner);
             }
-            (KeyCode::Char('c'), KeyModifiers::CONTROL) if self.running => {
-                self.cancel_query();
-                return Ok(false);
+            Overlay::Diff(view) => {
+                let colors = &self.config.colors;
+                let width = size.width.min(120);
+                let height = size.height.saturating_sub(4).max(3).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let rows = height.saturating_sub(2) as usize;
+                let digits = view.lines.len().max(1).to_string().len();
+                let number = |line: Option<usize>| line.map_or(String::new(), |l| (l + 1).to_string());
+                let lines: Vec<Line> = view.lines.iter().skip(view.scroll).take(rows).map(|line| {
+                    let (marker, style) = match line.change {
+                        Change::Same => (' ', Style::default()),
+                        Change::Added => ('+', Style::default().fg(rgb(colors.diff_added))),
+                        Change::Removed => ('-', Style::default().fg(rgb(colors.diff_removed))),
+                    };
+                    Line::from(vec![
+                        Span::styled(
+                            format!("{:>w$} {:>w$} ", number(line.old_line), number(line.new_line), w = digits),
+                            Style::default().fg(rgb(colors.status_fg)),
+                        ),
+                        Span::styled(format!("{} {}", marker, line.text), style),
+                    ])
+                }).collect();
+                let (added, removed) = view.counts();
+                let title = if added + removed == 0 {
+                    format!(" No changes against {} (Esc to close) ", view.against)
+                } else {
+                    format!(
+                        " Diff against {} (+{} -{}) - n/p: next/prev change, Enter to jump, Esc to close ",
+                        view.against, added, removed,
+                    )
+                };
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
+            }
+            Overlay::Completion(list) => {
+                let colors = &self.config.colors;
+                let visible = list.visible();
+                let width = (visible.iter().map(|name| name.chars().count()).max().unwrap_or(0) as u16 + 4)
+                    .clamp(30, size.width.min(60));
+                let height = (visible.len() as u16 + 2).clamp(3, size.height.min(14));
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let rows = height.saturating_sub(2) as usize;
+                let first = list.selected.saturating_sub(rows.saturating_sub(1));
+                let lines: Vec<Line> = if visible.is_empty() {
+                    vec![Line::from(" No matches")]
+                } else {
+                    visible.iter().enumerate().skip(first).take(rows).map(|(i, name)| {
+                        let style = if i == list.selected {
+                            Style::default()
+                                .fg(rgb(colors.autocomplete_selected_fg))
+                                .bg(rgb(colors.autocomplete_selected_bg))
+                        } else {
+                            Style::default()
+                        };
+                        Line::from(Span::styled(format!(" {} ", name), style))
+                    }).collect()
+                };
+                let title = if list.filter.is_empty() {
+                    " Complete ".to_string()
+                } else {
+                    format!(" Complete: {} ", list.filter)
+                };
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(Style::default().fg(rgb(colors.autocomplete_border)))
+                    .style(Style::default().bg(rgb(colors.autocomplete_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
+            }
+            Overlay::Switcher(switcher) => {
+                let lines = self.switcher_lines(switcher);
+                let width = size.width.min(60);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let colors = &self.config.colors;
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Switch context (Tab: next list, Esc to close) ")
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
             }
-            // Alt+Arrow keys for resizing
-            (KeyCode::Up, KeyModifiers::ALT) => {
-                if !self.results_hidden {
-                    self.split_offset = (self.split_offset + 5).min(self.max_split_offset);
+            Overlay::PasteHistory(list) => {
+                let lines = self.paste_history_lines(list);
+                let width = size.width.min(90);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let colors = &self.config.colors;
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Paste from history - Enter or 1-9 to paste, Esc to close ")
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
+            }
+            Overlay::Todo(list) => {
+                let lines = self.todo_lines(list);
+                let width = size.width.min(90);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let colors = &self.config.colors;
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" TODO / FIXME ({}) - Enter to jump, Esc to close ", list.items.len()))
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
+            }
+            Overlay::ObjectTree(tree) => {
+                let lines = self.object_tree_lines(tree);
+                let width = size.width.min(90);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let colors = &self.config.colors;
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Objects - Enter: open / insert table, ←/→: fold, Esc to close ")
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
+            }
+            Overlay::Stages(browser) => {
+                let lines = self.stage_lines(browser);
+                let width = size.width.min(90);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let colors = &self.config.colors;
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(browser.title())
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
+            }
+            Overlay::SaveAs(prompt) => {
+                let colors = &self.config.colors;
+                let mut lines = vec![
+                    Line::from(" The editor buffer goes to:"),
+                    Line::from(""),
+                    Line::from(vec![
+                        Span::styled(" File: ", Style::default().fg(rgb(colors.info_fg))),
+                        Span::raw(format!("{}_", prompt.path)),
+                    ]),
+                ];
+                if let Some(error) = &prompt.error {
+                    lines.push(Line::from(""));
+                    lines.push(Line::from(Span::styled(format!(" {}", error), Style::default().fg(rgb(colors.error_fg)))));
                 }
-                return Ok(false);
+                let width = size.width.min(72);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Save as (Esc to cancel) ")
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
             }
-            (KeyCode::Down, KeyModifiers::ALT) => {
-                if !self.results_hidden {
-                    self.split_offset = (self.split_offset - 5).max(self.min_split_offset);
+            Overlay::Goto(prompt) => {
+                let colors = &self.config.colors;
+                let mut lines = vec![Line::from(vec![
+                    Span::styled(" Go to: ", Style::default().fg(rgb(colors.info_fg))),
+                    Span::raw(format!("{}_", prompt.input)),
+                ])];
+                if prompt.invalid {
+                    lines.push(Line::from(Span::styled(
+                        " Expected line, line:col, +N or -N",
+                        Style::default().fg(rgb(colors.error_fg)),
+                    )));
                 }
-                return Ok(false);
+                let width = size.width.min(44);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Go to line (Esc to cancel) ")
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
             }
-            (KeyCode::Left, KeyModifiers::ALT) => {
-                // Hide results (show editor only)
-                self.results_hidden = true;
-                self.editor_hidden = false;
-                self.focus = Focus::Editor;
-                return Ok(false);
+            Overlay::Export(prompt) => {
+                let lines = self.export_lines(prompt);
+                let width = size.width.min(60);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let colors = &self.config.colors;
+                let title = if prompt.to_clipboard { " Copy selection as (Esc to cancel) " } else { " Export results as (Esc to cancel) " };
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
             }
-            (KeyCode::Right, KeyModifiers::ALT) => {
-                // Hide editor (show results only)
-                self.results_hidden = false;
-                self.editor_hidden = true;
-                self.focus = Focus::Results;
-                return Ok(false);
+            Overlay::Health(report) => {
+                let colors = &self.config.colors;
+                let lines: Vec<Line> = report.checks.iter()
+                    .map(|check| {
+                        let status_style = match check.status {
+                            CheckStatus::Ok => Style::default().fg(rgb(colors.info_fg)),
+                            CheckStatus::Warn => Style::default().fg(Color::Yellow),
+                            CheckStatus::Fail => Style::default().fg(rgb(colors.error_fg)).add_modifier(Modifier::BOLD),
+                        };
+                        Line::from(vec![
+                            Span::styled(format!(" {:<5}", check.status.label()), status_style),
+                            Span::styled(format!("{:<14}", check.name), Style::default().add_modifier(Modifier::BOLD)),
+                            Span::raw(check.detail.clone()),
+                        ])
+                    })
+                    .collect();
+                let width = size.width.min(96);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Health report (c: copy, Esc to close) ")
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(
+                    Paragraph::new(lines).block(block).wrap(ratatui::widgets::Wrap { trim: false }),
+                    area,
+                );
             }
-            (KeyCode::Char(' '), KeyModifiers::ALT) => {
-                // Show both panes
-                self.results_hidden = false;
-                self.editor_hidden = false;
-                return Ok(false);
+            Overlay::ColumnStats { column, stats } => {
+                let key_style = Style::default().fg(rgb(self.config.colors.info_fg));
+                let lines: Vec<Line> = stats.iter()
+                    .map(|(label, value)| Line::from(vec![
+                        Span::styled(format!(" {:<10}", label), key_style),
+                        Span::raw(value.clone()),
+                    ]))
+                    .collect();
+                let width = size.width.min(56);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let colors = &self.config.colors;
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" {} ", column))
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
+            }
+            Overlay::Replace(form) => {
+                let lines = self.replace_lines(form);
+                let width = size.width.min(72);
+                let height = (lines.len() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let colors = &self.config.colors;
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Replace values (Esc to cancel) ")
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(Paragraph::new(lines).block(block), area);
+            }
+            Overlay::Confirm { title, message, .. } => {
+                let width = size.width.min(64);
+                let height = (message.lines().count() as u16 + 2).min(size.height);
+                let area = Rect::new(
+                    size.x + (size.width - width) / 2,
+                    size.y + (size.height - height) / 2,
+                    width,
+                    height,
+                );
+                let colors = &self.config.colors;
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(title.as_str())
+                    .border_style(Style::default().fg(rgb(colors.help_border)))
+                    .style(Style::default().bg(rgb(colors.help_bg)));
+                f.render_widget(Clear, area);
+                f.render_widget(
+                    Paragraph::new(message.as_str())
+                        .block(block)
+                        .wrap(ratatui::widgets::Wrap { trim: false }),
+                    area,
+                );
             }
-            _ => {}
         }
+    }
+    
+    fn help_lines(&self) -> Vec<Line<'static>> {
+        let key_style = Style::default().fg(rgb(self.config.colors.info_fg));
+        let mut lines = Vec::new();
+        for (title, entries) in self.keymap.help_sections() {
+            if !lines.is_empty() {
+                lines.push(Line::from(""));
+            }
+            lines.push(Line::from(Span::styled(title, Style::default().add_modifier(Modifier::BOLD))));
+            for (keys, desc) in entries {
+                lines.push(Line::from(vec![
+                    Span::styled(format!("  {:<26}", keys), key_style),
+                    Span::raw(desc),
+                ]));
+            }
+        }
+        lines
+    }
+    
+    fn switcher_lines(&self, switcher: &Switcher) -> Vec<Line<'static>> {
+        let colors = &self.config.colors;
+        let active = Style::default().fg(rgb(colors.info_fg)).add_modifier(Modifier::BOLD);
+        let selected = Style::default()
+            .fg(rgb(colors.autocomplete_selected_fg))
+            .bg(rgb(colors.autocomplete_selected_bg));
         
-        // Route to focused pane
-        match self.focus {
-            Focus::Editor => {
-                // Get the current area where editor is drawn
-                let size = terminal.size()?;
-                let area = Rect::new(0, 0, size.width, size.height);
-                
-                // Calculate the editor area (same logic as in draw)
-                let editor_percent = ((50 + self.split_offset) as u16).clamp(20, 80);
-                let results_percent = 100 - editor_percent;
-                
-                let constraints = if self.results_hidden {
-                    vec![Constraint::Percentage(100)]
-                } else {
-                    vec![
-                        Constraint::Percentage(editor_percent),
-                        Constraint::Percentage(results_percent),
-                    ]
+        let mut tabs = vec![Span::raw(" ")];
+        for kind in ContextKind::ALL {
+            let style = if kind == switcher.kind { active } else { Style::default() };
+            tabs.push(Span::styled(kind.title(), style));
+            tabs.push(Span::raw("   "));
+        }
+        let mut lines = vec![
+            Line::from(tabs),
+            Line::from(format!(" Filter: {}_", switcher.filter)),
+            Line::from(""),
+        ];
+        
+        const MAX_SHOWN: usize = 12;
+        if switcher.is_loading() {
+            lines.push(Line::from(" Loading..."));
+            return lines;
+        }
+        let visible = switcher.visible();
+        if visible.is_empty() {
+            lines.push(Line::from(" No matches"));
+        }
+        let first = switcher.selected.saturating_sub(MAX_SHOWN - 1);
+        for (i, name) in visible.iter().enumerate().skip(first).take(MAX_SHOWN) {
+            let style = if i == switcher.selected { selected } else { Style::default() };
+            lines.push(Line::from(Span::styled(format!(" {} ", name), style)));
+        }
+        lines
+    }
+    
+    fn todo_lines(&self, list: &TodoList) -> Vec<Line<'static>> {
+        let colors = &self.config.colors;
+        let tag_style = Style::default().fg(rgb(colors.info_fg)).add_modifier(Modifier::BOLD);
+        let selected = Style::default()
+            .fg(rgb(colors.autocomplete_selected_fg))
+            .bg(rgb(colors.autocomplete_selected_bg));
+        if list.items.is_empty() {
+            return vec![Line::from(" No -- TODO: or -- FIXME: comments found")];
+        }
+        
+        const MAX_SHOWN: usize = 15;
+        let first = list.selected.saturating_sub(MAX_SHOWN - 1);
+        list.items.iter()
+            .enumerate()
+            .skip(first)
+            .take(MAX_SHOWN)
+            .map(|(i, item)| {
+                let location = match &item.source {
+                    TodoSource::Buffer => format!("line {}", item.line + 1),
+                    TodoSource::File(path) => format!("{}:{}", path.display(), item.line + 1),
                 };
-                
-                let chunks = Layout::default()
-                    .direction(Direction::Vertical)
-                    .constraints(constraints)
-                    .split(area);
-                
-                if !chunks.is_empty() {
-                    // Account for the border (1 char on each side)
-                    let editor_area = chunks[0];
-                    let inner_width = editor_area.width.saturating_sub(2) as usize;
-                    let inner_height = editor_area.height.saturating_sub(2) as usize;
-                    
-                    // Use texteditor's handle_editor_key directly
-                    crate::texteditor::handle_editor_key(&mut self.editor, key, inner_width, inner_height)?;
+                let style = if i == list.selected { selected } else { Style::default() };
+                Line::from(vec![
+                    Span::styled(format!(" {:<5} ", item.tag), tag_style),
+                    Span::styled(format!("{}  {} ", location, item.text), style),
+                ])
+            })
+            .collect()
+    }
+    
+    fn paste_history_lines(&self, list: &HistoryList) -> Vec<Line<'static>> {
+        let colors = &self.config.colors;
+        let number_style = Style::default().fg(rgb(colors.info_fg));
+        let selected = Style::default()
+            .fg(rgb(colors.autocomplete_selected_fg))
+            .bg(rgb(colors.autocomplete_selected_bg));
+        if list.items.is_empty() {
+            return vec![Line::from(" Nothing copied yet in this session")];
+        }
+        
+        list.items.iter()
+            .enumerate()
+            .map(|(i, text)| {
+                // The first non-blank line stands for the entry
+                let first = text.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or_default();
+                let more = text.lines().count().saturating_sub(1);
+                let summary = if more > 0 { format!("{}  (+{} lines)", first, more) } else { first.to_string() };
+                let number = if i < 9 { format!(" {} ", i + 1) } else { "   ".to_string() };
+                let style = if i == list.selected { selected } else { Style::default() };
+                Line::from(vec![
+                    Span::styled(number, number_style),
+                    Span::styled(format!(" {} ", summary), style),
+                ])
+            })
+            .collect()
+    }
+    
+    /// The tree's nodes around the selection, tables with their stats dimmed
+    fn object_tree_lines(&self, tree: &ObjectTree) -> Vec<Line<'static>> {
+        let colors = &self.config.colors;
+        let dimmed = Style::default().fg(rgb(colors.status_fg));
+        let selected = Style::default()
+            .fg(rgb(colors.autocomplete_selected_fg))
+            .bg(rgb(colors.autocomplete_selected_bg));
+        if tree.is_empty() {
+            return vec![Line::from(" No object names loaded yet; refresh_metadata fetches them")];
+        }
+        
+        const MAX_SHOWN: usize = 20;
+        let first = tree.selected.saturating_sub(MAX_SHOWN - 1);
+        tree.visible().into_iter().enumerate().skip(first).take(MAX_SHOWN).map(|(i, node)| {
+            let marker = match node {
+                TreeNode::Table { .. } => " ",
+                _ if tree.is_expanded(&node) => "▾",
+                _ => "▸",
+            };
+            let style = if i == tree.selected { selected } else { Style::default() };
+            let mut spans = vec![Span::styled(format!(" {}{} {} ", "  ".repeat(node.depth()), marker, node.name()), style)];
+            if let TreeNode::Table { database, schema, table } = &node {
+                if let Some(annotation) = self.table_annotation(database, schema, table) {
+                    spans.push(Span::styled(format!(" {}", annotation), dimmed));
                 }
             }
-            Focus::Results => {
-                self.results.handle_key(key);
-            }
-            Focus::DbTree => {} // Not implemented yet
+            Line::from(spans)
+        }).collect()
+    }
+    
+    fn stage_lines(&self, browser: &StageBrowser) -> Vec<Line<'static>> {
+        let colors = &self.config.colors;
+        let key_style = Style::default().fg(rgb(colors.info_fg));
+        let selected = Style::default()
+            .fg(rgb(colors.autocomplete_selected_fg))
+            .bg(rgb(colors.autocomplete_selected_bg));
+        let mut lines = Vec::new();
+        if let Some(input) = &browser.upload_input {
+            lines.push(Line::from(vec![Span::styled(" Upload file: ", key_style), Span::raw(format!("{}_", input))]));
+            lines.push(Line::from(""));
         }
         
-        Ok(false)
+        const MAX_SHOWN: usize = 15;
+        let rows = browser.rows();
+        if browser.is_loading() {
+            lines.push(Line::from(" Loading..."));
+        } else if rows.is_empty() {
+            lines.push(Line::from(match browser.current_stage() {
+                Some(_) => " Stage is empty",
+                None => " No stages in the current schema",
+            }));
+        }
+        let first = browser.selected.saturating_sub(MAX_SHOWN - 1);
+        for (i, (name, detail)) in rows.into_iter().enumerate().skip(first).take(MAX_SHOWN) {
+            let style = if i == browser.selected { selected } else { Style::default() };
+            lines.push(Line::from(vec![
+                Span::styled(format!(" {} ", name), style),
+                Span::styled(format!(" {}", detail), key_style),
+            ]));
+        }
+        if let Some(status) = &browser.status {
+            lines.push(Line::from(""));
+            lines.push(Line::from(format!(" {}", status)));
+        }
+        lines
     }
     
-    fn handle_mouse(&mut self, _mouse: MouseEvent) {
-        // TODO: Implement mouse handling for pane selection
+    fn export_lines(&self, prompt: &ExportPrompt) -> Vec<Line<'static>> {
+        let key_style = Style::default().fg(rgb(self.config.colors.info_fg));
+        if let Some(ExportFormat::Insert { table }) = &prompt.format {
+            return vec![
+                Line::from(vec![Span::styled(" Table:    ", key_style), Span::raw(format!("{}_", table))]),
+                Line::from(""),
+                Line::from(" Enter: write INSERT statements"),
+            ];
+        }
+        let target = if prompt.to_clipboard {
+            " Selected block goes to the clipboard"
+        } else {
+            " All rows are written to a file (select a block to copy)"
+        };
+        let mut lines = vec![Line::from(target), Line::from("")];
+        for (key, name) in [("c", "CSV"), ("m", "Markdown table"), ("i", "INSERT statements"), ("j", "JSON lines"), ("a", "JSON array")] {
+            lines.push(Line::from(vec![Span::styled(format!("  {}  ", key), key_style), Span::raw(name)]));
+        }
+        if !prompt.to_clipboard {
+            lines.push(Line::from(vec![Span::styled("  g  ", key_style), Span::raw("JSON lines, gzipped")]));
+            lines.push(Line::from(vec![Span::styled("  p  ", key_style), Span::raw("Parquet")]));
+            lines.push(Line::from(vec![Span::styled("  x  ", key_style), Span::raw("Excel workbook")]));
+            lines.push(Line::from(vec![Span::styled("  X  ", key_style), Span::raw("Excel workbook, a sheet per results tab")]));
+        }
+        let typed = if prompt.typed { "numbers and booleans unquoted" } else { "every value a string" };
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec![Span::styled("  t  ", key_style), Span::raw(format!("JSON values: {}", typed))]));
+        lines
     }
     
-    fn poll_db_responses(&mut self) {
-        while let Ok(response) = self.db_resp_rx.try_recv() {
-            match response {
-                DbWorkerResponse::Connected => {
-                    self.connected = true;
-                }
-                DbWorkerResponse::QueryStarted { query_idx: _, started, query_context } => {
-                    self.running = true;
-                    self.run_started = Some(started);
-                    // Add pending tab
-                    let tab = ResultsTab::new_pending_with_start(query_context, started);
-                    self.results.tabs.push(tab);
-                    self.results.tab_idx = self.results.tabs.len() - 1;
-                }
-                DbWorkerResponse::QueryFinished { query_idx: _, elapsed: _, result } => {
-                    self.running = false;
-                    self.results.add_result(result);
-                    self.focus = Focus::Results;
+    fn replace_lines(&self, form: &ReplaceForm) -> Vec<Line<'static>> {
+        let active = Style::default().fg(rgb(self.config.colors.info_fg));
+        let field = |label: &str, value: &str, which: ReplaceField| {
+            let cursor = if form.field == which { "_" } else { "" };
+            let style = if form.field == which { active } else { Style::default() };
+            Line::from(vec![
+                Span::styled(format!(" {:<10}", label), style),
+                Span::raw(format!("{}{}", value, cursor)),
+            ])
+        };
+        let mut lines = vec![
+            Line::from(format!(" Column {} (key: {})", form.column_name, form.key_name)),
+            Line::from(""),
+            field("Table:", &form.table, ReplaceField::Table),
+            field("Find:", &form.find, ReplaceField::Find),
+            field("Replace:", &form.replace, ReplaceField::Replace),
+            Line::from(""),
+        ];
+        match &form.preview {
+            None if form.table.trim().is_empty() => {
+                lines.push(Line::from(" Enter the table to update"));
+            }
+            None => lines.push(Line::from(" Enter: preview matching rows   Tab: next field")),
+            Some(preview) => {
+                let n = preview.keys.len();
+                let mut summary = format!(" {} row{} will change", n, if n == 1 { "" } else { "s" });
+                if preview.null_keys > 0 {
+                    summary.push_str(&format!("; {} with NULL key skipped", preview.null_keys));
                 }
-                DbWorkerResponse::QueryError { query_idx: _, elapsed, message } => {
-                    self.running = false;
-                    self.run_duration = Some(elapsed);
-                    self.error = Some(message.clone());
-                    self.results.add_result(ResultsContent::Error {
-                        message,
-                        cursor: 0,
-                        selection: None,
-                    });
+                lines.push(Line::from(summary));
+                if n > 0 {
+                    let sample: Vec<&str> = preview.keys.iter().take(5).map(|k| k.as_str()).collect();
+                    let more = if n > sample.len() { ", ..." } else { "" };
+                    lines.push(Line::from(format!(" Keys: {}{}", sample.join(", "), more)));
+                    lines.push(Line::from(" Enter: write UPDATE to the editor for review"));
                 }
             }
         }
+        lines
     }
     
-    fn run_query(&mut self) {
-        if self.running || !self.connected {
-            return;
+    fn draw_help(&mut self, f: &mut Frame, size: Rect, idx: usize) {
+        let width = size.width.min(72);
+        let height = size.height.saturating_sub(4).max(3).min(size.height);
+        let area = Rect::new(
+            size.x + (size.width - width) / 2,
+            size.y + (size.height - height) / 2,
+            width,
+            height,
+        );
+        
+        let lines = self.help_lines();
+        let visible = area.height.saturating_sub(2) as usize;
+        let max_scroll = lines.len().saturating_sub(visible);
+        let scroll = match &mut self.overlays[idx] {
+            Overlay::Help { scroll } => {
+                *scroll = (*scroll).min(max_scroll);
+                *scroll
+            }
+            _ => 0,
+        };
+        
+        let colors = &self.config.colors;
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Help (Esc to close) ")
+            .border_style(Style::default().fg(rgb(colors.help_border)))
+            .style(Style::default().bg(rgb(colors.help_bg)));
+        
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(lines).block(block).scroll((scroll as u16, 0)),
+            area,
+        );
+    }
+    
+    fn draw_ddl(&mut self, f: &mut Frame, size: Rect, idx: usize) {
+        let Overlay::Ddl(view) = &self.overlays[idx] else { return };
+        let colors = &self.config.colors;
+        let lines = match &view.ddl {
+            None => vec![Line::from(" Loading...")],
+            Some(Ok(ddl)) => highlight_sql(ddl, colors, self.config.sql_dialect()),
+            Some(Err(e)) => vec![Line::from(format!(" GET_DDL failed: {}", e))],
+        };
+        let title = format!(" DDL of {} (c: copy, e: open read-only, Esc to close) ", view.name);
+        
+        let width = size.width.min(100);
+        let height = (lines.len() as u16 + 2).clamp(3, size.height.saturating_sub(4).max(3)).min(size.height);
+        let area = Rect::new(
+            size.x + (size.width - width) / 2,
+            size.y + (size.height - height) / 2,
+            width,
+            height,
+        );
+        let visible = area.height.saturating_sub(2) as usize;
+        let max_scroll = lines.len().saturating_sub(visible);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_style(Style::default().fg(rgb(colors.help_border)))
+            .style(Style::default().bg(rgb(colors.help_bg)));
+        let paragraph = Paragraph::new(lines).block(block);
+        let scroll = match &mut self.overlays[idx] {
+            Overlay::Ddl(view) => {
+                view.scroll = view.scroll.min(max_scroll);
+                view.scroll
+            }
+            _ => 0,
+        };
+        
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph.scroll((scroll as u16, 0)), area);
+    }
+    
+    fn draw_source(&mut self, f: &mut Frame, size: Rect, idx: usize) {
+        let Overlay::Source(view) = &self.overlays[idx] else { return };
+        let colors = &self.config.colors;
+        let lines = highlight_sql(&view.sql, colors, self.config.sql_dialect());
+        let title = format!(" SQL of tab {} (c: copy, e: copy to editor, Esc to close) ", view.tab + 1);
+        
+        let width = size.width.min(100);
+        let height = (lines.len() as u16 + 2).clamp(3, size.height.saturating_sub(4).max(3)).min(size.height);
+        let area = Rect::new(
+            size.x + (size.width - width) / 2,
+            size.y + (size.height - height) / 2,
+            width,
+            height,
+        );
+        let visible = area.height.saturating_sub(2) as usize;
+        let max_scroll = lines.len().saturating_sub(visible);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_style(Style::default().fg(rgb(colors.help_border)))
+            .style(Style::default().bg(rgb(colors.help_bg)));
+        let paragraph = Paragraph::new(lines).block(block);
+        let scroll = match &mut self.overlays[idx] {
+            Overlay::Source(view) => {
+                view.scroll = view.scroll.min(max_scroll);
+                view.scroll
+            }
+            _ => 0,
+        };
+        
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph.scroll((scroll as u16, 0)), area);
+    }
+    
+    /// Column names beside their values, wrapped, the selected field kept
+    /// in view
+    fn draw_row_detail(&mut self, f: &mut Frame, size: Rect, idx: usize) {
+        let Overlay::RowDetail(detail) = &self.overlays[idx] else { return };
+        let colors = &self.config.colors;
+        let width = size.width.min(120);
+        let name_width = detail.name_width();
+        let value_width = (width as usize).saturating_sub(name_width + 5).max(1);
+        let layout = detail.layout(value_width);
+        let height = (layout.len() as u16 + 2).clamp(3, size.height.saturating_sub(4).max(3)).min(size.height);
+        let area = Rect::new(
+            size.x + (size.width - width) / 2,
+            size.y + (size.height - height) / 2,
+            width,
+            height,
+        );
+        
+        // Scroll so the whole selected field shows, or at least its start
+        let visible = area.height.saturating_sub(2) as usize;
+        let first = layout.iter().position(|&(i, _)| i == detail.selected).unwrap_or(0);
+        let last = layout.iter().rposition(|&(i, _)| i == detail.selected).unwrap_or(first);
+        let mut scroll = detail.scroll.min(first);
+        if last >= scroll + visible {
+            scroll = (last + 1 - visible).min(first);
         }
         
-        let query = self.get_current_query();
-        if query.is_empty() {
-            return;
+        let selected = Style::default()
+            .fg(rgb(colors.autocomplete_selected_fg))
+            .bg(rgb(colors.autocomplete_selected_bg));
+        let null_style = Style::default().fg(rgb(colors.null_fg)).add_modifier(Modifier::DIM | Modifier::ITALIC);
+        let separator = Style::default().fg(rgb(colors.status_fg));
+        let lines: Vec<Line> = layout.iter().enumerate().skip(scroll).take(visible)
+            .map(|(n, (i, text))| {
+                let starts_field = n == 0 || layout[n - 1].0 != *i;
+                let name = if starts_field { printable(&detail.fields[*i].0) } else { Default::default() };
+                let name = fit(&name, name_width);
+                let name_style = if *i == detail.selected { selected } else { Style::default().add_modifier(Modifier::BOLD) };
+                let value = match text {
+                    Some(text) => Span::raw(text.clone()),
+                    None => Span::styled("NULL", null_style),
+                };
+                Line::from(vec![Span::raw(" "), Span::styled(name, name_style), Span::styled(" │ ", separator), value])
+            })
+            .collect();
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" Row {} (c: copy value, C: copy row, Esc to close) ", detail.row + 1))
+            .border_style(Style::default().fg(rgb(colors.help_border)))
+            .style(Style::default().bg(rgb(colors.help_bg)));
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+        if let Overlay::RowDetail(detail) = &mut self.overlays[idx] {
+            detail.scroll = scroll;
+        }
+    }
+    
+    /// Value counts of a column as horizontal bars, longest first
+    fn draw_distribution(&mut self, f: &mut Frame, size: Rect, idx: usize) {
+        let Overlay::Distribution(dist) = &self.overlays[idx] else { return };
+        let colors = &self.config.colors;
+        let width = size.width.min(100);
+        let null_style = Style::default().fg(rgb(colors.null_fg)).add_modifier(Modifier::DIM | Modifier::ITALIC);
+        let count_style = Style::default().fg(rgb(colors.status_fg));
+        let bar_style = Style::default().fg(rgb(colors.info_fg));
+        let percent = |n: usize| if dist.total == 0 { 0.0 } else { n as f64 * 100.0 / dist.total as f64 };
+        let (title, mut lines) = match &dist.tally {
+            None => (
+                format!(" {}: counting... {}/{} rows ", dist.column, dist.scanned, dist.total),
+                Vec::new(),
+            ),
+            Some(Err(e)) => (format!(" {} ", dist.column), vec![Line::from(format!(" Value counts failed: {}", e))]),
+            Some(Ok(tally)) => {
+                let label_width = tally.label_width();
+                let max = tally.top.first().map_or(1, |(_, n)| *n).max(1);
+                let number_width = max.to_string().len();
+                let bar_width = (width as usize).saturating_sub(label_width + number_width + 16).max(1);
+                let lines = tally.top.iter()
+                    .map(|(value, n)| {
+                        let label = match value {
+                            Some(value) => Span::raw(fit(&printable(value), label_width)),
+                            None => Span::styled(fit("NULL", label_width), null_style),
+                        };
+                        Line::from(vec![
+                            Span::raw(" "),
+                            label,
+                            Span::raw(" "),
+                            Span::styled(format!("{:>w$} {:>5.1}% ", n, percent(*n), w = number_width), count_style),
+                            Span::styled(bar(*n as f64 / max as f64, bar_width), bar_style),
+                        ])
+                    })
+                    .collect();
+                let shown: usize = tally.top.iter().map(|(_, n)| n).sum();
+                let distinct = if tally.capped {
+                    format!("over {} distinct", tally.distinct)
+                } else {
+                    format!("{} distinct", tally.distinct)
+                };
+                let other = if shown < dist.total {
+                    format!(", {} other rows ({:.1}%)", dist.total - shown, percent(dist.total - shown))
+                } else {
+                    String::new()
+                };
+                (format!(" {}: {}{} (Esc to close) ", dist.column, distinct, other), lines)
+            }
+        };
+        if lines.is_empty() {
+            lines.push(Line::from(""));
+        }
+        let height = (lines.len() as u16 + 2).clamp(3, size.height.saturating_sub(4).max(3)).min(size.height);
+        let area = Rect::new(
+            size.x + (size.width - width) / 2,
+            size.y + (size.height - height) / 2,
+            width,
+            height,
+        );
+        let visible = area.height.saturating_sub(2) as usize;
+        let scroll = dist.scroll.min(lines.len().saturating_sub(visible));
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_style(Style::default().fg(rgb(colors.help_border)))
+            .style(Style::default().bg(rgb(colors.help_bg)));
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block).scroll((scroll as u16, 0)), area);
+        if let Overlay::Distribution(dist) = &mut self.overlays[idx] {
+            dist.scroll = scroll;
         }
+    }
+    
+    /// JSON tree of a cell, the selected member kept in view
+    fn draw_json(&mut self, f: &mut Frame, size: Rect, idx: usize) {
+        let Overlay::Json(view) = &self.overlays[idx] else { return };
+        let colors = &self.config.colors;
+        let visible = view.visible();
+        let width = size.width.min(120);
+        let height = (visible.len() as u16 + 2).clamp(3, size.height.saturating_sub(4).max(3)).min(size.height);
+        let area = Rect::new(
+            size.x + (size.width - width) / 2,
+            size.y + (size.height - height) / 2,
+            width,
+            height,
+        );
         
-        // Wrap in EXECUTE IMMEDIATE
-        let wrapped_query = format!("EXECUTE IMMEDIATE $$\n{}\n$$", query);
+        let rows = area.height.saturating_sub(2) as usize;
+        let cursor = view.cursor_line(&visible);
+        let mut scroll = view.scroll.min(cursor);
+        if cursor >= scroll + rows {
+            scroll = cursor + 1 - rows;
+        }
         
-        let _ = self.db_req_tx.send(DbWorkerRequest::RunQueries(vec![(wrapped_query, String::new())]));
+        let selected = Style::default()
+            .fg(rgb(colors.autocomplete_selected_fg))
+            .bg(rgb(colors.autocomplete_selected_bg));
+        let summary = Style::default().fg(rgb(colors.status_fg));
+        let lines: Vec<Line> = visible.iter().enumerate().skip(scroll).take(rows)
+            .map(|(n, &i)| {
+                let (label, value) = view.line(i);
+                let label_style = if n == cursor { selected } else { Style::default().add_modifier(Modifier::BOLD) };
+                Line::from(vec![Span::styled(label, label_style), Span::styled(printable(value).into_owned(), summary)])
+            })
+            .collect();
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" {} (c: copy path, C: copy JSONPath, v: copy value, Esc to close) ", view.column))
+            .border_style(Style::default().fg(rgb(colors.help_border)))
+            .style(Style::default().bg(rgb(colors.help_bg)));
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+        if let Overlay::Json(view) = &mut self.overlays[idx] {
+            view.scroll = scroll;
+        }
     }
     
-    fn cancel_query(&mut self) {
-        if self.running {
-            let _ = self.db_req_tx.send(DbWorkerRequest::Cancel);
+    /// Open `overlay` on top of the stack; an already-open overlay of the
+    /// same kind is closed instead so toggle keys behave.
+    fn toggle_overlay(&mut self, overlay: Overlay) {
+        let same_kind = |o: &Overlay| std::mem::discriminant(o) == std::mem::discriminant(&overlay);
+        if self.overlays.last().map_or(false, same_kind) {
+            self.overlays.pop();
+        } else {
+            self.overlays.push(overlay);
+        }
+    }
+    
+    /// Route a key to the topmost overlay. Esc and the overlay's own toggle
+    /// key close it; everything else is captured.
+    fn handle_overlay_key(&mut self, key: KeyEvent) {
+        if key.code == KeyCode::Esc {
+            self.overlays.pop();
+            return;
+        }
+        let toggles_top = match self.overlays.last() {
+            Some(Overlay::Help { .. }) => self.keymap.action_for(&key) == Some(Action::Help),
+            Some(Overlay::Switcher(_)) => self.keymap.action_for(&key) == Some(Action::SwitchContext),
+            Some(Overlay::Health(_)) => self.keymap.action_for(&key) == Some(Action::HealthReport),
+            Some(Overlay::Todo(_)) => self.keymap.action_for(&key) == Some(Action::TodoList),
+            Some(Overlay::Goto(_)) => self.keymap.action_for(&key) == Some(Action::GotoLine),
+            Some(Overlay::Diff(_)) => self.keymap.action_for(&key) == Some(Action::DiffBuffer),
+            Some(Overlay::PasteHistory(_)) => self.keymap.action_for(&key) == Some(Action::PasteHistory),
+            Some(Overlay::Stages(_)) => self.keymap.action_for(&key) == Some(Action::StageBrowser),
+            Some(Overlay::Ddl(_)) => self.keymap.action_for(&key) == Some(Action::ShowDdl),
+            Some(Overlay::ObjectTree(_)) => self.keymap.action_for(&key) == Some(Action::ObjectTree),
+            _ => false,
+        };
+        let outcome = if toggles_top {
+            OverlayOutcome::Close
+        } else if let Some(top) = self.overlays.last_mut() {
+            top.handle_key(key)
+        } else {
+            OverlayOutcome::Consumed
+        };
+        match outcome {
+            OverlayOutcome::Consumed => {}
+            OverlayOutcome::Close => {
+                self.overlays.pop();
+            }
+            OverlayOutcome::Submit => match self.overlays.last() {
+                Some(Overlay::Switcher(_)) => self.submit_switcher(),
+                Some(Overlay::Export(_)) => self.submit_export(),
+                Some(Overlay::Compare(_)) => self.submit_compare(),
+                Some(Overlay::ResultFile(_)) => self.submit_result_file(),
+                Some(Overlay::SaveAs(_)) => self.submit_save_as(),
+                Some(Overlay::Todo(_)) => self.submit_todo(),
+                Some(Overlay::Goto(_)) => self.submit_goto(),
+                Some(Overlay::ObjectTree(_)) => self.submit_object_tree(),
+                Some(Overlay::Diff(_)) => self.submit_diff(),
+                Some(Overlay::PasteHistory(list)) => {
+                    if let Some(text) = list.selected_item().cloned() {
+                        let width = self.editor_viewport_width();
+                        self.editor.insert_text(text, width);
+                        self.editor_hidden = false;
+                        self.focus = Focus::Editor;
+                    }
+                    self.overlays.pop();
+                }
+                Some(Overlay::Completion(list)) => {
+                    if let Some(name) = list.selected_item() {
+                        let replace_len = list.replace_len;
+                        let width = self.editor_viewport_width();
+                        self.editor.insert_identifier(replace_len, &[name.as_str()], self.config.identifier_quoting, width);
+                    }
+                    self.overlays.pop();
+                }
+                Some(Overlay::Ddl(view)) if view.open_in_editor => {
+                    if self.editor.is_modified() {
+                        self.results.message = Some(format!("Save the buffer before opening the DDL of {}", view.name));
+                        return;
+                    }
+                    self.editor.view_text(view.text().unwrap_or_default());
+                    self.results.message = Some(format!("DDL of {} opened read-only", view.name));
+                    self.overlays.pop();
+                    self.editor_hidden = false;
+                    self.focus = Focus::Editor;
+                }
+                Some(Overlay::Ddl(view)) => {
+                    let text = view.text().unwrap_or_default().to_string();
+                    self.results.message = Some(match crate::clipboard::set_text(text) {
+                        Ok(()) => format!("Copied DDL of {}", view.name),
+                        Err(e) => format!("Copy failed: {}", e),
+                    });
+                    self.overlays.pop();
+                }
+                Some(Overlay::Source(view)) if view.to_editor => {
+                    if self.editor.is_read_only() {
+                        self.results.message = Some("The editor is read-only".to_string());
+                        return;
+                    }
+                    let sql = view.sql.clone();
+                    let tab = view.tab;
+                    let width = self.editor_viewport_width();
+                    self.editor.insert_text(sql, width);
+                    self.results.message = Some(format!("SQL of tab {} copied to the editor", tab + 1));
+                    self.overlays.pop();
+                    self.editor_hidden = false;
+                    self.focus = Focus::Editor;
+                }
+                Some(Overlay::Source(view)) => {
+                    let text = view.sql.clone();
+                    self.results.message = Some(match crate::clipboard::set_text(text) {
+                        Ok(()) => format!("Copied SQL of tab {}", view.tab + 1),
+                        Err(e) => format!("Copy failed: {}", e),
+                    });
+                    self.overlays.pop();
+                }
+                Some(Overlay::RowDetail(detail)) => {
+                    let text = detail.copy_text();
+                    let what = if detail.copy_all {
+                        format!("row {}", detail.row + 1)
+                    } else {
+                        detail.fields.get(detail.selected).map(|(name, _)| name.clone()).unwrap_or_default()
+                    };
+                    self.results.message = Some(match crate::clipboard::set_text(text) {
+                        Ok(()) => format!("Copied {}", what),
+                        Err(e) => format!("Copy failed: {}", e),
+                    });
+                    self.overlays.pop();
+                }
+                Some(Overlay::Json(view)) => {
+                    let text = view.copy_text();
+                    self.results.message = Some(match crate::clipboard::set_text(text) {
+                        Ok(()) => format!("Copied {}", match view.copy {
+                            JsonCopy::QueryPath => view.query_path(),
+                            JsonCopy::JsonPath => view.json_path(),
+                            JsonCopy::Value => format!("value of {}", view.query_path()),
+                        }),
+                        Err(e) => format!("Copy failed: {}", e),
+                    });
+                    self.overlays.pop();
+                }
+                Some(Overlay::Health(report)) => {
+                    let text = report.to_text();
+                    self.overlays.pop();
+                    self.results.message = Some(match crate::clipboard::set_text(text) {
+                        Ok(()) => "Copied health report".to_string(),
+                        Err(e) => format!("Copy failed: {}", e),
+                    });
+                }
+                _ => self.submit_replace(),
+            },
+            OverlayOutcome::Accept | OverlayOutcome::Decline => {
+                if let Some(Overlay::Confirm { action, .. }) = self.overlays.pop() {
+                    self.run_confirm_action(action, outcome == OverlayOutcome::Accept);
+                }
+            }
+        }
+        self.load_switcher_options();
+        self.send_stage_requests();
+        self.request_tree_stats();
+    }
+    
+    /// The open stage browser; it may be below other overlays
+    fn stage_browser(&mut self) -> Option<&mut StageBrowser> {
+        self.overlays.iter_mut().find_map(|o| match o {
+            Overlay::Stages(browser) => Some(browser),
+            _ => None,
+        })
+    }
+    
+    /// Pass the stage browser's pending listing or transfer to the worker
+    fn send_stage_requests(&mut self) {
+        let Some(Overlay::Stages(browser)) = self.overlays.last_mut() else { return };
+        let Some(request) = browser.take_request() else { return };
+        let download_dir = std::env::current_dir().unwrap_or_default();
+        let request = match request {
+            StageRequest::ListStages => DbWorkerRequest::ListStages,
+            StageRequest::ListFiles(stage) => DbWorkerRequest::ListStageFiles(stage),
+            StageRequest::Put { ref path, .. } => {
+                let size = match std::fs::metadata(path) {
+                    Ok(meta) if meta.is_file() => meta.len(),
+                    _ => {
+                        browser.busy = false;
+                        browser.status = Some(format!("{} is not a file", path));
+                        return;
+                    }
+                };
+                let label = format!("Uploading {} ({})", path, human_bytes(size));
+                let sql = request.transfer_sql(&download_dir).unwrap_or_default();
+                DbWorkerRequest::StageTransfer { label, sql }
+            }
+            StageRequest::Get { ref file, .. } => {
+                let label = format!("Downloading {} to {}", file, download_dir.display());
+                let sql = request.transfer_sql(&download_dir).unwrap_or_default();
+                DbWorkerRequest::StageTransfer { label, sql }
+            }
+        };
+        let _ = self.db_req_tx.send(request);
+    }
+    
+    /// Fetch the switcher's current list from the worker the first time it is shown
+    fn load_switcher_options(&mut self) {
+        if let Some(Overlay::Switcher(switcher)) = self.overlays.last_mut() {
+            if let Some(kind) = switcher.take_load_request() {
+                let _ = self.db_req_tx.send(DbWorkerRequest::ListContext(kind));
+            }
+        }
+    }
+    
+    /// Fetch the stats of a schema the object tree just expanded
+    fn request_tree_stats(&mut self) {
+        let Some(Overlay::ObjectTree(tree)) = self.overlays.last_mut() else { return };
+        if let Some((database, schema)) = tree.take_expanded() {
+            self.request_table_stats(&database, &schema);
+        }
+    }
+    
+    /// Insert the selected table's qualified name at the editor caret
+    fn submit_object_tree(&mut self) {
+        let Some(Overlay::ObjectTree(tree)) = self.overlays.last() else { return };
+        let Some(TreeNode::Table { database, schema, table }) = tree.selected_node() else { return };
+        if self.editor.is_read_only() {
+            self.results.message = Some("The editor is read-only".to_string());
+            return;
+        }
+        self.overlays.pop();
+        let width = self.editor_viewport_width();
+        self.editor.insert_identifier(0, &[&database, &schema, &table], self.config.identifier_quoting, width);
+        self.editor_hidden = false;
+        self.focus = Focus::Editor;
+    }
+    
+    fn submit_switcher(&mut self) {
+        let Some(Overlay::Switcher(switcher)) = self.overlays.last() else { return };
+        let Some(name) = switcher.selected_name() else { return };
+        let kind = switcher.kind;
+        self.overlays.pop();
+        let _ = self.db_req_tx.send(DbWorkerRequest::UseContext { kind, name });
+    }
+    
+    /// Jump to the selected TODO, opening its file if it isn't the buffer
+    fn submit_todo(&mut self) {
+        let Some(Overlay::Todo(list)) = self.overlays.last() else { return };
+        let Some(item) = list.selected_item().cloned() else { return };
+        match item.source {
+            TodoSource::Buffer => self.editor.goto_line(item.line),
+            TodoSource::File(path) => {
+                if self.editor.is_modified() {
+                    self.results.message = Some(format!("Save the buffer before opening {}", path.display()));
+                    return;
+                }
+                if let Err(e) = self.editor.open_at_line(path.clone(), item.line) {
+                    self.results.message = Some(format!("Opening {} failed: {}", path.display(), e));
+                    return;
+                }
+            }
+        }
+        self.overlays.pop();
+        self.focus = Focus::Editor;
+    }
+    
+    /// Move the editor caret to the prompt's line and center it
+    fn submit_goto(&mut self) {
+        let Some(Overlay::Goto(prompt)) = self.overlays.pop() else { return };
+        let Some(target) = prompt.target() else { return };
+        let (line, col) = target.resolve(self.editor.caret_line(), self.editor.rope.len_lines());
+        let width = self.editor_viewport_width();
+        self.editor.goto_position(line, col, width);
+        self.focus = Focus::Editor;
+    }
+    
+    /// Diff the buffer against its file as last saved, or against the
+    /// clipboard when asked to or when the buffer has no file
+    fn open_diff(&mut self, clipboard: bool) {
+        let (name, text) = match &self.editor.filename {
+            Some(path) if !clipboard => match std::fs::read_to_string(path) {
+                Ok(text) => (path.display().to_string(), text),
+                Err(e) => {
+                    self.results.message = Some(format!("Reading {} failed: {}", path.display(), e));
+                    return;
+                }
+            },
+            _ => match crate::clipboard::get_text() {
+                Some(text) => (diff::CLIPBOARD.to_string(), text),
+                None => {
+                    self.results.message = Some("Nothing to compare with: no saved file or clipboard text".to_string());
+                    return;
+                }
+            },
+        };
+        self.overlays.push(Overlay::Diff(DiffView::new(name, &text, &self.editor.rope.to_string())));
+    }
+    
+    /// Jump to the buffer line at the top of the diff, or diff against the
+    /// clipboard instead
+    fn submit_diff(&mut self) {
+        let Some(Overlay::Diff(view)) = self.overlays.pop() else { return };
+        if view.compare_clipboard {
+            self.open_diff(true);
+            return;
+        }
+        if let Some(line) = view.target_line() {
+            self.editor.goto_line(line);
+            self.focus = Focus::Editor;
+        }
+    }
+    
+    fn submit_export(&mut self) {
+        let Some(Overlay::Export(prompt)) = self.overlays.pop() else { return };
+        let Some(format) = prompt.format else { return };
+        if !prompt.to_clipboard {
+            self.results.start_export(prompt.tab_idx, &format, prompt.typed);
+        } else if let Some(tab) = self.results.tabs.get_mut(prompt.tab_idx) {
+            self.results.message = Some(tab.copy_as(&format, prompt.typed));
+        }
+    }
+    
+    /// Compare the form's tabs; on failure the form stays open with the reason
+    fn submit_compare(&mut self) {
+        let Some(Overlay::Compare(form)) = self.overlays.last_mut() else { return };
+        match self.results.compare_tabs(form, self.config.tile_store_options()) {
+            Ok(summary) => {
+                self.overlays.pop();
+                self.results.message = Some(summary);
+            }
+            Err(e) => form.error = Some(e),
+        }
+    }
+    
+    /// Save the tab to, or open a result from, the prompt's file; on failure
+    /// the prompt stays open with the reason
+    fn submit_result_file(&mut self) {
+        let Some(Overlay::ResultFile(prompt)) = self.overlays.last_mut() else { return };
+        let path = saved_result::with_extension(&prompt.path);
+        let done = match prompt.save_tab {
+            Some(_) if path.exists() && prompt.error.is_none() => {
+                Err(format!("{} exists; Enter again replaces it", path.display()))
+            }
+            Some(tab) => self.results.save_result(tab, path).map(|_| None),
+            None => self.results.open_result(&path, self.config.tile_store_options()).map(Some),
+        };
+        match done {
+            Ok(message) => {
+                self.overlays.pop();
+                if let Some(message) = message {
+                    self.results.message = Some(message);
+                }
+            }
+            Err(e) => prompt.error = Some(e),
+        }
+    }
+    
+    /// Save the buffer under the prompt's file name; on failure the prompt
+    /// stays open with the reason
+    fn submit_save_as(&mut self) {
+        let Some(Overlay::SaveAs(prompt)) = self.overlays.last_mut() else { return };
+        let path = std::path::PathBuf::from(prompt.path.trim());
+        if path.exists() && prompt.error.is_none() {
+            prompt.error = Some(format!("{} exists; Enter again replaces it", path.display()));
+            return;
+        }
+        match self.editor.save_as(path.clone()) {
+            Ok(()) => {
+                self.overlays.pop();
+                self.results.message = Some(format!("Saved {}", path.display()));
+            }
+            Err(e) => prompt.error = Some(format!("Saving failed: {}", e)),
+        }
+    }
+    
+    /// Save the buffer to its file, or ask for one if it has none
+    fn save_buffer(&mut self) {
+        let Some(path) = self.editor.filename.clone() else {
+            let prompt = SaveAsPrompt::new(self.editor.get_save_path_suggestion());
+            self.overlays.push(Overlay::SaveAs(prompt));
+            return;
+        };
+        self.results.message = Some(match self.editor.save() {
+            Ok(()) => format!("Saved {}", path.display()),
+            Err(e) => format!("Saving {} failed: {}", path.display(), e),
+        });
+    }
+    
+    /// First Enter in the replace form previews the matching rows, the
+    /// second writes the UPDATE into the editor and closes the form.
+    fn submit_replace(&mut self) {
+        let width = self.editor_viewport_width();
+        let Some(Overlay::Replace(form)) = self.overlays.last_mut() else { return };
+        if form.table.trim().is_empty() {
+            form.field = ReplaceField::Table;
+            return;
+        }
+        match form.preview.take() {
+            None => {
+                let Some(tab) = self.results.tabs.get_mut(form.tab_idx) else { return };
+                match tab.replace_preview(form.column, form.key_column, &form.find) {
+                    Ok(preview) => form.preview = Some(preview),
+                    Err(e) => {
+                        self.results.message = Some(format!("Replace failed: {}", e));
+                        self.overlays.pop();
+                    }
+                }
+            }
+            Some(preview) if preview.keys.is_empty() => form.preview = Some(preview),
+            Some(preview) => {
+                let sql = form.update_sql(&preview.keys, self.config.identifier_quoting, self.config.sql_dialect());
+                self.overlays.pop();
+                self.editor.insert_text(sql, width);
+                self.editor_hidden = false;
+                self.focus = Focus::Editor;
+            }
+        }
+    }
+    
+    /// Insert the current time at the editor caret: ISO-8601 (or
+    /// `now_format`) in UTC or the session time zone, or epoch seconds.
+    /// Before the session offset is known, local time stands in for it.
+    fn insert_now(&mut self, action: Action) {
+        let now = chrono::Utc::now();
+        let text = if action == Action::InsertNowEpoch {
+            now.timestamp().to_string()
+        } else {
+            let offset = match action {
+                Action::InsertNowSession => self.session_offset.unwrap_or_else(|| *chrono::Local::now().offset()),
+                _ => FixedOffset::east_opt(0).expect("zero offset is valid"),
+            };
+            // Formatting reports bad strftime specifiers as an fmt error
+            let mut stamp = String::new();
+            if write!(stamp, "{}", now.with_timezone(&offset).format(&self.config.now_format)).is_err() {
+                self.results.message = Some(format!("Invalid now_format '{}'", self.config.now_format));
+                return;
+            }
+            if self.config.now_quoted { format!("'{}'", stamp) } else { stamp }
+        };
+        let width = self.editor_viewport_width();
+        self.editor.insert_text(text, width);
+        self.editor_hidden = false;
+        self.focus = Focus::Editor;
+    }
+    
+    /// Screen area left for the panes, below the production banner if shown
+    fn pane_area(&self, size: Rect) -> Rect {
+        if self.config.is_production() && size.height > 2 {
+            Rect { y: size.y + 1, height: size.height - 1, ..size }
+        } else {
+            size
+        }
+    }
+    
+    /// Text width inside the editor border, for caret column bookkeeping
+    fn editor_viewport_width(&self) -> usize {
+        crossterm::terminal::size()
+            .map(|(w, _)| w.saturating_sub(2) as usize)
+            .unwrap_or(80)
+    }
+    
+    fn run_confirm_action(&mut self, action: ConfirmAction, accepted: bool) {
+        match action {
+            ConfirmAction::RunProdWrites(query) => {
+                if accepted {
+                    self.start_query(query);
+                }
+            }
+            ConfirmAction::RerunProdWrites { new_tab } => {
+                if accepted {
+                    self.start_rerun(new_tab);
+                }
+            }
+            ConfirmAction::RestoreRecovery(path) => {
+                if accepted {
+                    if let Ok(data) = recovery::read(&path) {
+                        self.editor.restore_recovered(&data.content, data.filename);
+                    }
+                }
+                // Restored content is autosaved under our own file from here on
+                recovery::remove(&path);
+            }
+        }
+    }
+    
+    fn draw_editor(&mut self, f: &mut Frame, area: Rect) {
+        // Draw border around editor area
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title("SQL Editor")
+            .border_style(if self.focus == Focus::Editor {
+                Style::default().fg(Color::Cyan)
+            } else {
+                Style::default().fg(Color::Gray)
+            });
+        
+        // Get the inner area (excluding borders) before rendering
+        let inner = block.inner(area);
+        self.editor_area = inner;
+        
+        // Render the block
+        f.render_widget(block, area);
+        
+        self.editor.cost_annotations = if self.config.show_cost_annotations && !self.costs.is_empty() {
+            self.costs.annotations(&self.editor.rope.to_string(), self.config.sql_dialect())
+        } else {
+            Vec::new()
+        };
+        
+        // Use texteditor's draw_ui function directly on the inner area
+        crate::texteditor::draw_ui(f, &mut self.editor, inner);
+    }
+    
+    fn handle_key<B: Backend>(&mut self, key: KeyEvent, terminal: &mut Terminal<B>) -> io::Result<bool> {
+        if !self.overlays.is_empty() {
+            self.handle_overlay_key(key);
+            return Ok(false);
+        }
+        
+        // Global keys first
+        if let Some(action) = self.keymap.action_for(&key) {
+            match action {
+                Action::Quit => {
+                    // Set editor to exiting state
+                    self.editor.app_state = AppState::Exiting;
+                    return Ok(true);
+                }
+                Action::Save => {
+                    self.save_buffer();
+                    return Ok(false);
+                }
+                Action::SwitchFocus => {
+                    // In the editor the key first expands a snippet or
+                    // moves to its next stop
+                    if self.focus == Focus::Editor && self.editor.snippet_tab(self.editor_viewport_width()) {
+                        return Ok(false);
+                    }
+                    self.focus = match self.focus {
+                        Focus::Editor => Focus::Results,
+                        Focus::Results => Focus::Editor,
+                        Focus::DbTree => Focus::Editor,
+                    };
+                    return Ok(false);
+                }
+                Action::RunQuery => {
+                    self.run_query();
+                    return Ok(false);
+                }
+                // Only takes the key while a query runs; otherwise it is the editor's copy
+                Action::CancelQuery if self.running => {
+                    self.cancel_query();
+                    return Ok(false);
+                }
+                Action::CancelQuery => {}
+                Action::CancelQueue if self.results.has_queued() => {
+                    self.cancel.drop_queue();
+                    let dropped = self.results.drop_queued("Not run: removed from the queue");
+                    self.results.message = Some(format!(
+                        "Dropped {} queued statement{}; the running one continues",
+                        dropped,
+                        if dropped == 1 { "" } else { "s" },
+                    ));
+                    return Ok(false);
+                }
+                Action::CancelQueue => {}
+                Action::ExplainPlan => {
+                    self.explain_query(PlanFormat::Text);
+                    return Ok(false);
+                }
+                Action::ExplainJson => {
+                    self.explain_query(PlanFormat::Json);
+                    return Ok(false);
+                }
+                Action::RefreshCosts => {
+                    self.refresh_costs();
+                    return Ok(false);
+                }
+                Action::SwitchContext => {
+                    if self.connected {
+                        self.overlays.push(Overlay::Switcher(Switcher::new(ContextKind::Role)));
+                        self.load_switcher_options();
+                    }
+                    return Ok(false);
+                }
+                Action::ObjectTree => {
+                    self.toggle_overlay(Overlay::ObjectTree(ObjectTree::new(&self.metadata)));
+                    return Ok(false);
+                }
+                Action::StageBrowser => {
+                    if self.connected {
+                        self.toggle_overlay(Overlay::Stages(StageBrowser::new()));
+                        self.send_stage_requests();
+                    }
+                    return Ok(false);
+                }
+                Action::PerfOverlay => {
+                    self.show_perf = !self.show_perf;
+                    return Ok(false);
+                }
+                Action::Autocomplete if self.focus == Focus::Editor => {
+                    self.autocomplete();
+                    return Ok(false);
+                }
+                Action::Autocomplete => {}
+                Action::RefreshMetadata => {
+                    if self.connected {
+                        self.metadata_force = true;
+                        let _ = self.db_req_tx.send(DbWorkerRequest::ListMetadataDatabases);
+                        self.results.message = Some("Refreshing object names for autocomplete...".to_string());
+                    }
+                    return Ok(false);
+                }
+                Action::PreviewTable | Action::DescribeTable => {
+                    self.preview_table(action == Action::DescribeTable);
+                    return Ok(false);
+                }
+                Action::ShowDdl => {
+                    if !self.connected {
+                        return Ok(false);
+                    }
+                    match self.editor.identifier_at_caret() {
+                        Some(name) => {
+                            let _ = self.db_req_tx.send(DbWorkerRequest::FetchDdl(name.clone()));
+                            self.toggle_overlay(Overlay::Ddl(DdlView::new(name)));
+                        }
+                        None => self.results.message = Some("Put the caret on a table name to show its DDL".to_string()),
+                    }
+                    return Ok(false);
+                }
+                Action::TodoList => {
+                    let mut items = todo::scan_text(&self.editor.rope.to_string(), &TodoSource::Buffer);
+                    if let Some(dir) = &self.config.todo_folder {
+                        items.extend(todo::scan_folder(dir, self.editor.filename.as_deref()));
+                    }
+                    self.toggle_overlay(Overlay::Todo(TodoList::new(items)));
+                    return Ok(false);
+                }
+                Action::GotoLine => {
+                    self.toggle_overlay(Overlay::Goto(GotoPrompt::default()));
+                    return Ok(false);
+                }
+                Action::PasteHistory => {
+                    self.toggle_overlay(Overlay::PasteHistory(HistoryList::new()));
+                    return Ok(false);
+                }
+                Action::DiffBuffer => {
+                    self.open_diff(false);
+                    return Ok(false);
+                }
+                Action::ConvertIndentation => {
+                    self.results.message = Some(if self.editor.is_read_only() {
+                        "Editor is read-only".to_string()
+                    } else {
+                        match self.editor.convert_indentation() {
+                            0 => "Indentation already converted".to_string(),
+                            n => format!("Converted indentation of {} line{}", n, if n == 1 { "" } else { "s" }),
+                        }
+                    });
+                    return Ok(false);
+                }
+                Action::IndentLines | Action::OutdentLines if self.focus == Focus::Editor => {
+                    if self.editor.is_read_only() {
+                        self.results.message = Some("Editor is read-only".to_string());
+                    } else {
+                        let width = self.editor_viewport_width();
+                        self.editor.clear_extra_carets();
+                        if action == Action::IndentLines {
+                            self.editor.indent(width);
+                        } else {
+                            self.editor.dedent(width);
+                        }
+                    }
+                    return Ok(false);
+                }
+                Action::IndentLines | Action::OutdentLines => {}
+                Action::ToggleReadOnly => {
+                    let read_only = !self.editor.is_read_only();
+                    self.editor.set_read_only(read_only);
+                    self.results.message = Some(
+                        if read_only { "Editor is read-only" } else { "Editor is editable" }.to_string(),
+                    );
+                    return Ok(false);
+                }
+                Action::HealthReport => {
+                    let mut report = HealthReport::collect(&self.config);
+                    if let Some(client) = &self.session_client {
+                        report.set_driver_version(client);
+                    }
+                    self.toggle_overlay(Overlay::Health(report));
+                    return Ok(false);
+                }
+                Action::InsertNowUtc | Action::InsertNowSession | Action::InsertNowEpoch => {
+                    self.insert_now(action);
+                    return Ok(false);
+                }
+                Action::GrowEditor => {
+                    if !self.results_hidden {
+                        self.split_offset = (self.split_offset + 5).min(self.max_split_offset);
+                    }
+                    return Ok(false);
+                }
+                Action::ShrinkEditor => {
+                    if !self.results_hidden {
+                        self.split_offset = (self.split_offset - 5).max(self.min_split_offset);
+                    }
+                    return Ok(false);
+                }
+                Action::EditorOnly => {
+                    self.results_hidden = true;
+                    self.editor_hidden = false;
+                    self.focus = Focus::Editor;
+                    return Ok(false);
+                }
+                Action::ResultsOnly => {
+                    self.results_hidden = false;
+                    self.editor_hidden = true;
+                    self.focus = Focus::Results;
+                    return Ok(false);
+                }
+                Action::ShowBoth => {
+                    self.results_hidden = false;
+                    self.editor_hidden = false;
+                    return Ok(false);
+                }
+                Action::ReopenTab => {
+                    self.results.reopen_closed_tab();
+                    self.focus = Focus::Results;
+                    return Ok(false);
+                }
+                Action::Help => {
+                    self.toggle_overlay(Overlay::Help { scroll: 0 });
+                    return Ok(false);
+                }
+            }
+        }
+        
+        // A `/` search query is being typed in the results pane
+        if self.focus == Focus::Results && self.results.is_search_input_active() {
+            self.results.handle_key(key);
+            return Ok(false);
+        }
+        
+        // Enter on a table row shows the whole row; plans keep Enter to
+        // expand operators
+        if self.focus == Focus::Results && key.code == KeyCode::Enter {
+            if let Some(detail) = self.results.row_detail() {
+                self.overlays.push(Overlay::RowDetail(detail));
+                return Ok(false);
+            }
+        }
+        
+        // B marks a tab as the base of a comparison, then compares another
+        // tab with it
+        if self.focus == Focus::Results && key.code == KeyCode::Char('B') {
+            match self.results.compare_form() {
+                Ok(form) => self.overlays.push(Overlay::Compare(form)),
+                Err(message) => self.results.message = Some(message),
+            }
+            return Ok(false);
+        }
+        
+        // z saves the current tab to a file, Shift+Z opens a saved one
+        if self.focus == Focus::Results
+            && matches!(key.code, KeyCode::Char('z') | KeyCode::Char('Z'))
+            && !key.modifiers.contains(KeyModifiers::CONTROL)
+        {
+            match self.results.result_file_prompt(key.code == KeyCode::Char('z')) {
+                Ok(prompt) => self.overlays.push(Overlay::ResultFile(prompt)),
+                Err(message) => self.results.message = Some(message),
+            }
+            return Ok(false);
+        }
+        
+        // V counts the values of the cursor column
+        if self.focus == Focus::Results && key.code == KeyCode::Char('V') {
+            match self.results.distribution() {
+                Ok(dist) => self.overlays.push(Overlay::Distribution(dist)),
+                Err(message) => self.results.message = Some(message),
+            }
+            return Ok(false);
+        }
+        
+        // G charts numeric columns of the tab
+        if self.focus == Focus::Results && key.code == KeyCode::Char('G') {
+            match self.results.chart_view() {
+                Ok(chart) => self.overlays.push(Overlay::Chart(chart)),
+                Err(message) => self.results.message = Some(message),
+            }
+            return Ok(false);
+        }
+        
+        // J shows a VARIANT / JSON cell as a foldable tree
+        if self.focus == Focus::Results && key.code == KeyCode::Char('J') {
+            match self.results.json_view() {
+                Ok(view) => self.overlays.push(Overlay::Json(view)),
+                Err(message) => self.results.message = Some(message),
+            }
+            return Ok(false);
+        }
+        
+        // Q shows the SQL the current tab came from
+        if self.focus == Focus::Results && key.code == KeyCode::Char('Q') {
+            match self.results.source_view() {
+                Ok(view) => self.overlays.push(Overlay::Source(view)),
+                Err(message) => self.results.message = Some(message),
+            }
+            return Ok(false);
+        }
+        
+        // '?' opens help from the results pane, where it isn't text input
+        if self.focus == Focus::Results && key.code == KeyCode::Char('?') {
+            self.toggle_overlay(Overlay::Help { scroll: 0 });
+            return Ok(false);
+        }
+        
+        // 'r' in the results pane runs the tab's query again in place,
+        // Shift+R into a new tab
+        if self.focus == Focus::Results
+            && matches!(key.code, KeyCode::Char('r') | KeyCode::Char('R'))
+            && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            self.rerun_query(key.code == KeyCode::Char('R'));
+            return Ok(false);
+        }
+        
+        // 'u' in the results pane starts find-and-replace on the cursor column
+        if self.focus == Focus::Results
+            && matches!(key.code, KeyCode::Char('u') | KeyCode::Char('U'))
+            && !key.modifiers.contains(KeyModifiers::CONTROL)
+        {
+            match self.results.replace_form() {
+                Ok(mut form) => {
+                    form.table = replace::guess_table(&self.get_current_query()).unwrap_or_default();
+                    self.overlays.push(Overlay::Replace(form));
+                }
+                Err(message) => self.results.message = Some(message),
+            }
+            return Ok(false);
+        }
+        
+        // 'x' in the results pane copies or exports in a chosen format
+        if self.focus == Focus::Results && matches!(key.code, KeyCode::Char('x') | KeyCode::Char('X')) {
+            match self.results.export_prompt() {
+                Ok(mut prompt) => {
+                    prompt.table_guess = replace::guess_table(&self.get_current_query()).unwrap_or_default();
+                    self.overlays.push(Overlay::Export(prompt));
+                }
+                Err(message) => self.results.message = Some(message),
+            }
+            return Ok(false);
+        }
+        
+        // 's' in the results pane profiles the cursor column
+        if self.focus == Focus::Results && matches!(key.code, KeyCode::Char('s') | KeyCode::Char('S')) {
+            match self.results.column_stats() {
+                Ok((column, stats)) => self.overlays.push(Overlay::ColumnStats { column, stats }),
+                Err(message) => self.results.message = Some(message),
+            }
+            return Ok(false);
+        }
+        
+        // 'm' in the results pane fetches the next max_rows rows of a cut off result
+        if self.focus == Focus::Results && matches!(key.code, KeyCode::Char('m') | KeyCode::Char('M')) {
+            self.fetch_more_rows();
+            return Ok(false);
+        }
+        
+        // 'c' in the results pane cancels the current tab's statement
+        if self.focus == Focus::Results
+            && matches!(key.code, KeyCode::Char('c') | KeyCode::Char('C'))
+            && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            self.cancel_tab_query();
+            return Ok(false);
+        }
+        
+        // 'p' in the results pane fetches the query profile of the current tab
+        if self.focus == Focus::Results && matches!(key.code, KeyCode::Char('p') | KeyCode::Char('P')) {
+            self.fetch_query_profile();
+            return Ok(false);
+        }
+        
+        // Route to focused pane
+        match self.focus {
+            Focus::Editor => {
+                // Get the current area where editor is drawn
+                let size = terminal.size()?;
+                let area = self.pane_area(Rect::new(0, 0, size.width, size.height));
+                
+                // Calculate the editor area (same logic as in draw)
+                let editor_percent = ((50 + self.split_offset) as u16).clamp(20, 80);
+                let results_percent = 100 - editor_percent;
+                
+                let constraints = if self.results_hidden {
+                    vec![Constraint::Percentage(100)]
+                } else {
+                    vec![
+                        Constraint::Percentage(editor_percent),
+                        Constraint::Percentage(results_percent),
+                    ]
+                };
+                
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints(constraints)
+                    .split(area);
+                
+                if !chunks.is_empty() {
+                    // Account for the border (1 char on each side)
+                    let editor_area = chunks[0];
+                    let inner_width = editor_area.width.saturating_sub(2) as usize;
+                    let inner_height = editor_area.height.saturating_sub(2) as usize;
+                    
+                    // Use texteditor's handle_editor_key directly
+                    crate::texteditor::handle_editor_key(&mut self.editor, key, inner_width, inner_height)?;
+                }
+            }
+            Focus::Results => {
+                self.results.handle_key(key);
+            }
+            Focus::DbTree => {} // Not implemented yet
+        }
+        
+        Ok(false)
+    }
+    
+    /// A click in the editor's text puts the caret there and focuses the
+    /// editor; Alt+Click adds a caret instead
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if !self.overlays.is_empty() {
+            return;
+        }
+        // The pane's inner area less the status line
+        let area = Rect { height: self.editor_area.height.saturating_sub(1), ..self.editor_area };
+        let inside = |area: Rect| {
+            (area.x..area.x + area.width).contains(&mouse.column) && (area.y..area.y + area.height).contains(&mouse.row)
+        };
+        // Shift turns the vertical wheel sideways, for terminals without a
+        // horizontal one
+        let step = self.config.scroll_step.max(1) as isize;
+        let shift = mouse.modifiers.contains(KeyModifiers::SHIFT);
+        let wheel = match mouse.kind {
+            MouseEventKind::ScrollUp if shift => Some((0, -1)),
+            MouseEventKind::ScrollDown if shift => Some((0, 1)),
+            MouseEventKind::ScrollUp => Some((-1, 0)),
+            MouseEventKind::ScrollDown => Some((1, 0)),
+            MouseEventKind::ScrollLeft => Some((0, -1)),
+            MouseEventKind::ScrollRight => Some((0, 1)),
+            _ => None,
+        };
+        if let Some((rows, cols)) = wheel {
+            if inside(area) {
+                self.editor.wheel_scroll(rows * step, cols * step, area.width as usize, area.height as usize);
+            } else if inside(self.results_area) {
+                // Grid columns are wide, so sideways goes one at a time
+                self.results.wheel_scroll(rows * step, cols);
+            }
+            return;
+        }
+        if mouse.kind != MouseEventKind::Down(MouseButton::Left) || !inside(area) {
+            return;
+        }
+        self.focus = Focus::Editor;
+        if mouse.modifiers.contains(KeyModifiers::ALT) {
+            self.editor.add_caret_at(mouse.column, mouse.row, area);
+        } else {
+            self.editor.click_at(mouse.column, mouse.row, area);
+        }
+    }
+    
+    /// Text pasted into the terminal goes into the editor in one piece. A
+    /// prompt or the results search takes its first line as typed keys.
+    fn handle_paste(&mut self, text: &str) {
+        let first_line = text.lines().next().unwrap_or_default();
+        let typed = first_line.chars().map(|c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        if !self.overlays.is_empty() {
+            let depth = self.overlays.len();
+            for key in typed {
+                self.handle_overlay_key(key);
+                if self.overlays.len() != depth {
+                    break;
+                }
+            }
+            return;
+        }
+        match self.focus {
+            Focus::Results if self.results.is_search_input_active() => {
+                for key in typed {
+                    self.results.handle_key(key);
+                }
+            }
+            Focus::Editor => {
+                // The pane's inner area less the status line
+                let width = self.editor_area.width as usize;
+                let height = self.editor_area.height.saturating_sub(1) as usize;
+                self.editor.paste_text(text, width, height);
+            }
+            _ => {}
+        }
+    }
+    
+    fn poll_db_responses(&mut self) {
+        while let Ok(response) = self.db_resp_rx.try_recv() {
+            self.status_changed = true;
+            match response {
+                DbWorkerResponse::Connected => {
+                    self.connected = true;
+                    if self.config.metadata_max_age_secs > 0 {
+                        let _ = self.db_req_tx.send(DbWorkerRequest::ListMetadataDatabases);
+                    }
+                }
+                DbWorkerResponse::ConnectionFailed { message } => {
+                    self.results.message = Some(format!("Connection failed: {}", message));
+                }
+                DbWorkerResponse::KeepaliveFailed { message } => {
+                    self.results.message = Some(format!("Keepalive failed, the session may have expired: {}", message));
+                }
+                DbWorkerResponse::QueryStarted { query_idx, started, request } => {
+                    self.running = true;
+                    // The worker closes the cursor of a cut off result when a new batch starts
+                    for tab in &mut self.results.tabs {
+                        tab.resumable = false;
+                    }
+                    self.run_started = Some(started);
+                    let session_context = self.editor.status_context.clone();
+                    // Statements of a batch start in the tabs queued for them;
+                    // a parallel batch leaves the shown tab alone
+                    let queued = self.batch_tabs.get(query_idx).and_then(|&id| self.results.start_queued(id, started));
+                    match queued {
+                        Some(idx) => {
+                            self.results.tabs[idx].session_context = session_context;
+                            self.results.tabs[idx].request = Some(request);
+                            if !self.batch_parallel {
+                                self.results.tab_idx = idx;
+                            }
+                        }
+                        None => {
+                            let mut tab = ResultsTab::new_pending_with_start(request.context.clone(), started);
+                            tab.session_context = session_context;
+                            tab.request = Some(request);
+                            tab.batch_idx = Some(query_idx);
+                            if self.batch_tabs.len() <= query_idx {
+                                self.batch_tabs.resize(query_idx + 1, 0);
+                            }
+                            self.batch_tabs[query_idx] = tab.id;
+                            self.results.tabs.push(tab);
+                            self.results.tab_idx = self.results.tabs.len() - 1;
+                        }
+                    }
+                }
+                DbWorkerResponse::QueryFinished { query_idx, elapsed, query_id, result, truncated } => {
+                    if let Some(statement) = self.pending_statement.take() {
+                        match &result {
+                            ResultsContent::Plan(plan) => {
+                                if let Some(bytes) = plan.bytes_assigned {
+                                    self.costs.record_bytes(&statement, bytes);
+                                }
+                            }
+                            _ => self.costs.record_run(&statement, elapsed),
+                        }
+                    }
+                    let idx = self.results.add_tab_result(self.batch_tabs.get(query_idx).copied(), result);
+                    self.results.tabs[idx].query_id = query_id;
+                    self.results.tabs[idx].elapsed = Some(elapsed);
+                    self.results.tabs[idx].truncated = truncated;
+                    // Pool connections close with a parallel batch
+                    self.results.tabs[idx].resumable = truncated && !self.batch_parallel;
+                    // Still running while statements of the batch are queued or running
+                    self.running = self.results.has_unfinished();
+                    self.focus = Focus::Results;
+                }
+                DbWorkerResponse::QueryError { query_idx, elapsed, query_id, message } => {
+                    self.pending_statement = None;
+                    self.run_duration = Some(elapsed);
+                    self.error = Some(message.clone());
+                    let location = self.mark_error_position(query_idx, &message)
+                        .map(|(line, column)| format!(" at line {}, column {}", line + 1, column + 1))
+                        .unwrap_or_default();
+                    let total = self.error_origins.len();
+                    if total > 1 {
+                        let skipped = if query_idx + 1 < total && !self.batch_parallel { "; the rest were not run" } else { "" };
+                        self.results.message = Some(format!("Statement {} of {} failed{}{}", query_idx + 1, total, location, skipped));
+                    } else if !location.is_empty() {
+                        self.results.message = Some(format!("Error{} (marked in the editor)", location));
+                    }
+                    let idx = self.results.add_tab_result(self.batch_tabs.get(query_idx).copied(), ResultsContent::Error {
+                        message,
+                        cursor: 0,
+                        selection: None,
+                    });
+                    self.results.tabs[idx].query_id = query_id;
+                    self.results.tabs[idx].elapsed = Some(elapsed);
+                    // Statements of a parallel batch don't depend on each other
+                    if !self.batch_parallel {
+                        self.results.drop_queued("Not run: an earlier statement failed");
+                    }
+                    self.running = self.results.has_unfinished();
+                }
+                DbWorkerResponse::QueryCancelled { query_idx, elapsed } => {
+                    self.pending_statement = None;
+                    self.run_duration = Some(elapsed);
+                    self.results.add_tab_result(self.batch_tabs.get(query_idx).copied(), ResultsContent::Cancelled { elapsed });
+                    // One statement of a parallel batch can be cancelled on its own
+                    if !self.batch_parallel {
+                        self.results.drop_queued("Not run: the batch was cancelled");
+                    }
+                    self.running = self.results.has_unfinished();
+                }
+                DbWorkerResponse::TableStats { database, schema, stats } => {
+                    self.table_stats.insert((database, schema), stats);
+                }
+                DbWorkerResponse::ContextOptions { kind, names } => {
+                    let names = names.unwrap_or_else(|message| {
+                        self.results.message = Some(message);
+                        Vec::new()
+                    });
+                    // The switcher may have been closed meanwhile
+                    for overlay in &mut self.overlays {
+                        if let Overlay::Switcher(switcher) = overlay {
+                            switcher.set_options(kind, names.clone());
+                        }
+                    }
+                }
+                DbWorkerResponse::SessionContext(context) => {
+                    self.session_offset = context.offset();
+                    if let Some(client) = &context.client {
+                        for overlay in &mut self.overlays {
+                            if let Overlay::Health(report) = overlay {
+                                report.set_driver_version(client);
+                            }
+                        }
+                    }
+                    self.editor.status_context = Some(context.summary());
+                    self.session_client = context.client;
+                    self.session_database = context.database;
+                    self.session_schema = context.schema;
+                }
+                DbWorkerResponse::ContextSwitchFailed { message } => {
+                    self.results.message = Some(message);
+                }
+                DbWorkerResponse::MoreRows { rows, more, error } => {
+                    self.running = false;
+                    let Some(tab) = self.results.tabs.iter_mut().find(|t| t.resumable) else { continue };
+                    let count = rows.len();
+                    self.results.message = Some(match (tab.append_rows(rows), error) {
+                        (Ok(dropped), error) => {
+                            let mut message = format!("Fetched {} more rows", count);
+                            if let Some(error) = error {
+                                let _ = write!(message, ", then fetching failed: {}", error);
+                            }
+                            if dropped {
+                                message.push_str("; the sort / filter doesn't cover them, so all rows show again");
+                            }
+                            message
+                        }
+                        (Err(e), _) => format!("Storing fetched rows failed: {}", e),
+                    });
+                    tab.truncated = more;
+                    tab.resumable = more;
+                }
+                DbWorkerResponse::Stages(stages) => {
+                    if let Some(browser) = self.stage_browser() {
+                        browser.set_stages(stages);
+                    }
+                }
+                DbWorkerResponse::StageFiles { stage, files } => {
+                    if let Some(browser) = self.stage_browser() {
+                        browser.set_files(&stage, files);
+                    }
+                }
+                DbWorkerResponse::StageProgress { message } => match self.stage_browser() {
+                    Some(browser) => browser.status = Some(message),
+                    None => self.results.message = Some(message),
+                },
+                DbWorkerResponse::StageTransferDone { result } => {
+                    let status = result.unwrap_or_else(|e| format!("Transfer failed: {}", e));
+                    match self.stage_browser() {
+                        Some(browser) => browser.transfer_done(status),
+                        None => self.results.message = Some(status),
+                    }
+                    self.send_stage_requests();
+                }
+                DbWorkerResponse::Ddl { name, ddl } => {
+                    for overlay in &mut self.overlays {
+                        if let Overlay::Ddl(view) = overlay {
+                            if view.name == name && view.ddl.is_none() {
+                                view.ddl = Some(ddl.clone());
+                            }
+                        }
+                    }
+                }
+                DbWorkerResponse::MetadataDatabases(names) => {
+                    let force = std::mem::take(&mut self.metadata_force);
+                    let names = match names {
+                        Ok(names) => names,
+                        Err(e) => {
+                            if force {
+                                self.results.message = Some(format!("Refreshing object names failed: {}", e));
+                            }
+                            continue;
+                        }
+                    };
+                    self.metadata.retain_databases(&names);
+                    let targets = if force {
+                        names
+                    } else {
+                        self.metadata.stale(&names, self.config.metadata_max_age_secs)
+                    };
+                    for database in targets {
+                        let _ = self.db_req_tx.send(DbWorkerRequest::FetchMetadata(database));
+                    }
+                    self.metadata.save();
+                }
+                DbWorkerResponse::Metadata { database, columns } => {
+                    // Databases the role can list but not read are left as they were
+                    if let Ok(columns) = columns {
+                        self.metadata.set_database(&database, columns);
+                        self.metadata.save();
+                    }
+                }
+                DbWorkerResponse::CostEstimate { statement, bytes } => {
+                    if let Some(bytes) = bytes {
+                        self.costs.record_bytes(&statement, bytes);
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Offer cached names completing the one before the caret; a single
+    /// match is inserted right away
+    fn autocomplete(&mut self) {
+        let (parts, partial, replace_len) = complete::split_prefix(&self.editor.line_before_caret());
+        let names = self.metadata.completions(
+            &parts,
+            &partial,
+            self.session_database.as_deref(),
+            self.session_schema.as_deref(),
+        );
+        match names.as_slice() {
+            [] if self.metadata.databases.is_empty() => {
+                self.results.message = Some("No object names cached yet; Shift+F8 fetches them".to_string());
+            }
+            [] => self.results.message = Some(format!("No completions for '{}'", partial)),
+            [name] => {
+                let width = self.editor_viewport_width();
+                self.editor.insert_identifier(replace_len, &[name.as_str()], self.config.identifier_quoting, width);
+            }
+            _ => self.overlays.push(Overlay::Completion(CompletionList::new(names, replace_len))),
+        }
+    }
+    
+    fn run_query(&mut self) {
+        if self.running || !self.connected {
+            return;
+        }
+        
+        let query = self.get_current_query();
+        if query.is_empty() {
+            return;
+        }
+        
+        if self.hold_prod_writes(&query, ConfirmAction::RunProdWrites(query.clone())) {
+            return;
+        }
+        self.start_query(query);
+    }
+    
+    /// On a production profile, refuse `sql` if it writes without
+    /// --allow-prod-writes, or ask before running it with `action`. True
+    /// when the run has to wait.
+    fn hold_prod_writes(&mut self, sql: &str, action: ConfirmAction) -> bool {
+        if !self.config.is_production() {
+            return false;
+        }
+        let writes = guard::write_keywords(sql, self.config.sql_dialect());
+        if writes.is_empty() {
+            return false;
+        }
+        if self.config.prod_writes_need_flag && !self.config.allow_prod_writes {
+            self.results.message = Some(format!(
+                "{} refused: writes to production need --allow-prod-writes",
+                writes.join(", "),
+            ));
+            return true;
+        }
+        self.overlays.push(Overlay::Confirm {
+            title: " Production ".to_string(),
+            message: format!(
+                "This runs {} against {}.\n\nRun it? (y/n)",
+                writes.join(", "),
+                self.config.environment.as_deref().unwrap_or("production"),
+            ),
+            action,
+        });
+        true
+    }
+    
+    /// Run the current tab's statement again, exactly as it was sent, in
+    /// place of its results or in a new tab
+    fn rerun_query(&mut self, new_tab: bool) {
+        if self.running {
+            self.results.message = Some("Wait for the running query to finish".to_string());
+            return;
+        }
+        if !self.connected {
+            self.results.message = Some("Not connected".to_string());
+            return;
+        }
+        let sql = match self.results.tabs.get(self.results.tab_idx).and_then(|tab| tab.request.as_ref()) {
+            Some(request) => request.sql.clone(),
+            None => {
+                self.results.message = Some("This tab didn't come from a query".to_string());
+                return;
+            }
+        };
+        if self.hold_prod_writes(&sql, ConfirmAction::RerunProdWrites { new_tab }) {
+            return;
+        }
+        self.start_rerun(new_tab);
+    }
+    
+    fn start_rerun(&mut self, new_tab: bool) {
+        if self.running || !self.connected {
+            return;
+        }
+        match self.results.rerun(new_tab) {
+            Ok(request) => {
+                // The editor may have changed since, so errors aren't marked in it
+                self.error_origins.clear();
+                self.batch_tabs = vec![self.results.tabs[self.results.tab_idx].id];
+                self.pending_statement = None;
+                self.batch_parallel = false;
+                self.focus = Focus::Results;
+                let _ = self.db_req_tx.send(DbWorkerRequest::RunQueries(vec![request]));
+            }
+            Err(message) => self.results.message = Some(message),
+        }
+    }
+    
+    /// Send the batch to the worker statement by statement, or as one
+    /// EXECUTE IMMEDIATE block with the `script` directive
+    fn start_query(&mut self, query: String) {
+        if self.running || !self.connected {
+            return;
+        }
+        
+        let directives = match Directives::parse(&query) {
+            Ok(d) => d,
+            Err(message) => {
+                self.results.add_result(ResultsContent::Error { message, cursor: 0, selection: None });
+                self.focus = Focus::Results;
+                return;
+            }
+        };
+        
+        // Only a single statement's run time says anything about that statement
+        let statements = cost::split_statements(&query, self.config.sql_dialect());
+        self.pending_statement = match statements.as_slice() {
+            [(_, statement)] => Some(statement.to_string()),
+            _ => None,
+        };
+        
+        let timeout = self.config.query_timeout(directives.timeout_secs);
+        let batch = match QueryRequest::batch(&query, &directives, timeout, self.config.sql_dialect()) {
+            Ok(batch) if batch.is_empty() => return,
+            Ok(batch) => batch,
+            Err(message) => {
+                self.results.add_result(ResultsContent::Error { message, cursor: 0, selection: None });
+                self.focus = Focus::Results;
+                return;
+            }
+        };
+        
+        let origin = match self.editor.get_selection_range() {
+            Some((start, _)) if self.editor.has_selection() => start,
+            _ => 0,
+        };
+        let wrapper_lines = if directives.script && self.config.sql_dialect() == Dialect::Snowflake {
+            SCRIPT_WRAPPER_LINES
+        } else {
+            0
+        };
+        self.error_origins = batch.iter().map(|(offset, _)| (origin + offset, wrapper_lines)).collect();
+        self.editor.error_span = None;
+        
+        // Every statement gets its tab up front; the worker runs them in
+        // order, or several at once for a parallel batch
+        self.batch_tabs.clear();
+        for (i, (_, request)) in batch.iter().enumerate() {
+            let tab = ResultsTab::new_queued(request.context.clone(), i);
+            self.batch_tabs.push(tab.id);
+            self.results.tabs.push(tab);
+        }
+        self.results.tab_idx = self.results.tabs.len() - batch.len();
+        self.focus = Focus::Results;
+        
+        self.batch_parallel = directives.parallel && !directives.script && batch.len() > 1;
+        let queries = batch.into_iter().map(|(_, request)| request).collect();
+        let _ = self.db_req_tx.send(if self.batch_parallel {
+            DbWorkerRequest::RunParallel { queries, max_parallel: self.config.max_parallel_queries }
+        } else {
+            DbWorkerRequest::RunQueries(queries)
+        });
+    }
+    
+    /// Underline the spot a query error points at in the editor, when the
+    /// message carries a line and position and the run came from the
+    /// editor. Returns the 0-based line and column marked.
+    fn mark_error_position(&mut self, query_idx: usize, message: &str) -> Option<(usize, usize)> {
+        let &(origin, wrapper_lines) = self.error_origins.get(query_idx)?;
+        let pos = error_pos::parse(message, wrapper_lines)?;
+        let rope = &self.editor.rope;
+        if origin > rope.len_bytes() {
+            return None;
+        }
+        let line = rope.byte_to_line(origin) + pos.line;
+        if line >= rope.len_lines() {
+            return None;
+        }
+        // Positions on the first line count from where the run text starts
+        let column = if pos.line == 0 {
+            rope.byte_to_char(origin) - rope.line_to_char(line) + pos.column
+        } else {
+            pos.column
+        };
+        let text = rope.line(line).to_string();
+        let (start, first) = text.char_indices().nth(column)?;
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let end = if is_word(first) {
+            text[start..].find(|c: char| !is_word(c)).map_or(text.len(), |n| start + n)
+        } else {
+            start + first.len_utf8()
+        };
+        let line_start = rope.line_to_byte(line);
+        self.editor.error_span = Some((line_start + start, line_start + end));
+        Some((line, column))
+    }
+    
+    /// Run `EXPLAIN USING <format>` for the selection or buffer and show the
+    /// plan in its own tab. The statement is explained as-is, not wrapped in
+    /// EXECUTE IMMEDIATE, so it must be a single statement.
+    fn explain_query(&mut self, format: PlanFormat) {
+        if self.running || !self.connected {
+            return;
+        }
+        
+        let dialect = self.config.sql_dialect();
+        if dialect != Dialect::Snowflake {
+            self.results.message = Some(dialect.unsupported("EXPLAIN"));
+            return;
+        }
+        
+        let query = self.get_current_query();
+        let statement = query.trim().trim_end_matches(';').trim_end();
+        if statement.is_empty() {
+            return;
+        }
+        
+        let directives = match Directives::parse(statement) {
+            Ok(d) => d,
+            Err(message) => {
+                self.results.add_result(ResultsContent::Error { message, cursor: 0, selection: None });
+                self.focus = Focus::Results;
+                return;
+            }
+        };
+        
+        self.pending_statement = Some(statement.to_string());
+        self.error_origins.clear();
+        self.batch_tabs.clear();
+        let _ = self.db_req_tx.send(DbWorkerRequest::RunQueries(vec![QueryRequest {
+            sql: format!("EXPLAIN USING {}\n{}", format.keyword(), statement),
+            context: "EXPLAIN".to_string(),
+            warehouse: directives.warehouse,
+            explain: Some(format),
+            timeout: self.config.query_timeout(directives.timeout_secs),
+        }]));
+    }
+    
+    /// EXPLAIN every statement in the buffer in the background to refresh
+    /// the scanned-bytes annotations
+    fn refresh_costs(&mut self) {
+        if !self.connected || self.config.sql_dialect() != Dialect::Snowflake {
+            return;
+        }
+        let text = self.editor.rope.to_string();
+        let statements: Vec<String> = cost::split_statements(&text, Dialect::Snowflake)
+            .into_iter()
+            .map(|(_, statement)| statement.to_string())
+            .collect();
+        if statements.is_empty() {
+            return;
+        }
+        self.results.message = Some(format!("Estimating cost of {} statement(s)...", statements.len()));
+        let _ = self.db_req_tx.send(DbWorkerRequest::EstimateCosts(statements));
+    }
+    
+    /// Resume the current tab's result where `max_rows` cut it off
+    fn fetch_more_rows(&mut self) {
+        if self.running || !self.connected {
+            return;
+        }
+        let Some(tab) = self.results.tabs.get(self.results.tab_idx) else { return };
+        if !tab.resumable {
+            self.results.message = Some(if tab.truncated {
+                "The rest of this result is no longer available; run the query again".to_string()
+            } else {
+                "This result is complete".to_string()
+            });
+            return;
+        }
+        self.running = true;
+        self.run_started = Some(Instant::now());
+        self.results.message = Some(format!("Fetching up to {} more rows...", self.config.max_rows));
+        let _ = self.db_req_tx.send(DbWorkerRequest::FetchMore(self.config.max_rows));
+    }
+    
+    /// Open the operator stats of the current tab's query (its Snowsight
+    /// query profile) in a new tab
+    fn fetch_query_profile(&mut self) {
+        let Some(id) = self.results.current_query_id().map(|id| id.to_string()) else {
+            self.results.message = Some("No query ID for this tab".to_string());
+            return;
+        };
+        if self.running || !self.connected {
+            return;
+        }
+        let dialect = self.config.sql_dialect();
+        if dialect != Dialect::Snowflake {
+            self.results.message = Some(dialect.unsupported("The query profile"));
+            return;
+        }
+        // Query IDs are UUIDs; anything else is not spliced into SQL
+        if !id.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+            self.results.message = Some(format!("Unexpected query ID '{}'", id));
+            return;
+        }
+        self.pending_statement = None;
+        self.batch_tabs.clear();
+        let _ = self.db_req_tx.send(DbWorkerRequest::RunQueries(vec![QueryRequest {
+            sql: format!("SELECT * FROM TABLE(GET_QUERY_OPERATOR_STATS('{}'))", id),
+            context: format!("Profile {}", id),
+            warehouse: None,
+            explain: None,
+            timeout: self.config.query_timeout(None),
+        }]));
+    }
+    
+    /// Open the first rows of the table under the caret in a new tab, or
+    /// with `describe` its column names, types and nullability
+    fn preview_table(&mut self, describe: bool) {
+        if self.running || !self.connected {
+            return;
+        }
+        let Some(name) = self.editor.identifier_at_caret() else {
+            self.results.message = Some("Put the caret on a table name".to_string());
+            return;
+        };
+        // The name is spliced into SQL, so only identifier characters pass
+        let is_name = |c: char| c.is_alphanumeric() || matches!(c, '_' | '$' | '.' | '"');
+        if !name.chars().all(is_name) || name.contains("$$") {
+            self.results.message = Some(format!("'{}' is not a table name", name));
+            return;
+        }
+        let dialect = self.config.sql_dialect();
+        let (sql, context) = if describe {
+            (catalog::describe_sql(&name, dialect), format!("Describe {}", name))
+        } else {
+            (catalog::preview_sql(&name, 100, dialect), format!("Preview {}", name))
+        };
+        self.pending_statement = None;
+        self.error_origins.clear();
+        self.batch_tabs.clear();
+        let _ = self.db_req_tx.send(DbWorkerRequest::RunQueries(vec![QueryRequest {
+            sql,
+            context,
+            warehouse: None,
+            explain: None,
+            timeout: self.config.query_timeout(None),
+        }]));
+        self.focus = Focus::Results;
+    }
+    
+    /// Ask the worker for row counts of a schema's tables, once per schema.
+    /// Runs after any queued queries; the tree shows names until it arrives.
+    pub fn request_table_stats(&mut self, database: &str, schema: &str) {
+        let key = (database.to_string(), schema.to_string());
+        if !self.connected || self.table_stats.contains_key(&key) {
+            return;
+        }
+        self.table_stats.insert(key, Vec::new());
+        let _ = self.db_req_tx.send(DbWorkerRequest::FetchTableStats {
+            database: database.to_string(),
+            schema: schema.to_string(),
+        });
+    }
+    
+    /// Dimmed label for a table in the object tree, if its stats are loaded
+    pub fn table_annotation(&self, database: &str, schema: &str, table: &str) -> Option<String> {
+        self.table_stats.get(&(database.to_string(), schema.to_string()))?
+            .iter()
+            .find(|t| t.name == table)
+            .map(|t| t.annotation())
+            .filter(|a| !a.is_empty())
+    }
+    
+    fn cancel_query(&mut self) {
+        if self.running {
+            self.cancel.cancel();
+            self.results.mark_cancelling();
+            if self.batch_parallel {
+                self.results.drop_queued("Not run: the batch was cancelled");
+            }
+        }
+    }
+    
+    /// Cancel the statement of the current tab; in a parallel batch the
+    /// others keep running
+    fn cancel_tab_query(&mut self) {
+        // With no statement of its own running, c stops a background export
+        let tab_busy = self.results.tabs.get(self.results.tab_idx).is_some_and(|tab| tab.running || tab.queued);
+        if !tab_busy && self.results.cancel_export() {
+            return;
+        }
+        let Some(tab) = self.results.tabs.get_mut(self.results.tab_idx) else { return };
+        if tab.queued {
+            self.results.message = Some("This statement has not started; Shift+F5 drops the queue".to_string());
+            return;
+        }
+        if !tab.running {
+            return;
+        }
+        match tab.batch_idx {
+            Some(idx) if self.batch_parallel => {
+                if self.cancel.cancel_statement(idx) {
+                    tab.cancelling = true;
+                }
+            }
+            _ => self.cancel_query(),
         }
     }
     
@@ -0,0 +1,130 @@
+use crate::export::{parse_bool, parse_double, ColumnKind};
+use crate::tile_rowstore::NULL_SENTINEL;
+use rust_xlsxwriter::{Color, Format, FormatBorder, Workbook, Worksheet};
+use std::{io, path::Path};
+
+/// Rows a worksheet holds, header included
+const MAX_SHEET_ROWS: usize = 1_048_576;
+
+/// Columns a worksheet holds
+const MAX_SHEET_COLS: usize = 16_384;
+
+/// Characters a cell holds
+const MAX_CELL_CHARS: usize = 32_767;
+
+/// Sheet names longer than this are rejected by Excel
+const MAX_SHEET_NAME: usize = 31;
+
+/// A workbook written a sheet at a time; sheets stream their rows to temp
+/// files, so large results don't have to fit in memory
+pub struct XlsxWriter {
+    workbook: Workbook,
+    header: Format,
+}
+
+impl XlsxWriter {
+    pub fn new() -> Self {
+        let header = Format::new()
+            .set_bold()
+            .set_background_color(Color::RGB(0xD9E1F2))
+            .set_border_bottom(FormatBorder::Thin);
+        Self { workbook: Workbook::new(), header }
+    }
+
+    /// Add a sheet with a frozen, bold header row. `widths` are in
+    /// characters, per header; columns past Excel's limit are left out.
+    pub fn add_sheet<'a>(
+        &'a mut self,
+        name: &str,
+        headers: &[String],
+        kinds: &'a [ColumnKind],
+        widths: &[usize],
+    ) -> io::Result<SheetWriter<'a>> {
+        let sheet = self.workbook.add_worksheet_with_constant_memory();
+        sheet.set_name(sheet_name(name)).map_err(io::Error::other)?;
+        let ncols = headers.len().min(MAX_SHEET_COLS);
+        for (col, width) in widths.iter().take(ncols).enumerate() {
+            sheet.set_column_width(col as u16, *width as f64 + 2.0).map_err(io::Error::other)?;
+        }
+        for (col, header) in headers.iter().take(ncols).enumerate() {
+            sheet.write_string_with_format(0, col as u16, cell_text(header), &self.header).map_err(io::Error::other)?;
+        }
+        sheet.set_freeze_panes(1, 0).map_err(io::Error::other)?;
+        Ok(SheetWriter { sheet, kinds, ncols, rows_written: 0, truncated: false })
+    }
+
+    pub fn save(mut self, path: &Path) -> io::Result<()> {
+        self.workbook.save(path).map_err(io::Error::other)
+    }
+}
+
+/// Rows of one sheet; numbers and booleans are written as such where the
+/// column's kind says so and the value converts without loss
+pub struct SheetWriter<'a> {
+    sheet: &'a mut Worksheet,
+    kinds: &'a [ColumnKind],
+    ncols: usize,
+    rows_written: usize,
+    /// Rows were left out at Excel's row limit
+    pub truncated: bool,
+}
+
+impl SheetWriter<'_> {
+    /// Whether the sheet has reached Excel's row limit
+    pub fn is_full(&self) -> bool {
+        self.rows_written + 1 >= MAX_SHEET_ROWS
+    }
+
+    pub fn write_rows(&mut self, rows: &[Vec<String>]) -> io::Result<()> {
+        for row in rows {
+            if self.is_full() {
+                self.truncated = true;
+                return Ok(());
+            }
+            let r = (self.rows_written + 1) as u32;
+            for (col, value) in row.iter().take(self.ncols).enumerate() {
+                if value == NULL_SENTINEL {
+                    continue;
+                }
+                let c = col as u16;
+                let kind = self.kinds.get(col).copied().unwrap_or(ColumnKind::Text);
+                let written = match (kind, parse_double(value), parse_bool(value)) {
+                    (ColumnKind::Number, Some(n), _) => self.sheet.write_number(r, c, n),
+                    (ColumnKind::Boolean, _, Some(b)) => self.sheet.write_boolean(r, c, b),
+                    _ => self.sheet.write_string(r, c, cell_text(value)),
+                };
+                written.map_err(io::Error::other)?;
+            }
+            self.rows_written += 1;
+        }
+        Ok(())
+    }
+
+    /// Put a filter on the header row; returns the number of rows written
+    pub fn finish(self) -> io::Result<usize> {
+        if self.ncols > 0 {
+            self.sheet
+                .autofilter(0, 0, self.rows_written as u32, (self.ncols - 1) as u16)
+                .map_err(io::Error::other)?;
+        }
+        Ok(self.rows_written)
+    }
+}
+
+/// `name` without the characters Excel forbids in sheet names, cut to its
+/// length limit
+fn sheet_name(name: &str) -> String {
+    let cleaned: String = name.chars()
+        .map(|c| if matches!(c, '[' | ']' | ':' | '*' | '?' | '/' | '\\') { '_' } else { c })
+        .take(MAX_SHEET_NAME)
+        .collect();
+    cleaned.trim_matches('\'').to_string()
+}
+
+/// `value` cut to what a cell holds
+fn cell_text(value: &str) -> &str {
+    match value.char_indices().nth(MAX_CELL_CHARS) {
+        Some((i, _)) => &value[..i],
+        None => value,
+    }
+}
@@ -0,0 +1,111 @@
+use minimal_frost::{start_db_worker, CancelHandle, DbWorkerRequest, DbWorkerResponse, FakeExecutor, QueryRequest, RequestSender};
+use std::{
+    sync::mpsc::Receiver,
+    time::Duration,
+};
+
+const WAIT: Duration = Duration::from_secs(5);
+
+fn request(sql: &str) -> QueryRequest {
+    QueryRequest {
+        sql: sql.to_string(),
+        context: sql.to_string(),
+        warehouse: None,
+        explain: None,
+        timeout: None,
+    }
+}
+
+/// A worker serving `executor`, past its connect-time responses
+fn start(executor: FakeExecutor) -> (RequestSender, Receiver<DbWorkerResponse>, CancelHandle) {
+    let (requests, responses, cancel) = start_db_worker(Box::new(executor));
+    assert!(matches!(responses.recv_timeout(WAIT), Ok(DbWorkerResponse::Connected)));
+    assert!(matches!(responses.recv_timeout(WAIT), Ok(DbWorkerResponse::SessionContext(_))));
+    (requests, responses, cancel)
+}
+
+/// Responses to a batch, up to the session context sent after it
+fn batch_responses(responses: &Receiver<DbWorkerResponse>) -> Vec<DbWorkerResponse> {
+    let mut batch = Vec::new();
+    loop {
+        match responses.recv_timeout(WAIT).expect("the batch to finish") {
+            DbWorkerResponse::SessionContext(_) => return batch,
+            response => batch.push(response),
+        }
+    }
+}
+
+#[test]
+fn batch_runs_in_order() {
+    let executor = FakeExecutor::new()
+        .with_rows("SELECT id, name FROM t", &["ID", "NAME"], &[&["1", "a"], &["2", "b"]])
+        .with_message("CREATE TABLE t2 (id NUMBER)", "Table T2 successfully created.");
+    let (requests, responses, _cancel) = start(executor);
+    requests.send(DbWorkerRequest::RunQueries(vec![
+        request("select id, name from t;"),
+        request("CREATE TABLE t2 (id NUMBER)"),
+    ])).unwrap();
+
+    let batch = batch_responses(&responses);
+    assert_eq!(batch.len(), 4, "{:?}", batch);
+    assert!(matches!(&batch[0], DbWorkerResponse::QueryStarted { query_idx: 0, request, .. } if request.sql == "select id, name from t;"));
+    match &batch[1] {
+        DbWorkerResponse::QueryFinished { query_idx: 0, result, truncated: false, .. } => {
+            let result = format!("{:?}", result);
+            assert!(result.starts_with("Table"), "{}", result);
+            assert!(result.contains("nrows: 2"), "{}", result);
+        }
+        other => panic!("expected the first result, got {:?}", other),
+    }
+    assert!(matches!(&batch[2], DbWorkerResponse::QueryStarted { query_idx: 1, .. }));
+    match &batch[3] {
+        DbWorkerResponse::QueryFinished { query_idx: 1, result, .. } => {
+            assert!(format!("{:?}", result).contains("Table T2 successfully created."));
+        }
+        other => panic!("expected the second result, got {:?}", other),
+    }
+}
+
+#[test]
+fn error_stops_the_batch() {
+    let executor = FakeExecutor::new()
+        .with_error("SELECT * FROM missing", "Object 'MISSING' does not exist")
+        .with_rows("SELECT 1", &["1"], &[&["1"]]);
+    let (requests, responses, _cancel) = start(executor);
+    requests.send(DbWorkerRequest::RunQueries(vec![request("SELECT * FROM missing"), request("SELECT 1")])).unwrap();
+
+    let batch = batch_responses(&responses);
+    assert_eq!(batch.len(), 2, "{:?}", batch);
+    assert!(matches!(&batch[0], DbWorkerResponse::QueryStarted { query_idx: 0, .. }));
+    assert!(matches!(
+        &batch[1],
+        DbWorkerResponse::QueryError { query_idx: 0, message, .. } if message == "Object 'MISSING' does not exist"
+    ));
+}
+
+#[test]
+fn unknown_statement_is_an_error() {
+    let (requests, responses, _cancel) = start(FakeExecutor::new());
+    requests.send(DbWorkerRequest::RunQueries(vec![request("SELECT 2")])).unwrap();
+
+    let batch = batch_responses(&responses);
+    assert!(matches!(
+        batch.last(),
+        Some(DbWorkerResponse::QueryError { message, .. }) if message == "No canned result for: SELECT 2"
+    ));
+}
+
+#[test]
+fn cancel_stops_the_running_statement() {
+    let executor = FakeExecutor::new()
+        .with_rows("SELECT 1", &["1"], &[&["1"]])
+        .with_latency(Duration::from_secs(30));
+    let (requests, responses, cancel) = start(executor);
+    requests.send(DbWorkerRequest::RunQueries(vec![request("SELECT 1"), request("SELECT 1")])).unwrap();
+
+    assert!(matches!(responses.recv_timeout(WAIT), Ok(DbWorkerResponse::QueryStarted { query_idx: 0, .. })));
+    cancel.cancel();
+    let batch = batch_responses(&responses);
+    assert_eq!(batch.len(), 1, "{:?}", batch);
+    assert!(matches!(&batch[0], DbWorkerResponse::QueryCancelled { query_idx: 0, .. }));
+}
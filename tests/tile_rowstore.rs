@@ -0,0 +1,75 @@
+use arrow_schema::DataType;
+use minimal_frost::{ColumnKind, TileCompression, TileRowStore, TileStoreOptions};
+
+const NULL: &str = "<Frost-NULL>";
+
+fn headers() -> Vec<String> {
+    ["id", "name", "status", "amount", "active"].iter().map(|h| h.to_string()).collect()
+}
+
+const KINDS: [ColumnKind; 5] = [ColumnKind::Number, ColumnKind::Text, ColumnKind::Text, ColumnKind::Number, ColumnKind::Boolean];
+
+/// Row `i`: row 1500 has an amount that doesn't print back from a double,
+/// row 2200 a boolean spelled 1
+fn row(i: usize) -> Vec<String> {
+    vec![
+        i.to_string(),
+        if i.is_multiple_of(7) { NULL.to_string() } else { format!("name {}", i) },
+        if i.is_multiple_of(3) { NULL.to_string() } else { ["OPEN", "CLOSED"][i % 2].to_string() },
+        match i {
+            1500 => "1.50".to_string(),
+            _ if i.is_multiple_of(5) => NULL.to_string(),
+            _ => format!("{}.25", i),
+        },
+        if i == 2200 { "1".to_string() } else { ["true", "false"][i % 2].to_string() },
+    ]
+}
+
+fn on_file(compression: TileCompression) -> TileStoreOptions {
+    TileStoreOptions { memory_rows: 0, compression, ..TileStoreOptions::default() }
+}
+
+fn round_trip(options: TileStoreOptions) {
+    let rows: Vec<Vec<String>> = (0..2500).map(row).collect();
+    let mut store = TileRowStore::from_rows(&headers(), &KINDS, rows.clone().into_iter(), options).unwrap();
+    assert_eq!(store.get_rows(0, 2500).unwrap(), rows);
+    assert_eq!(store.get_rows(990, 20).unwrap(), rows[990..1010].to_vec());
+
+    let more: Vec<Vec<String>> = (2500..3700).map(row).collect();
+    store.append_rows(more.clone()).unwrap();
+    let all: Vec<Vec<String>> = rows.into_iter().chain(more).collect();
+    assert_eq!(store.nrows, 3700);
+    assert_eq!(store.get_rows(0, 3700).unwrap(), all);
+
+    let mut reader = store.reader().unwrap();
+    let read: Vec<Vec<String>> = (0..reader.tile_count()).flat_map(|t| reader.read_tile(t).unwrap()).collect();
+    assert_eq!(read, all);
+}
+
+#[test]
+fn rows_read_back_as_written() {
+    round_trip(TileStoreOptions::default());
+    round_trip(on_file(TileCompression::None));
+    round_trip(on_file(TileCompression::Lz4));
+    round_trip(on_file(TileCompression::Zstd));
+    // Outgrows memory on the append
+    round_trip(TileStoreOptions { memory_rows: 3000, ..TileStoreOptions::default() });
+}
+
+#[test]
+fn columns_are_typed_where_every_value_prints_back() {
+    let rows = (0..2500).map(row);
+    let store = TileRowStore::from_rows(&headers(), &KINDS, rows, on_file(TileCompression::Lz4)).unwrap();
+    let mut reader = store.reader().unwrap();
+    let types = |batch: arrow_array::RecordBatch| -> Vec<DataType> {
+        batch.columns().iter().map(|column| column.data_type().clone()).collect()
+    };
+    let dictionary = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+
+    let first = types(reader.read_batch(0).unwrap());
+    assert_eq!(first, [DataType::Int64, DataType::Utf8, dictionary.clone(), DataType::Float64, DataType::Boolean]);
+    // 1.50 would come back as 1.5
+    assert_eq!(types(reader.read_batch(1).unwrap())[3], DataType::Utf8);
+    // 1 would come back as true
+    assert_eq!(types(reader.read_batch(2).unwrap())[4], dictionary);
+}
@@ -0,0 +1,191 @@
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use minimal_frost::{config::Backend, keymap::Keymap, Config, FakeExecutor, Workspace};
+use ratatui::{backend::TestBackend, Terminal};
+use std::{
+    collections::VecDeque,
+    sync::OnceLock,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// One step of a scripted session
+enum Step {
+    Key(KeyCode, KeyModifiers),
+    Type(&'static str),
+    /// Let the worker answer while no keys come in
+    Wait(Duration),
+}
+
+/// Keep recovery files and caches of the test run out of the real data dirs
+fn isolate_data_dirs() {
+    static DIR: OnceLock<tempfile::TempDir> = OnceLock::new();
+    let dir = DIR.get_or_init(|| tempfile::tempdir().expect("temp dir"));
+    std::env::set_var("HOME", dir.path());
+    std::env::set_var("XDG_DATA_HOME", dir.path().join("data"));
+    std::env::set_var("XDG_CACHE_HOME", dir.path().join("cache"));
+}
+
+/// Run `steps` against a workspace on a fake database, then quit; returns
+/// the last frame drawn
+fn run_session(executor: FakeExecutor, steps: Vec<Step>) -> (Workspace, String) {
+    isolate_data_dirs();
+    // The REST backend keeps the startup health check off the ODBC driver
+    let config = Config { backend: Backend::Rest, autosave_interval_secs: 0, ..Config::default() };
+    let keymap = Keymap::new(&config.keys).expect("default keymap");
+    let mut workspace = Workspace::new(config, keymap, Box::new(executor));
+    let mut terminal = Terminal::new(TestBackend::new(120, 30)).expect("test terminal");
+
+    let mut events: VecDeque<Event> = VecDeque::new();
+    let mut steps: VecDeque<Step> = steps.into();
+    // A startup health report closes on Esc; the quit chord ends the run
+    steps.push_front(Step::Key(KeyCode::Esc, KeyModifiers::NONE));
+    steps.push_back(Step::Key(KeyCode::Char('q'), KeyModifiers::CONTROL));
+    let mut waiting_until: Option<Instant> = None;
+    workspace
+        .run_with_events(&mut terminal, |timeout| {
+            if let Some(until) = waiting_until {
+                if Instant::now() < until {
+                    thread::sleep(timeout.min(until - Instant::now()));
+                    return Ok(None);
+                }
+                waiting_until = None;
+            }
+            if let Some(event) = events.pop_front() {
+                return Ok(Some(event));
+            }
+            match steps.pop_front() {
+                Some(Step::Key(code, modifiers)) => Ok(Some(Event::Key(KeyEvent::new(code, modifiers)))),
+                Some(Step::Type(text)) => {
+                    events.extend(text.chars().map(|c| Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))));
+                    Ok(events.pop_front())
+                }
+                Some(Step::Wait(duration)) => {
+                    waiting_until = Some(Instant::now() + duration);
+                    Ok(None)
+                }
+                None => panic!("the quit chord didn't end the event loop"),
+            }
+        })
+        .expect("event loop");
+
+    let buffer = terminal.backend().buffer();
+    let area = buffer.area;
+    let screen = (0..area.height)
+        .map(|y| (0..area.width).map(|x| buffer[(x, y)].symbol()).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n");
+    (workspace, screen)
+}
+
+fn run_query(sql: &'static str) -> Vec<Step> {
+    vec![
+        Step::Wait(Duration::from_millis(300)),
+        Step::Type(sql),
+        Step::Key(KeyCode::Enter, KeyModifiers::CONTROL),
+        Step::Wait(Duration::from_secs(1)),
+    ]
+}
+
+#[test]
+fn query_result_shows_in_the_grid() {
+    let executor = FakeExecutor::new().with_rows(
+        "SELECT * FROM orders",
+        &["ORDER_ID", "CUSTOMER"],
+        &[&["1001", "Acme Corp"], &["1002", "Globex"]],
+    );
+    let (workspace, screen) = run_session(executor, run_query("SELECT * FROM orders"));
+    assert!(workspace.connected);
+    assert_eq!(workspace.results.tabs.len(), 1);
+    for text in ["ORDER_ID", "CUSTOMER", "Acme Corp", "Globex"] {
+        assert!(screen.contains(text), "{} missing from:\n{}", text, screen);
+    }
+}
+
+#[test]
+fn query_error_shows_in_the_results_pane() {
+    let executor = FakeExecutor::new().with_error("SELECT * FROM missing", "Object 'MISSING' does not exist");
+    let (workspace, screen) = run_session(executor, run_query("SELECT * FROM missing"));
+    assert!(!workspace.running);
+    assert!(screen.contains("Object 'MISSING' does not exist"), "error missing from:\n{}", screen);
+}
+
+#[test]
+fn save_asks_for_a_file_the_first_time() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let path = dir.path().join("query.sql");
+    // The prompt starts out with the working directory
+    let suggested = std::env::current_dir().expect("working dir").join("").display().to_string();
+    let mut steps = vec![
+        Step::Wait(Duration::from_millis(300)),
+        Step::Type("SELECT 1"),
+        Step::Key(KeyCode::Char('s'), KeyModifiers::CONTROL),
+    ];
+    steps.extend(suggested.chars().map(|_| Step::Key(KeyCode::Backspace, KeyModifiers::NONE)));
+    steps.push(Step::Type(Box::leak(path.display().to_string().into_boxed_str())));
+    steps.push(Step::Key(KeyCode::Enter, KeyModifiers::NONE));
+    let (workspace, screen) = run_session(FakeExecutor::new(), steps);
+    assert_eq!(std::fs::read_to_string(&path).expect("saved file"), "SELECT 1");
+    assert_eq!(workspace.results.message.as_deref(), Some(format!("Saved {}", path.display()).as_str()), "{}", screen);
+}
+
+#[test]
+fn indent_and_outdent_the_caret_line() {
+    let steps = vec![
+        Step::Wait(Duration::from_millis(300)),
+        Step::Type("SELECT 1"),
+        Step::Key(KeyCode::Char('.'), KeyModifiers::ALT),
+        Step::Key(KeyCode::Char('.'), KeyModifiers::ALT),
+        Step::Key(KeyCode::Char(','), KeyModifiers::ALT),
+    ];
+    let (workspace, _) = run_session(FakeExecutor::new(), steps);
+    assert_eq!(workspace.editor.rope.to_string(), "    SELECT 1");
+}
+
+#[test]
+fn sort_is_built_in_the_background_and_swapped_in() {
+    let executor = FakeExecutor::new().with_rows(
+        "SELECT * FROM orders",
+        &["ORDER_ID", "CUSTOMER"],
+        &[&["1002", "Globex"], &["1001", "Acme Corp"]],
+    );
+    let mut steps = run_query("SELECT * FROM orders");
+    // Running a query focuses its results
+    steps.push(Step::Key(KeyCode::Char('o'), KeyModifiers::NONE));
+    steps.push(Step::Wait(Duration::from_millis(500)));
+    let (workspace, screen) = run_session(executor, steps);
+    assert_eq!(workspace.results.message.as_deref(), Some("2 rows, sorted by ORDER_ID"), "{}", screen);
+    let acme = screen.find("Acme Corp").expect("first row shown");
+    let globex = screen.find("Globex").expect("second row shown");
+    assert!(acme < globex, "rows not sorted:\n{}", screen);
+}
+
+#[test]
+fn compare_runs_in_the_background_and_opens_a_tab() {
+    let executor = FakeExecutor::new()
+        .with_rows("SELECT * FROM old", &["ID", "STATUS"], &[&["1", "open"], &["2", "open"], &["3", "open"]])
+        .with_rows("SELECT * FROM new", &["ID", "STATUS"], &[&["1", "open"], &["2", "closed"], &["4", "open"]]);
+    let mut steps = run_query("SELECT * FROM old");
+    // B marks the base tab, then the tab to compare with it
+    steps.extend([
+        Step::Key(KeyCode::Char('B'), KeyModifiers::SHIFT),
+        Step::Key(KeyCode::Tab, KeyModifiers::NONE),
+        Step::Key(KeyCode::Char('a'), KeyModifiers::CONTROL),
+    ]);
+    steps.extend(run_query("SELECT * FROM new"));
+    steps.extend([
+        Step::Key(KeyCode::Char('B'), KeyModifiers::SHIFT),
+        Step::Key(KeyCode::Enter, KeyModifiers::NONE),
+        Step::Wait(Duration::from_millis(500)),
+    ]);
+    let (workspace, screen) = run_session(executor, steps);
+    assert_eq!(workspace.results.tabs.len(), 3, "{}", screen);
+    assert_eq!(
+        workspace.results.message.as_deref(),
+        Some("Tab 2 compared with tab 1 on ID: 1 added, 1 removed, 1 changed, 1 unchanged"),
+        "{}",
+        screen,
+    );
+    for text in ["ADDED", "REMOVED", "CHANGED", "open → closed"] {
+        assert!(screen.contains(text), "{} missing from:\n{}", text, screen);
+    }
+}